@@ -0,0 +1,1996 @@
+use std::sync::OnceLock;
+
+use half::f16;
+
+use super::{dot, principal_axis, sub, Quality};
+use crate::pixel::{decode_rgba_f32, encode_rgba_f32};
+use crate::vk_format::{is_format_srgb, VkFormat};
+
+type Vec4 = [f32; 4];
+
+/// Levels of the ranges of integer sequences, ascending.
+const RANGES: [u32; 21] = [
+    2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64, 80, 96, 128, 160, 192, 256,
+];
+
+/// Levels of the ranges allowed for color endpoint values.
+const COLOR_RANGES: &[u32] = RANGES.split_at(4).1;
+
+/// Levels of the weight ranges selectable by the block mode.
+const WEIGHT_LEVELS: [u32; 12] = [2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32];
+
+/// Widths of the trit bits following each of the 5 values of a trit block.
+const TRIT_BITS: [u32; 5] = [2, 2, 1, 2, 1];
+
+/// Widths of the quint bits following each of the 3 values of a quint block.
+const QUINT_BITS: [u32; 3] = [3, 2, 2];
+
+/// Bits of the values a, b, c and d of the 8 modes of HDR RGB endpoints.
+const HDR_RGB_BITS: [[u32; 4]; 8] = [
+    [9, 7, 6, 7],
+    [9, 8, 6, 6],
+    [10, 6, 7, 7],
+    [10, 7, 7, 6],
+    [11, 8, 6, 5],
+    [11, 6, 8, 6],
+    [12, 7, 7, 5],
+    [12, 6, 7, 6],
+];
+
+/// Texels of blocks that are invalid or use HDR in the LDR profile.
+const ERROR_COLOR: Vec4 = [1.0, 0.0, 1.0, 1.0];
+
+/// Precision of decoded texels, selected by the `KTXastcDecodeMode`
+/// metadata.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum DecodeMode {
+    #[default]
+    Float16,
+
+    /// 8 bit UNORM, for LDR formats only.
+    Unorm8,
+
+    /// Shared exponent RGB9E5, alpha is 1.
+    Rgb9e5,
+}
+
+/// How the blocks of a format are decoded.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Profile {
+    /// Whether HDR endpoints are allowed, for SFLOAT formats.
+    pub hdr: bool,
+    pub srgb: bool,
+    pub mode: DecodeMode,
+}
+
+impl Profile {
+    pub(crate) fn new(format: VkFormat, mode: DecodeMode) -> Self {
+        Self {
+            hdr: is_astc_hdr(format),
+            srgb: is_format_srgb(format),
+            mode,
+        }
+    }
+
+    /// Converts an interpolated 16 bit value of an LDR channel.
+    fn ldr(&self, value: i32) -> f32 {
+        if self.srgb || self.mode == DecodeMode::Unorm8 {
+            (value >> 8) as f32 / 255.0
+        } else if value == 0xFFFF {
+            1.0
+        } else {
+            // the exact quotient rounded toward zero
+            let exact = value as f32 / 65536.0;
+            let rounded = f16::from_f32(exact);
+            if rounded.to_f32() > exact {
+                f16::from_bits(rounded.to_bits() - 1).to_f32()
+            } else {
+                rounded.to_f32()
+            }
+        }
+    }
+
+    /// Applies the shared exponent precision of `DecodeMode::Rgb9e5`.
+    fn finish(&self, texel: Vec4) -> Vec4 {
+        if self.mode != DecodeMode::Rgb9e5 || self.srgb {
+            return texel;
+        }
+        let mut bytes = [0; 4];
+        encode_rgba_f32(VkFormat::E5B9G9R9_UFLOAT_PACK32, texel, &mut bytes);
+        decode_rgba_f32(VkFormat::E5B9G9R9_UFLOAT_PACK32, &bytes).unwrap()
+    }
+}
+
+/// Whether `format` is an ASTC format, with 2D or 3D blocks.
+pub(crate) fn is_astc(format: VkFormat) -> bool {
+    matches!(format as u32, 157..=184 | 1000066000..=1000066013 | 1000288000..=1000288029)
+}
+
+/// Whether `format` is an ASTC format with 2D blocks.
+pub(crate) fn is_astc_2d(format: VkFormat) -> bool {
+    matches!(format as u32, 157..=184 | 1000066000..=1000066013)
+}
+
+/// Whether `format` is an HDR (SFLOAT) ASTC format.
+pub(crate) fn is_astc_hdr(format: VkFormat) -> bool {
+    match format as u32 {
+        1000066000..=1000066013 => true,
+        n @ 1000288000..=1000288029 => (n - 1000288000) % 3 == 2,
+        _ => false,
+    }
+}
+
+/// Bits `[offset, offset + count)` of a block.
+#[inline]
+fn field(bits: u128, offset: u32, count: u32) -> u32 {
+    if offset >= 128 {
+        return 0;
+    }
+    ((bits >> offset) & ((1u128 << count) - 1)) as u32
+}
+
+#[inline]
+fn mask(bits: u32) -> u32 {
+    if bits >= 32 {
+        u32::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+/// Trits (3) or quints (5) per value and the number of plain bits of a
+/// range with `levels` levels.
+fn encoding(levels: u32) -> (u32, u32) {
+    let base = if levels.is_multiple_of(3) {
+        3
+    } else if levels.is_multiple_of(5) {
+        5
+    } else {
+        1
+    };
+    (base, (levels / base).trailing_zeros())
+}
+
+/// Length in bits of a sequence of `count` values of a range.
+fn sequence_bits(count: u32, levels: u32) -> u32 {
+    let (base, bits) = encoding(levels);
+    count * bits
+        + match base {
+            3 => (8 * count).div_ceil(5),
+            5 => (7 * count).div_ceil(3),
+            _ => 0,
+        }
+}
+
+/// Unpacks the 5 trits of a trit block.
+fn trits(packed: u32) -> [u32; 5] {
+    let bit = |i: u32| (packed >> i) & 1;
+    let (c, t3, t4);
+    if (packed >> 2) & 7 == 7 {
+        c = (packed >> 5 & 7) << 2 | (packed & 3);
+        (t3, t4) = (2, 2);
+    } else {
+        c = packed & 0x1F;
+        (t3, t4) = if (packed >> 5) & 3 == 3 {
+            (bit(7), 2)
+        } else {
+            ((packed >> 5) & 3, bit(7))
+        };
+    }
+    let cbit = |i: u32| (c >> i) & 1;
+    let (t0, t1, t2) = if c & 3 == 3 {
+        (cbit(3) << 1 | (cbit(2) & !cbit(3) & 1), cbit(4), 2)
+    } else if (c >> 2) & 3 == 3 {
+        (c & 3, 2, 2)
+    } else {
+        (
+            cbit(1) << 1 | (cbit(0) & !cbit(1) & 1),
+            (c >> 2) & 3,
+            cbit(4),
+        )
+    };
+    [t0, t1, t2, t3, t4]
+}
+
+/// Unpacks the 3 quints of a quint block.
+fn quints(packed: u32) -> [u32; 3] {
+    let bit = |i: u32| (packed >> i) & 1;
+    if (packed >> 1) & 3 == 3 && (packed >> 5) & 3 == 0 {
+        let q2 = bit(0) << 2 | (bit(4) & !bit(0) & 1) << 1 | (bit(3) & !bit(0) & 1);
+        return [4, 4, q2];
+    }
+    let (q2, c) = if (packed >> 1) & 3 == 3 {
+        (
+            4,
+            (packed >> 3 & 3) << 3 | (!(packed >> 5) & 3) << 1 | bit(0),
+        )
+    } else {
+        ((packed >> 5) & 3, packed & 0x1F)
+    };
+    let (q0, q1) = if c & 7 == 5 {
+        (c >> 3 & 3, 4)
+    } else {
+        (c & 7, c >> 3 & 3)
+    };
+    [q0, q1, q2]
+}
+
+/// Smallest packing of `digits` trits or quints, which leaves the bits of
+/// missing trailing digits zero.
+fn pack_digits(digits: &[u32], base: u32) -> u32 {
+    static TRITS: OnceLock<[u8; 243]> = OnceLock::new();
+    static QUINTS: OnceLock<[u8; 125]> = OnceLock::new();
+    let index = digits.iter().rev().fold(0, |index, &d| index * base + d) as usize;
+    if base == 3 {
+        TRITS.get_or_init(|| {
+            let mut codes = [0; 243];
+            for packed in (0..256).rev() {
+                codes[trits(packed).iter().rev().fold(0, |i, &t| i * 3 + t) as usize] =
+                    packed as u8;
+            }
+            codes
+        })[index] as u32
+    } else {
+        QUINTS.get_or_init(|| {
+            let mut codes = [0; 125];
+            for packed in (0..128).rev() {
+                codes[quints(packed).iter().rev().fold(0, |i, &q| i * 5 + q) as usize] =
+                    packed as u8;
+            }
+            codes
+        })[index] as u32
+    }
+}
+
+/// Reads `count` values of a range from the bits of a sequence starting at
+/// `offset`.
+fn decode_sequence(bits: u128, offset: u32, count: u32, levels: u32) -> Vec<u32> {
+    let (base, width) = encoding(levels);
+    let end = offset + sequence_bits(count, levels);
+    let mut position = offset;
+    let mut read = |n: u32| {
+        let value = if position < end {
+            field(bits, position, n) & mask(end - position)
+        } else {
+            0
+        };
+        position += n;
+        value
+    };
+
+    let mut values = Vec::with_capacity(count as usize + 4);
+    while values.len() < count as usize {
+        let digit_bits: &[u32] = match base {
+            3 => &TRIT_BITS,
+            5 => &QUINT_BITS,
+            _ => {
+                values.push(read(width));
+                continue;
+            }
+        };
+        let (mut low, mut packed, mut shift) = ([0; 5], 0, 0);
+        for (i, &n) in digit_bits.iter().enumerate() {
+            low[i] = read(width);
+            packed |= read(n) << shift;
+            shift += n;
+        }
+        let digits = if base == 3 {
+            trits(packed).to_vec()
+        } else {
+            quints(packed).to_vec()
+        };
+        values.extend(digits.iter().zip(low).map(|(d, low)| d << width | low));
+    }
+    values.truncate(count as usize);
+    values
+}
+
+/// Writes `values` of a range as a sequence starting at `offset`.
+fn encode_sequence(values: &[u32], levels: u32, bits: &mut u128, offset: u32) {
+    let (base, width) = encoding(levels);
+    let end = offset + sequence_bits(values.len() as u32, levels);
+    let mut position = offset;
+    let mut write = |value: u32, n: u32| {
+        if position < end {
+            *bits |= ((value & mask(n.min(end - position))) as u128) << position;
+        }
+        position += n;
+    };
+
+    if base == 1 {
+        values.iter().for_each(|&v| write(v, width));
+        return;
+    }
+    let digit_bits: &[u32] = if base == 3 { &TRIT_BITS } else { &QUINT_BITS };
+    for chunk in values.chunks(digit_bits.len()) {
+        let digits: Vec<u32> = chunk.iter().map(|v| v >> width).collect();
+        let packed = pack_digits(&digits, base);
+        let mut shift = 0;
+        for (i, &n) in digit_bits.iter().enumerate() {
+            write(chunk.get(i).copied().unwrap_or(0), width);
+            write(packed >> shift, n);
+            shift += n;
+        }
+    }
+}
+
+/// Repeats the `bits` bits of `value` to a `to` bit value.
+fn replicate(value: u32, bits: u32, to: u32) -> u32 {
+    let (mut result, mut filled) = (0, 0);
+    while filled < to {
+        result = result << bits | value;
+        filled += bits;
+    }
+    result >> (filled - to)
+}
+
+/// Color endpoint value in `[0, 255]` of a code of a range.
+fn unquantize_color(code: u32, levels: u32) -> u32 {
+    let (base, bits) = encoding(levels);
+    if base == 1 {
+        return replicate(code, bits, 8);
+    }
+    let (digit, low) = (code >> bits, code & mask(bits));
+    let bit = |i: u32| (low >> i) & 1;
+    let (b, c) = match (base, bits) {
+        (3, 1) => (0, 204),
+        (3, 2) => (bit(1) * 0x116, 93),
+        (3, 3) => (bit(2) * 0x10A + bit(1) * 0x85, 44),
+        (3, 4) => (bit(3) * 0x104 + bit(2) * 0x82 + bit(1) * 0x41, 22),
+        (3, 5) => (
+            bit(4) * 0x102 + bit(3) * 0x81 + bit(2) * 0x40 + bit(1) * 0x20,
+            11,
+        ),
+        (3, _) => (
+            bit(5) * 0x101 + bit(4) * 0x80 + bit(3) * 0x40 + bit(2) * 0x20 + bit(1) * 0x10,
+            5,
+        ),
+        (_, 1) => (0, 113),
+        (_, 2) => (bit(1) * 0x10C, 54),
+        (_, 3) => (bit(2) * 0x105 + bit(1) * 0x82, 26),
+        (_, 4) => (bit(3) * 0x102 + bit(2) * 0x81 + bit(1) * 0x40, 13),
+        _ => (
+            bit(4) * 0x101 + bit(3) * 0x80 + bit(2) * 0x40 + bit(1) * 0x20,
+            6,
+        ),
+    };
+    let a = if bit(0) == 1 { 0x1FF } else { 0 };
+    let t = (digit * c + b) ^ a;
+    (a & 0x80) | (t >> 2)
+}
+
+/// Weight in `[0, 64]` of a code of a weight range.
+fn unquantize_weight(code: u32, levels: u32) -> u32 {
+    let (base, bits) = encoding(levels);
+    let value = if base == 1 {
+        replicate(code, bits, 6)
+    } else if bits == 0 {
+        match base {
+            3 => [0, 32, 63][code as usize],
+            _ => [0, 16, 32, 47, 63][code as usize],
+        }
+    } else {
+        let (digit, low) = (code >> bits, code & mask(bits));
+        let bit = |i: u32| (low >> i) & 1;
+        let (b, c) = match (base, bits) {
+            (3, 1) => (0, 50),
+            (3, 2) => (bit(1) * 0x45, 23),
+            (3, _) => (bit(2) * 0x42 + bit(1) * 0x21, 11),
+            (_, 1) => (0, 28),
+            _ => (bit(1) * 0x42, 13),
+        };
+        let a = if bit(0) == 1 { 0x7F } else { 0 };
+        let t = (digit * c + b) ^ a;
+        (a & 0x20) | (t >> 2)
+    };
+    if value > 32 {
+        value + 1
+    } else {
+        value
+    }
+}
+
+/// Unquantized values of the codes of a range, with the code of the nearest
+/// value for every integer up to the largest value.
+struct Range {
+    values: Vec<u32>,
+    codes: Vec<u8>,
+}
+
+impl Range {
+    fn new(levels: u32, unquantize: fn(u32, u32) -> u32) -> Self {
+        let values: Vec<u32> = (0..levels).map(|code| unquantize(code, levels)).collect();
+        let codes = (0..=*values.iter().max().unwrap())
+            .map(|v| {
+                (0..levels)
+                    .min_by_key(|&code| values[code as usize].abs_diff(v))
+                    .unwrap() as u8
+            })
+            .collect();
+        Self { values, codes }
+    }
+
+    /// Code with the value nearest to `value`.
+    fn quantize(&self, value: f32) -> u32 {
+        let max = (self.codes.len() - 1) as f32;
+        self.codes[value.round().clamp(0.0, max) as usize] as u32
+    }
+}
+
+/// Color endpoint range with `levels` levels.
+fn color_range(levels: u32) -> &'static Range {
+    static RANGES: OnceLock<Vec<Range>> = OnceLock::new();
+    let ranges = RANGES.get_or_init(|| {
+        COLOR_RANGES
+            .iter()
+            .map(|&l| Range::new(l, unquantize_color))
+            .collect()
+    });
+    &ranges[COLOR_RANGES.iter().position(|&l| l == levels).unwrap()]
+}
+
+/// Weight range with `levels` levels.
+fn weight_range(levels: u32) -> &'static Range {
+    static RANGES: OnceLock<Vec<Range>> = OnceLock::new();
+    let ranges = RANGES.get_or_init(|| {
+        WEIGHT_LEVELS
+            .iter()
+            .map(|&l| Range::new(l, unquantize_weight))
+            .collect()
+    });
+    &ranges[WEIGHT_LEVELS.iter().position(|&l| l == levels).unwrap()]
+}
+
+/// Weight grid and weight range of a block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BlockMode {
+    grid_width: u32,
+    grid_height: u32,
+    dual_plane: bool,
+    weight_levels: u32,
+}
+
+impl BlockMode {
+    fn weight_count(&self) -> u32 {
+        self.grid_width * self.grid_height * (1 + self.dual_plane as u32)
+    }
+
+    fn weight_bits(&self) -> u32 {
+        sequence_bits(self.weight_count(), self.weight_levels)
+    }
+}
+
+/// Decodes the 11 bit block mode of a 2D block, `None` for reserved modes
+/// and modes with too few or too many weight bits.
+fn decode_block_mode(mode: u32) -> Option<BlockMode> {
+    let a = (mode >> 5) & 3;
+    let (mut high, mut dual_plane) = ((mode >> 9) & 1, (mode >> 10) & 1 == 1);
+    let (range, grid_width, grid_height);
+    if mode & 3 != 0 {
+        range = (mode & 3) << 1 | (mode >> 4) & 1;
+        let b = (mode >> 7) & 3;
+        (grid_width, grid_height) = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if mode & 0x100 != 0 => ((b & 1) + 2, a + 2),
+            _ => (a + 2, (b & 1) + 6),
+        };
+    } else {
+        if (mode >> 2) & 3 == 0 {
+            return None;
+        }
+        range = (mode >> 1) & 6 | (mode >> 4) & 1;
+        let b = (mode >> 9) & 3;
+        (grid_width, grid_height) = match (mode >> 7) & 3 {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+                (high, dual_plane) = (0, false);
+                (a + 6, b + 6)
+            }
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        };
+    }
+    let block_mode = BlockMode {
+        grid_width,
+        grid_height,
+        dual_plane,
+        weight_levels: WEIGHT_LEVELS[(range - 2 + 6 * high) as usize],
+    };
+    let bits = block_mode.weight_bits();
+    (block_mode.weight_count() <= 64 && (24..=96).contains(&bits)).then_some(block_mode)
+}
+
+/// Largest color range fitting `value_count` values in `available` bits.
+fn color_levels(value_count: u32, available: u32) -> Option<u32> {
+    COLOR_RANGES
+        .iter()
+        .rev()
+        .copied()
+        .find(|&levels| sequence_bits(value_count, levels) <= available)
+}
+
+/// Number of color values of a color endpoint mode.
+#[inline]
+fn value_count(cem: u32) -> u32 {
+    (cem / 4 + 1) * 2
+}
+
+/// Color endpoint modes of the partitions and the number of their bits
+/// stored below the weights.
+fn endpoint_modes(bits: u128, partitions: u32, weight_bits: u32) -> (Vec<u32>, u32) {
+    if partitions == 1 {
+        return (vec![field(bits, 13, 4)], 0);
+    }
+    let mut encoded = field(bits, 23, 6);
+    if encoded & 3 == 0 {
+        return (vec![encoded >> 2; partitions as usize], 0);
+    }
+    let extra = 3 * partitions - 4;
+    encoded |= field(bits, 128 - weight_bits - extra, extra) << 6;
+    let class = (encoded & 3) - 1;
+    let modes = (0..partitions)
+        .map(|i| {
+            let offset = (encoded >> (2 + i)) & 1;
+            let mode = (encoded >> (2 + partitions + 2 * i)) & 3;
+            (class + offset) << 2 | mode
+        })
+        .collect();
+    (modes, extra)
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+/// Partition of the texel at `(x, y)` of a block with `partitions`
+/// partitions and partition index `seed`.
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> usize {
+    let (x, y) = if small_block {
+        (x << 1, y << 1)
+    } else {
+        (x, y)
+    };
+    let seed = seed + (partitions - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut seeds = [0, 4, 8, 12, 16, 20, 24, 28, 18, 22, 26].map(|shift| (rnum >> shift) & 0xF);
+    seeds.iter_mut().for_each(|s| *s *= *s);
+    let (sh1, sh2) = match (seed & 1 == 1, seed & 2 == 2) {
+        (true, high) => (
+            if high { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        ),
+        (false, high) => (
+            if partitions == 3 { 6 } else { 5 },
+            if high { 4 } else { 5 },
+        ),
+    };
+    let [s1, s2, s3, s4, s5, s6, s7, s8, ..] = seeds;
+    let (s1, s3, s5, s7) = (s1 >> sh1, s3 >> sh1, s5 >> sh1, s7 >> sh1);
+    let (s2, s4, s6, s8) = (s2 >> sh2, s4 >> sh2, s6 >> sh2, s8 >> sh2);
+
+    // the z terms vanish for 2D blocks
+    let a = (s1 * x + s2 * y + (rnum >> 14)) & 0x3F;
+    let b = (s3 * x + s4 * y + (rnum >> 10)) & 0x3F;
+    let c = if partitions < 3 {
+        0
+    } else {
+        (s5 * x + s6 * y + (rnum >> 6)) & 0x3F
+    };
+    let d = if partitions < 4 {
+        0
+    } else {
+        (s7 * x + s8 * y + (rnum >> 2)) & 0x3F
+    };
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+/// Contributions, as grid index and weight out of 16, of the grid points of
+/// a weight grid to every texel of a block.
+fn infill(width: u32, height: u32, grid_width: u32, grid_height: u32) -> Vec<[(usize, u32); 4]> {
+    let ds = (1024 + width / 2) / (width - 1);
+    let dt = (1024 + height / 2) / (height - 1);
+    let count = (grid_width * grid_height) as usize;
+    (0..width * height)
+        .map(|i| {
+            let (s, t) = (i % width, i / width);
+            let gs = (ds * s * (grid_width - 1) + 32) >> 6;
+            let gt = (dt * t * (grid_height - 1) + 32) >> 6;
+            let (js, fs, jt, ft) = (gs >> 4, gs & 15, gt >> 4, gt & 15);
+            let w11 = (fs * ft + 8) >> 4;
+            let v0 = (js + jt * grid_width) as usize;
+            let row = grid_width as usize;
+            [
+                (v0, 16 + w11 - fs - ft),
+                (v0 + 1, fs - w11),
+                (v0 + row, ft - w11),
+                (v0 + row + 1, w11),
+            ]
+            .map(|(index, weight)| {
+                if weight == 0 || index >= count {
+                    (0, 0)
+                } else {
+                    (index, weight)
+                }
+            })
+        })
+        .collect()
+}
+
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let a = (a >> 1) & 0x3F;
+    (if a & 0x20 != 0 { a - 0x40 } else { a }, b)
+}
+
+fn blue_contract([r, g, b, a]: [i32; 4]) -> [i32; 4] {
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+/// Endpoints of a color endpoint mode from its unquantized values, 8 bit
+/// for LDR channels and 16 bit logarithmic for HDR channels, with the HDR
+/// channels.
+fn decode_endpoints(cem: u32, v: &[i32]) -> ([[i32; 4]; 2], [bool; 4]) {
+    const LDR: [bool; 4] = [false; 4];
+    const HDR: [bool; 4] = [true; 4];
+    let clamp = |e: [i32; 4]| e.map(|c| c.clamp(0, 255));
+    let hdr_luminance = |y0: i32, y1: i32| {
+        (
+            [
+                [y0 << 4, y0 << 4, y0 << 4, 0x7800],
+                [y1 << 4, y1 << 4, y1 << 4, 0x7800],
+            ],
+            HDR,
+        )
+    };
+    match cem {
+        0 => ([[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]], LDR),
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xC0);
+            let l1 = (l0 + (v[1] & 0x3F)).min(255);
+            ([[l0, l0, l0, 255], [l1, l1, l1, 255]], LDR)
+        }
+        2 => {
+            let (y0, y1) = if v[1] >= v[0] {
+                (v[0] << 4, v[1] << 4)
+            } else {
+                ((v[1] << 4) + 8, (v[0] << 4) - 8)
+            };
+            hdr_luminance(y0, y1)
+        }
+        3 => {
+            let (y0, d) = if v[0] & 0x80 != 0 {
+                ((v[1] & 0xE0) << 4 | (v[0] & 0x7F) << 2, (v[1] & 0x1F) << 2)
+            } else {
+                ((v[1] & 0xF0) << 4 | (v[0] & 0x7F) << 1, (v[1] & 0x0F) << 1)
+            };
+            hdr_luminance(y0, (y0 + d).min(0xFFF))
+        }
+        4 => ([[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]], LDR),
+        5 => {
+            let (b1, a0) = bit_transfer_signed(v[1], v[0]);
+            let (b3, a2) = bit_transfer_signed(v[3], v[2]);
+            let l1 = a0 + b1;
+            ([[a0, a0, a0, a2], clamp([l1, l1, l1, a2 + b3])], LDR)
+        }
+        6 => {
+            let scaled = [v[0], v[1], v[2]].map(|c| (c * v[3]) >> 8);
+            (
+                [
+                    [scaled[0], scaled[1], scaled[2], 255],
+                    [v[0], v[1], v[2], 255],
+                ],
+                LDR,
+            )
+        }
+        7 => {
+            let [e0, e1] = hdr_rgb_scale(v);
+            (
+                [[e0[0], e0[1], e0[2], 0x7800], [e1[0], e1[1], e1[2], 0x7800]],
+                HDR,
+            )
+        }
+        8 | 12 => {
+            let (a0, a1) = if cem == 12 { (v[6], v[7]) } else { (255, 255) };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                ([[v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1]], LDR)
+            } else {
+                let e0 = blue_contract([v[1], v[3], v[5], a1]);
+                (([e0, blue_contract([v[0], v[2], v[4], a0])]), LDR)
+            }
+        }
+        9 | 13 => {
+            let (b1, a0) = bit_transfer_signed(v[1], v[0]);
+            let (b3, a2) = bit_transfer_signed(v[3], v[2]);
+            let (b5, a4) = bit_transfer_signed(v[5], v[4]);
+            let (b7, a6) = if cem == 13 {
+                bit_transfer_signed(v[7], v[6])
+            } else {
+                (0, 255)
+            };
+            let base = [a0, a2, a4, a6];
+            let offset = [a0 + b1, a2 + b3, a4 + b5, a6 + b7];
+            if b1 + b3 + b5 >= 0 {
+                ([base, clamp(offset)], LDR)
+            } else {
+                (
+                    [clamp(blue_contract(offset)), clamp(blue_contract(base))],
+                    LDR,
+                )
+            }
+        }
+        10 => {
+            let scaled = [v[0], v[1], v[2]].map(|c| (c * v[3]) >> 8);
+            (
+                [
+                    [scaled[0], scaled[1], scaled[2], v[4]],
+                    [v[0], v[1], v[2], v[5]],
+                ],
+                LDR,
+            )
+        }
+        _ => {
+            let [e0, e1] = hdr_rgb(v);
+            let (a0, a1, alpha_hdr) = match cem {
+                11 => (0x7800, 0x7800, true),
+                14 => (v[6], v[7], false),
+                _ => {
+                    let (a0, a1) = hdr_alpha(v[6], v[7]);
+                    (a0, a1, true)
+                }
+            };
+            let endpoints = [[e0[0], e0[1], e0[2], a0], [e1[0], e1[1], e1[2], a1]];
+            (endpoints, [true, true, true, alpha_hdr])
+        }
+    }
+}
+
+/// HDR RGB endpoints of mode 11 as 16 bit logarithmic values.
+fn hdr_rgb(v: &[i32]) -> [[i32; 3]; 2] {
+    let major = (v[4] >> 7 & 1) | (v[5] >> 7 & 1) << 1;
+    if major == 3 {
+        return [
+            [v[0] << 8, v[2] << 8, (v[4] & 0x7F) << 9],
+            [v[1] << 8, v[3] << 8, (v[5] & 0x7F) << 9],
+        ];
+    }
+    let mode = (v[1] >> 7 & 1) | (v[2] >> 7 & 1) << 1 | (v[3] >> 7 & 1) << 2;
+    let bit = |value: i32, n: u32| (value >> n) & 1;
+    let mut a = v[0] | (v[1] & 0x40) << 2;
+    let mut b0 = v[2] & 0x3F;
+    let mut b1 = v[3] & 0x3F;
+    let mut c = v[1] & 0x3F;
+    let d_bits = HDR_RGB_BITS[mode as usize][3];
+    let sign_extend = |d: i32| {
+        let m = 1 << (d_bits - 1);
+        ((d & (2 * m - 1)) ^ m) - m
+    };
+    let (d0, d1) = (sign_extend(v[4]), sign_extend(v[5]));
+
+    let [x0, x1, x2, x3] = [bit(v[2], 6), bit(v[3], 6), bit(v[4], 6), bit(v[5], 6)];
+    let [x4, x5] = [bit(v[4], 5), bit(v[5], 5)];
+    let ohm = 1 << mode;
+    if ohm & 0xA4 != 0 {
+        a |= x0 << 9;
+    }
+    if ohm & 0x08 != 0 {
+        a |= x2 << 9;
+    }
+    if ohm & 0x50 != 0 {
+        a |= x4 << 9 | x5 << 10;
+    }
+    if ohm & 0xA0 != 0 {
+        a |= x1 << 10;
+    }
+    if ohm & 0xC0 != 0 {
+        a |= x2 << 11;
+    }
+    if ohm & 0x04 != 0 {
+        c |= x1 << 6;
+    }
+    if ohm & 0xE8 != 0 {
+        c |= x3 << 6;
+    }
+    if ohm & 0x20 != 0 {
+        c |= x2 << 7;
+    }
+    if ohm & 0x5B != 0 {
+        b0 |= x0 << 6;
+        b1 |= x1 << 6;
+    }
+    if ohm & 0x12 != 0 {
+        b0 |= x2 << 7;
+        b1 |= x3 << 7;
+    }
+
+    let shift = (mode >> 1) ^ 3;
+    let [a, b0, b1, c, d0, d1] = [a, b0, b1, c, d0, d1].map(|value| value << shift);
+    let mut e1 = [a, a - b0, a - b1];
+    let mut e0 = [a - c, a - b0 - c - d0, a - b1 - c - d1];
+    if major > 0 {
+        e0.swap(0, major as usize);
+        e1.swap(0, major as usize);
+    }
+    [e0, e1].map(|e| e.map(|c| c.clamp(0, 0xFFF) << 4))
+}
+
+/// HDR RGB endpoints of mode 7, a base color and a scale, as 16 bit
+/// logarithmic values.
+fn hdr_rgb_scale(v: &[i32]) -> [[i32; 3]; 2] {
+    let mode_value = (v[0] & 0xC0) >> 6 | (v[1] & 0x80) >> 5 | (v[2] & 0x80) >> 4;
+    let (major, mode) = if mode_value & 0xC != 0xC {
+        (mode_value >> 2, mode_value & 3)
+    } else if mode_value != 0xF {
+        (mode_value & 3, 4)
+    } else {
+        (0, 5)
+    };
+    let bit = |value: i32, n: u32| (value >> n) & 1;
+    let [x0, x1, x2, x3] = [bit(v[1], 6), bit(v[1], 5), bit(v[2], 6), bit(v[2], 5)];
+    let [x4, x5, x6] = [bit(v[3], 7), bit(v[3], 6), bit(v[3], 5)];
+    let (mut red, mut green, mut blue, mut scale) =
+        (v[0] & 0x3F, v[1] & 0x1F, v[2] & 0x1F, v[3] & 0x1F);
+
+    let ohm = 1 << mode;
+    if ohm & 0x30 != 0 {
+        green |= x0 << 6;
+        blue |= x2 << 6;
+    }
+    if ohm & 0x3A != 0 {
+        green |= x1 << 5;
+        blue |= x3 << 5;
+    }
+    if ohm & 0x3D != 0 {
+        scale |= x6 << 5;
+    }
+    if ohm & 0x2D != 0 {
+        scale |= x5 << 6;
+    }
+    if ohm & 0x04 != 0 {
+        scale |= x4 << 7;
+        red |= x3 << 6;
+    }
+    if ohm & 0x3B != 0 {
+        red |= x4 << 6;
+    }
+    if ohm & 0x10 != 0 {
+        red |= x5 << 7;
+    }
+    if ohm & 0x0F != 0 {
+        red |= x2 << 7;
+    }
+    if ohm & 0x05 != 0 {
+        red |= x1 << 8 | x0 << 9;
+    }
+    if ohm & 0x0A != 0 {
+        red |= x0 << 8;
+    }
+    if ohm & 0x02 != 0 {
+        red |= x6 << 9 | x5 << 10;
+    }
+    if ohm & 0x01 != 0 {
+        red |= x3 << 10;
+    }
+
+    let shift = [1, 1, 2, 3, 4, 5][mode as usize];
+    let [red, mut green, mut blue, scale] = [red, green, blue, scale].map(|value| value << shift);
+    if mode != 5 {
+        green = red - green;
+        blue = red - blue;
+    }
+    let mut e1 = [red, green, blue];
+    if major > 0 {
+        e1.swap(0, major as usize);
+    }
+    let e0 = e1.map(|c| c - scale);
+    [e0, e1].map(|e| e.map(|c| c.clamp(0, 0xFFF) << 4))
+}
+
+/// HDR alpha endpoints of mode 15 as 16 bit logarithmic values.
+fn hdr_alpha(v6: i32, v7: i32) -> (i32, i32) {
+    let selector = (v6 >> 7 & 1) | (v7 >> 6 & 2);
+    let (mut a0, mut a1) = (v6 & 0x7F, v7 & 0x7F);
+    if selector == 3 {
+        return (a0 << 9, a1 << 9);
+    }
+    a0 |= (a1 << (selector + 1)) & 0x780;
+    a1 &= 0x3F >> selector;
+    a1 ^= 32 >> selector;
+    a1 -= 32 >> selector;
+    a0 <<= 4 - selector;
+    a1 <<= 4 - selector;
+    a1 = (a1 + a0).clamp(0, 0xFFF);
+    (a0 << 4, a1 << 4)
+}
+
+/// FP16 bits of a 16 bit logarithmic value.
+fn lns_to_f16(value: i32) -> u16 {
+    let (exponent, mantissa) = (value >> 11, value & 0x7FF);
+    let mantissa = if mantissa < 512 {
+        3 * mantissa
+    } else if mantissa < 1536 {
+        4 * mantissa - 512
+    } else {
+        5 * mantissa - 2048
+    };
+    ((exponent << 10 | mantissa >> 3) as u16).min(0x7BFF)
+}
+
+/// Continuous 16 bit logarithmic value of `value`, the inverse of
+/// [`lns_to_f16`].
+fn lns(value: f32) -> f32 {
+    let value = if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(0.0, 65504.0)
+    };
+    let (exponent, mantissa) = if value < 2f32.powi(-14) {
+        (0.0, value * 2f32.powi(14) * 8192.0)
+    } else {
+        let exponent = value.log2().floor();
+        (
+            exponent + 15.0,
+            (value / 2f32.powf(exponent) - 1.0) * 8192.0,
+        )
+    };
+    let mantissa = if mantissa < 1536.0 {
+        mantissa / 3.0
+    } else if mantissa < 5632.0 {
+        (mantissa + 512.0) / 4.0
+    } else {
+        (mantissa + 2048.0) / 5.0
+    };
+    exponent * 2048.0 + mantissa.min(2047.0)
+}
+
+/// Decodes a block with a footprint of `width` x `height` texels to `out`
+/// in row-major order. Invalid blocks decode to magenta.
+pub(crate) fn decode_block(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    profile: Profile,
+    out: &mut [Vec4],
+) {
+    let bits = u128::from_le_bytes(data[..16].try_into().unwrap());
+    if decode(bits, width, height, profile, out).is_none() {
+        out.fill(ERROR_COLOR);
+    }
+}
+
+fn decode(bits: u128, width: u32, height: u32, profile: Profile, out: &mut [Vec4]) -> Option<()> {
+    if bits & 0x1FF == 0x1FC {
+        return decode_void_extent(bits, profile, out);
+    }
+    let mode = decode_block_mode(bits as u32 & 0x7FF)?;
+    if mode.grid_width > width || mode.grid_height > height {
+        return None;
+    }
+    let partitions = field(bits, 11, 2) + 1;
+    if partitions == 4 && mode.dual_plane {
+        return None;
+    }
+
+    let weight_bits = mode.weight_bits();
+    let (cems, extra_bits) = endpoint_modes(bits, partitions, weight_bits);
+    let config_start = if partitions == 1 { 17 } else { 29 };
+    let color_end = (128 - weight_bits - extra_bits).checked_sub(2 * mode.dual_plane as u32)?;
+    let count: u32 = cems.iter().map(|&cem| value_count(cem)).sum();
+    if count > 18 || color_end < config_start {
+        return None;
+    }
+    let levels = color_levels(count, color_end - config_start)?;
+    let table = &color_range(levels).values;
+    let values: Vec<i32> = decode_sequence(bits, config_start, count, levels)
+        .iter()
+        .map(|&code| table[code as usize] as i32)
+        .collect();
+    let mut offset = 0;
+    let endpoints: Vec<_> = cems
+        .iter()
+        .map(|&cem| {
+            offset += value_count(cem) as usize;
+            decode_endpoints(cem, &values[offset - value_count(cem) as usize..offset])
+        })
+        .collect();
+    if !profile.hdr && endpoints.iter().any(|(_, hdr)| hdr.contains(&true)) {
+        return None;
+    }
+    let plane_channel = mode.dual_plane.then(|| field(bits, color_end, 2) as usize);
+
+    let weights = decode_weights(bits, &mode, width, height);
+    let seed = field(bits, 13, 10);
+    let small_block = width * height < 31;
+    for (i, texel) in out.iter_mut().enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        let partition = match partitions {
+            1 => 0,
+            _ => select_partition(seed, x, y, partitions, small_block),
+        };
+        let (e, hdr) = &endpoints[partition];
+        for c in 0..4 {
+            let weight = weights[i][(plane_channel == Some(c)) as usize] as i32;
+            // sRGB expands color endpoints to the middle of their range, alpha is linear
+            let expand = |value: i32| match (hdr[c], profile.srgb && c < 3) {
+                (true, _) => value,
+                (false, true) => value << 8 | 0x80,
+                (false, false) => value * 257,
+            };
+            let value = (expand(e[0][c]) * (64 - weight) + expand(e[1][c]) * weight + 32) >> 6;
+            texel[c] = if hdr[c] {
+                f16::from_bits(lns_to_f16(value)).to_f32()
+            } else {
+                profile.ldr(value)
+            };
+        }
+        *texel = profile.finish(*texel);
+    }
+    Some(())
+}
+
+fn decode_void_extent(bits: u128, profile: Profile, out: &mut [Vec4]) -> Option<()> {
+    if field(bits, 10, 2) != 3 {
+        return None;
+    }
+    let color: [u32; 4] = std::array::from_fn(|c| field(bits, 64 + 16 * c as u32, 16));
+    let texel = if bits >> 9 & 1 == 1 {
+        if !profile.hdr {
+            return None;
+        }
+        color.map(|c| f16::from_bits(c as u16).to_f32())
+    } else {
+        color.map(|c| profile.ldr(c as i32))
+    };
+    out.fill(profile.finish(texel));
+    Some(())
+}
+
+/// Weights in `[0, 64]` of both planes of every texel.
+fn decode_weights(bits: u128, mode: &BlockMode, width: u32, height: u32) -> Vec<[u32; 2]> {
+    let planes = 1 + mode.dual_plane as usize;
+    let table = &weight_range(mode.weight_levels).values;
+    let grid: Vec<u32> = decode_sequence(
+        bits.reverse_bits(),
+        0,
+        mode.weight_count(),
+        mode.weight_levels,
+    )
+    .iter()
+    .map(|&code| table[code as usize])
+    .collect();
+    infill(width, height, mode.grid_width, mode.grid_height)
+        .iter()
+        .map(|contributions| {
+            std::array::from_fn(|plane| {
+                let plane = plane.min(planes - 1);
+                let sum: u32 = contributions
+                    .iter()
+                    .map(|&(index, weight)| grid[index * planes + plane] * weight)
+                    .sum();
+                (sum + 8) >> 4
+            })
+        })
+        .collect()
+}
+
+/// A weight grid and the contributions of its points to the texels.
+struct Grid {
+    width: u32,
+    height: u32,
+    infill: Vec<[(usize, u32); 4]>,
+}
+
+/// A block mode with its bits and grid.
+struct ModeChoice {
+    bits: u32,
+    mode: BlockMode,
+    grid: usize,
+}
+
+/// Partitions, plane and color endpoint mode shared by the block modes
+/// compared for a block.
+struct Layout<'a> {
+    partitions: u32,
+    seed: u32,
+    labels: &'a [u8],
+    cem: u32,
+    plane_channel: Option<usize>,
+}
+
+/// Encoder of the blocks of one format, holding the block modes, weight
+/// grids and partitionings of its footprint.
+pub(crate) struct Encoder {
+    width: u32,
+    height: u32,
+    profile: Profile,
+    quality: Quality,
+    grids: Vec<Grid>,
+    modes: Vec<ModeChoice>,
+
+    /// Partition of every texel for 2 and 3 partitions and every seed.
+    partitionings: [Vec<Vec<u8>>; 2],
+}
+
+impl Encoder {
+    pub(crate) fn new(format: VkFormat, mode: DecodeMode, quality: Quality) -> Self {
+        let (width, height, _) = crate::vk_format::get_format_block_extent(format);
+        let mut grids: Vec<Grid> = Vec::new();
+        let mut modes: Vec<ModeChoice> = Vec::new();
+        for bits in (0..2048).filter(|bits| bits & 0x1FF != 0x1FC) {
+            let Some(mode) = decode_block_mode(bits) else {
+                continue;
+            };
+            if mode.grid_width > width
+                || mode.grid_height > height
+                || modes.iter().any(|choice| choice.mode == mode)
+            {
+                continue;
+            }
+            let size = (mode.grid_width, mode.grid_height);
+            let grid = match grids.iter().position(|g| (g.width, g.height) == size) {
+                Some(grid) => grid,
+                None => {
+                    grids.push(Grid {
+                        width: size.0,
+                        height: size.1,
+                        infill: infill(width, height, size.0, size.1),
+                    });
+                    grids.len() - 1
+                }
+            };
+            modes.push(ModeChoice { bits, mode, grid });
+        }
+
+        let small_block = width * height < 31;
+        let partitionings = [2, 3].map(|partitions| {
+            (0..1024)
+                .map(|seed| {
+                    (0..width * height)
+                        .map(|i| {
+                            select_partition(seed, i % width, i / width, partitions, small_block)
+                                as u8
+                        })
+                        .collect()
+                })
+                .collect()
+        });
+        Self {
+            width,
+            height,
+            profile: Profile::new(format, mode),
+            quality,
+            grids,
+            modes,
+            partitionings,
+        }
+    }
+
+    /// Texel in the space endpoints are fitted in: `[0, 255]` for LDR
+    /// channels and alpha, 1/256 of the logarithmic value for HDR color.
+    fn to_unit(&self, texel: Vec4) -> Vec4 {
+        let ldr = |c: f32| c.clamp(0.0, 1.0) * 255.0;
+        if self.profile.hdr {
+            [
+                lns(texel[0]) / 256.0,
+                lns(texel[1]) / 256.0,
+                lns(texel[2]) / 256.0,
+                ldr(texel[3]),
+            ]
+        } else {
+            texel.map(ldr)
+        }
+    }
+
+    /// Encodes the `width` x `height` texels of a block in row-major order.
+    /// Constant blocks are stored as void extent blocks; otherwise block
+    /// modes, partitionings and dual plane choices are compared by the
+    /// error of the decoded block, more of them with higher quality.
+    pub(crate) fn encode_block(&self, texels: &[Vec4]) -> [u8; 16] {
+        if texels.iter().all(|t| t == &texels[0]) {
+            return self.encode_void_extent(texels[0]);
+        }
+        let targets: Vec<Vec4> = texels.iter().map(|&t| self.to_unit(t)).collect();
+        let alpha = targets.iter().any(|t| t[3] < 254.5);
+        let gray = !self.profile.hdr && targets.iter().all(|t| t[0] == t[1] && t[1] == t[2]);
+        let cem = match (self.profile.hdr, gray, alpha) {
+            (true, _, false) => 11,
+            (true, _, true) => 14,
+            (false, true, false) => 0,
+            (false, true, true) => 4,
+            (false, false, false) => 8,
+            (false, false, true) => 12,
+        };
+        let (max_partitions, seed_count, mode_count) = match self.quality {
+            Quality::Fast => (1, 0, 1),
+            Quality::Normal => (2, 2, 2),
+            Quality::Slow => (3, 4, 4),
+        };
+
+        let mut planes = vec![None];
+        if alpha && self.quality != Quality::Fast {
+            planes.push(Some(3));
+        } else if self.quality == Quality::Slow && !gray {
+            planes.extend([Some(0), Some(1), Some(2)]);
+        }
+
+        let zeros = vec![0u8; targets.len()];
+        let mut best = ([0; 16], f32::INFINITY);
+        for partitions in 1..=max_partitions {
+            if value_count(cem) * partitions > 18 {
+                break;
+            }
+            let seeds = match partitions {
+                1 => vec![0],
+                _ => self.ranked_seeds(&targets, partitions as usize, seed_count),
+            };
+            for seed in seeds {
+                let labels = match partitions {
+                    1 => &zeros,
+                    _ => &self.partitionings[partitions as usize - 2][seed as usize],
+                };
+                for &plane_channel in &planes {
+                    let layout = Layout {
+                        partitions,
+                        seed,
+                        labels,
+                        cem,
+                        plane_channel,
+                    };
+                    let candidate = self.encode_layout(&layout, &targets, mode_count);
+                    if candidate.1 < best.1 {
+                        best = candidate;
+                    }
+                }
+            }
+        }
+        best.0
+    }
+
+    fn encode_void_extent(&self, texel: Vec4) -> [u8; 16] {
+        let (hdr, color) = if self.profile.hdr {
+            (
+                1,
+                texel.map(|c| f16::from_f32(c.max(0.0)).to_bits() as u128),
+            )
+        } else if self.profile.srgb {
+            (
+                0,
+                texel.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u128 * 257),
+            )
+        } else {
+            (
+                0,
+                texel.map(|c| (c.clamp(0.0, 1.0) * 65535.0).round() as u128),
+            )
+        };
+        // all ones extent coordinates mark the extent as unspecified
+        let bits = 0x1FC
+            | hdr << 9
+            | 3 << 10
+            | ((1u128 << 52) - 1) << 12
+            | color[0] << 64
+            | color[1] << 80
+            | color[2] << 96
+            | color[3] << 112;
+        bits.to_le_bytes()
+    }
+
+    /// Seeds whose partitions fit lines through the texels best, among the
+    /// partitionings closest to a k-means clustering of the texels.
+    fn ranked_seeds(&self, targets: &[Vec4], partitions: usize, count: usize) -> Vec<u32> {
+        let clusters = kmeans(targets, partitions);
+        let permutations: Vec<Vec<usize>> = (0..partitions.pow(partitions as u32))
+            .map(|code| {
+                (0..partitions)
+                    .map(|i| code / partitions.pow(i as u32) % partitions)
+                    .collect()
+            })
+            .filter(|p: &Vec<usize>| (0..partitions).all(|i| p.contains(&i)))
+            .collect();
+        let tables = &self.partitionings[partitions - 2];
+        let mut scored: Vec<(usize, u32)> = tables
+            .iter()
+            .enumerate()
+            .filter_map(|(seed, labels)| {
+                let mut matrix = [[0; 4]; 4];
+                for (&label, &cluster) in labels.iter().zip(&clusters) {
+                    matrix[label as usize][cluster] += 1;
+                }
+                if matrix[..partitions]
+                    .iter()
+                    .any(|row| row.iter().sum::<usize>() == 0)
+                {
+                    return None;
+                }
+                let matches = permutations
+                    .iter()
+                    .map(|p| (0..partitions).map(|i| matrix[i][p[i]]).sum::<usize>())
+                    .max()
+                    .unwrap();
+                Some((targets.len() - matches, seed as u32))
+            })
+            .collect();
+        scored.sort();
+
+        let mut shortlist: Vec<u32> = Vec::new();
+        for (_, seed) in scored {
+            if shortlist.len() == 16 * count {
+                break;
+            }
+            if !shortlist
+                .iter()
+                .any(|&s| tables[s as usize] == tables[seed as usize])
+            {
+                shortlist.push(seed);
+            }
+        }
+        let residual = |seed: &u32| -> f32 {
+            let labels = &tables[*seed as usize];
+            (0..partitions)
+                .map(|p| {
+                    let members: Vec<Vec4> = targets
+                        .iter()
+                        .zip(labels)
+                        .filter(|&(_, &l)| l as usize == p)
+                        .map(|(t, _)| *t)
+                        .collect();
+                    let (mean, axis) = principal_axis(&members);
+                    members
+                        .iter()
+                        .map(|t| {
+                            let d = sub(*t, mean);
+                            dot(d, d) - dot(d, axis).powi(2)
+                        })
+                        .sum::<f32>()
+                })
+                .sum()
+        };
+        let mut ranked: Vec<(f32, u32)> = shortlist
+            .iter()
+            .map(|seed| (residual(seed), *seed))
+            .collect();
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ranked.iter().take(count).map(|&(_, seed)| seed).collect()
+    }
+
+    /// Channels fitted by the endpoints of a color endpoint mode.
+    fn channels(cem: u32) -> [bool; 4] {
+        match cem {
+            0 | 8 | 11 => [true, true, true, false],
+            _ => [true; 4],
+        }
+    }
+
+    /// Encodes the block with the block modes estimated best for `layout`
+    /// and returns the best block with its error.
+    fn encode_layout(
+        &self,
+        layout: &Layout,
+        targets: &[Vec4],
+        mode_count: usize,
+    ) -> ([u8; 16], f32) {
+        let channels = Self::channels(layout.cem);
+        let endpoints: Vec<[Vec4; 2]> = (0..layout.partitions as usize)
+            .map(|p| fit_endpoints(targets, layout.labels, p, layout.plane_channel, channels))
+            .collect();
+        let ideal = ideal_weights(
+            targets,
+            layout.labels,
+            &endpoints,
+            layout.plane_channel,
+            channels,
+        );
+        let spans: Vec<[f32; 2]> = endpoints
+            .iter()
+            .map(|[e0, e1]| {
+                let d = sub(*e1, *e0);
+                let plane0: f32 = (0..4)
+                    .filter(|&c| channels[c] && layout.plane_channel != Some(c))
+                    .map(|c| d[c] * d[c])
+                    .sum();
+                let plane1 = layout.plane_channel.map_or(0.0, |c| d[c] * d[c]);
+                [plane0, plane1]
+            })
+            .collect();
+
+        // estimated error of every usable block mode: weights lost to the
+        // grid, weight quantization and endpoint quantization
+        let planes = 1 + layout.plane_channel.is_some() as usize;
+        let value_count = value_count(layout.cem) * layout.partitions;
+        let span_total: f32 = layout
+            .labels
+            .iter()
+            .map(|&p| spans[p as usize].iter().sum::<f32>())
+            .sum();
+        let mut grid_errors = vec![None; self.grids.len()];
+        let mut ranked: Vec<(f32, &ModeChoice, u32)> = Vec::new();
+        for choice in &self.modes {
+            if choice.mode.dual_plane != layout.plane_channel.is_some()
+                || (layout.partitions == 4 && choice.mode.dual_plane)
+            {
+                continue;
+            }
+            let Some(levels) =
+                self.encoded_color_levels(&choice.mode, layout.partitions, value_count)
+            else {
+                continue;
+            };
+            let grid_error = *grid_errors[choice.grid].get_or_insert_with(|| {
+                let grid = &self.grids[choice.grid];
+                (0..planes)
+                    .map(|plane| {
+                        let values: Vec<f32> = ideal.iter().map(|w| w[plane]).collect();
+                        let restored = infill_values(grid, &decimate(grid, &values, 0));
+                        values
+                            .iter()
+                            .zip(restored)
+                            .zip(layout.labels)
+                            .map(|((v, r), &p)| spans[p as usize][plane] * (v - r).powi(2))
+                            .sum::<f32>()
+                    })
+                    .sum::<f32>()
+            });
+            let weight_step = 1.0 / (choice.mode.weight_levels - 1) as f32;
+            let weight_error = span_total * weight_step * weight_step / 12.0;
+            let color_step = 255.0 / (levels - 1) as f32;
+            let color_error = targets.len() as f32 * 4.0 * color_step * color_step / 12.0;
+            ranked.push((grid_error + weight_error + color_error, choice, levels));
+        }
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut best = ([0; 16], f32::INFINITY);
+        for &(_, choice, levels) in ranked.iter().take(mode_count) {
+            let candidate = self.encode_mode(layout, targets, choice, levels, &endpoints);
+            if candidate.1 < best.1 {
+                best = candidate;
+            }
+        }
+        best
+    }
+
+    /// Color range of a block mode with a shared color endpoint mode.
+    fn encoded_color_levels(
+        &self,
+        mode: &BlockMode,
+        partitions: u32,
+        value_count: u32,
+    ) -> Option<u32> {
+        let start = if partitions == 1 { 17 } else { 29 };
+        let end = (128 - mode.weight_bits()).checked_sub(2 * mode.dual_plane as u32)?;
+        color_levels(value_count, end.checked_sub(start)?)
+    }
+
+    /// Encodes the block with one block mode, refining the endpoints by
+    /// least squares on the decoded weights.
+    fn encode_mode(
+        &self,
+        layout: &Layout,
+        targets: &[Vec4],
+        choice: &ModeChoice,
+        color_levels: u32,
+        initial: &[[Vec4; 2]],
+    ) -> ([u8; 16], f32) {
+        let (refinements, decimation) = match self.quality {
+            Quality::Fast => (0, 0),
+            Quality::Normal => (1, 1),
+            Quality::Slow => (2, 2),
+        };
+        let channels = Self::channels(layout.cem);
+        let grid = &self.grids[choice.grid];
+        let planes = 1 + layout.plane_channel.is_some() as usize;
+        let weight_range = weight_range(choice.mode.weight_levels);
+
+        let mut endpoints = initial.to_vec();
+        let mut best = ([0; 16], f32::INFINITY);
+        for _ in 0..=refinements {
+            let (codes, decoded): (Vec<Vec<u32>>, Vec<[Vec4; 2]>) = endpoints
+                .iter()
+                .map(|e| self.encode_endpoints(layout.cem, e, color_levels))
+                .unzip();
+            let ideal = ideal_weights(
+                targets,
+                layout.labels,
+                &decoded,
+                layout.plane_channel,
+                channels,
+            );
+
+            let grid_count = (grid.width * grid.height) as usize;
+            let mut weight_codes = vec![0; grid_count * planes];
+            let mut effective = vec![[0.0f32; 2]; targets.len()];
+            for plane in 0..planes {
+                let values: Vec<f32> = ideal.iter().map(|w| w[plane]).collect();
+                let grid_values = decimate(grid, &values, decimation);
+                let mut quantized = vec![0.0; grid_count];
+                for (g, value) in grid_values.iter().enumerate() {
+                    let code = weight_range.quantize(value * 64.0);
+                    weight_codes[g * planes + plane] = code;
+                    quantized[g] = weight_range.values[code as usize] as f32;
+                }
+                for (i, contributions) in grid.infill.iter().enumerate() {
+                    let sum: f32 = contributions
+                        .iter()
+                        .map(|&(g, w)| quantized[g] * w as f32)
+                        .sum();
+                    effective[i][plane] = ((sum as u32 + 8) >> 4) as f32 / 64.0;
+                }
+            }
+
+            let bits = self.assemble(layout, choice, &codes.concat(), color_levels, &weight_codes);
+            let error = self.error(bits, targets);
+            if error < best.1 {
+                best = (bits.to_le_bytes(), error);
+            }
+            endpoints = (0..layout.partitions as usize)
+                .map(|p| {
+                    least_squares(
+                        targets,
+                        layout.labels,
+                        p,
+                        &effective,
+                        layout.plane_channel,
+                        channels,
+                    )
+                    .unwrap_or(endpoints[p])
+                })
+                .collect();
+        }
+        best
+    }
+
+    /// Quantized color values of endpoints and the endpoints they decode to.
+    fn encode_endpoints(
+        &self,
+        cem: u32,
+        endpoints: &[Vec4; 2],
+        levels: u32,
+    ) -> (Vec<u32>, [Vec4; 2]) {
+        let range = color_range(levels);
+        let table = &range.values;
+        let [e0, e1] = endpoints;
+        let luminance = |e: &Vec4| (e[0] + e[1] + e[2]) / 3.0;
+        let values: Vec<f32> = match cem {
+            0 => vec![luminance(e0), luminance(e1)],
+            4 => vec![luminance(e0), luminance(e1), e0[3], e1[3]],
+            8 => vec![e0[0], e1[0], e0[1], e1[1], e0[2], e1[2]],
+            12 => vec![e0[0], e1[0], e0[1], e1[1], e0[2], e1[2], e0[3], e1[3]],
+            _ => {
+                // 12 bit logarithmic endpoints
+                let rgb = |e: &Vec4| [e[0] * 16.0, e[1] * 16.0, e[2] * 16.0];
+                let quantized = encode_hdr_rgb(rgb(e0), rgb(e1), range);
+                let mut values: Vec<f32> = quantized.map(|v| v as f32).to_vec();
+                if cem == 14 {
+                    values.extend([e0[3], e1[3]]);
+                }
+                values
+            }
+        };
+        let mut codes: Vec<u32> = values.iter().map(|&v| range.quantize(v)).collect();
+        if cem == 8 || cem == 12 {
+            // keep the sum of endpoint 1 larger, the other order selects blue contraction
+            let sum = |first: usize| {
+                (0..3)
+                    .map(|c| table[codes[2 * c + first] as usize])
+                    .sum::<u32>()
+            };
+            if sum(1) < sum(0) {
+                codes.chunks_exact_mut(2).for_each(|pair| pair.swap(0, 1));
+            }
+        }
+        let unquantized: Vec<i32> = codes
+            .iter()
+            .map(|&code| table[code as usize] as i32)
+            .collect();
+        let (decoded, hdr) = decode_endpoints(cem, &unquantized);
+        let unit = |e: [i32; 4]| -> Vec4 {
+            std::array::from_fn(|c| {
+                if hdr[c] {
+                    e[c] as f32 / 256.0
+                } else {
+                    e[c] as f32
+                }
+            })
+        };
+        (codes, [unit(decoded[0]), unit(decoded[1])])
+    }
+
+    /// Packs the fields of a block with a shared color endpoint mode.
+    fn assemble(
+        &self,
+        layout: &Layout,
+        choice: &ModeChoice,
+        colors: &[u32],
+        color_levels: u32,
+        weights: &[u32],
+    ) -> u128 {
+        let mut bits = choice.bits as u128 | ((layout.partitions - 1) as u128) << 11;
+        let start = if layout.partitions == 1 {
+            bits |= (layout.cem as u128) << 13;
+            17
+        } else {
+            bits |= (layout.seed as u128) << 13 | (layout.cem as u128) << 25;
+            29
+        };
+        let mut end = 128 - choice.mode.weight_bits();
+        if let Some(channel) = layout.plane_channel {
+            end -= 2;
+            bits |= (channel as u128) << end;
+        }
+        debug_assert!(sequence_bits(colors.len() as u32, color_levels) <= end - start);
+        encode_sequence(colors, color_levels, &mut bits, start);
+
+        let mut weight_bits = 0;
+        encode_sequence(weights, choice.mode.weight_levels, &mut weight_bits, 0);
+        bits | weight_bits.reverse_bits()
+    }
+
+    /// Squared error of the decoded block in the space of [`Self::to_unit`].
+    fn error(&self, bits: u128, targets: &[Vec4]) -> f32 {
+        let mut decoded = vec![[0.0; 4]; targets.len()];
+        decode_block(
+            &bits.to_le_bytes(),
+            self.width,
+            self.height,
+            self.profile,
+            &mut decoded,
+        );
+        decoded
+            .iter()
+            .zip(targets)
+            .map(|(&d, t)| {
+                let d = sub(self.to_unit(d), *t);
+                dot(d, d)
+            })
+            .sum()
+    }
+}
+
+/// Color values of HDR RGB endpoints (mode 11) from 12 bit logarithmic
+/// endpoints, in the sub-mode with the least endpoint error once quantized
+/// to `range`, which can change the sub-mode bits.
+fn encode_hdr_rgb(e0: [f32; 3], e1: [f32; 3], range: &Range) -> [i32; 6] {
+    let quantize =
+        |values: &[i32; 6]| values.map(|v| range.values[range.quantize(v as f32) as usize] as i32);
+    let error = |values: &[i32; 6], targets: [[f32; 3]; 2]| -> f32 {
+        let decoded = hdr_rgb(&quantize(values));
+        (0..2)
+            .flat_map(|e| (0..3).map(move |c| (e, c)))
+            .map(|(e, c)| (decoded[e][c] as f32 / 16.0 - targets[e][c]).powi(2))
+            .sum()
+    };
+
+    // every channel directly with 8 or 7 bits, as the fallback
+    let direct = |[r0, g0, b0]: [f32; 3], [r1, g1, b1]: [f32; 3]| {
+        let byte = |c: f32| (c / 16.0).round().clamp(0.0, 255.0) as i32;
+        let seven = |c: f32| (c / 32.0).round().clamp(0.0, 127.0) as i32;
+        [
+            byte(r0),
+            byte(r1),
+            byte(g0),
+            byte(g1),
+            0x80 | seven(b0),
+            0x80 | seven(b1),
+        ]
+    };
+    let mut best = direct(e0, e1);
+    let mut best_error = error(&best, [e0, e1]);
+
+    // the major component is the largest channel of the brighter endpoint
+    let (e0, e1) = if e0.iter().sum::<f32>() > e1.iter().sum::<f32>() {
+        (e1, e0)
+    } else {
+        (e0, e1)
+    };
+    let major = (0..3).max_by(|&a, &b| e1[a].total_cmp(&e1[b])).unwrap();
+    let order = |e: [f32; 3]| -> [f32; 3] {
+        let mut e = e;
+        e.swap(0, major);
+        e
+    };
+    let (s0, s1) = (order(e0), order(e1));
+    for (mode, &[a_bits, b_bits, c_bits, d_bits]) in HDR_RGB_BITS.iter().enumerate() {
+        let scale = (1 << ((mode >> 1) ^ 3)) as f32;
+        let unsigned =
+            |value: f32, bits: u32| (value / scale).round().clamp(0.0, ((1 << bits) - 1) as f32);
+        let d_max = ((1 << (d_bits - 1)) - 1) as f32;
+        let signed = |value: f32| (value / scale).round().clamp(-d_max - 1.0, d_max);
+
+        let a = unsigned(s1[0], a_bits);
+        let base = a * scale;
+        let b0 = unsigned(base - s1[1], b_bits);
+        let b1 = unsigned(base - s1[2], b_bits);
+        let c = unsigned(base - s0[0], c_bits);
+        let d0 = signed(base - b0 * scale - c * scale - s0[1]);
+        let d1 = signed(base - b1 * scale - c * scale - s0[2]);
+        let fields = [a, b0, b1, c, d0, d1].map(|v| v as i32);
+        let values = pack_hdr_rgb(mode as i32, major as i32, fields);
+        let candidate_error = error(&values, [e0, e1]);
+        if candidate_error < best_error {
+            (best, best_error) = (values, candidate_error);
+        }
+    }
+    quantize(&best)
+}
+
+/// Packs the fields of HDR RGB endpoints, the inverse of [`hdr_rgb`].
+fn pack_hdr_rgb(mode: i32, major: i32, [a, b0, b1, c, d0, d1]: [i32; 6]) -> [i32; 6] {
+    let ohm = 1 << mode;
+    let bit = |value: i32, n: u32| (value >> n) & 1;
+    let x0 = if ohm & 0xA4 != 0 {
+        bit(a, 9)
+    } else {
+        bit(b0, 6)
+    };
+    let x1 = if ohm & 0xA0 != 0 {
+        bit(a, 10)
+    } else if ohm & 0x04 != 0 {
+        bit(c, 6)
+    } else {
+        bit(b1, 6)
+    };
+    let x2 = if ohm & 0x08 != 0 {
+        bit(a, 9)
+    } else if ohm & 0xC0 != 0 {
+        bit(a, 11)
+    } else if ohm & 0x20 != 0 {
+        bit(c, 7)
+    } else if ohm & 0x12 != 0 {
+        bit(b0, 7)
+    } else {
+        bit(d0, 6)
+    };
+    let x3 = if ohm & 0xE8 != 0 {
+        bit(c, 6)
+    } else if ohm & 0x12 != 0 {
+        bit(b1, 7)
+    } else {
+        bit(d1, 6)
+    };
+    let (x4, x5) = if ohm & 0x50 != 0 {
+        (bit(a, 9), bit(a, 10))
+    } else {
+        (bit(d0, 5), bit(d1, 5))
+    };
+    [
+        a & 0xFF,
+        (mode & 1) << 7 | bit(a, 8) << 6 | c & 0x3F,
+        (mode >> 1 & 1) << 7 | x0 << 6 | b0 & 0x3F,
+        (mode >> 2 & 1) << 7 | x1 << 6 | b1 & 0x3F,
+        (major & 1) << 7 | x2 << 6 | x4 << 5 | d0 & 0x1F,
+        (major >> 1 & 1) << 7 | x3 << 6 | x5 << 5 | d1 & 0x1F,
+    ]
+}
+
+/// Labels of a k-means clustering of `points`, seeded with mutually
+/// distant points.
+fn kmeans(points: &[Vec4], k: usize) -> Vec<usize> {
+    let distance = |a: &Vec4, b: &Vec4| {
+        let d = sub(*a, *b);
+        dot(d, d)
+    };
+    let nearest = |centers: &[Vec4], p: &Vec4| {
+        (0..centers.len())
+            .min_by(|&a, &b| distance(&centers[a], p).total_cmp(&distance(&centers[b], p)))
+            .unwrap()
+    };
+    let (mean, _) = principal_axis(points);
+    let first = points
+        .iter()
+        .max_by(|a, b| distance(a, &mean).total_cmp(&distance(b, &mean)));
+    let mut centers = vec![*first.unwrap()];
+    while centers.len() < k {
+        let farthest = points.iter().max_by(|a, b| {
+            let to_centers = |p: &Vec4| distance(&centers[nearest(&centers, p)], p);
+            to_centers(a).total_cmp(&to_centers(b))
+        });
+        centers.push(*farthest.unwrap());
+    }
+
+    let mut labels = vec![0; points.len()];
+    for _ in 0..4 {
+        for (label, p) in labels.iter_mut().zip(points) {
+            *label = nearest(&centers, p);
+        }
+        for (k, center) in centers.iter_mut().enumerate() {
+            let members: Vec<Vec4> = points
+                .iter()
+                .zip(&labels)
+                .filter(|&(_, &l)| l == k)
+                .map(|(p, _)| *p)
+                .collect();
+            if !members.is_empty() {
+                *center = principal_axis(&members).0;
+            }
+        }
+    }
+    labels
+}
+
+/// Endpoints spanning the texels of a partition along their principal
+/// axis. The channel of the second plane spans its own range.
+fn fit_endpoints(
+    targets: &[Vec4],
+    labels: &[u8],
+    partition: usize,
+    plane_channel: Option<usize>,
+    channels: [bool; 4],
+) -> [Vec4; 2] {
+    let members: Vec<Vec4> = targets
+        .iter()
+        .zip(labels)
+        .filter(|&(_, &l)| l as usize == partition)
+        .map(|(t, _)| *t)
+        .collect();
+    let plane0 = |c: usize| channels[c] && plane_channel != Some(c);
+    let masked: Vec<Vec4> = members
+        .iter()
+        .map(|t| std::array::from_fn(|c| if plane0(c) { t[c] } else { 0.0 }))
+        .collect();
+    let (mean, axis) = principal_axis(&masked);
+    let projections = masked.iter().map(|t| dot(sub(*t, mean), axis));
+    let (low, high) = projections.fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), t| {
+        (lo.min(t), hi.max(t))
+    });
+    let (all_mean, _) = principal_axis(&members);
+
+    let mut endpoints = [low, high].map(|t| -> Vec4 {
+        std::array::from_fn(|c| {
+            if plane0(c) {
+                mean[c] + axis[c] * t
+            } else {
+                all_mean[c]
+            }
+        })
+    });
+    if let Some(c) = plane_channel {
+        let values = members.iter().map(|t| t[c]);
+        endpoints[0][c] = values.clone().fold(f32::INFINITY, f32::min);
+        endpoints[1][c] = values.fold(f32::NEG_INFINITY, f32::max);
+    }
+    endpoints
+}
+
+/// Weight in `[0, 1]` of every texel on the endpoints of its partition, for
+/// both planes.
+fn ideal_weights(
+    targets: &[Vec4],
+    labels: &[u8],
+    endpoints: &[[Vec4; 2]],
+    plane_channel: Option<usize>,
+    channels: [bool; 4],
+) -> Vec<[f32; 2]> {
+    let project = |t: &Vec4, [e0, e1]: &[Vec4; 2], include: &dyn Fn(usize) -> bool| {
+        let mask =
+            |v: Vec4| -> Vec4 { std::array::from_fn(|c| if include(c) { v[c] } else { 0.0 }) };
+        let d = mask(sub(*e1, *e0));
+        let length = dot(d, d);
+        if length < f32::EPSILON {
+            return 0.0;
+        }
+        (dot(mask(sub(*t, *e0)), d) / length).clamp(0.0, 1.0)
+    };
+    targets
+        .iter()
+        .zip(labels)
+        .map(|(t, &p)| {
+            let e = &endpoints[p as usize];
+            let plane0 = project(t, e, &|c| channels[c] && plane_channel != Some(c));
+            let plane1 = plane_channel.map_or(0.0, |channel| project(t, e, &|c| c == channel));
+            [plane0, plane1]
+        })
+        .collect()
+}
+
+/// Grid values whose infill approximates the texel `values`: a weighted
+/// average of the texels each grid point contributes to, followed by
+/// `iterations` corrections of the residual.
+fn decimate(grid: &Grid, values: &[f32], iterations: usize) -> Vec<f32> {
+    let count = (grid.width * grid.height) as usize;
+    let mut totals = vec![0.0f32; count];
+    for contributions in &grid.infill {
+        for &(g, w) in contributions {
+            totals[g] += w as f32;
+        }
+    }
+    let spread = |residuals: &[f32]| {
+        let mut sums = vec![0.0f32; count];
+        for (contributions, r) in grid.infill.iter().zip(residuals) {
+            for &(g, w) in contributions {
+                sums[g] += w as f32 * r;
+            }
+        }
+        sums.iter()
+            .zip(&totals)
+            .map(|(s, t)| if *t > 0.0 { s / t } else { 0.0 })
+            .collect::<Vec<f32>>()
+    };
+
+    let mut grid_values = spread(values);
+    for _ in 0..iterations {
+        let restored = infill_values(grid, &grid_values);
+        let residuals: Vec<f32> = values.iter().zip(restored).map(|(v, r)| v - r).collect();
+        for (value, correction) in grid_values.iter_mut().zip(spread(&residuals)) {
+            *value = (*value + correction).clamp(0.0, 1.0);
+        }
+    }
+    grid_values
+}
+
+/// Texel values interpolated from grid values.
+fn infill_values(grid: &Grid, grid_values: &[f32]) -> Vec<f32> {
+    grid.infill
+        .iter()
+        .map(|contributions| {
+            contributions
+                .iter()
+                .map(|&(g, w)| grid_values[g] * w as f32)
+                .sum::<f32>()
+                / 16.0
+        })
+        .collect()
+}
+
+/// Endpoints of a partition minimizing the squared error for fixed
+/// weights, `None` when the weights do not determine them.
+fn least_squares(
+    targets: &[Vec4],
+    labels: &[u8],
+    partition: usize,
+    weights: &[[f32; 2]],
+    plane_channel: Option<usize>,
+    channels: [bool; 4],
+) -> Option<[Vec4; 2]> {
+    let mut endpoints = [[0.0; 4]; 2];
+    for c in (0..4).filter(|&c| channels[c]) {
+        let plane = (plane_channel == Some(c)) as usize;
+        let (mut aa, mut bb, mut ab, mut ax, mut bx) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for ((t, w), _) in targets
+            .iter()
+            .zip(weights)
+            .zip(labels)
+            .filter(|&(_, &l)| l as usize == partition)
+        {
+            let beta = w[plane];
+            let alpha = 1.0 - beta;
+            aa += alpha * alpha;
+            bb += beta * beta;
+            ab += alpha * beta;
+            ax += alpha * t[c];
+            bx += beta * t[c];
+        }
+        let det = aa * bb - ab * ab;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        endpoints[0][c] = ((ax * bb - bx * ab) / det).clamp(0.0, 255.0);
+        endpoints[1][c] = ((bx * aa - ax * ab) / det).clamp(0.0, 255.0);
+    }
+    for c in (0..4).filter(|&c| !channels[c]) {
+        endpoints[0][c] = 255.0;
+        endpoints[1][c] = 255.0;
+    }
+    Some(endpoints)
+}
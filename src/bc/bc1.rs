@@ -0,0 +1,238 @@
+use super::{dot, principal_axis, sub, Block, Quality, Vec3};
+
+/// Encodes a 4x4 block to BC1. With `alpha_threshold` set, texels with a
+/// lower alpha are stored as transparent in the 3-color mode, otherwise that
+/// mode may use its black entry.
+pub(crate) fn encode_block(
+    block: &Block,
+    quality: Quality,
+    alpha_threshold: Option<f32>,
+) -> [u8; 8] {
+    let transparent: [bool; 16] =
+        std::array::from_fn(|i| alpha_threshold.is_some_and(|threshold| block[i][3] < threshold));
+    let colors: [Vec3; 16] =
+        std::array::from_fn(|i| [0, 1, 2].map(|c| block[i][c].clamp(0.0, 1.0)));
+    let opaque: Vec<Vec3> = (0..16).filter(|&i| !transparent[i]).map(|i| colors[i]).collect();
+    if opaque.is_empty() {
+        // equal endpoints select the 3-color mode, index 3 is transparent
+        return [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+    }
+
+    let modes: &[bool] = if transparent.contains(&true) {
+        &[true]
+    } else if quality == Quality::Fast {
+        &[false]
+    } else {
+        &[false, true]
+    };
+    let mut best = ([0; 8], f32::INFINITY);
+    for &three_color in modes {
+        let (a, b) = match quality {
+            Quality::Fast => range_fit(&opaque),
+            Quality::Normal | Quality::Slow => cluster_fit(&opaque, three_color),
+        };
+        let (mut c0, mut c1) = (quantize(a), quantize(b));
+        // the order of the endpoints selects the mode
+        if (c0 < c1) != three_color {
+            std::mem::swap(&mut c0, &mut c1);
+        }
+        let candidate = write_block(c0, c1, &colors, &transparent, alpha_threshold.is_none());
+        if candidate.1 < best.1 {
+            best = candidate;
+        }
+    }
+    best.0
+}
+
+/// Decodes a BC1 block. `alpha` selects the RGBA variant, where index 3 of
+/// the 3-color mode is transparent instead of opaque black.
+pub(crate) fn decode_block(data: &[u8], alpha: bool) -> Block {
+    decode(data, alpha, true)
+}
+
+/// Decodes the color half of a BC2 or BC3 block, which always uses the
+/// 4-color mode.
+pub(crate) fn decode_color(data: &[u8]) -> Block {
+    decode(data, false, false)
+}
+
+fn decode(data: &[u8], alpha: bool, three_color: bool) -> Block {
+    let c0 = u16::from_le_bytes([data[0], data[1]]);
+    let c1 = u16::from_le_bytes([data[2], data[3]]);
+    let indices = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let palette = if three_color {
+        palette(c0, c1)
+    } else {
+        four_color_palette(c0, c1)
+    };
+    std::array::from_fn(|i| {
+        let index = (indices >> (2 * i)) as usize & 3;
+        let [r, g, b] = palette[index];
+        if three_color && c0 <= c1 && index == 3 && alpha {
+            [0.0; 4]
+        } else {
+            [r, g, b, 1.0]
+        }
+    })
+}
+
+/// Quantizes a color to RGB565.
+fn quantize(color: Vec3) -> u16 {
+    let r = (color[0].clamp(0.0, 1.0) * 31.0).round() as u16;
+    let g = (color[1].clamp(0.0, 1.0) * 63.0).round() as u16;
+    let b = (color[2].clamp(0.0, 1.0) * 31.0).round() as u16;
+    r << 11 | g << 5 | b
+}
+
+/// Expands RGB565 to 8 bits per channel by bit replication.
+fn expand(color: u16) -> Vec3 {
+    let r = (color >> 11) & 31;
+    let g = (color >> 5) & 63;
+    let b = color & 31;
+    [
+        ((r << 3) | (r >> 2)) as f32 / 255.0,
+        ((g << 2) | (g >> 4)) as f32 / 255.0,
+        ((b << 3) | (b >> 2)) as f32 / 255.0,
+    ]
+}
+
+/// Snaps a color to the nearest RGB565 value.
+fn snap(color: Vec3) -> Vec3 {
+    expand(quantize(color))
+}
+
+/// Palette of a block, in the 4-color mode when `c0 > c1` and in the
+/// 3-color mode with black otherwise.
+fn palette(c0: u16, c1: u16) -> [Vec3; 4] {
+    if c0 > c1 {
+        return four_color_palette(c0, c1);
+    }
+    let (a, b) = (expand(c0), expand(c1));
+    [a, b, [0, 1, 2].map(|c| (a[c] + b[c]) / 2.0), [0.0; 3]]
+}
+
+fn four_color_palette(c0: u16, c1: u16) -> [Vec3; 4] {
+    let (a, b) = (expand(c0), expand(c1));
+    let mix = |wa: f32, wb: f32| [0, 1, 2].map(|c| (a[c] * wa + b[c] * wb) / (wa + wb));
+    [a, b, mix(2.0, 1.0), mix(1.0, 2.0)]
+}
+
+/// Picks the nearest palette entry for every texel and returns the block
+/// with its squared error. Black is only used for opaque texels when
+/// `allow_black` is set.
+fn write_block(
+    c0: u16,
+    c1: u16,
+    colors: &[Vec3; 16],
+    transparent: &[bool; 16],
+    allow_black: bool,
+) -> ([u8; 8], f32) {
+    let palette = palette(c0, c1);
+    let entries = if c0 > c1 || allow_black { 4 } else { 3 };
+    let mut indices = 0u32;
+    let mut error = 0.0;
+    for i in 0..16 {
+        let index = if transparent[i] {
+            3
+        } else {
+            let distances = palette.map(|p| {
+                let d = sub(colors[i], p);
+                dot(d, d)
+            });
+            let index = (0..entries)
+                .min_by(|&x, &y| distances[x].total_cmp(&distances[y]))
+                .unwrap();
+            error += distances[index];
+            index
+        };
+        indices |= (index as u32) << (2 * i);
+    }
+    let [c0_lo, c0_hi] = c0.to_le_bytes();
+    let [c1_lo, c1_hi] = c1.to_le_bytes();
+    let [i0, i1, i2, i3] = indices.to_le_bytes();
+    ([c0_lo, c0_hi, c1_lo, c1_hi, i0, i1, i2, i3], error)
+}
+
+/// Endpoints at the extent of the points along their principal axis.
+fn range_fit(points: &[Vec3]) -> (Vec3, Vec3) {
+    let (mean, axis) = principal_axis(points);
+    let (mut min, mut max) = (0.0f32, 0.0f32);
+    for p in points {
+        let t = dot(sub(*p, mean), axis);
+        min = min.min(t);
+        max = max.max(t);
+    }
+    let at = |t: f32| [0, 1, 2].map(|c| mean[c] + axis[c] * t);
+    (at(max), at(min))
+}
+
+/// Endpoints minimizing the squared error over every split of the points,
+/// ordered along the principal axis, into the 4 (or 3) palette entries.
+/// The error is evaluated with the endpoints snapped to RGB565.
+fn cluster_fit(points: &[Vec3], three_color: bool) -> (Vec3, Vec3) {
+    let (mean, axis) = principal_axis(points);
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|p, q| dot(sub(*p, mean), axis).total_cmp(&dot(sub(*q, mean), axis)));
+
+    let n = sorted.len();
+    let mut prefix = vec![[0.0f32; 3]; n + 1];
+    for (i, p) in sorted.iter().enumerate() {
+        prefix[i + 1] = [0, 1, 2].map(|c| prefix[i][c] + p[c]);
+    }
+    let sum = |from: usize, to: usize| [0, 1, 2].map(|c| prefix[to][c] - prefix[from][c]);
+
+    let mut best = (range_fit(points), f32::INFINITY);
+    let mut evaluate = |clusters: &[(usize, usize, f32)]| {
+        // weights of the endpoints for every cluster: alpha for a, beta for b
+        let (mut aa, mut bb, mut ab) = (0.0, 0.0, 0.0);
+        let (mut ax, mut bx) = ([0.0f32; 3], [0.0f32; 3]);
+        for &(from, to, alpha) in clusters {
+            let count = (to - from) as f32;
+            let beta = 1.0 - alpha;
+            aa += alpha * alpha * count;
+            bb += beta * beta * count;
+            ab += alpha * beta * count;
+            let s = sum(from, to);
+            for c in 0..3 {
+                ax[c] += alpha * s[c];
+                bx[c] += beta * s[c];
+            }
+        }
+        let det = aa * bb - ab * ab;
+        if det.abs() < f32::EPSILON {
+            return;
+        }
+        let a = snap([0, 1, 2].map(|c| (ax[c] * bb - bx[c] * ab) / det));
+        let b = snap([0, 1, 2].map(|c| (bx[c] * aa - ax[c] * ab) / det));
+        // squared error up to the constant sum of the squared points
+        let error = dot(a, a) * aa + dot(b, b) * bb + 2.0 * dot(a, b) * ab
+            - 2.0 * dot(a, ax)
+            - 2.0 * dot(b, bx);
+        if error < best.1 {
+            best = ((a, b), error);
+        }
+    };
+
+    if three_color {
+        for i in 0..=n {
+            for j in i..=n {
+                evaluate(&[(0, i, 0.0), (i, j, 0.5), (j, n, 1.0)]);
+            }
+        }
+    } else {
+        for i in 0..=n {
+            for j in i..=n {
+                for k in j..=n {
+                    evaluate(&[
+                        (0, i, 0.0),
+                        (i, j, 1.0 / 3.0),
+                        (j, k, 2.0 / 3.0),
+                        (k, n, 1.0),
+                    ]);
+                }
+            }
+        }
+    }
+    best.0
+}
+
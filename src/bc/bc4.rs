@@ -0,0 +1,125 @@
+use super::Quality;
+
+/// Encodes 16 values, in `[0, 1]` or `[-1, 1]` when `signed`, to a BC4
+/// block. The fast path interpolates between the extremes, otherwise the
+/// endpoints around them are searched in both modes of the format.
+pub(crate) fn encode_block(values: &[f32; 16], signed: bool, quality: Quality) -> [u8; 8] {
+    let codes = values.map(|v| quantize(v, signed));
+    let (min, max) = (*codes.iter().min().unwrap(), *codes.iter().max().unwrap());
+    if quality == Quality::Fast || min == max {
+        // equal endpoints select the 6 value mode, where the first is exact
+        return write_block(max, min, values, signed).0;
+    }
+
+    // the 6 value mode stores the range limits, fit the values between them
+    let (low, high) = if signed { (-127, 127) } else { (0, 255) };
+    let inner = codes.iter().filter(|&&c| c != low && c != high);
+    let inner_min = inner.clone().min().copied().unwrap_or(low);
+    let inner_max = inner.max().copied().unwrap_or(high);
+
+    let mut best = ([0; 8], f32::INFINITY);
+    for (lo, hi, six_values) in [(min, max, false), (inner_min, inner_max, true)] {
+        for a in (lo - SEARCH_RADIUS).max(low)..=(lo + SEARCH_RADIUS).min(high) {
+            for b in (hi - SEARCH_RADIUS).max(low)..=(hi + SEARCH_RADIUS).min(high) {
+                // the order of the endpoints selects the mode
+                if a >= b {
+                    continue;
+                }
+                let (c0, c1) = if six_values { (a, b) } else { (b, a) };
+                let candidate = write_block(c0, c1, values, signed);
+                if candidate.1 < best.1 {
+                    best = candidate;
+                }
+            }
+        }
+    }
+    best.0
+}
+
+/// Distance in steps of the endpoint codes searched around the extremes.
+const SEARCH_RADIUS: i32 = 3;
+
+/// Quantizes a value to an endpoint code, -127 to 127 when `signed`, 0 to
+/// 255 otherwise.
+fn quantize(value: f32, signed: bool) -> i32 {
+    if signed {
+        (value.clamp(-1.0, 1.0) * 127.0).round() as i32
+    } else {
+        (value.clamp(0.0, 1.0) * 255.0).round() as i32
+    }
+}
+
+fn dequantize(code: i32, signed: bool) -> f32 {
+    code as f32 / if signed { 127.0 } else { 255.0 }
+}
+
+/// Picks the nearest palette entry for every value and returns the block
+/// with its squared error.
+fn write_block(c0: i32, c1: i32, values: &[f32; 16], signed: bool) -> ([u8; 8], f32) {
+    let palette = palette(dequantize(c0, signed), dequantize(c1, signed), signed);
+    let mut indices = 0u64;
+    let mut error = 0.0;
+    for (i, &v) in values.iter().enumerate() {
+        let v = if signed { v.clamp(-1.0, 1.0) } else { v.clamp(0.0, 1.0) };
+        let (index, distance) = palette
+            .iter()
+            .map(|p| (p - v) * (p - v))
+            .enumerate()
+            .min_by(|x, y| x.1.total_cmp(&y.1))
+            .unwrap();
+        error += distance;
+        indices |= (index as u64) << (3 * i);
+    }
+    let mut block = [0u8; 8];
+    block[0] = c0 as u8;
+    block[1] = c1 as u8;
+    block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    (block, error)
+}
+
+/// Decodes a BC4 block, also the alpha half of BC3 and each half of BC5, to
+/// 16 values in `[0, 1]`, or `[-1, 1]` when `signed`.
+pub(crate) fn decode_block(data: &[u8], signed: bool) -> [f32; 16] {
+    let (e0, e1) = if signed {
+        // -128 and -127 both map to -1
+        let snorm = |v: u8| (v as i8).max(-127) as f32 / 127.0;
+        (snorm(data[0]), snorm(data[1]))
+    } else {
+        (data[0] as f32 / 255.0, data[1] as f32 / 255.0)
+    };
+    let palette = palette(e0, e1, signed);
+
+    let mut indices = [0u8; 8];
+    indices[..6].copy_from_slice(&data[2..8]);
+    let indices = u64::from_le_bytes(indices);
+    std::array::from_fn(|i| palette[(indices >> (3 * i)) as usize & 7])
+}
+
+/// The 8 values of a block, interpolating 6 values between the endpoints when
+/// the first is greater, or 4 values followed by the range limits otherwise.
+fn palette(e0: f32, e1: f32, signed: bool) -> [f32; 8] {
+    let mix = |w0: f32, w1: f32, n: f32| (e0 * w0 + e1 * w1) / n;
+    if e0 > e1 {
+        [
+            e0,
+            e1,
+            mix(6.0, 1.0, 7.0),
+            mix(5.0, 2.0, 7.0),
+            mix(4.0, 3.0, 7.0),
+            mix(3.0, 4.0, 7.0),
+            mix(2.0, 5.0, 7.0),
+            mix(1.0, 6.0, 7.0),
+        ]
+    } else {
+        [
+            e0,
+            e1,
+            mix(4.0, 1.0, 5.0),
+            mix(3.0, 2.0, 5.0),
+            mix(2.0, 3.0, 5.0),
+            mix(1.0, 4.0, 5.0),
+            if signed { -1.0 } else { 0.0 },
+            1.0,
+        ]
+    }
+}
@@ -0,0 +1,431 @@
+use super::bc7::{is_anchor, subset, weights};
+use super::{dot, principal_axis, sub, BitReader, BitWriter, Block, Quality, Vec3};
+
+/// Bits of a mode's endpoints in stream order: endpoint, channel, first bit
+/// and bit count. Endpoints 0 and 1 belong to the first region, 2 and 3 to
+/// the second.
+type Layout = &'static [(u8, u8, u8, u8)];
+
+/// Endpoint precision, delta precision of the red, green and blue channels
+/// (equal to the precision for modes without transformed endpoints), whether
+/// the endpoints are deltas and the bit layout of a mode, `None` for the
+/// reserved modes.
+fn mode(mode: u32) -> Option<(u32, [u32; 3], bool, Layout)> {
+    Some(match mode {
+        0x00 => (10, [5, 5, 5], true, layout(mode)),
+        0x01 => (7, [6, 6, 6], true, layout(mode)),
+        0x02 => (11, [5, 4, 4], true, layout(mode)),
+        0x06 => (11, [4, 5, 4], true, layout(mode)),
+        0x0A => (11, [4, 4, 5], true, layout(mode)),
+        0x0E => (9, [5, 5, 5], true, layout(mode)),
+        0x12 => (8, [6, 5, 5], true, layout(mode)),
+        0x16 => (8, [5, 6, 5], true, layout(mode)),
+        0x1A => (8, [5, 5, 6], true, layout(mode)),
+        0x1E => (6, [6, 6, 6], false, layout(mode)),
+        0x03 => (10, [10, 10, 10], false, layout(mode)),
+        0x07 => (11, [9, 9, 9], true, layout(mode)),
+        0x0B => (12, [8, 8, 8], true, layout(mode)),
+        0x0F => (16, [4, 4, 4], true, layout(mode)),
+        _ => return None,
+    })
+}
+
+fn layout(mode: u32) -> Layout {
+    match mode {
+        0x00 => &[
+            (2, 1, 4, 1), (2, 2, 4, 1), (3, 2, 4, 1), (0, 0, 0, 10), (0, 1, 0, 10),
+            (0, 2, 0, 10), (1, 0, 0, 5), (3, 1, 4, 1), (2, 1, 0, 4), (1, 1, 0, 5), (3, 2, 0, 1),
+            (3, 1, 0, 4), (1, 2, 0, 5), (3, 2, 1, 1), (2, 2, 0, 4), (2, 0, 0, 5), (3, 2, 2, 1),
+            (3, 0, 0, 5), (3, 2, 3, 1),
+        ],
+        0x01 => &[
+            (2, 1, 5, 1), (3, 1, 4, 1), (3, 1, 5, 1), (0, 0, 0, 7), (3, 2, 0, 1), (3, 2, 1, 1),
+            (2, 2, 4, 1), (0, 1, 0, 7), (2, 2, 5, 1), (3, 2, 2, 1), (2, 1, 4, 1), (0, 2, 0, 7),
+            (3, 2, 3, 1), (3, 2, 5, 1), (3, 2, 4, 1), (1, 0, 0, 6), (2, 1, 0, 4), (1, 1, 0, 6),
+            (3, 1, 0, 4), (1, 2, 0, 6), (2, 2, 0, 4), (2, 0, 0, 6), (3, 0, 0, 6),
+        ],
+        0x02 => &[
+            (0, 0, 0, 10), (0, 1, 0, 10), (0, 2, 0, 10), (1, 0, 0, 5), (0, 0, 10, 1),
+            (2, 1, 0, 4), (1, 1, 0, 4), (0, 1, 10, 1), (3, 2, 0, 1), (3, 1, 0, 4), (1, 2, 0, 4),
+            (0, 2, 10, 1), (3, 2, 1, 1), (2, 2, 0, 4), (2, 0, 0, 5), (3, 2, 2, 1), (3, 0, 0, 5),
+            (3, 2, 3, 1),
+        ],
+        0x06 => &[
+            (0, 0, 0, 10), (0, 1, 0, 10), (0, 2, 0, 10), (1, 0, 0, 4), (0, 0, 10, 1),
+            (3, 1, 4, 1), (2, 1, 0, 4), (1, 1, 0, 5), (0, 1, 10, 1), (3, 1, 0, 4), (1, 2, 0, 4),
+            (0, 2, 10, 1), (3, 2, 1, 1), (2, 2, 0, 4), (2, 0, 0, 4), (3, 2, 0, 1), (3, 2, 2, 1),
+            (3, 0, 0, 4), (2, 1, 4, 1), (3, 2, 3, 1),
+        ],
+        0x0A => &[
+            (0, 0, 0, 10), (0, 1, 0, 10), (0, 2, 0, 10), (1, 0, 0, 4), (0, 0, 10, 1),
+            (2, 2, 4, 1), (2, 1, 0, 4), (1, 1, 0, 4), (0, 1, 10, 1), (3, 2, 0, 1), (3, 1, 0, 4),
+            (1, 2, 0, 5), (0, 2, 10, 1), (2, 2, 0, 4), (2, 0, 0, 4), (3, 2, 1, 1), (3, 2, 2, 1),
+            (3, 0, 0, 4), (3, 2, 4, 1), (3, 2, 3, 1),
+        ],
+        0x0E => &[
+            (0, 0, 0, 9), (2, 2, 4, 1), (0, 1, 0, 9), (2, 1, 4, 1), (0, 2, 0, 9), (3, 2, 4, 1),
+            (1, 0, 0, 5), (3, 1, 4, 1), (2, 1, 0, 4), (1, 1, 0, 5), (3, 2, 0, 1), (3, 1, 0, 4),
+            (1, 2, 0, 5), (3, 2, 1, 1), (2, 2, 0, 4), (2, 0, 0, 5), (3, 2, 2, 1), (3, 0, 0, 5),
+            (3, 2, 3, 1),
+        ],
+        0x12 => &[
+            (0, 0, 0, 8), (3, 1, 4, 1), (2, 2, 4, 1), (0, 1, 0, 8), (3, 2, 2, 1), (2, 1, 4, 1),
+            (0, 2, 0, 8), (3, 2, 3, 1), (3, 2, 4, 1), (1, 0, 0, 6), (2, 1, 0, 4), (1, 1, 0, 5),
+            (3, 2, 0, 1), (3, 1, 0, 4), (1, 2, 0, 5), (3, 2, 1, 1), (2, 2, 0, 4), (2, 0, 0, 6),
+            (3, 0, 0, 6),
+        ],
+        0x16 => &[
+            (0, 0, 0, 8), (3, 2, 0, 1), (2, 2, 4, 1), (0, 1, 0, 8), (2, 1, 5, 1), (2, 1, 4, 1),
+            (0, 2, 0, 8), (3, 1, 5, 1), (3, 2, 4, 1), (1, 0, 0, 5), (3, 1, 4, 1), (2, 1, 0, 4),
+            (1, 1, 0, 6), (3, 1, 0, 4), (1, 2, 0, 5), (3, 2, 1, 1), (2, 2, 0, 4), (2, 0, 0, 5),
+            (3, 2, 2, 1), (3, 0, 0, 5), (3, 2, 3, 1),
+        ],
+        0x1A => &[
+            (0, 0, 0, 8), (3, 2, 1, 1), (2, 2, 4, 1), (0, 1, 0, 8), (2, 2, 5, 1), (2, 1, 4, 1),
+            (0, 2, 0, 8), (3, 2, 5, 1), (3, 2, 4, 1), (1, 0, 0, 5), (3, 1, 4, 1), (2, 1, 0, 4),
+            (1, 1, 0, 5), (3, 2, 0, 1), (3, 1, 0, 4), (1, 2, 0, 6), (2, 2, 0, 4), (2, 0, 0, 5),
+            (3, 2, 2, 1), (3, 0, 0, 5), (3, 2, 3, 1),
+        ],
+        0x1E => &[
+            (0, 0, 0, 6), (3, 1, 4, 1), (3, 2, 0, 1), (3, 2, 1, 1), (2, 2, 4, 1), (0, 1, 0, 6),
+            (2, 1, 5, 1), (2, 2, 5, 1), (3, 2, 2, 1), (2, 1, 4, 1), (0, 2, 0, 6), (3, 1, 5, 1),
+            (3, 2, 3, 1), (3, 2, 5, 1), (3, 2, 4, 1), (1, 0, 0, 6), (2, 1, 0, 4), (1, 1, 0, 6),
+            (3, 1, 0, 4), (1, 2, 0, 6), (2, 2, 0, 4), (2, 0, 0, 6), (3, 0, 0, 6),
+        ],
+        0x03 => &[
+            (0, 0, 0, 10), (0, 1, 0, 10), (0, 2, 0, 10), (1, 0, 0, 10), (1, 1, 0, 10),
+            (1, 2, 0, 10),
+        ],
+        0x07 => &[
+            (0, 0, 0, 10), (0, 1, 0, 10), (0, 2, 0, 10), (1, 0, 0, 9), (0, 0, 10, 1),
+            (1, 1, 0, 9), (0, 1, 10, 1), (1, 2, 0, 9), (0, 2, 10, 1),
+        ],
+        0x0B => &[
+            (0, 0, 0, 10), (0, 1, 0, 10), (0, 2, 0, 10), (1, 0, 0, 8), (0, 0, 11, 1),
+            (0, 0, 10, 1), (1, 1, 0, 8), (0, 1, 11, 1), (0, 1, 10, 1), (1, 2, 0, 8),
+            (0, 2, 11, 1), (0, 2, 10, 1),
+        ],
+        0x0F => &[
+            (0, 0, 0, 10), (0, 1, 0, 10), (0, 2, 0, 10), (1, 0, 0, 4), (0, 0, 15, 1),
+            (0, 0, 14, 1), (0, 0, 13, 1), (0, 0, 12, 1), (0, 0, 11, 1), (0, 0, 10, 1),
+            (1, 1, 0, 4), (0, 1, 15, 1), (0, 1, 14, 1), (0, 1, 13, 1), (0, 1, 12, 1),
+            (0, 1, 11, 1), (0, 1, 10, 1), (1, 2, 0, 4), (0, 2, 15, 1), (0, 2, 14, 1),
+            (0, 2, 13, 1), (0, 2, 12, 1), (0, 2, 11, 1), (0, 2, 10, 1),
+        ],
+        _ => unreachable!(),
+    }
+}
+
+#[inline]
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+/// Scales an endpoint of `bits` precision to the 16 bit interpolation range.
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 { -unquantized } else { unquantized }
+    } else if bits >= 15 || value == 0 {
+        value
+    } else if value == (1 << bits) - 1 {
+        0xFFFF
+    } else {
+        ((value << 15) + 0x4000) >> (bits - 1)
+    }
+}
+
+/// Scales an interpolated value to the bits of a half float.
+fn finish_unquantize(value: i32, signed: bool) -> u16 {
+    if signed {
+        let magnitude = (value.abs() * 31) >> 5;
+        if value < 0 {
+            0x8000 | magnitude as u16
+        } else {
+            magnitude as u16
+        }
+    } else {
+        ((value * 31) >> 6) as u16
+    }
+}
+
+/// Decodes a BC6H block of the `SFLOAT` variant when `signed`, `UFLOAT`
+/// otherwise. Blocks with a reserved mode decode to black.
+pub(crate) fn decode_block(data: &[u8], signed: bool) -> Block {
+    let mut bits = BitReader::new(data);
+    let mut mode_bits = bits.read(2);
+    if mode_bits > 1 {
+        mode_bits |= bits.read(3) << 2;
+    }
+    let Some((precision, delta_bits, transformed, layout)) = mode(mode_bits) else {
+        return [[0.0, 0.0, 0.0, 1.0]; 16];
+    };
+
+    let mut endpoints = [[0i32; 3]; 4];
+    for &(endpoint, channel, first, count) in layout {
+        endpoints[endpoint as usize][channel as usize] |= (bits.read(count as u32) << first) as i32;
+    }
+    let regions = if mode_bits & 3 == 3 { 1 } else { 2 };
+    let partition = if regions == 2 { bits.read(5) as usize } else { 0 };
+
+    let mask = ((1u64 << precision) - 1) as i32;
+    for c in 0..3 {
+        let base = endpoints[0][c];
+        if signed {
+            endpoints[0][c] = sign_extend(base, precision);
+        }
+        for endpoint in &mut endpoints[1..regions * 2] {
+            if transformed {
+                let delta = sign_extend(endpoint[c], delta_bits[c]);
+                endpoint[c] = (base + delta) & mask;
+            }
+            if signed {
+                endpoint[c] = sign_extend(endpoint[c], precision);
+            }
+        }
+    }
+    for endpoint in &mut endpoints[..regions * 2] {
+        for channel in endpoint.iter_mut() {
+            *channel = unquantize(*channel, precision, signed);
+        }
+    }
+
+    let index_bits = if regions == 2 { 3 } else { 4 };
+    let indices: [u32; 16] = std::array::from_fn(|i| {
+        let anchor = is_anchor(regions, partition, i);
+        bits.read(index_bits - anchor as u32)
+    });
+
+    std::array::from_fn(|i| {
+        let s = subset(regions, partition, i);
+        let weight = weights(index_bits)[indices[i] as usize] as i32;
+        let (e0, e1) = (endpoints[2 * s], endpoints[2 * s + 1]);
+        let [r, g, b] = std::array::from_fn(|c| {
+            let value = (e0[c] * (64 - weight) + e1[c] * weight + 32) >> 6;
+            half::f16::from_bits(finish_unquantize(value, signed)).to_f32()
+        });
+        [r, g, b, 1.0]
+    })
+}
+
+/// Modes with one region, from the lowest to the highest endpoint precision.
+const ONE_REGION_MODES: [u32; 4] = [0x03, 0x07, 0x0B, 0x0F];
+
+const TWO_REGION_MODES: [u32; 10] = [
+    0x00, 0x01, 0x02, 0x06, 0x0A, 0x0E, 0x12, 0x16, 0x1A, 0x1E,
+];
+
+/// Number of partitions, ranked by how well a line fits their regions, that
+/// are encoded with every two region mode, all of them at `Quality::Slow`.
+const PARTITION_CANDIDATES: usize = 4;
+
+/// Encodes the RGB channels of a block to BC6H, `SFLOAT` when `signed` and
+/// `UFLOAT` otherwise. The fast path only tries the one region modes, all
+/// candidates are compared by the error of the decoded block.
+pub(crate) fn encode_block(block: &Block, signed: bool, quality: Quality) -> [u8; 16] {
+    let texels: [Vec3; 16] = block.map(|t| [0, 1, 2].map(|c| to_interpolated(t[c], signed)));
+    let all = [true; 16];
+    let mut candidates: Vec<(u32, usize, Vec<[Vec3; 2]>)> = ONE_REGION_MODES
+        .iter()
+        .map(|&mode| (mode, 0, vec![fit(&texels, &all, signed)]))
+        .collect();
+
+    if quality != Quality::Fast {
+        let mut partitions: Vec<(f32, usize)> = (0..32)
+            .map(|p| {
+                let error = (0..2)
+                    .map(|s| line_error(&texels, &std::array::from_fn(|i| subset(2, p, i) == s)))
+                    .sum();
+                (error, p)
+            })
+            .collect();
+        partitions.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let count = if quality == Quality::Slow { 32 } else { PARTITION_CANDIDATES };
+        for &(_, p) in &partitions[..count] {
+            let fits: Vec<[Vec3; 2]> = (0..2)
+                .map(|s| fit(&texels, &std::array::from_fn(|i| subset(2, p, i) == s), signed))
+                .collect();
+            for &mode in &TWO_REGION_MODES {
+                candidates.push((mode, p, fits.clone()));
+            }
+        }
+    }
+
+    let mut best = ([0; 16], f32::INFINITY);
+    for (mode, partition, endpoints) in candidates {
+        let Some(data) = encode_mode(mode, partition, &endpoints, &texels, signed) else {
+            continue;
+        };
+        let decoded = decode_block(&data, signed);
+        let error: f32 = (0..16)
+            .map(|i| {
+                let d = sub([0, 1, 2].map(|c| to_interpolated(decoded[i][c], signed)), texels[i]);
+                dot(d, d)
+            })
+            .sum();
+        if error < best.1 {
+            best = (data, error);
+        }
+    }
+    best.0
+}
+
+/// Maps a value to the 16 bit range endpoints are interpolated in, the
+/// inverse of [`finish_unquantize`]. Half floats are close to logarithmic,
+/// so errors in this range are close to relative errors.
+fn to_interpolated(value: f32, signed: bool) -> f32 {
+    let bits = half::f16::from_f32(value).to_bits();
+    let magnitude = (bits & 0x7FFF).min(0x7BFF) as f32;
+    let negative = bits & 0x8000 != 0;
+    if value.is_nan() || (negative && !signed) {
+        0.0
+    } else if signed {
+        let v = magnitude * 32.0 / 31.0;
+        if negative { -v } else { v }
+    } else {
+        magnitude * 64.0 / 31.0
+    }
+}
+
+/// Squared distance of the selected texels to the line through them.
+fn line_error(texels: &[Vec3; 16], selected: &[bool; 16]) -> f32 {
+    let points: Vec<Vec3> = (0..16).filter(|&i| selected[i]).map(|i| texels[i]).collect();
+    let (mean, axis) = principal_axis(&points);
+    points
+        .iter()
+        .map(|&p| {
+            let d = sub(p, mean);
+            dot(d, d) - dot(d, axis).powi(2)
+        })
+        .sum()
+}
+
+/// Endpoints at the extent of the selected texels along their principal
+/// axis.
+fn fit(texels: &[Vec3; 16], selected: &[bool; 16], signed: bool) -> [Vec3; 2] {
+    let points: Vec<Vec3> = (0..16).filter(|&i| selected[i]).map(|i| texels[i]).collect();
+    let (mean, axis) = principal_axis(&points);
+    let (mut min, mut max) = (0.0f32, 0.0f32);
+    for &p in &points {
+        let t = dot(sub(p, mean), axis);
+        min = min.min(t);
+        max = max.max(t);
+    }
+    let (low, high) = if signed { (-32767.0, 32767.0) } else { (0.0, 65535.0) };
+    [min, max].map(|t| [0, 1, 2].map(|c| (mean[c] + axis[c] * t).clamp(low, high)))
+}
+
+/// Quantizes an interpolated value to an endpoint of `bits` precision, the
+/// nearest after [`unquantize`].
+fn quantize(value: f32, bits: u32, signed: bool) -> i32 {
+    let (max, scale) = if signed {
+        ((1 << (bits - 1)) - 1, (1 << (bits - 1)) as f32 / 32768.0)
+    } else {
+        (((1u32 << bits) - 1) as i32, (1u64 << bits) as f32 / 65536.0)
+    };
+    let estimate = (value.abs() * scale).round() as i32;
+    let sign = if signed && value < 0.0 { -1 } else { 1 };
+    let quantized = (estimate - 1..=estimate + 1)
+        .map(|q| q.clamp(0, max))
+        .min_by_key(|&q| (unquantize(q * sign, bits, signed) - value.round() as i32).abs())
+        .unwrap();
+    quantized * sign
+}
+
+/// Encodes the block in `mode` with `endpoints` for each region, `None`
+/// when the endpoint deltas do not fit the mode.
+fn encode_mode(
+    mode_bits: u32,
+    partition: usize,
+    endpoints: &[[Vec3; 2]],
+    texels: &[Vec3; 16],
+    signed: bool,
+) -> Option<[u8; 16]> {
+    let (precision, delta_bits, transformed, layout) = mode(mode_bits)?;
+    let regions = endpoints.len();
+    let index_bits = if regions == 2 { 3 } else { 4 };
+    let weights = weights(index_bits);
+    let mut quantized = [[0i32; 3]; 4];
+    for (q, e) in quantized.iter_mut().zip(endpoints.iter().flatten()) {
+        *q = e.map(|v| quantize(v, precision, signed));
+    }
+
+    let mut indices = [0u32; 16];
+    for s in 0..regions {
+        let (e0, e1) = (quantized[2 * s], quantized[2 * s + 1]);
+        let palette: Vec<Vec3> = weights
+            .iter()
+            .map(|&w| {
+                [0, 1, 2].map(|c| {
+                    let a = unquantize(e0[c], precision, signed);
+                    let b = unquantize(e1[c], precision, signed);
+                    ((a * (64 - w as i32) + b * w as i32 + 32) >> 6) as f32
+                })
+            })
+            .collect();
+        for i in (0..16).filter(|&i| subset(regions, partition, i) == s) {
+            indices[i] = (0..palette.len())
+                .min_by(|&x, &y| {
+                    let (dx, dy) = (sub(palette[x], texels[i]), sub(palette[y], texels[i]));
+                    dot(dx, dx).total_cmp(&dot(dy, dy))
+                })
+                .unwrap() as u32;
+        }
+        // the top bit of the anchor index is implied zero, swap the endpoints
+        // and invert the indices of the region otherwise
+        let anchor = (0..16)
+            .find(|&i| subset(regions, partition, i) == s && is_anchor(regions, partition, i))
+            .unwrap();
+        if indices[anchor] >= 1 << (index_bits - 1) {
+            quantized.swap(2 * s, 2 * s + 1);
+            for i in (0..16).filter(|&i| subset(regions, partition, i) == s) {
+                indices[i] = (1 << index_bits) - 1 - indices[i];
+            }
+        }
+    }
+
+    let mut stored = quantized;
+    for endpoint in &mut stored[..2 * regions] {
+        for value in endpoint.iter_mut() {
+            *value &= (1 << precision) - 1;
+        }
+    }
+    if transformed {
+        for (i, endpoint) in stored.iter_mut().enumerate().take(2 * regions).skip(1) {
+            for c in 0..3 {
+                let delta = quantized[i][c] - quantized[0][c];
+                let limit = 1 << (delta_bits[c] - 1);
+                if delta < -limit || delta >= limit {
+                    return None;
+                }
+                endpoint[c] = delta & ((1 << delta_bits[c]) - 1);
+            }
+        }
+    }
+
+    let mut bits = BitWriter::new();
+    bits.write(mode_bits, if mode_bits < 2 { 2 } else { 5 });
+    for &(endpoint, channel, first, count) in layout {
+        let value = stored[endpoint as usize][channel as usize] as u32 >> first;
+        bits.write(value, count as u32);
+    }
+    if regions == 2 {
+        bits.write(partition as u32, 5);
+    }
+    for (i, &index) in indices.iter().enumerate() {
+        let anchor = is_anchor(regions, partition, i);
+        bits.write(index, index_bits - anchor as u32);
+    }
+    Some(bits.finish())
+}
@@ -0,0 +1,751 @@
+use super::{dot, principal_axis, sub, BitReader, BitWriter, Block, Quality};
+
+/// Texel to subset 1 bit masks of the 64 two subset partitions, shared with
+/// BC6H.
+pub(super) const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Subset of every texel in the 64 three subset partitions.
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor texel of the second subset of the two subset partitions, shared
+/// with BC6H.
+pub(super) const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor texels of the second and third subsets of the three subset
+/// partitions.
+const ANCHORS_3: [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+        3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+        8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+        3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+        15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+        15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+        15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+/// Interpolation weights of 2, 3 and 4 bit indices, shared with BC6H.
+pub(super) const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+pub(super) const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+pub(super) const WEIGHTS_4: [u32; 16] = [
+    0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64,
+];
+
+#[inline]
+pub(super) fn weights(index_bits: u32) -> &'static [u32] {
+    match index_bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+/// Layout of one of the 8 BC7 modes.
+struct Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    index_bits_2: u32,
+}
+
+const MODES: [Mode; 8] = [
+    Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 3,
+        index_bits_2: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: true,
+        index_bits: 3,
+        index_bits_2: 0,
+    },
+    Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        index_bits_2: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        index_bits_2: 0,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        index_bits_2: 3,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        index_bits_2: 2,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 4,
+        index_bits_2: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        index_bits_2: 0,
+    },
+];
+
+/// Subset of texel `i` in `partition`.
+#[inline]
+pub(super) fn subset(subsets: usize, partition: usize, i: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> i) as usize & 1,
+        _ => PARTITIONS_3[partition][i] as usize,
+    }
+}
+
+/// Whether texel `i` is the anchor of its subset, whose index is stored
+/// with one bit less.
+#[inline]
+pub(super) fn is_anchor(subsets: usize, partition: usize, i: usize) -> bool {
+    i == 0
+        || match subsets {
+            1 => false,
+            2 => i == ANCHORS_2[partition] as usize,
+            _ => i == ANCHORS_3[0][partition] as usize || i == ANCHORS_3[1][partition] as usize,
+        }
+}
+
+/// Expands an endpoint channel of `bits` bits to 8 bits by bit replication.
+#[inline]
+fn expand(value: u32, bits: u32) -> u32 {
+    if bits >= 8 {
+        value
+    } else {
+        value << (8 - bits) | value >> (2 * bits - 8)
+    }
+}
+
+#[inline]
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u32 {
+    (e0 * (64 - weight) + e1 * weight + 32) >> 6
+}
+
+/// Decodes a BC7 block. Blocks with the reserved mode decode to transparent
+/// black.
+pub(crate) fn decode_block(data: &[u8]) -> Block {
+    let mut bits = BitReader::new(data);
+    let Some(mode_index) = (0..8).find(|_| bits.read(1) == 1) else {
+        return [[0.0; 4]; 16];
+    };
+    let mode = &MODES[mode_index];
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    // channels of every endpoint, stored channel by channel
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for c in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[c] = bits.read(mode.color_bits);
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        endpoint[3] = bits.read(mode.alpha_bits);
+    }
+
+    let (mut color_bits, mut alpha_bits) = (mode.color_bits, mode.alpha_bits);
+    if mode.endpoint_p_bits || mode.shared_p_bits {
+        let p_bits: Vec<u32> = if mode.endpoint_p_bits {
+            (0..endpoint_count).map(|_| bits.read(1)).collect()
+        } else {
+            (0..mode.subsets).flat_map(|_| [bits.read(1); 2]).collect()
+        };
+        for (endpoint, p) in endpoints.iter_mut().zip(p_bits) {
+            for channel in endpoint.iter_mut() {
+                *channel = *channel << 1 | p;
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        for channel in &mut endpoint[..3] {
+            *channel = expand(*channel, color_bits);
+        }
+        endpoint[3] = if alpha_bits > 0 {
+            expand(endpoint[3], alpha_bits)
+        } else {
+            255
+        };
+    }
+
+    let indices: [u32; 16] = std::array::from_fn(|i| {
+        let anchor = is_anchor(mode.subsets, partition, i);
+        bits.read(mode.index_bits - anchor as u32)
+    });
+    let indices_2: [u32; 16] = std::array::from_fn(|i| {
+        if mode.index_bits_2 == 0 {
+            0
+        } else {
+            bits.read(mode.index_bits_2 - (i == 0) as u32)
+        }
+    });
+
+    std::array::from_fn(|i| {
+        let s = subset(mode.subsets, partition, i);
+        let (e0, e1) = (endpoints[2 * s], endpoints[2 * s + 1]);
+        let (color_weight, alpha_weight) = if mode.index_bits_2 == 0 {
+            let weight = weights(mode.index_bits)[indices[i] as usize];
+            (weight, weight)
+        } else if index_selection == 0 {
+            (
+                weights(mode.index_bits)[indices[i] as usize],
+                weights(mode.index_bits_2)[indices_2[i] as usize],
+            )
+        } else {
+            (
+                weights(mode.index_bits_2)[indices_2[i] as usize],
+                weights(mode.index_bits)[indices[i] as usize],
+            )
+        };
+        let mut texel: [u32; 4] = std::array::from_fn(|c| {
+            let weight = if c < 3 { color_weight } else { alpha_weight };
+            interpolate(e0[c], e1[c], weight)
+        });
+        if rotation > 0 {
+            texel.swap(3, rotation as usize - 1);
+        }
+        texel.map(|c| c as f32 / 255.0)
+    })
+}
+
+type Vec4 = [f32; 4];
+
+/// Encodes a block to BC7. The fast preset only uses mode 6, the others
+/// search the modes suited to the block over its best fitting partitions.
+/// With `perceptual`, color errors are weighted by their luma contribution.
+pub(crate) fn encode_block(block: &Block, quality: Quality, perceptual: bool) -> [u8; 16] {
+    let texels: [Vec4; 16] = block.map(|t| t.map(|c| c.clamp(0.0, 1.0) * 255.0));
+    let opaque = texels.iter().all(|t| t[3] == 255.0);
+    let channel_weights = if perceptual {
+        [0.299 * 3.0, 0.587 * 3.0, 0.114 * 3.0, 1.0]
+    } else {
+        [1.0; 4]
+    };
+    let (partitions, refinements, rotations) = match quality {
+        Quality::Fast => (0, 0, 0..1),
+        Quality::Normal => (4, 1, 0..1),
+        Quality::Slow => (16, 2, 0..4),
+    };
+
+    let candidate = |mode, partition, rotation, index_selection| Candidate {
+        mode,
+        partition,
+        rotation,
+        index_selection,
+    };
+    let mut candidates = vec![candidate(6, 0, 0, 0)];
+    if quality != Quality::Fast {
+        if !opaque || quality == Quality::Slow {
+            for rotation in rotations {
+                candidates.extend([
+                    candidate(5, 0, rotation, 0),
+                    candidate(4, 0, rotation, 0),
+                    candidate(4, 0, rotation, 1),
+                ]);
+            }
+        }
+        let two = ranked_partitions(&texels, 2, 64, partitions);
+        if opaque {
+            // modes 1 and 3 store no alpha, mode 0 has 16 partitions
+            for &p in &two {
+                candidates.extend([candidate(1, p, 0, 0), candidate(3, p, 0, 0)]);
+            }
+            for p in ranked_partitions(&texels, 3, 16, partitions / 2) {
+                candidates.push(candidate(0, p, 0, 0));
+            }
+            for p in ranked_partitions(&texels, 3, 64, partitions / 2) {
+                candidates.push(candidate(2, p, 0, 0));
+            }
+        } else {
+            candidates.extend(two.iter().map(|&p| candidate(7, p, 0, 0)));
+        }
+    }
+
+    let mut best = ([0; 16], f32::INFINITY);
+    for candidate in &candidates {
+        let encoded = encode_mode(candidate, &texels, &channel_weights, refinements);
+        if encoded.1 < best.1 {
+            best = encoded;
+        }
+    }
+    best.0
+}
+
+/// The `count` partitions of `subsets` subsets, among the first `available`,
+/// whose subsets are closest to lines.
+fn ranked_partitions(
+    texels: &[Vec4; 16],
+    subsets: usize,
+    available: usize,
+    count: usize,
+) -> Vec<usize> {
+    let mut ranked: Vec<(f32, usize)> = (0..available)
+        .map(|p| {
+            let error = (0..subsets)
+                .map(|s| {
+                    let points: Vec<Vec4> = (0..16)
+                        .filter(|&i| subset(subsets, p, i) == s)
+                        .map(|i| texels[i])
+                        .collect();
+                    let (mean, axis) = principal_axis(&points);
+                    points
+                        .iter()
+                        .map(|&x| {
+                            let d = sub(x, mean);
+                            dot(d, d) - dot(d, axis).powi(2)
+                        })
+                        .sum::<f32>()
+                })
+                .sum();
+            (error, p)
+        })
+        .collect();
+    ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+    ranked.iter().take(count).map(|&(_, p)| p).collect()
+}
+
+/// Endpoints of one subset: the stored values without p-bits, the p-bits,
+/// the indices of every texel for each index set and the squared error.
+struct SubsetFit {
+    values: [[u32; 4]; 2],
+    p_bits: [u32; 2],
+    indices: [[u32; 16]; 2],
+    error: f32,
+}
+
+/// Mode and mode fields of an encoding attempt.
+struct Candidate {
+    mode: usize,
+    partition: usize,
+    rotation: u32,
+    index_selection: u32,
+}
+
+/// Encodes the block as `candidate`, returning the block and its weighted
+/// squared error.
+fn encode_mode(
+    candidate: &Candidate,
+    texels: &[Vec4; 16],
+    channel_weights: &Vec4,
+    refinements: usize,
+) -> ([u8; 16], f32) {
+    let &Candidate {
+        mode: mode_index,
+        partition,
+        rotation,
+        index_selection,
+    } = candidate;
+    let mode = &MODES[mode_index];
+    // the decoder swaps alpha back into place
+    let (mut texels, mut channel_weights) = (*texels, *channel_weights);
+    if rotation > 0 {
+        for texel in &mut texels {
+            texel.swap(rotation as usize - 1, 3);
+        }
+        channel_weights.swap(rotation as usize - 1, 3);
+    }
+
+    // channels and index bits of each index set
+    const COLOR: [bool; 4] = [true, true, true, false];
+    const ALPHA: [bool; 4] = [false, false, false, true];
+    let groups: Vec<([bool; 4], u32)> = if mode.index_bits_2 == 0 {
+        vec![([true, true, true, mode.alpha_bits > 0], mode.index_bits)]
+    } else if index_selection == 0 {
+        vec![(COLOR, mode.index_bits), (ALPHA, mode.index_bits_2)]
+    } else {
+        vec![(COLOR, mode.index_bits_2), (ALPHA, mode.index_bits)]
+    };
+
+    let mut fits = Vec::with_capacity(mode.subsets);
+    for s in 0..mode.subsets {
+        let members: Vec<usize> =
+            (0..16).filter(|&i| subset(mode.subsets, partition, i) == s).collect();
+        let mut endpoints = [[255.0f32; 4]; 2];
+        for (mask, _) in &groups {
+            let points: Vec<Vec4> = members
+                .iter()
+                .map(|&i| std::array::from_fn(|c| if mask[c] { texels[i][c] } else { 0.0 }))
+                .collect();
+            let (mean, axis) = principal_axis(&points);
+            let (mut min, mut max) = (0.0f32, 0.0f32);
+            for &p in &points {
+                let t = dot(sub(p, mean), axis);
+                min = min.min(t);
+                max = max.max(t);
+            }
+            for c in (0..4).filter(|&c| mask[c]) {
+                endpoints[0][c] = mean[c] + axis[c] * min;
+                endpoints[1][c] = mean[c] + axis[c] * max;
+            }
+        }
+
+        let mut best = fit_subset(mode, &groups, &endpoints, &texels, &members, &channel_weights);
+        for _ in 0..refinements {
+            let refined = least_squares(&groups, &best, &texels, &members, &endpoints);
+            let fit = fit_subset(mode, &groups, &refined, &texels, &members, &channel_weights);
+            if fit.error >= best.error {
+                break;
+            }
+            best = fit;
+            endpoints = refined;
+        }
+
+        // the top bit of the anchor index is implied zero, swap the endpoints
+        // and invert the indices of the set otherwise
+        let anchor = *members
+            .iter()
+            .find(|&&i| is_anchor(mode.subsets, partition, i))
+            .unwrap();
+        for (g, (mask, bits)) in groups.iter().enumerate() {
+            if best.indices[g][anchor] >= 1 << (bits - 1) {
+                for c in (0..4).filter(|&c| mask[c] || groups.len() == 1) {
+                    let [a, b] = &mut best.values;
+                    std::mem::swap(&mut a[c], &mut b[c]);
+                }
+                best.p_bits.swap(0, 1);
+                for &i in &members {
+                    best.indices[g][i] = (1 << bits) - 1 - best.indices[g][i];
+                }
+            }
+        }
+        fits.push(best);
+    }
+
+    let mut bits = BitWriter::new();
+    bits.write(1 << mode_index, mode_index as u32 + 1);
+    bits.write(partition as u32, mode.partition_bits);
+    bits.write(rotation, mode.rotation_bits);
+    bits.write(index_selection, mode.index_selection_bits);
+    for c in 0..4 {
+        let channel_bits = if c < 3 { mode.color_bits } else { mode.alpha_bits };
+        for fit in &fits {
+            for value in &fit.values {
+                bits.write(value[c], channel_bits);
+            }
+        }
+    }
+    if mode.endpoint_p_bits {
+        for fit in &fits {
+            bits.write(fit.p_bits[0], 1);
+            bits.write(fit.p_bits[1], 1);
+        }
+    } else if mode.shared_p_bits {
+        for fit in &fits {
+            bits.write(fit.p_bits[0], 1);
+        }
+    }
+    // the set with `index_bits` is stored first
+    let (primary, secondary) = if index_selection == 0 { (0, 1) } else { (1, 0) };
+    let index = |g: usize, i: usize| fits[subset(mode.subsets, partition, i)].indices[g][i];
+    for i in 0..16 {
+        let anchor = is_anchor(mode.subsets, partition, i);
+        bits.write(index(primary, i), mode.index_bits - anchor as u32);
+    }
+    if mode.index_bits_2 > 0 {
+        for i in 0..16 {
+            bits.write(index(secondary, i), mode.index_bits_2 - (i == 0) as u32);
+        }
+    }
+    (bits.finish(), fits.iter().map(|fit| fit.error).sum())
+}
+
+/// Quantizes `endpoints`, choosing the p-bits with the lowest error, and
+/// selects the nearest palette entry of every member texel.
+fn fit_subset(
+    mode: &Mode,
+    groups: &[([bool; 4], u32)],
+    endpoints: &[Vec4; 2],
+    texels: &[Vec4; 16],
+    members: &[usize],
+    channel_weights: &Vec4,
+) -> SubsetFit {
+    let p_choices: &[[u32; 2]] = if mode.endpoint_p_bits {
+        &[[0, 0], [0, 1], [1, 0], [1, 1]]
+    } else if mode.shared_p_bits {
+        &[[0, 0], [1, 1]]
+    } else {
+        &[[0, 0]]
+    };
+    let p_bit = |p: u32| (mode.endpoint_p_bits || mode.shared_p_bits).then_some(p);
+
+    let mut best = (([[0; 4]; 2], [0; 2]), f32::INFINITY);
+    for &p_bits in p_choices {
+        let mut values = [[0; 4]; 2];
+        let mut error = 0.0;
+        for e in 0..2 {
+            for c in 0..4 {
+                let bits = if c < 3 { mode.color_bits } else { mode.alpha_bits };
+                if bits == 0 {
+                    continue;
+                }
+                values[e][c] = quantize(endpoints[e][c], bits, p_bit(p_bits[e]));
+                let value = unquantize(values[e][c], bits, p_bit(p_bits[e])) as f32;
+                error += channel_weights[c] * (value - endpoints[e][c]).powi(2);
+            }
+        }
+        if error < best.1 {
+            best = ((values, p_bits), error);
+        }
+    }
+    let (values, p_bits) = best.0;
+
+    let colors: [[u32; 4]; 2] = std::array::from_fn(|e| {
+        std::array::from_fn(|c| {
+            let bits = if c < 3 { mode.color_bits } else { mode.alpha_bits };
+            if bits == 0 {
+                255
+            } else {
+                unquantize(values[e][c], bits, p_bit(p_bits[e]))
+            }
+        })
+    });
+    let mut fit = SubsetFit {
+        values,
+        p_bits,
+        indices: [[0; 16]; 2],
+        error: 0.0,
+    };
+    for &i in members {
+        for (g, (mask, bits)) in groups.iter().enumerate() {
+            let (index, error) = weights(*bits)
+                .iter()
+                .map(|&w| {
+                    (0..4)
+                        .filter(|&c| mask[c])
+                        .map(|c| {
+                            let value = interpolate(colors[0][c], colors[1][c], w) as f32;
+                            channel_weights[c] * (value - texels[i][c]).powi(2)
+                        })
+                        .sum::<f32>()
+                })
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            fit.indices[g][i] = index as u32;
+            fit.error += error;
+        }
+        // channels without an index set, alpha in the opaque modes
+        for c in (0..4).filter(|&c| groups.iter().all(|(mask, _)| !mask[c])) {
+            fit.error += channel_weights[c] * (colors[0][c] as f32 - texels[i][c]).powi(2);
+        }
+    }
+    fit
+}
+
+/// Endpoints minimizing the squared error of the member texels for the
+/// indices of `fit`, keeping `endpoints` where the system is singular.
+fn least_squares(
+    groups: &[([bool; 4], u32)],
+    fit: &SubsetFit,
+    texels: &[Vec4; 16],
+    members: &[usize],
+    endpoints: &[Vec4; 2],
+) -> [Vec4; 2] {
+    let mut refined = *endpoints;
+    for (g, (mask, bits)) in groups.iter().enumerate() {
+        let (mut aa, mut bb, mut ab) = (0.0, 0.0, 0.0);
+        let (mut ax, mut bx) = ([0.0f32; 4], [0.0f32; 4]);
+        for &i in members {
+            let beta = weights(*bits)[fit.indices[g][i] as usize] as f32 / 64.0;
+            let alpha = 1.0 - beta;
+            aa += alpha * alpha;
+            bb += beta * beta;
+            ab += alpha * beta;
+            for c in 0..4 {
+                ax[c] += alpha * texels[i][c];
+                bx[c] += beta * texels[i][c];
+            }
+        }
+        let det = aa * bb - ab * ab;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        for c in (0..4).filter(|&c| mask[c]) {
+            refined[0][c] = ((ax[c] * bb - bx[c] * ab) / det).clamp(0.0, 255.0);
+            refined[1][c] = ((bx[c] * aa - ax[c] * ab) / det).clamp(0.0, 255.0);
+        }
+    }
+    refined
+}
+
+/// Quantizes an endpoint channel to `bits` bits, followed by `p_bit` when
+/// the mode has one, the nearest after expansion.
+fn quantize(value: f32, bits: u32, p_bit: Option<u32>) -> u32 {
+    let max: i32 = (1 << bits) - 1;
+    let estimate = match p_bit {
+        Some(p) => ((value / 255.0 * ((max << 1) | 1) as f32 - p as f32) / 2.0).round(),
+        None => (value / 255.0 * max as f32).round(),
+    } as i32;
+    (estimate - 1..=estimate + 1)
+        .map(|q| q.clamp(0, max) as u32)
+        .min_by(|&a, &b| {
+            let distance = |q| (unquantize(q, bits, p_bit) as f32 - value).abs();
+            distance(a).total_cmp(&distance(b))
+        })
+        .unwrap()
+}
+
+/// Expands a stored endpoint channel to 8 bits.
+fn unquantize(value: u32, bits: u32, p_bit: Option<u32>) -> u32 {
+    match p_bit {
+        Some(p) => expand(value << 1 | p, bits + 1),
+        None => expand(value, bits),
+    }
+}
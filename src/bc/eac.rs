@@ -0,0 +1,161 @@
+use super::{texel_position, Quality};
+
+/// Modifiers of the 16 tables, scaled by the multiplier of a block.
+const MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// How the values of a block are reconstructed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Variant {
+    /// 8 bit alpha of `ETC2_R8G8B8A8`.
+    Alpha,
+
+    /// 11 bit channels of `EAC_R11` and `EAC_R11G11`, in `[-1, 1]` when
+    /// signed.
+    R11 { signed: bool },
+}
+
+impl Variant {
+    /// Range of the base codeword.
+    fn bases(self) -> std::ops::RangeInclusive<i32> {
+        match self {
+            Variant::R11 { signed: true } => -127..=127,
+            _ => 0..=255,
+        }
+    }
+
+    /// Largest reconstructed value, which maps to 1.
+    fn scale(self) -> f32 {
+        match self {
+            Variant::Alpha => 255.0,
+            Variant::R11 { signed: false } => 2047.0,
+            Variant::R11 { signed: true } => 1023.0,
+        }
+    }
+
+    /// Reconstructed value of a texel with the given modifier.
+    fn value(self, base: i32, multiplier: i32, modifier: i32) -> i32 {
+        // a zero multiplier selects the finest step for 11 bit channels
+        let step = if multiplier == 0 { 1 } else { multiplier * 8 };
+        match self {
+            Variant::Alpha => (base + modifier * multiplier).clamp(0, 255),
+            Variant::R11 { signed: false } => (base * 8 + 4 + modifier * step).clamp(0, 2047),
+            Variant::R11 { signed: true } => (base * 8 + modifier * step).clamp(-1023, 1023),
+        }
+    }
+}
+
+/// Decodes an EAC block to 16 values in `[0, 1]`, or `[-1, 1]` for signed
+/// 11 bit channels.
+pub(crate) fn decode_block(data: &[u8], variant: Variant) -> [f32; 16] {
+    let bits = u64::from_be_bytes(data[..8].try_into().unwrap());
+    let base = match variant {
+        // -128 is decoded as -127
+        Variant::R11 { signed: true } => ((bits >> 56) as u8 as i8).max(-127) as i32,
+        _ => (bits >> 56) as i32,
+    };
+    let multiplier = (bits >> 52) as i32 & 15;
+    let modifiers = &MODIFIERS[(bits >> 48) as usize & 15];
+    std::array::from_fn(|i| {
+        let index = (bits >> (45 - 3 * texel_position(i))) as usize & 7;
+        variant.value(base, multiplier, modifiers[index]) as f32 / variant.scale()
+    })
+}
+
+/// Encodes 16 values to an EAC block. For every table the multiplier and
+/// base covering the range of the values are searched, over a wider
+/// neighborhood with higher quality.
+pub(crate) fn encode_block(values: &[f32; 16], variant: Variant, quality: Quality) -> [u8; 8] {
+    let low = if variant == (Variant::R11 { signed: true }) { -1.0 } else { 0.0 };
+    let targets = values.map(|v| v.clamp(low, 1.0) * variant.scale());
+    let min = targets.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = targets.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let (multiplier_radius, base_radius) = match quality {
+        Quality::Fast => (0, 0),
+        Quality::Normal => (1, 1),
+        Quality::Slow => (2, 3),
+    };
+    // values change by 8 per base and multiplier step in 11 bits
+    let unit = if variant == Variant::Alpha { 1.0 } else { 8.0 };
+
+    let mut best = ([0; 8], f32::INFINITY);
+    for (table, modifiers) in MODIFIERS.iter().enumerate() {
+        let (low, high) = (modifiers[3], modifiers[7]);
+        let estimate = ((max - min) / ((high - low) as f32 * unit)).round() as i32;
+        let range = estimate - multiplier_radius..=estimate + multiplier_radius;
+        let mut multipliers: Vec<i32> = range
+            .map(|m| m.clamp(1, 15))
+            .collect();
+        if variant != Variant::Alpha {
+            multipliers.push(0);
+        }
+        multipliers.dedup();
+
+        for multiplier in multipliers {
+            // base placing the midpoint of the modifiers at the midpoint of the values
+            let step = match (variant, multiplier) {
+                (Variant::Alpha, _) => multiplier as f32,
+                (_, 0) => 1.0,
+                _ => (multiplier * 8) as f32,
+            };
+            let center = (min + max) / 2.0 - (low + high) as f32 / 2.0 * step;
+            let estimate = match variant {
+                Variant::Alpha => center,
+                Variant::R11 { signed: false } => (center - 4.0) / 8.0,
+                Variant::R11 { signed: true } => center / 8.0,
+            }
+            .round() as i32;
+            for base in estimate - base_radius..=estimate + base_radius {
+                if !variant.bases().contains(&base) {
+                    continue;
+                }
+                let candidate = write_block(variant, base, multiplier, table, &targets);
+                if candidate.1 < best.1 {
+                    best = candidate;
+                }
+            }
+        }
+    }
+    best.0
+}
+
+/// Picks the nearest modifier for every value and returns the block with its
+/// squared error.
+fn write_block(
+    variant: Variant,
+    base: i32,
+    multiplier: i32,
+    table: usize,
+    targets: &[f32; 16],
+) -> ([u8; 8], f32) {
+    let palette = MODIFIERS[table].map(|m| variant.value(base, multiplier, m) as f32);
+    let mut bits = (base as u8 as u64) << 56 | (multiplier as u64) << 52 | (table as u64) << 48;
+    let mut error = 0.0;
+    for (i, &target) in targets.iter().enumerate() {
+        let (index, distance) = palette
+            .iter()
+            .map(|p| (p - target) * (p - target))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        error += distance;
+        bits |= (index as u64) << (45 - 3 * texel_position(i));
+    }
+    (bits.to_be_bytes(), error)
+}
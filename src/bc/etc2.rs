@@ -0,0 +1,514 @@
+use super::{dot, principal_axis, sub, texel_position, Block, Quality};
+
+/// Small and large modifier of the 8 intensity tables of the individual and
+/// differential modes.
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+/// Distances of the paint colors in the T and H modes.
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+type Color = [i32; 3];
+
+/// Bit of the 64 bit big-endian block selecting the differential mode, or
+/// marking the block opaque with punch-through alpha.
+const DIFFERENTIAL_BIT: u32 = 33;
+
+#[inline]
+fn field(bits: u64, low: u32, count: u32) -> i32 {
+    ((bits >> low) & ((1 << count) - 1)) as i32
+}
+
+#[inline]
+fn put(bits: &mut u64, low: u32, count: u32, value: i32) {
+    *bits |= (value as u64 & ((1 << count) - 1)) << low;
+}
+
+#[inline]
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+/// Expands a channel of `bits` bits to 8 bits by bit replication.
+#[inline]
+fn expand(value: i32, bits: u32) -> i32 {
+    value << (8 - bits) | value >> (2 * bits - 8)
+}
+
+/// Decodes an ETC2 RGB block, or with `punchthrough` an `ETC2_R8G8B8A1`
+/// block, where the differential bit marks opaque blocks and index 2 of
+/// other blocks is transparent.
+pub(crate) fn decode_block(data: &[u8], punchthrough: bool) -> Block {
+    let bits = u64::from_be_bytes(data[..8].try_into().unwrap());
+    let flag = field(bits, DIFFERENTIAL_BIT, 1) == 1;
+    let transparent_index = punchthrough && !flag;
+    let index = |i: usize| {
+        let p = texel_position(i) as u32;
+        (field(bits, 16 + p, 1) << 1 | field(bits, p, 1)) as usize
+    };
+    let texel = |color: Color| {
+        let [r, g, b] = color.map(|c| c.clamp(0, 255) as f32 / 255.0);
+        [r, g, b, 1.0]
+    };
+
+    if !flag && !punchthrough {
+        let c0 = [60, 52, 44].map(|low| expand(field(bits, low, 4), 4));
+        let c1 = [56, 48, 40].map(|low| expand(field(bits, low, 4), 4));
+        return decode_subblocks(bits, [c0, c1], false);
+    }
+
+    let base = [59, 51, 43].map(|low| field(bits, low, 5));
+    let delta = [56, 48, 40].map(|low| sign_extend(field(bits, low, 3), 3));
+    let overflow: [bool; 3] = std::array::from_fn(|c| !(0..32).contains(&(base[c] + delta[c])));
+    if overflow[0] {
+        // T mode
+        let r0 = field(bits, 59, 2) << 2 | field(bits, 56, 2);
+        let c0 = [r0, field(bits, 52, 4), field(bits, 48, 4)];
+        let c1 = [44, 40, 36].map(|low| field(bits, low, 4));
+        let d = DISTANCES[(field(bits, 34, 2) << 1 | field(bits, 32, 1)) as usize];
+        let palette = t_palette(c0.map(|c| expand(c, 4)), c1.map(|c| expand(c, 4)), d);
+        std::array::from_fn(|i| match index(i) {
+            2 if transparent_index => [0.0; 4],
+            index => texel(palette[index]),
+        })
+    } else if overflow[1] {
+        // H mode
+        let c0 = [
+            field(bits, 59, 4),
+            field(bits, 56, 3) << 1 | field(bits, 52, 1),
+            field(bits, 51, 1) << 3 | field(bits, 47, 3),
+        ];
+        let c1 = [43, 39, 35].map(|low| field(bits, low, 4));
+        let order = (pack(c0) >= pack(c1)) as i32;
+        let d = DISTANCES[(field(bits, 34, 1) << 2 | field(bits, 32, 1) << 1 | order) as usize];
+        let palette = h_palette(c0.map(|c| expand(c, 4)), c1.map(|c| expand(c, 4)), d);
+        std::array::from_fn(|i| match index(i) {
+            2 if transparent_index => [0.0; 4],
+            index => texel(palette[index]),
+        })
+    } else if overflow[2] {
+        let plane = [
+            [
+                field(bits, 57, 6),
+                field(bits, 56, 1) << 6 | field(bits, 49, 6),
+                field(bits, 48, 1) << 5 | field(bits, 43, 2) << 3 | field(bits, 39, 3),
+            ],
+            [field(bits, 34, 5) << 1 | field(bits, 32, 1), field(bits, 25, 7), field(bits, 19, 6)],
+            [field(bits, 13, 6), field(bits, 6, 7), field(bits, 0, 6)],
+        ];
+        let plane = expand_plane(plane);
+        std::array::from_fn(|i| texel(planar_color(&plane, i)))
+    } else {
+        let c0 = base.map(|c| expand(c, 5));
+        let c1 = std::array::from_fn(|c| expand(base[c] + delta[c], 5));
+        decode_subblocks(bits, [c0, c1], transparent_index)
+    }
+}
+
+/// Decodes the two subblocks of the individual and differential modes.
+fn decode_subblocks(bits: u64, colors: [Color; 2], transparent_index: bool) -> Block {
+    let tables = [field(bits, 37, 3), field(bits, 34, 3)];
+    let flip = field(bits, 32, 1) == 1;
+    std::array::from_fn(|i| {
+        let p = texel_position(i) as u32;
+        let index = (field(bits, 16 + p, 1) << 1 | field(bits, p, 1)) as usize;
+        let s = subblock(flip, i);
+        match subblock_palette(colors[s], tables[s] as usize, transparent_index)[index] {
+            Some(color) => {
+                let [r, g, b] = color.map(|c| c as f32 / 255.0);
+                [r, g, b, 1.0]
+            }
+            None => [0.0; 4],
+        }
+    })
+}
+
+/// Subblock of texel `i`, the left and right halves or with `flip` the top
+/// and bottom halves.
+#[inline]
+fn subblock(flip: bool, i: usize) -> usize {
+    let (x, y) = (i % 4, i / 4);
+    if flip { y / 2 } else { x / 2 }
+}
+
+/// Colors of the 4 indices of a subblock, `None` for the transparent index.
+/// With punch-through alpha the small modifiers are zero.
+fn subblock_palette(base: Color, table: usize, transparent_index: bool) -> [Option<Color>; 4] {
+    let [small, large] = MODIFIERS[table];
+    let shade = |m: i32| Some(base.map(|c| (c + m).clamp(0, 255)));
+    if transparent_index {
+        [shade(0), shade(large), None, shade(-large)]
+    } else {
+        [shade(small), shade(large), shade(-small), shade(-large)]
+    }
+}
+
+/// The 12 bit value of a color with 4 bit channels, ordering the H mode
+/// colors.
+#[inline]
+fn pack(color: Color) -> i32 {
+    color[0] << 8 | color[1] << 4 | color[2]
+}
+
+fn t_palette(c0: Color, c1: Color, d: i32) -> [Color; 4] {
+    let shift = |c: Color, d: i32| c.map(|v| (v + d).clamp(0, 255));
+    [c0, shift(c1, d), c1, shift(c1, -d)]
+}
+
+fn h_palette(c0: Color, c1: Color, d: i32) -> [Color; 4] {
+    let shift = |c: Color, d: i32| c.map(|v| (v + d).clamp(0, 255));
+    [shift(c0, d), shift(c0, -d), shift(c1, d), shift(c1, -d)]
+}
+
+/// Expands the origin, horizontal and vertical colors of the planar mode
+/// from 6, 7 and 6 bits per channel.
+fn expand_plane(plane: [Color; 3]) -> [Color; 3] {
+    plane.map(|c| [expand(c[0], 6), expand(c[1], 7), expand(c[2], 6)])
+}
+
+#[inline]
+fn planar_color(plane: &[Color; 3], i: usize) -> Color {
+    let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+    let [o, h, v] = plane;
+    std::array::from_fn(|c| {
+        ((x * (h[c] - o[c]) + y * (v[c] - o[c]) + 4 * o[c] + 2) >> 2).clamp(0, 255)
+    })
+}
+
+/// Encodes a block to ETC2 RGB, or with `alpha_threshold` to
+/// `ETC2_R8G8B8A1` where texels with a lower alpha are transparent. The
+/// individual, differential and planar modes are always tried, the T and H
+/// modes above the fast preset.
+pub(crate) fn encode_block(
+    block: &Block,
+    quality: Quality,
+    alpha_threshold: Option<f32>,
+) -> [u8; 8] {
+    let colors: [Color; 16] =
+        block.map(|t| [0, 1, 2].map(|c| (t[c].clamp(0.0, 1.0) * 255.0).round() as i32));
+    let transparent: [bool; 16] =
+        std::array::from_fn(|i| alpha_threshold.is_some_and(|threshold| block[i][3] < threshold));
+    let punchthrough = alpha_threshold.is_some();
+    let opaque = !transparent.contains(&true);
+    let texels = Texels {
+        colors,
+        transparent,
+        transparent_index: punchthrough && !opaque,
+    };
+    // without punch-through alpha the bit selects the differential mode,
+    // which T, H and planar blocks also set
+    let flag = (opaque as i32) | !punchthrough as i32;
+
+    let mut best = (0u64, i32::MAX);
+    let mut consider = |candidate: (u64, i32)| {
+        if candidate.1 < best.1 {
+            best = candidate;
+        }
+    };
+    for flip in [false, true] {
+        let means: [[f32; 3]; 2] = std::array::from_fn(|s| {
+            let members: Vec<usize> =
+                (0..16).filter(|&i| subblock(flip, i) == s && !transparent[i]).collect();
+            let n = members.len().max(1) as f32;
+            [0, 1, 2].map(|c| members.iter().map(|&i| colors[i][c] as f32).sum::<f32>() / n)
+        });
+        if !punchthrough {
+            let bases = means.map(|m| m.map(|v| quantize(v, 4)));
+            consider(encode_subblocks(&texels, flip, bases, 4, quality, 0));
+        }
+        let bases = means.map(|m| m.map(|v| quantize(v, 5)));
+        consider(encode_subblocks(&texels, flip, bases, 5, quality, flag));
+    }
+    if opaque {
+        consider(encode_planar(&texels));
+    }
+    if quality != Quality::Fast {
+        let points: Vec<[f32; 3]> =
+            (0..16).filter(|&i| !transparent[i]).map(|i| colors[i].map(|c| c as f32)).collect();
+        let (mean, axis) = principal_axis(&points);
+        let clusters: [Color; 2] = std::array::from_fn(|side| {
+            let members: Vec<&[f32; 3]> = points
+                .iter()
+                .filter(|&&p| (dot(sub(p, mean), axis) >= 0.0) == (side == 1))
+                .collect();
+            let n = members.len().max(1) as f32;
+            let cluster_mean = [0, 1, 2].map(|c| members.iter().map(|p| p[c]).sum::<f32>() / n);
+            let cluster_mean = if members.is_empty() { mean } else { cluster_mean };
+            cluster_mean.map(|v| quantize(v, 4))
+        });
+        for d in 0..8 {
+            for [c0, c1] in [clusters, [clusters[1], clusters[0]]] {
+                consider(encode_t(&texels, c0, c1, d, flag));
+                // the lowest bit of the distance orders the colors
+                if (pack(c0) >= pack(c1)) as usize == d & 1 {
+                    consider(encode_h(&texels, c0, c1, d, flag));
+                }
+            }
+        }
+    }
+    best.0.to_be_bytes()
+}
+
+/// Colors and alpha of the texels being encoded.
+struct Texels {
+    colors: [Color; 16],
+    transparent: [bool; 16],
+    transparent_index: bool,
+}
+
+impl Texels {
+    /// Picks the nearest color of `palette` for every opaque texel for which
+    /// `include` holds and index 2 for transparent ones, returning the index
+    /// bits and the squared error.
+    fn select<F>(&self, include: F, palette: &[Option<Color>; 4]) -> (u64, i32)
+    where
+        F: Fn(usize) -> bool,
+    {
+        let mut bits = 0;
+        let mut error = 0;
+        for i in (0..16).filter(|&i| include(i)) {
+            let index = if self.transparent[i] {
+                2
+            } else {
+                let (index, distance) = palette
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, color)| {
+                        Some((index, distance(&(*color)?, &self.colors[i])))
+                    })
+                    .min_by_key(|&(_, distance)| distance)
+                    .unwrap();
+                error += distance;
+                index as i32
+            };
+            let p = texel_position(i) as u32;
+            put(&mut bits, 16 + p, 1, index >> 1);
+            put(&mut bits, p, 1, index & 1);
+        }
+        (bits, error)
+    }
+
+    /// The palette of the T and H modes with the transparent index removed
+    /// when the block has transparent texels.
+    fn paint(&self, palette: [Color; 4]) -> [Option<Color>; 4] {
+        let mut palette = palette.map(Some);
+        if self.transparent_index {
+            palette[2] = None;
+        }
+        palette
+    }
+}
+
+#[inline]
+fn distance(a: &Color, b: &Color) -> i32 {
+    (0..3).map(|c| (a[c] - b[c]).pow(2)).sum()
+}
+
+/// Quantizes a channel to `bits` bits, the nearest after expansion.
+fn quantize(value: f32, bits: u32) -> i32 {
+    let max = (1 << bits) - 1;
+    let estimate = (value / 255.0 * max as f32).round() as i32;
+    (estimate - 1..=estimate + 1)
+        .map(|q| q.clamp(0, max))
+        .min_by(|&a, &b| {
+            let distance = |q| (expand(q, bits) as f32 - value).abs();
+            distance(a).total_cmp(&distance(b))
+        })
+        .unwrap()
+}
+
+/// Encodes the individual mode with 4 bit colors or the differential mode
+/// with 5 bit colors, picking the best table of each subblock. Above the
+/// normal preset the neighbors of each base color are searched too.
+fn encode_subblocks(
+    texels: &Texels,
+    flip: bool,
+    bases: [Color; 2],
+    color_bits: u32,
+    quality: Quality,
+    flag: i32,
+) -> (u64, i32) {
+    let max = (1 << color_bits) - 1;
+    let offsets: &[i32] = if quality == Quality::Slow { &[0, -1, 1] } else { &[0] };
+
+    // best base, table, index bits and error of each subblock
+    let fit = |s: usize, base: Color| {
+        let color = base.map(|c| expand(c, color_bits));
+        (0..8)
+            .map(|table| {
+                let palette = subblock_palette(color, table, texels.transparent_index);
+                let (bits, error) = texels.select(|i| subblock(flip, i) == s, &palette);
+                (base, table, bits, error)
+            })
+            .min_by_key(|fit| fit.3)
+            .unwrap()
+    };
+    let fits: [(Color, usize, u64, i32); 2] = std::array::from_fn(|s| {
+        let mut best = fit(s, bases[s]);
+        for &dr in offsets {
+            for &dg in offsets {
+                for &db in offsets {
+                    if (dr, dg, db) == (0, 0, 0) {
+                        continue;
+                    }
+                    let offset = [dr, dg, db];
+                    let base = std::array::from_fn(|c| (bases[s][c] + offset[c]).clamp(0, max));
+                    let candidate = fit(s, base);
+                    if candidate.3 < best.3 {
+                        best = candidate;
+                    }
+                }
+            }
+        }
+        best
+    });
+
+    let [(c0, table0, bits0, error0), (mut c1, mut table1, mut bits1, mut error1)] = fits;
+    let mut bits = 0;
+    if color_bits == 4 {
+        for (c, low) in [60, 52, 44].into_iter().enumerate() {
+            put(&mut bits, low, 4, c0[c]);
+            put(&mut bits, low - 4, 4, c1[c]);
+        }
+    } else {
+        // the second color is stored as a delta in [-4, 3]
+        let delta: Color = std::array::from_fn(|c| c1[c] - c0[c]);
+        if delta.iter().any(|d| !(-4..=3).contains(d)) {
+            c1 = std::array::from_fn(|c| c0[c] + delta[c].clamp(-4, 3));
+            (c1, table1, bits1, error1) = fit(1, c1);
+        }
+        for (c, low) in [59, 51, 43].into_iter().enumerate() {
+            put(&mut bits, low, 5, c0[c]);
+            put(&mut bits, low - 3, 3, c1[c] - c0[c]);
+        }
+        put(&mut bits, DIFFERENTIAL_BIT, 1, flag);
+    }
+    put(&mut bits, 37, 3, table0 as i32);
+    put(&mut bits, 34, 3, table1 as i32);
+    put(&mut bits, 32, 1, flip as i32);
+    (bits | bits0 | bits1, error0 + error1)
+}
+
+/// Encodes the T mode: `c0` alone and `c1` with the colors at distance `d`.
+fn encode_t(texels: &Texels, c0: Color, c1: Color, d: usize, flag: i32) -> (u64, i32) {
+    let palette = t_palette(c0.map(|c| expand(c, 4)), c1.map(|c| expand(c, 4)), DISTANCES[d]);
+    let (mut bits, error) = texels.select(|_| true, &texels.paint(palette));
+    put(&mut bits, 59, 2, c0[0] >> 2);
+    put(&mut bits, 56, 2, c0[0] & 3);
+    put(&mut bits, 52, 4, c0[1]);
+    put(&mut bits, 48, 4, c0[2]);
+    put(&mut bits, 44, 4, c1[0]);
+    put(&mut bits, 40, 4, c1[1]);
+    put(&mut bits, 36, 4, c1[2]);
+    put(&mut bits, 34, 2, d as i32 >> 1);
+    put(&mut bits, 32, 1, d as i32 & 1);
+    put(&mut bits, DIFFERENTIAL_BIT, 1, flag);
+    // the red base and delta of the differential mode must overflow
+    if (c0[0] >> 2) + (c0[0] & 3) >= 4 {
+        put(&mut bits, 61, 3, 7);
+    } else {
+        put(&mut bits, 58, 1, 1);
+    }
+    (bits, error)
+}
+
+/// Encodes the H mode: both colors with the colors at distance `d`, whose
+/// lowest bit must match the order of the colors.
+fn encode_h(texels: &Texels, c0: Color, c1: Color, d: usize, flag: i32) -> (u64, i32) {
+    let palette = h_palette(c0.map(|c| expand(c, 4)), c1.map(|c| expand(c, 4)), DISTANCES[d]);
+    let (mut bits, error) = texels.select(|_| true, &texels.paint(palette));
+    put(&mut bits, 59, 4, c0[0]);
+    put(&mut bits, 56, 3, c0[1] >> 1);
+    put(&mut bits, 52, 1, c0[1] & 1);
+    put(&mut bits, 51, 1, c0[2] >> 3);
+    put(&mut bits, 47, 3, c0[2] & 7);
+    put(&mut bits, 43, 4, c1[0]);
+    put(&mut bits, 39, 4, c1[1]);
+    put(&mut bits, 35, 4, c1[2]);
+    put(&mut bits, 34, 1, d as i32 >> 2);
+    put(&mut bits, 32, 1, (d as i32 >> 1) & 1);
+    put(&mut bits, DIFFERENTIAL_BIT, 1, flag);
+    // red must stay in range and green must overflow
+    if c0[0] + sign_extend(c0[1] >> 1, 3) < 0 {
+        put(&mut bits, 63, 1, 1);
+    }
+    if ((c0[1] & 1) << 1 | c0[2] >> 3) + ((c0[2] & 7) >> 1) >= 4 {
+        put(&mut bits, 53, 3, 7);
+    } else {
+        put(&mut bits, 50, 1, 1);
+    }
+    (bits, error)
+}
+
+/// Encodes the planar mode with the least squares plane through the texels.
+fn encode_planar(texels: &Texels) -> (u64, i32) {
+    // the texel grid is symmetric around (1.5, 1.5), with 20 as the sum of
+    // squared offsets along each axis
+    let plane: [[f32; 3]; 3] = {
+        let mut sums = [[0.0f32; 3]; 3];
+        for (i, color) in texels.colors.iter().enumerate() {
+            let (dx, dy) = ((i % 4) as f32 - 1.5, (i / 4) as f32 - 1.5);
+            for c in 0..3 {
+                let v = color[c] as f32;
+                sums[0][c] += v / 16.0;
+                sums[1][c] += dx * v / 20.0;
+                sums[2][c] += dy * v / 20.0;
+            }
+        }
+        let [mean, slope_x, slope_y] = sums;
+        let origin: [f32; 3] = std::array::from_fn(|c| mean[c] - 1.5 * (slope_x[c] + slope_y[c]));
+        [
+            origin,
+            std::array::from_fn(|c| origin[c] + 4.0 * slope_x[c]),
+            std::array::from_fn(|c| origin[c] + 4.0 * slope_y[c]),
+        ]
+    };
+    let [o, h, v] = plane.map(|p| [quantize(p[0], 6), quantize(p[1], 7), quantize(p[2], 6)]);
+    let expanded = expand_plane([o, h, v]);
+    let error = (0..16)
+        .map(|i| {
+            let color = planar_color(&expanded, i);
+            (0..3).map(|c| (color[c] - texels.colors[i][c]).pow(2)).sum::<i32>()
+        })
+        .sum();
+
+    let mut bits = 0;
+    put(&mut bits, 57, 6, o[0]);
+    put(&mut bits, 56, 1, o[1] >> 6);
+    put(&mut bits, 49, 6, o[1] & 63);
+    put(&mut bits, 48, 1, o[2] >> 5);
+    put(&mut bits, 43, 2, (o[2] >> 3) & 3);
+    put(&mut bits, 39, 3, o[2] & 7);
+    put(&mut bits, 34, 5, h[0] >> 1);
+    put(&mut bits, 32, 1, h[0] & 1);
+    put(&mut bits, 25, 7, h[1]);
+    put(&mut bits, 19, 6, h[2]);
+    put(&mut bits, 13, 6, v[0]);
+    put(&mut bits, 6, 7, v[1]);
+    put(&mut bits, 0, 6, v[2]);
+    put(&mut bits, DIFFERENTIAL_BIT, 1, 1);
+    // red and green must stay in range and blue must overflow
+    let red_delta = sign_extend((o[0] & 3) << 1 | o[1] >> 6, 3);
+    if (o[0] >> 2) + red_delta < 0 {
+        put(&mut bits, 63, 1, 1);
+    }
+    let green_delta = sign_extend((o[1] & 3) << 1 | o[2] >> 5, 3);
+    if ((o[1] & 63) >> 2) + green_delta < 0 {
+        put(&mut bits, 55, 1, 1);
+    }
+    if ((o[2] >> 3) & 3) + ((o[2] & 7) >> 1) >= 4 {
+        put(&mut bits, 45, 3, 7);
+    } else {
+        put(&mut bits, 42, 1, 1);
+    }
+    (bits, error)
+}
@@ -0,0 +1,240 @@
+pub(crate) mod astc;
+pub(crate) mod bc1;
+pub(crate) mod bc4;
+pub(crate) mod bc6h;
+pub(crate) mod bc7;
+pub(crate) mod eac;
+pub(crate) mod etc2;
+
+use crate::vk_format::VkFormat;
+
+/// Speed and quality trade-off of the block encoders.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quality {
+    /// Endpoints from the extent of the block along its principal axis
+    /// (range fit), in the simplest modes of the format.
+    Fast,
+
+    /// Endpoints from a least squares fit of every ordered clustering of the
+    /// texels (cluster fit) or a search around the extremes, and a search
+    /// over the modes and partitions of the format.
+    #[default]
+    Normal,
+
+    /// Like `Normal`, with every rotation and more partitions and refinement
+    /// steps where the format has them.
+    Slow,
+}
+
+/// Options of [`crate::texture::TextureKtx2::compress`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompressOptions {
+    pub quality: Quality,
+
+    /// Texels with a lower alpha are stored as transparent by formats with
+    /// 1-bit alpha such as `BC1_RGBA_UNORM_BLOCK`.
+    pub alpha_threshold: f32,
+
+    /// Maps color values in `[min, max]`, such as elevation in meters, to
+    /// `[0, 1]`, or `[-1, 1]` for SNORM formats. Values are clamped to the
+    /// range of the format otherwise.
+    pub value_range: Option<(f32, f32)>,
+
+    /// Weights color errors by their contribution to luma, for formats that
+    /// support it such as `BC7_UNORM_BLOCK`.
+    pub perceptual: bool,
+}
+
+impl Default for CompressOptions {
+    fn default() -> Self {
+        Self {
+            quality: Quality::default(),
+            alpha_threshold: 0.5,
+            value_range: None,
+            perceptual: false,
+        }
+    }
+}
+
+/// A 4x4 block of `[r, g, b, a]` texels in row-major order.
+pub(crate) type Block = [[f32; 4]; 16];
+
+type Vec3 = [f32; 3];
+
+#[inline]
+fn sub<const N: usize>(a: [f32; N], b: [f32; N]) -> [f32; N] {
+    std::array::from_fn(|i| a[i] - b[i])
+}
+
+#[inline]
+fn dot<const N: usize>(a: [f32; N], b: [f32; N]) -> f32 {
+    (0..N).map(|i| a[i] * b[i]).sum()
+}
+
+/// Position of texel `i`, in row-major order, in the column-major order of
+/// the index bits of ETC2 and EAC blocks.
+#[inline]
+fn texel_position(i: usize) -> usize {
+    (i % 4) * 4 + i / 4
+}
+
+/// Mean and principal axis of `points`, the axis is zero when all points are
+/// equal.
+fn principal_axis<const N: usize>(points: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let n = points.len().max(1) as f32;
+    let mut mean = [0.0; N];
+    for p in points {
+        for c in 0..N {
+            mean[c] += p[c] / n;
+        }
+    }
+
+    let mut covariance = [[0.0f32; N]; N];
+    for p in points {
+        let d = sub(*p, mean);
+        for i in 0..N {
+            for j in 0..N {
+                covariance[i][j] += d[i] * d[j];
+            }
+        }
+    }
+
+    // power iteration, starting from the row with the largest variance
+    let start = (0..N)
+        .max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b]))
+        .unwrap();
+    let mut axis = covariance[start];
+    for _ in 0..8 {
+        let next = covariance.map(|row| dot(row, axis));
+        let length = dot(next, next).sqrt();
+        if length < f32::EPSILON {
+            return (mean, [0.0; N]);
+        }
+        axis = next.map(|c| c / length);
+    }
+    (mean, axis)
+}
+
+/// Reads the fields of a 128 bit block, least significant bit first.
+struct BitReader {
+    bits: u128,
+}
+
+impl BitReader {
+    fn new(data: &[u8]) -> Self {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&data[..16]);
+        Self {
+            bits: u128::from_le_bytes(bytes),
+        }
+    }
+
+    #[inline]
+    fn read(&mut self, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        let value = (self.bits & ((1u128 << count) - 1)) as u32;
+        self.bits >>= count;
+        value
+    }
+}
+
+/// Writes the fields of a 128 bit block, least significant bit first.
+struct BitWriter {
+    bits: u128,
+    offset: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { bits: 0, offset: 0 }
+    }
+
+    /// Appends the low `count` bits of `value`.
+    #[inline]
+    fn write(&mut self, value: u32, count: u32) {
+        let mask = (1u128 << count) - 1;
+        self.bits |= (value as u128 & mask) << self.offset;
+        self.offset += count;
+    }
+
+    fn finish(self) -> [u8; 16] {
+        debug_assert_eq!(self.offset, 128);
+        self.bits.to_le_bytes()
+    }
+}
+
+/// Decoder of the blocks of a BC format, `None` for other formats.
+pub(crate) fn block_decoder(format: VkFormat) -> Option<fn(&[u8]) -> Block> {
+    Some(match format {
+        VkFormat::BC1_RGB_UNORM_BLOCK | VkFormat::BC1_RGB_SRGB_BLOCK => {
+            |data| bc1::decode_block(data, false)
+        }
+        VkFormat::BC1_RGBA_UNORM_BLOCK | VkFormat::BC1_RGBA_SRGB_BLOCK => {
+            |data| bc1::decode_block(data, true)
+        }
+        VkFormat::BC2_UNORM_BLOCK | VkFormat::BC2_SRGB_BLOCK => |data| {
+            let alpha = u64::from_le_bytes(data[..8].try_into().unwrap());
+            let mut block = bc1::decode_color(&data[8..]);
+            for (i, texel) in block.iter_mut().enumerate() {
+                texel[3] = ((alpha >> (4 * i)) & 15) as f32 / 15.0;
+            }
+            block
+        },
+        VkFormat::BC3_UNORM_BLOCK | VkFormat::BC3_SRGB_BLOCK => |data| {
+            let alpha = bc4::decode_block(&data[..8], false);
+            let mut block = bc1::decode_color(&data[8..]);
+            for (texel, a) in block.iter_mut().zip(alpha) {
+                texel[3] = a;
+            }
+            block
+        },
+        VkFormat::BC4_UNORM_BLOCK => |data| decode_bc4(data, false),
+        VkFormat::BC4_SNORM_BLOCK => |data| decode_bc4(data, true),
+        VkFormat::BC5_UNORM_BLOCK => |data| decode_bc5(data, false),
+        VkFormat::BC5_SNORM_BLOCK => |data| decode_bc5(data, true),
+        VkFormat::BC6H_UFLOAT_BLOCK => |data| bc6h::decode_block(data, false),
+        VkFormat::BC6H_SFLOAT_BLOCK => |data| bc6h::decode_block(data, true),
+        VkFormat::BC7_UNORM_BLOCK | VkFormat::BC7_SRGB_BLOCK => bc7::decode_block,
+        VkFormat::ETC2_R8G8B8_UNORM_BLOCK | VkFormat::ETC2_R8G8B8_SRGB_BLOCK => {
+            |data| etc2::decode_block(data, false)
+        }
+        VkFormat::ETC2_R8G8B8A1_UNORM_BLOCK | VkFormat::ETC2_R8G8B8A1_SRGB_BLOCK => {
+            |data| etc2::decode_block(data, true)
+        }
+        VkFormat::ETC2_R8G8B8A8_UNORM_BLOCK | VkFormat::ETC2_R8G8B8A8_SRGB_BLOCK => |data| {
+            let alpha = eac::decode_block(&data[..8], eac::Variant::Alpha);
+            let mut block = etc2::decode_block(&data[8..], false);
+            for (texel, a) in block.iter_mut().zip(alpha) {
+                texel[3] = a;
+            }
+            block
+        },
+        VkFormat::EAC_R11_UNORM_BLOCK => |data| decode_r11(data, false),
+        VkFormat::EAC_R11_SNORM_BLOCK => |data| decode_r11(data, true),
+        VkFormat::EAC_R11G11_UNORM_BLOCK => |data| decode_r11g11(data, false),
+        VkFormat::EAC_R11G11_SNORM_BLOCK => |data| decode_r11g11(data, true),
+        _ => return None,
+    })
+}
+
+fn decode_bc4(data: &[u8], signed: bool) -> Block {
+    bc4::decode_block(data, signed).map(|r| [r, 0.0, 0.0, 1.0])
+}
+
+fn decode_bc5(data: &[u8], signed: bool) -> Block {
+    let r = bc4::decode_block(&data[..8], signed);
+    let g = bc4::decode_block(&data[8..], signed);
+    std::array::from_fn(|i| [r[i], g[i], 0.0, 1.0])
+}
+
+fn decode_r11(data: &[u8], signed: bool) -> Block {
+    eac::decode_block(data, eac::Variant::R11 { signed }).map(|r| [r, 0.0, 0.0, 1.0])
+}
+
+fn decode_r11g11(data: &[u8], signed: bool) -> Block {
+    let r = eac::decode_block(&data[..8], eac::Variant::R11 { signed });
+    let g = eac::decode_block(&data[8..], eac::Variant::R11 { signed });
+    std::array::from_fn(|i| [r[i], g[i], 0.0, 1.0])
+}
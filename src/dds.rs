@@ -0,0 +1,433 @@
+use std::io::{Cursor, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::pixel::ChannelSource;
+use crate::texture::TextureKtx2;
+use crate::vk_format::{
+    get_format_block_extent, get_format_block_size_bytes, get_format_layout, VkFormat,
+};
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: u32 = 124;
+const PIXEL_FORMAT_SIZE: u32 = 32;
+
+// DDS_HEADER flags
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDSD_DEPTH: u32 = 0x800000;
+
+// DDS_HEADER caps
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xFC00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+// DDS_PIXELFORMAT flags
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+const DDPF_BUMPDUDV: u32 = 0x80000;
+
+// DDS_HEADER_DXT10
+const D3D10_RESOURCE_DIMENSION_TEXTURE1D: u32 = 2;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const D3D10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const DDS_ALPHA_MODE_PREMULTIPLIED: u32 = 2;
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+/// FourCC codes of the legacy header, including the numeric D3DFORMAT codes
+/// of float and 16 bit formats. The first code of a format is written.
+const LEGACY_FOURCC: [(u32, VkFormat); 20] = [
+    (fourcc(b"DXT1"), VkFormat::BC1_RGBA_UNORM_BLOCK),
+    (fourcc(b"DXT3"), VkFormat::BC2_UNORM_BLOCK),
+    (fourcc(b"DXT2"), VkFormat::BC2_UNORM_BLOCK),
+    (fourcc(b"DXT5"), VkFormat::BC3_UNORM_BLOCK),
+    (fourcc(b"DXT4"), VkFormat::BC3_UNORM_BLOCK),
+    (fourcc(b"ATI1"), VkFormat::BC4_UNORM_BLOCK),
+    (fourcc(b"BC4U"), VkFormat::BC4_UNORM_BLOCK),
+    (fourcc(b"BC4S"), VkFormat::BC4_SNORM_BLOCK),
+    (fourcc(b"ATI2"), VkFormat::BC5_UNORM_BLOCK),
+    (fourcc(b"BC5U"), VkFormat::BC5_UNORM_BLOCK),
+    (fourcc(b"BC5S"), VkFormat::BC5_SNORM_BLOCK),
+    (36, VkFormat::R16G16B16A16_UNORM),
+    (110, VkFormat::R16G16B16A16_SNORM),
+    (111, VkFormat::R16_SFLOAT),
+    (112, VkFormat::R16G16_SFLOAT),
+    (113, VkFormat::R16G16B16A16_SFLOAT),
+    (114, VkFormat::R32_SFLOAT),
+    (115, VkFormat::R32G32_SFLOAT),
+    (116, VkFormat::R32G32B32A32_SFLOAT),
+    (fourcc(b"DXT1"), VkFormat::BC1_RGB_UNORM_BLOCK),
+];
+
+/// Legacy pixel formats described by bit masks: pixel format flags, bit
+/// count and `[r, g, b, a]` masks. Files without the alpha mask read as
+/// opaque.
+const LEGACY_MASKS: [(u32, u32, [u32; 4], VkFormat); 14] = [
+    (DDPF_RGB, 32, [0xFF, 0xFF00, 0xFF0000, 0xFF000000], VkFormat::R8G8B8A8_UNORM),
+    (DDPF_RGB, 32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000], VkFormat::B8G8R8A8_UNORM),
+    (DDPF_RGB, 32, [0x3FF, 0xFFC00, 0x3FF00000, 0xC0000000], VkFormat::A2B10G10R10_UNORM_PACK32),
+    (DDPF_RGB, 32, [0x3FF00000, 0xFFC00, 0x3FF, 0xC0000000], VkFormat::A2R10G10B10_UNORM_PACK32),
+    (DDPF_RGB, 32, [0xFFFF, 0xFFFF0000, 0, 0], VkFormat::R16G16_UNORM),
+    (DDPF_RGB, 24, [0xFF0000, 0xFF00, 0xFF, 0], VkFormat::B8G8R8_UNORM),
+    (DDPF_RGB, 16, [0xF800, 0x7E0, 0x1F, 0], VkFormat::R5G6B5_UNORM_PACK16),
+    (DDPF_RGB, 16, [0x7C00, 0x3E0, 0x1F, 0x8000], VkFormat::A1R5G5B5_UNORM_PACK16),
+    (DDPF_RGB, 16, [0xF00, 0xF0, 0xF, 0xF000], VkFormat::A4R4G4B4_UNORM_PACK16_EXT),
+    (DDPF_LUMINANCE, 8, [0xFF, 0, 0, 0], VkFormat::R8_UNORM),
+    (DDPF_LUMINANCE, 16, [0xFFFF, 0, 0, 0], VkFormat::R16_UNORM),
+    (DDPF_LUMINANCE, 16, [0xFF, 0, 0, 0xFF00], VkFormat::R8G8_UNORM),
+    (DDPF_BUMPDUDV, 16, [0xFF, 0xFF00, 0, 0], VkFormat::R8G8_SNORM),
+    (DDPF_BUMPDUDV, 32, [0xFF, 0xFF00, 0xFF0000, 0xFF000000], VkFormat::R8G8B8A8_SNORM),
+];
+
+/// DXGI_FORMAT values of the DX10 header.
+const DXGI_FORMATS: [(u32, VkFormat); 73] = [
+    (2, VkFormat::R32G32B32A32_SFLOAT),
+    (3, VkFormat::R32G32B32A32_UINT),
+    (4, VkFormat::R32G32B32A32_SINT),
+    (6, VkFormat::R32G32B32_SFLOAT),
+    (7, VkFormat::R32G32B32_UINT),
+    (8, VkFormat::R32G32B32_SINT),
+    (10, VkFormat::R16G16B16A16_SFLOAT),
+    (11, VkFormat::R16G16B16A16_UNORM),
+    (12, VkFormat::R16G16B16A16_UINT),
+    (13, VkFormat::R16G16B16A16_SNORM),
+    (14, VkFormat::R16G16B16A16_SINT),
+    (16, VkFormat::R32G32_SFLOAT),
+    (17, VkFormat::R32G32_UINT),
+    (18, VkFormat::R32G32_SINT),
+    (24, VkFormat::A2B10G10R10_UNORM_PACK32),
+    (25, VkFormat::A2B10G10R10_UINT_PACK32),
+    (26, VkFormat::B10G11R11_UFLOAT_PACK32),
+    (28, VkFormat::R8G8B8A8_UNORM),
+    (29, VkFormat::R8G8B8A8_SRGB),
+    (30, VkFormat::R8G8B8A8_UINT),
+    (31, VkFormat::R8G8B8A8_SNORM),
+    (32, VkFormat::R8G8B8A8_SINT),
+    (34, VkFormat::R16G16_SFLOAT),
+    (35, VkFormat::R16G16_UNORM),
+    (36, VkFormat::R16G16_UINT),
+    (37, VkFormat::R16G16_SNORM),
+    (38, VkFormat::R16G16_SINT),
+    (40, VkFormat::D32_SFLOAT),
+    (41, VkFormat::R32_SFLOAT),
+    (42, VkFormat::R32_UINT),
+    (43, VkFormat::R32_SINT),
+    (49, VkFormat::R8G8_UNORM),
+    (50, VkFormat::R8G8_UINT),
+    (51, VkFormat::R8G8_SNORM),
+    (52, VkFormat::R8G8_SINT),
+    (54, VkFormat::R16_SFLOAT),
+    (55, VkFormat::D16_UNORM),
+    (56, VkFormat::R16_UNORM),
+    (57, VkFormat::R16_UINT),
+    (58, VkFormat::R16_SNORM),
+    (59, VkFormat::R16_SINT),
+    (61, VkFormat::R8_UNORM),
+    (62, VkFormat::R8_UINT),
+    (63, VkFormat::R8_SNORM),
+    (64, VkFormat::R8_SINT),
+    (67, VkFormat::E5B9G9R9_UFLOAT_PACK32),
+    (71, VkFormat::BC1_RGBA_UNORM_BLOCK),
+    (72, VkFormat::BC1_RGBA_SRGB_BLOCK),
+    (74, VkFormat::BC2_UNORM_BLOCK),
+    (75, VkFormat::BC2_SRGB_BLOCK),
+    (77, VkFormat::BC3_UNORM_BLOCK),
+    (78, VkFormat::BC3_SRGB_BLOCK),
+    (80, VkFormat::BC4_UNORM_BLOCK),
+    (81, VkFormat::BC4_SNORM_BLOCK),
+    (83, VkFormat::BC5_UNORM_BLOCK),
+    (84, VkFormat::BC5_SNORM_BLOCK),
+    (85, VkFormat::R5G6B5_UNORM_PACK16),
+    (86, VkFormat::A1R5G5B5_UNORM_PACK16),
+    (87, VkFormat::B8G8R8A8_UNORM),
+    (91, VkFormat::B8G8R8A8_SRGB),
+    (95, VkFormat::BC6H_UFLOAT_BLOCK),
+    (96, VkFormat::BC6H_SFLOAT_BLOCK),
+    (98, VkFormat::BC7_UNORM_BLOCK),
+    (99, VkFormat::BC7_SRGB_BLOCK),
+    (115, VkFormat::A4R4G4B4_UNORM_PACK16_EXT),
+    // BC1 without alpha has no DXGI format of its own
+    (71, VkFormat::BC1_RGB_UNORM_BLOCK),
+    (72, VkFormat::BC1_RGB_SRGB_BLOCK),
+    // typeless formats read as UNORM
+    (27, VkFormat::R8G8B8A8_UNORM),
+    (70, VkFormat::BC1_RGBA_UNORM_BLOCK),
+    (73, VkFormat::BC2_UNORM_BLOCK),
+    (76, VkFormat::BC3_UNORM_BLOCK),
+    (94, VkFormat::BC6H_UFLOAT_BLOCK),
+    (97, VkFormat::BC7_UNORM_BLOCK),
+];
+
+impl TextureKtx2 {
+    /// Reads a DDS file with a legacy or DX10 header, including mip chains,
+    /// arrays, cubemaps and volumes. Legacy luminance formats get the
+    /// `KTXswizzle` `rrr1`, or `rrrg` with alpha, and `DXT2`, `DXT4` and the
+    /// premultiplied DX10 alpha mode mark the DFD as premultiplied.
+    pub fn from_dds(bytes: &[u8]) -> Result<TextureKtx2, anyhow::Error> {
+        let mut cursor = Cursor::new(bytes);
+        let mut magic = [0u8; 4];
+        cursor.read_exact(&mut magic)?;
+        let mut header = [0u32; 31];
+        cursor.read_u32_into::<LittleEndian>(&mut header)?;
+        if &magic != DDS_MAGIC || header[0] != HEADER_SIZE {
+            anyhow::bail!("Not a DDS file");
+        }
+        let (height, width, depth, mip_count) = (header[2], header[3], header[5], header[6]);
+        let (pixel_flags, code, bit_count) = (header[19], header[20], header[21]);
+        let masks = [header[22], header[23], header[24], header[25]];
+        let caps2 = header[27];
+        let legacy_dimension = match caps2 & DDSCAPS2_VOLUME {
+            0 => D3D10_RESOURCE_DIMENSION_TEXTURE2D,
+            _ => D3D10_RESOURCE_DIMENSION_TEXTURE3D,
+        };
+
+        let mut premultiplied = false;
+        let mut swizzle = None;
+        let mut opaque_mask = 0;
+        let (format, layers, cubemap, dimension) =
+            if pixel_flags & DDPF_FOURCC != 0 && code == fourcc(b"DX10") {
+                let mut dx10 = [0u32; 5];
+                cursor.read_u32_into::<LittleEndian>(&mut dx10)?;
+                let Some(&(_, format)) = DXGI_FORMATS.iter().find(|&&(dxgi, _)| dxgi == dx10[0])
+                else {
+                    anyhow::bail!("Unsupported DXGI_FORMAT {}", dx10[0]);
+                };
+                premultiplied = dx10[4] & 0x7 == DDS_ALPHA_MODE_PREMULTIPLIED;
+                let cubemap = dx10[2] & D3D10_RESOURCE_MISC_TEXTURECUBE != 0;
+                (format, dx10[3].max(1), cubemap, dx10[1])
+            } else if pixel_flags & DDPF_FOURCC != 0 {
+                let Some(&(_, format)) = LEGACY_FOURCC.iter().find(|&&(c, _)| c == code) else {
+                    let name = String::from_utf8_lossy(&code.to_le_bytes()).into_owned();
+                    anyhow::bail!("Unsupported DDS FourCC {:?} ({})", name, code);
+                };
+                premultiplied = code == fourcc(b"DXT2") || code == fourcc(b"DXT4");
+                (format, 1, caps2 & DDSCAPS2_CUBEMAP != 0, legacy_dimension)
+            } else {
+                let alpha = if pixel_flags & DDPF_ALPHAPIXELS != 0 { masks[3] } else { 0 };
+                let kind = pixel_flags & (DDPF_RGB | DDPF_LUMINANCE | DDPF_BUMPDUDV);
+                let Some(&(_, _, row, format)) = LEGACY_MASKS.iter().find(|(flags, bits, row, _)| {
+                    *flags == kind
+                        && *bits == bit_count
+                        && row[..3] == masks[..3]
+                        && (row[3] == alpha || alpha == 0)
+                }) else {
+                    anyhow::bail!("Unsupported DDS pixel format {:#x} {:x?}", pixel_flags, masks);
+                };
+                opaque_mask = row[3] & !alpha;
+                if kind == DDPF_LUMINANCE {
+                    use ChannelSource::*;
+                    swizzle = Some(if row[3] != 0 { [R, R, R, G] } else { [R, R, R, One] });
+                    opaque_mask = 0;
+                }
+                (format, 1, caps2 & DDSCAPS2_CUBEMAP != 0, legacy_dimension)
+            };
+        let volume = dimension == D3D10_RESOURCE_DIMENSION_TEXTURE3D;
+        let one_dimensional = dimension == D3D10_RESOURCE_DIMENSION_TEXTURE1D;
+        let all_faces = caps2 & DDSCAPS2_CUBEMAP_ALL_FACES == DDSCAPS2_CUBEMAP_ALL_FACES;
+        if cubemap && caps2 & DDSCAPS2_CUBEMAP != 0 && !all_faces {
+            anyhow::bail!("Partial DDS cubemaps are not supported");
+        }
+        let extent = width.max(height).max(if volume { depth } else { 1 }).max(1);
+        if mip_count > extent.ilog2() + 1 {
+            anyhow::bail!("Invalid DDS mip count {} for {}x{}x{}", mip_count, width, height, depth);
+        }
+
+        // DDS stores the mip chain of every layer and face in turn, KTX2
+        // every layer and face of a level in turn. Sizes are checked against
+        // the file before anything is allocated.
+        let faces = if cubemap { 6 } else { 1 };
+        let (block_width, block_height, block_depth) = get_format_block_extent(format);
+        let block_size = get_format_block_size_bytes(format) as u64;
+        let extent_height = if one_dimensional { 1 } else { height };
+        let extent_depth = if volume { depth } else { 1 };
+        let sizes: Option<Vec<u64>> = (0..mip_count.max(1))
+            .map(|level| {
+                let blocks = |size: u32, block: u32| (size >> level).max(1).div_ceil(block) as u64;
+                blocks(width, block_width)
+                    .checked_mul(blocks(extent_height, block_height))?
+                    .checked_mul(blocks(extent_depth, block_depth))?
+                    .checked_mul(block_size)
+            })
+            .collect();
+        let images = layers as u64 * faces;
+        let total = sizes
+            .as_ref()
+            .and_then(|sizes| sizes.iter().try_fold(0u64, |total, &size| total.checked_add(size)))
+            .and_then(|total| total.checked_mul(images));
+        let mut data = &bytes[cursor.position() as usize..];
+        let (Some(sizes), Some(total)) = (sizes, total) else {
+            anyhow::bail!("Invalid DDS size {}x{}x{} with {} images", width, height, depth, images);
+        };
+        if (data.len() as u64) < total {
+            anyhow::bail!("Truncated DDS image data");
+        }
+        let sizes: Vec<usize> = sizes.into_iter().map(|size| size as usize).collect();
+        let images = images as usize;
+
+        let mut texture = TextureKtx2::new(width, height, format);
+        texture.header.pixel_height = if one_dimensional { 0 } else { height };
+        texture.header.pixel_depth = if volume { depth } else { 0 };
+        texture.header.layer_count = if layers > 1 { layers } else { 0 };
+        texture.header.face_count = faces as u32;
+        texture.header.level_count = mip_count.max(1);
+        texture.dfd_descriptor_block[0].set_alpha_premultiplied(premultiplied);
+        texture.level_images =
+            sizes.iter().map(|&size| Vec::with_capacity(size * images)).collect();
+        for _ in 0..images {
+            for (level, &size) in texture.level_images.iter_mut().zip(&sizes) {
+                level.extend_from_slice(&data[..size]);
+                data = &data[size..];
+            }
+        }
+
+        if opaque_mask != 0 {
+            let texel_size = (bit_count / 8) as usize;
+            for level in &mut texture.level_images {
+                for texel in level.chunks_exact_mut(texel_size) {
+                    let mut value = [0u8; 4];
+                    value[..texel_size].copy_from_slice(texel);
+                    let value = u32::from_le_bytes(value) | opaque_mask;
+                    texel.copy_from_slice(&value.to_le_bytes()[..texel_size]);
+                }
+            }
+        }
+        if let Some(swizzle) = swizzle {
+            texture.set_stored_swizzle(swizzle)?;
+        }
+        Ok(texture)
+    }
+
+    /// Writes the texture as a DDS file. The legacy header is used when it
+    /// can describe the format and there is a single layer, the DX10 header
+    /// otherwise, e.g. for sRGB formats, BC6H, BC7 and arrays.
+    pub fn to_dds(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let format = self.header.vk_format;
+        if self.header.supercompression_scheme != 0 {
+            anyhow::bail!("Cannot write supercompressed textures to DDS");
+        }
+        let dfd = &self.dfd_descriptor_block[0];
+        let premultiplied = dfd.is_alpha_premultiplied();
+        let swizzle = self.stored_swizzle()?;
+        let layers = self.header.layer_count.max(1);
+        let cubemap = self.header.face_count == 6;
+        let volume = self.header.pixel_depth > 0;
+        let levels = self.level_images.len() as u32;
+
+        // legacy luminance formats stand for the swizzle they are read with
+        let legacy_masks = LEGACY_MASKS.iter().find(|(flags, _, row, f)| {
+            use ChannelSource::*;
+            let luminance = if row[3] != 0 { [R, R, R, G] } else { [R, R, R, One] };
+            *f == format && (*flags != DDPF_LUMINANCE || swizzle == Some(luminance))
+        });
+        let legacy_fourcc = LEGACY_FOURCC.iter().find(|&&(_, f)| f == format).map(|&(code, _)| {
+            match code {
+                code if code == fourcc(b"DXT3") && premultiplied => fourcc(b"DXT2"),
+                code if code == fourcc(b"DXT5") && premultiplied => fourcc(b"DXT4"),
+                code => code,
+            }
+        });
+        let legacy_premultiplied =
+            legacy_fourcc.is_some_and(|code| code == fourcc(b"DXT2") || code == fourcc(b"DXT4"));
+        let legacy = layers == 1
+            && (legacy_masks.is_some() || legacy_fourcc.is_some())
+            && premultiplied == legacy_premultiplied;
+        let dxgi = DXGI_FORMATS.iter().find(|&&(_, f)| f == format).map(|&(dxgi, _)| dxgi);
+        if !legacy && dxgi.is_none() {
+            anyhow::bail!("{:?} has no DDS equivalent", format);
+        }
+
+        let (width, height, _) = self.level_extent(0);
+        let compressed = get_format_layout(format).is_none();
+        let pitch = if compressed {
+            self.level_byte_length(0) as u32 / (layers * self.header.face_count)
+        } else {
+            width * get_format_block_size_bytes(format)
+        };
+        let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT;
+        flags |= if compressed { DDSD_LINEARSIZE } else { DDSD_PITCH };
+        let mut caps = DDSCAPS_TEXTURE;
+        let mut caps2 = 0;
+        if levels > 1 {
+            flags |= DDSD_MIPMAPCOUNT;
+            caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+        }
+        if cubemap {
+            caps |= DDSCAPS_COMPLEX;
+            caps2 |= DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALL_FACES;
+        }
+        if volume {
+            flags |= DDSD_DEPTH;
+            caps |= DDSCAPS_COMPLEX;
+            caps2 |= DDSCAPS2_VOLUME;
+        }
+
+        let (pixel_flags, code, bit_count, masks) = match (legacy, legacy_masks, legacy_fourcc) {
+            (true, Some(&(kind, bits, row, _)), _) => {
+                let alpha = if row[3] != 0 { DDPF_ALPHAPIXELS } else { 0 };
+                (kind | alpha, 0, bits, row)
+            }
+            (true, None, Some(code)) => (DDPF_FOURCC, code, 0, [0; 4]),
+            _ => (DDPF_FOURCC, fourcc(b"DX10"), 0, [0; 4]),
+        };
+
+        let mut out = Vec::new();
+        out.write_all(DDS_MAGIC)?;
+        let mut header = [0u32; 31];
+        header[0] = HEADER_SIZE;
+        header[1] = flags;
+        header[2] = height;
+        header[3] = width;
+        header[4] = pitch;
+        header[5] = if volume { self.header.pixel_depth } else { 0 };
+        header[6] = levels;
+        header[18] = PIXEL_FORMAT_SIZE;
+        header[19] = pixel_flags;
+        header[20] = code;
+        header[21] = bit_count;
+        header[22..26].copy_from_slice(&masks);
+        header[26] = caps;
+        header[27] = caps2;
+        for word in header {
+            out.write_u32::<LittleEndian>(word)?;
+        }
+        if !legacy {
+            let dimension = if volume {
+                D3D10_RESOURCE_DIMENSION_TEXTURE3D
+            } else if self.header.pixel_height == 0 {
+                D3D10_RESOURCE_DIMENSION_TEXTURE1D
+            } else {
+                D3D10_RESOURCE_DIMENSION_TEXTURE2D
+            };
+            let misc_flag = if cubemap { D3D10_RESOURCE_MISC_TEXTURECUBE } else { 0 };
+            let alpha_mode = if premultiplied { DDS_ALPHA_MODE_PREMULTIPLIED } else { 0 };
+            out.write_u32::<LittleEndian>(dxgi.unwrap())?;
+            out.write_u32::<LittleEndian>(dimension)?;
+            out.write_u32::<LittleEndian>(misc_flag)?;
+            out.write_u32::<LittleEndian>(layers)?;
+            out.write_u32::<LittleEndian>(alpha_mode)?;
+        }
+
+        let images = (layers * self.header.face_count) as usize;
+        for image in 0..images {
+            for level in &self.level_images {
+                let size = level.len() / images;
+                out.write_all(&level[image * size..(image + 1) * size])?;
+            }
+        }
+        Ok(out)
+    }
+}
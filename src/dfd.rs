@@ -1,4 +1,48 @@
-use crate::vk_format::{VkFormat, get_format_pixel_size_bytes};
+use crate::bc::astc::{is_astc, is_astc_hdr};
+use crate::vk_format::{
+    get_format_block_extent, get_format_block_size_bytes, get_format_layout, is_format_srgb,
+    Channel, ChannelLayout, FormatLayout, NumericFormat, VkFormat,
+};
+
+// KHR_DF_MODEL_RGBSDA
+const COLOR_MODEL_RGBSDA: u32 = 1;
+const COLOR_MODEL_BC1A: u32 = 128;
+const COLOR_MODEL_BC2: u32 = 129;
+const COLOR_MODEL_BC3: u32 = 130;
+const COLOR_MODEL_BC4: u32 = 131;
+const COLOR_MODEL_BC5: u32 = 132;
+const COLOR_MODEL_BC6H: u32 = 133;
+const COLOR_MODEL_BC7: u32 = 134;
+const COLOR_MODEL_ETC2: u32 = 161;
+const COLOR_MODEL_ASTC: u32 = 162;
+const COLOR_PRIMARIES_UNSPECIFIED: u32 = 0;
+const COLOR_PRIMARIES_BT709: u32 = 1;
+const TRANSFER_LINEAR: u32 = 1;
+const TRANSFER_SRGB: u32 = 2;
+const FLAG_ALPHA_PREMULTIPLIED: u32 = 1;
+
+// Sample qualifiers, stored with the channel id in the top byte of the first sample word
+const SAMPLE_LINEAR: u32 = 0x10;
+const SAMPLE_EXPONENT: u32 = 0x20;
+const SAMPLE_SIGNED: u32 = 0x40;
+const SAMPLE_FLOAT: u32 = 0x80;
+
+// Channel ids of the block compressed color models
+const CHANNEL_BC1A_COLOR: u32 = 0;
+const CHANNEL_BC1A_ALPHA_PRESENT: u32 = 1;
+const CHANNEL_BC_COLOR: u32 = 0;
+const CHANNEL_BC_ALPHA: u32 = 15;
+const CHANNEL_BC5_RED: u32 = 0;
+const CHANNEL_BC5_GREEN: u32 = 1;
+const CHANNEL_ETC2_RED: u32 = 0;
+const CHANNEL_ETC2_GREEN: u32 = 1;
+const CHANNEL_ETC2_COLOR: u32 = 2;
+const CHANNEL_ETC2_ALPHA: u32 = 15;
+const CHANNEL_ASTC_DATA: u32 = 0;
+
+// IEEE 754 floating-point representation for -1.0f and 1.0f
+const FLOAT_MINUS_ONE: u32 = 0xBF800000;
+const FLOAT_ONE: u32 = 0x3F800000;
 
 #[derive(Clone)]
 #[repr(C)]
@@ -23,140 +67,302 @@ pub struct BasicDataFormatDescriptor {
 }
 
 impl BasicDataFormatDescriptor {
+    /// Describes the format, panicking for those [`Self::try_new`] cannot.
     pub fn new(vk_format: VkFormat) -> Self {
-        match vk_format {
-            VkFormat::R16_SFLOAT => {
-                let samples = vec![DFDSampleType {
-                    row_0: (15 << 16) | 0b11000000 << 24,
-                    row_1: 0u32,
-                    row_2: 0xBF800000u32, // IEEE 754 floating-point representation for -1.0f
-                    row_3: 0x3F800000u32, // IEEE 754 floating-point representation for 1.0f
-                }];
-                let descriptor_block_size =
-                    (24 + std::mem::size_of::<DFDSampleType>() * samples.len()) as u32;
-                BasicDataFormatDescriptor {
-                    dfd_total_size: descriptor_block_size + 4,
-                    row_0: 0u32,
-                    row_1: 2 | descriptor_block_size << 16,
-                    row_2: 1 << 0 | 1 << 8 | 1 << 16,
-                    row_3: 0u32,
-                    row_4: get_format_pixel_size_bytes(vk_format) as u32,
-                    row_5: 0u32,
-                    samples,
-                }
+        Self::try_new(vk_format).unwrap_or_else(|| panic!("Unsupported format {:?}", vk_format))
+    }
+
+    /// Describes uncompressed, BC, ETC2, EAC and ASTC formats, or `None` for
+    /// others such as PVRTC and 4:2:2 formats.
+    pub(crate) fn try_new(vk_format: VkFormat) -> Option<Self> {
+        if let Some(layout) = get_format_layout(vk_format) {
+            return Some(BasicDataFormatDescriptor::from_layout(vk_format, &layout));
+        }
+        Some(match vk_format {
+            VkFormat::BC1_RGB_UNORM_BLOCK | VkFormat::BC1_RGB_SRGB_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_BC1A,
+                    &[(CHANNEL_BC1A_COLOR, 0, 64)],
+                )
             }
-            VkFormat::R16G16B16A16_SFLOAT => {
-                let samples = vec![
-                    // R
-                    DFDSampleType {
-                        row_0: 15 << 16,
-                        row_1: 0u32,
-                        row_2: 0xBF800000u32, // IEEE 754 floating-point representation for -1.0f
-                        row_3: 0x3F800000u32, // IEEE 754 floating-point representation for 1.0f
-                    },
-                    // G
-                    DFDSampleType {
-                        row_0: 16 | 15 << 16 | 0b0000_0001 << 24,
-                        row_1: 0u32,
-                        row_2: 0xBF800000u32, // IEEE 754 floating-point representation for -1.0f
-                        row_3: 0x3F800000u32, // IEEE 754 floating-point representation for 1.0f
-                    },
-                    // B
-                    DFDSampleType {
-                        row_0: 32 | 15 << 16 | 0b0000_0010 << 24,
-                        row_1: 0u32,
-                        row_2: 0xBF800000u32, // IEEE 754 floating-point representation for -1.0f
-                        row_3: 0x3F800000u32, // IEEE 754 floating-point representation for 1.0f
-                    },
-                    // A
-                    DFDSampleType {
-                        row_0: 48 | 15 << 16 | 0b0000_1111 << 24,
-                        row_1: 0u32,
-                        row_2: 0xBF800000u32, // IEEE 754 floating-point representation for -1.0f
-                        row_3: 0x3F800000u32, // IEEE 754 floating-point representation for 1.0f
-                    },
-                ];
-                let descriptor_block_size =
-                    (24 + std::mem::size_of::<DFDSampleType>() * samples.len()) as u32;
-                BasicDataFormatDescriptor {
-                    dfd_total_size: descriptor_block_size + 4,
-                    row_0: 0u32,
-                    row_1: 2 | descriptor_block_size << 16,
-                    row_2: 1 << 0 | 1 << 8 | 1 << 16,
-                    row_3: 0u32,
-                    row_4: get_format_pixel_size_bytes(vk_format) as u32,
-                    row_5: 0u32,
-                    samples,
-                }
+            VkFormat::BC1_RGBA_UNORM_BLOCK | VkFormat::BC1_RGBA_SRGB_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_BC1A,
+                    &[(CHANNEL_BC1A_ALPHA_PRESENT, 0, 64)],
+                )
             }
-            VkFormat::R8G8B8A8_UNORM => {
-                let samples = vec![
-                    // R
-                    DFDSampleType {
-                        row_0: 7 << 16,
-                        row_1: 0u32,
-                        row_2: 0,
-                        row_3: 255,
-                    },
-                    // G
-                    DFDSampleType {
-                        row_0: 8 | 7 << 16 | 0b0000_0001 << 24,
-                        row_1: 0u32,
-                        row_2: 0,
-                        row_3: 255,
-                    },
-                    // B
-                    DFDSampleType {
-                        row_0: 16 | 7 << 16 | 0b0000_0010 << 24,
-                        row_1: 0u32,
-                        row_2: 0,
-                        row_3: 255,
-                    },
-                    // A
-                    DFDSampleType {
-                        row_0: 24 | 7 << 16 | 0b0000_1111 << 24,
-                        row_1: 0u32,
-                        row_2: 0,
-                        row_3: 255,
-                    },
-                ];
-                let descriptor_block_size =
-                    (24 + std::mem::size_of::<DFDSampleType>() * samples.len()) as u32;
-                BasicDataFormatDescriptor {
-                    dfd_total_size: descriptor_block_size + 4,
-                    row_0: 0u32,
-                    row_1: 2 | descriptor_block_size << 16,
-                    row_2: 1 << 0 | 1 << 8 | 1 << 16,
-                    row_3: 0u32,
-                    row_4: get_format_pixel_size_bytes(vk_format) as u32,
-                    row_5: 0u32,
-                    samples,
-                }
+            VkFormat::BC2_UNORM_BLOCK | VkFormat::BC2_SRGB_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_BC2,
+                    &[(CHANNEL_BC_ALPHA, 0, 64), (CHANNEL_BC_COLOR, 64, 64)],
+                )
+            }
+            VkFormat::BC3_UNORM_BLOCK | VkFormat::BC3_SRGB_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_BC3,
+                    &[(CHANNEL_BC_ALPHA, 0, 64), (CHANNEL_BC_COLOR, 64, 64)],
+                )
+            }
+            VkFormat::BC4_UNORM_BLOCK | VkFormat::BC4_SNORM_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_BC4,
+                    &[(CHANNEL_BC_COLOR, 0, 64)],
+                )
+            }
+            VkFormat::BC5_UNORM_BLOCK | VkFormat::BC5_SNORM_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_BC5,
+                    &[(CHANNEL_BC5_RED, 0, 64), (CHANNEL_BC5_GREEN, 64, 64)],
+                )
+            }
+            VkFormat::BC6H_UFLOAT_BLOCK | VkFormat::BC6H_SFLOAT_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_BC6H,
+                    &[(CHANNEL_BC_COLOR, 0, 128)],
+                )
+            }
+            VkFormat::BC7_UNORM_BLOCK | VkFormat::BC7_SRGB_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_BC7,
+                    &[(CHANNEL_BC_COLOR, 0, 128)],
+                )
             }
-            VkFormat::BC1_RGB_UNORM_BLOCK => {
-                let samples = vec![
-                    // R
-                    DFDSampleType {
-                        row_0: 63 << 16,
-                        row_1: 0u32,
-                        row_2: 0,
-                        row_3: u32::MAX,
-                    },
-                ];
-                let descriptor_block_size =
-                    (24 + std::mem::size_of::<DFDSampleType>() * samples.len()) as u32;
-                BasicDataFormatDescriptor {
-                    dfd_total_size: descriptor_block_size + 4,
-                    row_0: 0u32,
-                    row_1: 2 | descriptor_block_size << 16,
-                    row_2: 128 | 1 << 8 | 1 << 16,
-                    row_3: 3 | 3 << 8,
-                    row_4: 8,
-                    row_5: 0u32,
-                    samples,
+            VkFormat::ETC2_R8G8B8_UNORM_BLOCK
+            | VkFormat::ETC2_R8G8B8_SRGB_BLOCK
+            | VkFormat::ETC2_R8G8B8A1_UNORM_BLOCK
+            | VkFormat::ETC2_R8G8B8A1_SRGB_BLOCK => BasicDataFormatDescriptor::from_block(
+                vk_format,
+                COLOR_MODEL_ETC2,
+                &[(CHANNEL_ETC2_COLOR, 0, 64)],
+            ),
+            VkFormat::ETC2_R8G8B8A8_UNORM_BLOCK | VkFormat::ETC2_R8G8B8A8_SRGB_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_ETC2,
+                    &[(CHANNEL_ETC2_ALPHA, 0, 64), (CHANNEL_ETC2_COLOR, 64, 64)],
+                )
+            }
+            VkFormat::EAC_R11_UNORM_BLOCK | VkFormat::EAC_R11_SNORM_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_ETC2,
+                    &[(CHANNEL_ETC2_RED, 0, 64)],
+                )
+            }
+            VkFormat::EAC_R11G11_UNORM_BLOCK | VkFormat::EAC_R11G11_SNORM_BLOCK => {
+                BasicDataFormatDescriptor::from_block(
+                    vk_format,
+                    COLOR_MODEL_ETC2,
+                    &[(CHANNEL_ETC2_RED, 0, 64), (CHANNEL_ETC2_GREEN, 64, 64)],
+                )
+            }
+            _ if is_astc(vk_format) => BasicDataFormatDescriptor::from_block(
+                vk_format,
+                COLOR_MODEL_ASTC,
+                &[(CHANNEL_ASTC_DATA, 0, 128)],
+            ),
+            _ => return None,
+        })
+    }
+
+    /// Describes a block compressed format by the channel id, bit offset and
+    /// bit length of its samples.
+    fn from_block(vk_format: VkFormat, color_model: u32, samples: &[(u32, u32, u32)]) -> Self {
+        let (block_width, block_height, block_depth) = get_format_block_extent(vk_format);
+        let transfer_function = if is_format_srgb(vk_format) {
+            TRANSFER_SRGB
+        } else {
+            TRANSFER_LINEAR
+        };
+
+        // sample ranges follow dfdutils: [-1, 1] for float, the full signed
+        // or unsigned integer range otherwise
+        let (qualifiers, lower, upper) = match vk_format {
+            VkFormat::BC6H_UFLOAT_BLOCK => (SAMPLE_FLOAT, FLOAT_MINUS_ONE, FLOAT_ONE),
+            VkFormat::BC6H_SFLOAT_BLOCK => {
+                (SAMPLE_FLOAT | SAMPLE_SIGNED, FLOAT_MINUS_ONE, FLOAT_ONE)
+            }
+            VkFormat::BC4_SNORM_BLOCK
+            | VkFormat::BC5_SNORM_BLOCK
+            | VkFormat::EAC_R11_SNORM_BLOCK
+            | VkFormat::EAC_R11G11_SNORM_BLOCK => (SAMPLE_SIGNED, 0x80000000, 0x7FFFFFFF),
+            _ if is_astc_hdr(vk_format) => {
+                (SAMPLE_FLOAT | SAMPLE_SIGNED, FLOAT_MINUS_ONE, FLOAT_ONE)
+            }
+            _ => (0, 0, u32::MAX),
+        };
+
+        let samples = samples
+            .iter()
+            .map(|&(channel, bit_offset, bit_length)| DFDSampleType {
+                row_0: bit_offset | (bit_length - 1) << 16 | (channel | qualifiers) << 24,
+                row_1: 0u32,
+                row_2: lower,
+                row_3: upper,
+            })
+            .collect::<Vec<_>>();
+        let descriptor_block_size =
+            (24 + std::mem::size_of::<DFDSampleType>() * samples.len()) as u32;
+        BasicDataFormatDescriptor {
+            dfd_total_size: descriptor_block_size + 4,
+            row_0: 0u32,
+            row_1: 2 | descriptor_block_size << 16,
+            row_2: color_model | COLOR_PRIMARIES_BT709 << 8 | transfer_function << 16,
+            row_3: (block_width - 1) | (block_height - 1) << 8 | (block_depth - 1) << 16,
+            row_4: get_format_block_size_bytes(vk_format),
+            row_5: 0u32,
+            samples,
+        }
+    }
+
+    /// KHR_DF_TRANSFER value of the descriptor.
+    pub fn transfer_function(&self) -> u32 {
+        (self.row_2 >> 16) & 0xFF
+    }
+
+    /// Whether color values are encoded with the sRGB transfer function.
+    pub fn is_srgb(&self) -> bool {
+        self.transfer_function() == TRANSFER_SRGB
+    }
+
+    /// Whether color values are premultiplied by alpha.
+    pub fn is_alpha_premultiplied(&self) -> bool {
+        (self.row_2 >> 24) & FLAG_ALPHA_PREMULTIPLIED != 0
+    }
+
+    /// Sets whether color values are premultiplied by alpha.
+    pub(crate) fn set_alpha_premultiplied(&mut self, premultiplied: bool) {
+        self.row_2 &= !(FLAG_ALPHA_PREMULTIPLIED << 24);
+        if premultiplied {
+            self.row_2 |= FLAG_ALPHA_PREMULTIPLIED << 24;
+        }
+    }
+
+    /// Sets the transfer function of the color channels of an uncompressed
+    /// format, for formats without an sRGB variant such as `R16_UNORM`.
+    #[cfg(feature = "png")]
+    pub(crate) fn set_srgb(&mut self, srgb: bool) {
+        let transfer_function = if srgb { TRANSFER_SRGB } else { TRANSFER_LINEAR };
+        self.row_2 = self.row_2 & !(0xFF << 16) | transfer_function << 16;
+        for sample in &mut self.samples {
+            if (sample.row_0 >> 24) & 0xF == Channel::A as u32 {
+                sample.row_0 &= !(SAMPLE_LINEAR << 24);
+                if srgb {
+                    sample.row_0 |= SAMPLE_LINEAR << 24;
                 }
             }
-            _ => panic!("Unsupported format {:?}", vk_format),
+        }
+    }
+
+    /// Describes an uncompressed format with one sample per channel, following
+    /// the conventions of the Khronos dfdutils library.
+    fn from_layout(vk_format: VkFormat, layout: &FormatLayout) -> Self {
+        let channels = layout.channels();
+        let srgb = channels.iter().any(|c| c.numeric == NumericFormat::Srgb);
+        let depth_stencil = channels
+            .iter()
+            .any(|c| matches!(c.channel, Channel::Depth | Channel::Stencil));
+
+        let samples = if vk_format == VkFormat::E5B9G9R9_UFLOAT_PACK32 {
+            // each 9 bit mantissa is followed by a sample for the shared exponent
+            channels
+                .iter()
+                .flat_map(|channel| {
+                    [
+                        DFDSampleType {
+                            row_0: channel.bit_offset
+                                | (channel.bit_length - 1) << 16
+                                | (channel.channel as u32) << 24,
+                            row_1: 0u32,
+                            row_2: 0,
+                            row_3: 8448,
+                        },
+                        DFDSampleType {
+                            row_0: 27
+                                | 4 << 16
+                                | (channel.channel as u32 | SAMPLE_EXPONENT) << 24,
+                            row_1: 0u32,
+                            row_2: 15,
+                            row_3: 31,
+                        },
+                    ]
+                })
+                .collect()
+        } else {
+            channels
+                .iter()
+                .map(|channel| DFDSampleType::from_channel(channel, srgb))
+                .collect::<Vec<_>>()
+        };
+
+        let descriptor_block_size =
+            (24 + std::mem::size_of::<DFDSampleType>() * samples.len()) as u32;
+        let color_primaries = if depth_stencil {
+            COLOR_PRIMARIES_UNSPECIFIED
+        } else {
+            COLOR_PRIMARIES_BT709
+        };
+        let transfer_function = if srgb { TRANSFER_SRGB } else { TRANSFER_LINEAR };
+        BasicDataFormatDescriptor {
+            dfd_total_size: descriptor_block_size + 4,
+            row_0: 0u32,
+            row_1: 2 | descriptor_block_size << 16,
+            row_2: COLOR_MODEL_RGBSDA | color_primaries << 8 | transfer_function << 16,
+            row_3: 0u32,
+            row_4: layout.texel_size,
+            row_5: 0u32,
+            samples,
+        }
+    }
+}
+
+impl DFDSampleType {
+    /// Sample covering a whole channel. `srgb` marks the alpha channel of
+    /// sRGB formats as linear.
+    fn from_channel(channel: &ChannelLayout, srgb: bool) -> Self {
+        let bits = channel.bit_length;
+        let mut qualifiers = 0;
+        let (lower, upper) = match channel.numeric {
+            NumericFormat::Unorm | NumericFormat::Srgb => {
+                (0, if bits >= 32 { u32::MAX } else { (1 << bits) - 1 })
+            }
+            NumericFormat::Snorm => {
+                qualifiers |= SAMPLE_SIGNED;
+                let upper = if bits > 32 { 0x7FFFFFFF } else { (1u32 << (bits - 1)) - 1 };
+                (upper.wrapping_neg(), upper)
+            }
+            NumericFormat::Uint | NumericFormat::Uscaled => (0, 1),
+            NumericFormat::Sint | NumericFormat::Sscaled => {
+                qualifiers |= SAMPLE_SIGNED;
+                (u32::MAX, 1)
+            }
+            NumericFormat::Sfloat => {
+                qualifiers |= SAMPLE_SIGNED | SAMPLE_FLOAT;
+                (FLOAT_MINUS_ONE, FLOAT_ONE)
+            }
+            NumericFormat::Ufloat => {
+                qualifiers |= SAMPLE_FLOAT;
+                (0, FLOAT_ONE)
+            }
+        };
+        if srgb && channel.channel == Channel::A {
+            qualifiers |= SAMPLE_LINEAR;
+        }
+        DFDSampleType {
+            row_0: channel.bit_offset
+                | (bits - 1) << 16
+                | (channel.channel as u32 | qualifiers) << 24,
+            row_1: 0u32,
+            row_2: lower,
+            row_3: upper,
         }
     }
 }
@@ -0,0 +1,172 @@
+use std::io::Cursor;
+
+use ::exr::prelude::traits::*;
+use ::exr::prelude::{read, AnyChannel, AnyChannels, FlatSamples, Image, SmallVec};
+use bytemuck::Pod;
+use half::f16;
+
+use crate::texture::TextureKtx2;
+use crate::vk_format::{get_format_layout, NumericFormat, VkFormat};
+
+const CHANNEL_NAMES: [&str; 4] = ["R", "G", "B", "A"];
+
+impl TextureKtx2 {
+    /// Reads the first layer of an OpenEXR image as a single level texture.
+    /// Half channels become `R16..._SFLOAT` with their exact values, `u32`
+    /// channels `R32..._UINT` and others `R32..._SFLOAT`. Channels named `R`,
+    /// `G`, `B` and `A` keep their place and other channels are then
+    /// ignored, otherwise up to four channels are taken in name order.
+    pub fn from_exr(bytes: &[u8]) -> Result<TextureKtx2, anyhow::Error> {
+        let image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(Cursor::new(bytes))?;
+        let layer = image.layer_data;
+        let list = &layer.channel_data.list;
+
+        // the base name without a layer prefix such as "diffuse."
+        let name = |channel: &AnyChannel<FlatSamples>| {
+            let name = channel.name.to_string();
+            name.rsplit('.').next().unwrap_or_default().to_uppercase()
+        };
+        let named: Vec<(usize, &AnyChannel<FlatSamples>)> = list
+            .iter()
+            .filter_map(|c| Some((CHANNEL_NAMES.iter().position(|&n| n == name(c))?, c)))
+            .collect();
+        let slots: Vec<Option<&FlatSamples>> = if !named.is_empty() {
+            let count = named.iter().map(|&(slot, _)| slot + 1).max().unwrap();
+            (0..count)
+                .map(|slot| named.iter().find(|&&(s, _)| s == slot).map(|(_, c)| &c.sample_data))
+                .collect()
+        } else if list.len() <= 4 {
+            list.iter().map(|c| Some(&c.sample_data)).collect()
+        } else {
+            let names: Vec<String> = list.iter().map(|c| c.name.to_string()).collect();
+            anyhow::bail!("Unsupported EXR channels {:?}", names);
+        };
+
+        let samples = slots.iter().flatten();
+        let half = samples.clone().all(|s| matches!(s, FlatSamples::F16(_)));
+        let uint = samples.clone().all(|s| matches!(s, FlatSamples::U32(_)));
+        let (formats, data) = if half {
+            let planes = slots.iter().map(|s| match s {
+                Some(FlatSamples::F16(values)) => Some(values.as_slice()),
+                _ => None,
+            });
+            let formats = [
+                VkFormat::R16_SFLOAT,
+                VkFormat::R16G16_SFLOAT,
+                VkFormat::R16G16B16_SFLOAT,
+                VkFormat::R16G16B16A16_SFLOAT,
+            ];
+            (formats, interleave(&planes.collect::<Vec<_>>()))
+        } else if uint {
+            let planes = slots.iter().map(|s| match s {
+                Some(FlatSamples::U32(values)) => Some(values.as_slice()),
+                _ => None,
+            });
+            let formats = [
+                VkFormat::R32_UINT,
+                VkFormat::R32G32_UINT,
+                VkFormat::R32G32B32_UINT,
+                VkFormat::R32G32B32A32_UINT,
+            ];
+            (formats, interleave(&planes.collect::<Vec<_>>()))
+        } else {
+            let planes: Vec<Option<Vec<f32>>> = slots
+                .iter()
+                .map(|s| s.map(|s| s.values_as_f32().collect()))
+                .collect();
+            let planes: Vec<Option<&[f32]>> = planes.iter().map(|p| p.as_deref()).collect();
+            let formats = [
+                VkFormat::R32_SFLOAT,
+                VkFormat::R32G32_SFLOAT,
+                VkFormat::R32G32B32_SFLOAT,
+                VkFormat::R32G32B32A32_SFLOAT,
+            ];
+            (formats, interleave(&planes))
+        };
+
+        let format = formats[slots.len() - 1];
+        let mut texture = TextureKtx2::new(layer.size.0 as u32, layer.size.1 as u32, format);
+        texture.level_images[0] = data;
+        Ok(texture)
+    }
+
+    /// Writes one 2D image of the texture as an OpenEXR image with a channel
+    /// for each channel of the format, `RGBA` for block compressed formats.
+    /// Float formats of up to 16 bits are written as half, keeping their
+    /// values, `R32..._UINT` formats as `u32` and others as linear float.
+    pub fn to_exr(
+        &self,
+        level: u32,
+        layer: u32,
+        face: u32,
+        slice: u32,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        let format = self.header.vk_format;
+        let plane = self.plane_index(level, layer, face, slice)?;
+        let (width, height, _) = self.level_extent(level);
+        let layout = get_format_layout(format);
+        let slots: Vec<usize> = match &layout {
+            Some(l) => l.channels().iter().map(|c| l.rgba_index(c.channel)).collect(),
+            None => (0..4).collect(),
+        };
+        let numeric = |f: fn(NumericFormat, u32) -> bool| {
+            layout.is_some_and(|l| l.channels().iter().all(|c| f(c.numeric, c.bit_length)))
+        };
+        let uint = numeric(|n, bits| n == NumericFormat::Uint && bits == 32);
+
+        let samples: Vec<FlatSamples> = if uint {
+            let texels = self.level_planes::<u32>(level)?.nth(plane).unwrap();
+            // storage order of the channels to `[r, g, b, a]` order
+            (0..slots.len())
+                .map(|c| {
+                    let values = texels.iter().skip(c).step_by(slots.len()).copied();
+                    FlatSamples::U32(values.collect())
+                })
+                .collect()
+        } else {
+            let half = numeric(|n, bits| {
+                matches!(n, NumericFormat::Sfloat | NumericFormat::Ufloat) && bits <= 16
+            });
+            let image = self.decode_image(level, layer, face, slice, false)?;
+            slots
+                .iter()
+                .map(|&c| {
+                    let values = image.pixels.iter().map(|p| p[c]);
+                    if half {
+                        FlatSamples::F16(values.map(f16::from_f32).collect())
+                    } else {
+                        FlatSamples::F32(values.collect())
+                    }
+                })
+                .collect()
+        };
+
+        let channels: Vec<AnyChannel<FlatSamples>> = slots
+            .iter()
+            .zip(samples)
+            .map(|(&c, samples)| AnyChannel::new(CHANNEL_NAMES[c], samples))
+            .collect();
+        let image = Image::from_channels(
+            (width as usize, height as usize),
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        );
+        let mut out = Vec::new();
+        image.write().to_buffered(Cursor::new(&mut out))?;
+        Ok(out)
+    }
+}
+
+/// Interleaves channel planes as texels, missing channels are zero.
+fn interleave<T: Pod>(planes: &[Option<&[T]>]) -> Vec<u8> {
+    let count = planes.iter().flatten().map(|p| p.len()).next().unwrap_or(0);
+    let texels: Vec<T> = (0..count)
+        .flat_map(|i| planes.iter().map(move |p| p.map_or(T::zeroed(), |p| p[i])))
+        .collect();
+    bytemuck::cast_slice(&texels).to_vec()
+}
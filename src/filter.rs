@@ -3,8 +3,84 @@ pub enum FilterType {
     /// Nearest Neighbor
     Nearest,
 
+    /// Box filter, averages the input pixels covered by an output pixel
+    Box,
+
+    /// Triangle (tent) filter, bilinear when magnifying
+    Triangle,
+
+    /// Cubic B-spline, smooth but blurry
+    CubicBSpline,
+
+    /// Catmull-Rom spline, sharp cubic interpolation
+    CatmullRom,
+
+    /// Mitchell-Netravali cubic with B = C = 1/3
+    Mitchell,
+
+    /// Gaussian with a standard deviation of 0.5
+    Gaussian,
+
+    /// Kaiser windowed sinc with alpha 4 and window 3
+    Kaiser,
+
     /// Lanczos with window 3
     Lanczos3,
+
+    /// Lanczos with the given window, raised to at least 1
+    Lanczos(f32),
+}
+
+impl FilterType {
+    /// The kernel and support of the filter.
+    pub fn to_filter(self) -> Filter<'static> {
+        match self {
+            FilterType::Nearest => Filter {
+                kernel: Box::new(box_kernel),
+                support: 0.0,
+            },
+            FilterType::Box => Filter {
+                kernel: Box::new(box_window_kernel),
+                support: 0.5,
+            },
+            FilterType::Triangle => Filter {
+                kernel: Box::new(triangle_kernel),
+                support: 1.0,
+            },
+            FilterType::CubicBSpline => Filter {
+                kernel: Box::new(|x| cubic_kernel(x, 1.0, 0.0)),
+                support: 2.0,
+            },
+            FilterType::CatmullRom => Filter {
+                kernel: Box::new(|x| cubic_kernel(x, 0.0, 0.5)),
+                support: 2.0,
+            },
+            FilterType::Mitchell => Filter {
+                kernel: Box::new(|x| cubic_kernel(x, 1.0 / 3.0, 1.0 / 3.0)),
+                support: 2.0,
+            },
+            FilterType::Gaussian => Filter {
+                kernel: Box::new(|x| gaussian_kernel(x, 0.5)),
+                support: 1.5,
+            },
+            FilterType::Kaiser => Filter {
+                kernel: Box::new(|x| kaiser_kernel(x, 4.0, 3.0)),
+                support: 3.0,
+            },
+            FilterType::Lanczos3 => Filter {
+                kernel: Box::new(lanczos3_kernel),
+                support: 3.0,
+            },
+            FilterType::Lanczos(window) => {
+                // smaller windows, and NaN, would give a kernel that is zero everywhere
+                let window = window.max(1.0);
+                Filter {
+                    kernel: Box::new(move |x| lanczos_kernel(x, window)),
+                    support: window,
+                }
+            }
+        }
+    }
 }
 
 /// A Representation of a separable filter.
@@ -23,6 +99,73 @@ pub fn box_kernel(_x: f32) -> f32 {
     1.0
 }
 
+/// Calculate the box kernel of width 1, only the input pixels whose centre
+/// lies inside the output pixel contribute.
+pub fn box_window_kernel(x: f32) -> f32 {
+    if x.abs() <= 0.5 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Calculate the triangle kernel.
+pub fn triangle_kernel(x: f32) -> f32 {
+    (1.0 - x.abs()).max(0.0)
+}
+
+/// Calculate the Mitchell-Netravali family of cubic kernels with support 2.
+/// B = 1, C = 0 is the cubic B-spline, B = 0, C = 0.5 is Catmull-Rom.
+pub fn cubic_kernel(x: f32, b: f32, c: f32) -> f32 {
+    let x = x.abs();
+    let k = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b)
+    } else if x < 2.0 {
+        (-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    } else {
+        0.0
+    };
+    k / 6.0
+}
+
+/// Calculate the gaussian kernel with standard deviation `sigma`.
+pub fn gaussian_kernel(x: f32, sigma: f32) -> f32 {
+    let a = 1.0 / (sigma * (2.0 * std::f32::consts::PI).sqrt());
+    a * (-x * x / (2.0 * sigma * sigma)).exp()
+}
+
+// zeroth order modified Bessel function of the first kind.
+fn bessel_i0(x: f32) -> f32 {
+    let half = 0.5 * x;
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 0.0;
+    loop {
+        k += 1.0;
+        term *= half / k;
+        let ds = term * term;
+        sum += ds;
+        if ds <= sum * 1e-6 {
+            return sum;
+        }
+    }
+}
+
+/// Calculate the Kaiser windowed sinc kernel with shape parameter `alpha`
+/// over a window of `width` pixels on each side.
+pub fn kaiser_kernel(x: f32, alpha: f32, width: f32) -> f32 {
+    let t = x / width;
+    if t.abs() < 1.0 {
+        sinc(x) * bessel_i0(alpha * (1.0 - t * t).sqrt()) / bessel_i0(alpha)
+    } else {
+        0.0
+    }
+}
+
 // sinc function: the ideal sampling filter.
 fn sinc(t: f32) -> f32 {
     let a = t * std::f32::consts::PI;
@@ -43,6 +186,11 @@ fn lanczos(x: f32, t: f32) -> f32 {
     }
 }
 
+/// Calculate the lanczos kernel with the given window
+pub fn lanczos_kernel(x: f32, window: f32) -> f32 {
+    lanczos(x, window)
+}
+
 /// Calculate the lanczos kernel with a window of 3
 pub(crate) fn lanczos3_kernel(x: f32) -> f32 {
     lanczos(x, 3.0)
@@ -0,0 +1,216 @@
+use crate::texture::TextureKtx2;
+use crate::vk_format::VkFormat;
+
+impl TextureKtx2 {
+    /// Reads a Radiance RGBE image as a single level `R32G32B32_SFLOAT`
+    /// texture, which holds every RGBE value exactly. Flat, run length
+    /// encoded and old style run length encoded scanlines are supported, in
+    /// the standard `-Y height +X width` orientation.
+    pub fn from_hdr(bytes: &[u8]) -> Result<TextureKtx2, anyhow::Error> {
+        let mut position = 0;
+        let mut line = || {
+            let length = bytes[position..].iter().position(|&b| b == b'\n');
+            let Some(length) = length else {
+                anyhow::bail!("Truncated Radiance header");
+            };
+            let line = &bytes[position..position + length];
+            position += length + 1;
+            Ok(String::from_utf8_lossy(line).into_owned())
+        };
+        let magic = line()?;
+        if magic != "#?RADIANCE" && magic != "#?RGBE" {
+            anyhow::bail!("Not a Radiance image");
+        }
+        loop {
+            let line = line()?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=")
+                && format != "32-bit_rle_rgbe"
+            {
+                anyhow::bail!("Unsupported Radiance format {}", format);
+            }
+        }
+        let resolution = line()?;
+        let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (height.parse::<u32>()?, width.parse::<u32>()?),
+            _ => anyhow::bail!("Unsupported Radiance orientation {}", resolution),
+        };
+        if width == 0 || height == 0 {
+            anyhow::bail!("Invalid Radiance resolution {}", resolution);
+        }
+
+        let mut data = &bytes[position..];
+        // every flat or run length encoded scanline takes at least 4 bytes
+        if (data.len() as u64) < height as u64 * 4 {
+            anyhow::bail!("Truncated Radiance image data");
+        }
+        let mut texture = TextureKtx2::new(width, height, VkFormat::R32G32B32_SFLOAT);
+        let texels = texture.level_as_mut::<[f32; 3]>(0)?;
+        let mut scanline = vec![[0u8; 4]; width as usize];
+        for row in texels.chunks_exact_mut(width as usize) {
+            data = read_scanline(data, &mut scanline)?;
+            for (texel, rgbe) in row.iter_mut().zip(&scanline) {
+                *texel = rgbe_to_float(*rgbe);
+            }
+        }
+        Ok(texture)
+    }
+
+    /// Writes one 2D image of the texture as a run length encoded Radiance
+    /// RGBE image of its linear color. Negative values become zero.
+    pub fn to_hdr(
+        &self,
+        level: u32,
+        layer: u32,
+        face: u32,
+        slice: u32,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        let image = self.decode_image(level, layer, face, slice, false)?;
+        let mut out = format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            image.height, image.width
+        )
+        .into_bytes();
+        let width = image.width as usize;
+        for row in image.pixels.chunks_exact(width) {
+            let scanline: Vec<[u8; 4]> =
+                row.iter().map(|p| float_to_rgbe([p[0], p[1], p[2]])).collect();
+            write_scanline(&scanline, &mut out);
+        }
+        Ok(out)
+    }
+}
+
+fn rgbe_to_float(rgbe: [u8; 4]) -> [f32; 3] {
+    if rgbe[3] == 0 {
+        return [0.0; 3];
+    }
+    let scale = 2f32.powi(rgbe[3] as i32 - 136);
+    [rgbe[0] as f32 * scale, rgbe[1] as f32 * scale, rgbe[2] as f32 * scale]
+}
+
+/// Inverse of [`rgbe_to_float`], exact for the values it returns.
+fn float_to_rgbe(rgb: [f32; 3]) -> [u8; 4] {
+    let max = rgb.iter().fold(0.0f32, |max, &v| max.max(v));
+    if max < 1e-32 || !max.is_finite() {
+        return [0; 4];
+    }
+    // max = mantissa * 2^exponent with mantissa in [0.5, 1)
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max >= 2f32.powi(exponent) {
+        exponent += 1;
+    } else if max < 2f32.powi(exponent - 1) {
+        exponent -= 1;
+    }
+    let scale = 2f32.powi(8 - exponent);
+    let [r, g, b] = rgb.map(|v| (v.max(0.0) * scale).min(255.0) as u8);
+    [r, g, b, (exponent + 128).clamp(0, 255) as u8]
+}
+
+/// Reads the RGBE texels of one scanline, returning the remaining data.
+fn read_scanline<'a>(
+    data: &'a [u8],
+    scanline: &mut [[u8; 4]],
+) -> Result<&'a [u8], anyhow::Error> {
+    let truncated = || anyhow::anyhow!("Truncated Radiance image data");
+    let width = scanline.len();
+    let header = data.get(..4).ok_or_else(truncated)?;
+    let rle = (8..0x8000).contains(&width) && header[..2] == [2, 2] && header[2] < 0x80;
+    if !rle {
+        // flat texels, where (1, 1, 1, n) repeats the previous texel n times,
+        // with consecutive repeats counting in higher bytes
+        let mut data = data;
+        let (mut x, mut shift) = (0, 0);
+        while x < width {
+            let texel: [u8; 4] = data.get(..4).ok_or_else(truncated)?.try_into().unwrap();
+            data = &data[4..];
+            if texel[..3] == [1, 1, 1] && x > 0 {
+                if texel[3] == 0 || shift >= usize::BITS {
+                    anyhow::bail!("Invalid Radiance run length");
+                }
+                let count = (texel[3] as usize) << shift;
+                let previous = scanline[x - 1];
+                let end = x.saturating_add(count).min(width);
+                scanline[x..end].fill(previous);
+                x = end;
+                shift += 8;
+            } else {
+                scanline[x] = texel;
+                x += 1;
+                shift = 0;
+            }
+        }
+        return Ok(data);
+    }
+    if ((header[2] as usize) << 8 | header[3] as usize) != width {
+        anyhow::bail!("Radiance scanline width mismatch");
+    }
+
+    // each component is encoded separately as runs and literals
+    let mut data = &data[4..];
+    for c in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let &[code, value, ..] = data else {
+                return Err(truncated());
+            };
+            if code > 128 {
+                let count = (code - 128) as usize;
+                if x + count > width {
+                    anyhow::bail!("Invalid Radiance run length");
+                }
+                scanline[x..x + count].iter_mut().for_each(|texel| texel[c] = value);
+                x += count;
+                data = &data[2..];
+            } else {
+                let count = code as usize;
+                if count == 0 || x + count > width {
+                    anyhow::bail!("Invalid Radiance run length");
+                }
+                let values = data.get(1..1 + count).ok_or_else(truncated)?;
+                for (texel, &value) in scanline[x..x + count].iter_mut().zip(values) {
+                    texel[c] = value;
+                }
+                x += count;
+                data = &data[1 + count..];
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// Appends one scanline, run length encoded when the width allows it.
+fn write_scanline(scanline: &[[u8; 4]], out: &mut Vec<u8>) {
+    let width = scanline.len();
+    if !(8..0x8000).contains(&width) {
+        out.extend(scanline.iter().flatten());
+        return;
+    }
+    out.extend([2, 2, (width >> 8) as u8, width as u8]);
+    for c in 0..4 {
+        let values: Vec<u8> = scanline.iter().map(|texel| texel[c]).collect();
+        let mut x = 0;
+        while x < width {
+            let run = values[x..].iter().take(127).take_while(|&&v| v == values[x]).count();
+            if run >= 4 {
+                out.extend([128 + run as u8, values[x]]);
+                x += run;
+                continue;
+            }
+            // literals up to the next run of at least 4 values
+            let mut end = x + 1;
+            while end < width && end - x < 128 {
+                let ahead = &values[end..(end + 4).min(width)];
+                if ahead.len() == 4 && ahead.iter().all(|&v| v == ahead[0]) {
+                    break;
+                }
+                end += 1;
+            }
+            out.push((end - x) as u8);
+            out.extend(&values[x..end]);
+            x = end;
+        }
+    }
+}
@@ -0,0 +1,587 @@
+use std::io::{Cursor, Read};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::pixel::ChannelSource;
+use crate::texture::TextureKtx2;
+use crate::vk_format::{get_format_block_size_bytes, is_format_compressed, VkFormat};
+
+const KTX1_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const ENDIANNESS: u32 = 0x04030201;
+
+// glFormat and glBaseInternalFormat values
+const GL_ALPHA: u32 = 0x1906;
+const GL_RGB: u32 = 0x1907;
+const GL_RGBA: u32 = 0x1908;
+const GL_LUMINANCE: u32 = 0x1909;
+const GL_LUMINANCE_ALPHA: u32 = 0x190A;
+const GL_RED: u32 = 0x1903;
+const GL_RG: u32 = 0x8227;
+const GL_BGR: u32 = 0x80E0;
+const GL_BGRA: u32 = 0x80E1;
+
+// glType values
+const GL_UNSIGNED_BYTE: u32 = 0x1401;
+const GL_UNSIGNED_SHORT: u32 = 0x1403;
+const GL_FLOAT: u32 = 0x1406;
+const GL_HALF_FLOAT: u32 = 0x140B;
+const GL_HALF_FLOAT_OES: u32 = 0x8D61;
+const GL_UNSIGNED_SHORT_4_4_4_4: u32 = 0x8033;
+const GL_UNSIGNED_SHORT_5_5_5_1: u32 = 0x8034;
+const GL_UNSIGNED_SHORT_5_6_5: u32 = 0x8363;
+
+/// Number of components of each `glFormat`.
+const GL_FORMAT_COMPONENTS: [(u32, usize); 18] = [
+    (GL_RED, 1),
+    (GL_ALPHA, 1),
+    (GL_LUMINANCE, 1),
+    (0x8D94, 1), // GL_RED_INTEGER
+    (0x1902, 1), // GL_DEPTH_COMPONENT
+    (0x1901, 1), // GL_STENCIL_INDEX
+    (GL_RG, 2),
+    (GL_LUMINANCE_ALPHA, 2),
+    (0x8228, 2), // GL_RG_INTEGER
+    (0x84F9, 2), // GL_DEPTH_STENCIL
+    (GL_RGB, 3),
+    (GL_BGR, 3),
+    (0x8D98, 3), // GL_RGB_INTEGER
+    (0x8D9A, 3), // GL_BGR_INTEGER
+    (GL_RGBA, 4),
+    (GL_BGRA, 4),
+    (0x8D99, 4), // GL_RGBA_INTEGER
+    (0x8D9B, 4), // GL_BGRA_INTEGER
+];
+
+/// `glType` values packing all components of a texel, which is then
+/// `glTypeSize` bytes.
+const GL_PACKED_TYPES: [u32; 13] = [
+    GL_UNSIGNED_SHORT_4_4_4_4,
+    GL_UNSIGNED_SHORT_5_5_5_1,
+    0x8035, // GL_UNSIGNED_INT_8_8_8_8
+    0x8036, // GL_UNSIGNED_INT_10_10_10_2
+    GL_UNSIGNED_SHORT_5_6_5,
+    0x8364, // GL_UNSIGNED_SHORT_5_6_5_REV
+    0x8365, // GL_UNSIGNED_SHORT_4_4_4_4_REV
+    0x8366, // GL_UNSIGNED_SHORT_1_5_5_5_REV
+    0x8367, // GL_UNSIGNED_INT_8_8_8_8_REV
+    0x8368, // GL_UNSIGNED_INT_2_10_10_10_REV
+    0x8C3B, // GL_UNSIGNED_INT_10F_11F_11F_REV
+    0x8C3E, // GL_UNSIGNED_INT_5_9_9_9_REV
+    0x84FA, // GL_UNSIGNED_INT_24_8
+];
+
+/// Sized `glInternalFormat` values, including the legacy luminance and alpha
+/// formats whose channels are given by a `KTXswizzle`.
+const GL_INTERNAL_FORMATS: [(u32, VkFormat); 131] = [
+    (0x8229, VkFormat::R8_UNORM),
+    (0x8F94, VkFormat::R8_SNORM),
+    (0x8232, VkFormat::R8_UINT),
+    (0x8231, VkFormat::R8_SINT),
+    (0x8FBD, VkFormat::R8_SRGB),
+    (0x822B, VkFormat::R8G8_UNORM),
+    (0x8F95, VkFormat::R8G8_SNORM),
+    (0x8238, VkFormat::R8G8_UINT),
+    (0x8237, VkFormat::R8G8_SINT),
+    (0x8FBE, VkFormat::R8G8_SRGB),
+    (0x8051, VkFormat::R8G8B8_UNORM),
+    (0x8F96, VkFormat::R8G8B8_SNORM),
+    (0x8D7D, VkFormat::R8G8B8_UINT),
+    (0x8D8F, VkFormat::R8G8B8_SINT),
+    (0x8C41, VkFormat::R8G8B8_SRGB),
+    (0x8058, VkFormat::R8G8B8A8_UNORM),
+    (0x8F97, VkFormat::R8G8B8A8_SNORM),
+    (0x8D7C, VkFormat::R8G8B8A8_UINT),
+    (0x8D8E, VkFormat::R8G8B8A8_SINT),
+    (0x8C43, VkFormat::R8G8B8A8_SRGB),
+    (0x822A, VkFormat::R16_UNORM),
+    (0x8F98, VkFormat::R16_SNORM),
+    (0x8234, VkFormat::R16_UINT),
+    (0x8233, VkFormat::R16_SINT),
+    (0x822D, VkFormat::R16_SFLOAT),
+    (0x822C, VkFormat::R16G16_UNORM),
+    (0x8F99, VkFormat::R16G16_SNORM),
+    (0x823A, VkFormat::R16G16_UINT),
+    (0x8239, VkFormat::R16G16_SINT),
+    (0x822F, VkFormat::R16G16_SFLOAT),
+    (0x8054, VkFormat::R16G16B16_UNORM),
+    (0x8F9A, VkFormat::R16G16B16_SNORM),
+    (0x8D77, VkFormat::R16G16B16_UINT),
+    (0x8D89, VkFormat::R16G16B16_SINT),
+    (0x881B, VkFormat::R16G16B16_SFLOAT),
+    (0x805B, VkFormat::R16G16B16A16_UNORM),
+    (0x8F9B, VkFormat::R16G16B16A16_SNORM),
+    (0x8D76, VkFormat::R16G16B16A16_UINT),
+    (0x8D88, VkFormat::R16G16B16A16_SINT),
+    (0x881A, VkFormat::R16G16B16A16_SFLOAT),
+    (0x8236, VkFormat::R32_UINT),
+    (0x8235, VkFormat::R32_SINT),
+    (0x822E, VkFormat::R32_SFLOAT),
+    (0x823C, VkFormat::R32G32_UINT),
+    (0x823B, VkFormat::R32G32_SINT),
+    (0x8230, VkFormat::R32G32_SFLOAT),
+    (0x8D71, VkFormat::R32G32B32_UINT),
+    (0x8D83, VkFormat::R32G32B32_SINT),
+    (0x8815, VkFormat::R32G32B32_SFLOAT),
+    (0x8D70, VkFormat::R32G32B32A32_UINT),
+    (0x8D82, VkFormat::R32G32B32A32_SINT),
+    (0x8814, VkFormat::R32G32B32A32_SFLOAT),
+    (0x8D62, VkFormat::R5G6B5_UNORM_PACK16),
+    (0x8056, VkFormat::R4G4B4A4_UNORM_PACK16),
+    (0x8057, VkFormat::R5G5B5A1_UNORM_PACK16),
+    (0x8059, VkFormat::A2B10G10R10_UNORM_PACK32),
+    (0x906F, VkFormat::A2B10G10R10_UINT_PACK32),
+    (0x8C3A, VkFormat::B10G11R11_UFLOAT_PACK32),
+    (0x8C3D, VkFormat::E5B9G9R9_UFLOAT_PACK32),
+    (0x81A5, VkFormat::D16_UNORM),
+    (0x8CAC, VkFormat::D32_SFLOAT),
+    (0x8D48, VkFormat::S8_UINT),
+    // luminance and alpha
+    (0x8040, VkFormat::R8_UNORM),
+    (0x8042, VkFormat::R16_UNORM),
+    (0x8045, VkFormat::R8G8_UNORM),
+    (0x8048, VkFormat::R16G16_UNORM),
+    (0x803C, VkFormat::R8_UNORM),
+    (0x803E, VkFormat::R16_UNORM),
+    (0x8C47, VkFormat::R8_SRGB),
+    (0x8C45, VkFormat::R8G8_SRGB),
+    (0x881E, VkFormat::R16_SFLOAT),
+    (0x8818, VkFormat::R32_SFLOAT),
+    (0x881F, VkFormat::R16G16_SFLOAT),
+    (0x8819, VkFormat::R32G32_SFLOAT),
+    (0x881C, VkFormat::R16_SFLOAT),
+    (0x8816, VkFormat::R32_SFLOAT),
+    // S3TC, RGTC and BPTC
+    (0x83F0, VkFormat::BC1_RGB_UNORM_BLOCK),
+    (0x83F1, VkFormat::BC1_RGBA_UNORM_BLOCK),
+    (0x83F2, VkFormat::BC2_UNORM_BLOCK),
+    (0x83F3, VkFormat::BC3_UNORM_BLOCK),
+    (0x8C4C, VkFormat::BC1_RGB_SRGB_BLOCK),
+    (0x8C4D, VkFormat::BC1_RGBA_SRGB_BLOCK),
+    (0x8C4E, VkFormat::BC2_SRGB_BLOCK),
+    (0x8C4F, VkFormat::BC3_SRGB_BLOCK),
+    (0x8DBB, VkFormat::BC4_UNORM_BLOCK),
+    (0x8DBC, VkFormat::BC4_SNORM_BLOCK),
+    (0x8DBD, VkFormat::BC5_UNORM_BLOCK),
+    (0x8DBE, VkFormat::BC5_SNORM_BLOCK),
+    (0x8E8C, VkFormat::BC7_UNORM_BLOCK),
+    (0x8E8D, VkFormat::BC7_SRGB_BLOCK),
+    (0x8E8E, VkFormat::BC6H_SFLOAT_BLOCK),
+    (0x8E8F, VkFormat::BC6H_UFLOAT_BLOCK),
+    // ETC1 is a subset of ETC2
+    (0x8D64, VkFormat::ETC2_R8G8B8_UNORM_BLOCK),
+    (0x9274, VkFormat::ETC2_R8G8B8_UNORM_BLOCK),
+    (0x9275, VkFormat::ETC2_R8G8B8_SRGB_BLOCK),
+    (0x9276, VkFormat::ETC2_R8G8B8A1_UNORM_BLOCK),
+    (0x9277, VkFormat::ETC2_R8G8B8A1_SRGB_BLOCK),
+    (0x9278, VkFormat::ETC2_R8G8B8A8_UNORM_BLOCK),
+    (0x9279, VkFormat::ETC2_R8G8B8A8_SRGB_BLOCK),
+    (0x9270, VkFormat::EAC_R11_UNORM_BLOCK),
+    (0x9271, VkFormat::EAC_R11_SNORM_BLOCK),
+    (0x9272, VkFormat::EAC_R11G11_UNORM_BLOCK),
+    (0x9273, VkFormat::EAC_R11G11_SNORM_BLOCK),
+    // ASTC
+    (0x93B0, VkFormat::ASTC_4x4_UNORM_BLOCK),
+    (0x93B1, VkFormat::ASTC_5x4_UNORM_BLOCK),
+    (0x93B2, VkFormat::ASTC_5x5_UNORM_BLOCK),
+    (0x93B3, VkFormat::ASTC_6x5_UNORM_BLOCK),
+    (0x93B4, VkFormat::ASTC_6x6_UNORM_BLOCK),
+    (0x93B5, VkFormat::ASTC_8x5_UNORM_BLOCK),
+    (0x93B6, VkFormat::ASTC_8x6_UNORM_BLOCK),
+    (0x93B7, VkFormat::ASTC_8x8_UNORM_BLOCK),
+    (0x93B8, VkFormat::ASTC_10x5_UNORM_BLOCK),
+    (0x93B9, VkFormat::ASTC_10x6_UNORM_BLOCK),
+    (0x93BA, VkFormat::ASTC_10x8_UNORM_BLOCK),
+    (0x93BB, VkFormat::ASTC_10x10_UNORM_BLOCK),
+    (0x93BC, VkFormat::ASTC_12x10_UNORM_BLOCK),
+    (0x93BD, VkFormat::ASTC_12x12_UNORM_BLOCK),
+    (0x93D0, VkFormat::ASTC_4x4_SRGB_BLOCK),
+    (0x93D1, VkFormat::ASTC_5x4_SRGB_BLOCK),
+    (0x93D2, VkFormat::ASTC_5x5_SRGB_BLOCK),
+    (0x93D3, VkFormat::ASTC_6x5_SRGB_BLOCK),
+    (0x93D4, VkFormat::ASTC_6x6_SRGB_BLOCK),
+    (0x93D5, VkFormat::ASTC_8x5_SRGB_BLOCK),
+    (0x93D6, VkFormat::ASTC_8x6_SRGB_BLOCK),
+    (0x93D7, VkFormat::ASTC_8x8_SRGB_BLOCK),
+    (0x93D8, VkFormat::ASTC_10x5_SRGB_BLOCK),
+    (0x93D9, VkFormat::ASTC_10x6_SRGB_BLOCK),
+    (0x93DA, VkFormat::ASTC_10x8_SRGB_BLOCK),
+    (0x93DB, VkFormat::ASTC_10x10_SRGB_BLOCK),
+    (0x93DC, VkFormat::ASTC_12x10_SRGB_BLOCK),
+    (0x93DD, VkFormat::ASTC_12x12_SRGB_BLOCK),
+];
+
+/// Unsized `glInternalFormat` values of legacy files, sized by `glType`.
+const GL_UNSIZED_INTERNAL_FORMATS: [u32; 8] =
+    [GL_ALPHA, GL_LUMINANCE, GL_LUMINANCE_ALPHA, GL_RED, GL_RG, GL_RGB, GL_RGBA, GL_BGRA];
+
+/// Formats of unsized internal formats by `glType` and component count.
+const GL_UNSIZED_FORMATS: [(u32, [VkFormat; 4]); 5] = [
+    (
+        GL_UNSIGNED_BYTE,
+        [
+            VkFormat::R8_UNORM,
+            VkFormat::R8G8_UNORM,
+            VkFormat::R8G8B8_UNORM,
+            VkFormat::R8G8B8A8_UNORM,
+        ],
+    ),
+    (
+        GL_UNSIGNED_SHORT,
+        [
+            VkFormat::R16_UNORM,
+            VkFormat::R16G16_UNORM,
+            VkFormat::R16G16B16_UNORM,
+            VkFormat::R16G16B16A16_UNORM,
+        ],
+    ),
+    (
+        GL_HALF_FLOAT,
+        [
+            VkFormat::R16_SFLOAT,
+            VkFormat::R16G16_SFLOAT,
+            VkFormat::R16G16B16_SFLOAT,
+            VkFormat::R16G16B16A16_SFLOAT,
+        ],
+    ),
+    (
+        GL_HALF_FLOAT_OES,
+        [
+            VkFormat::R16_SFLOAT,
+            VkFormat::R16G16_SFLOAT,
+            VkFormat::R16G16B16_SFLOAT,
+            VkFormat::R16G16B16A16_SFLOAT,
+        ],
+    ),
+    (
+        GL_FLOAT,
+        [
+            VkFormat::R32_SFLOAT,
+            VkFormat::R32G32_SFLOAT,
+            VkFormat::R32G32B32_SFLOAT,
+            VkFormat::R32G32B32A32_SFLOAT,
+        ],
+    ),
+];
+
+/// Unsized internal formats with packed `glType` values.
+const GL_UNSIZED_PACKED_FORMATS: [(u32, u32, VkFormat); 3] = [
+    (GL_RGB, GL_UNSIGNED_SHORT_5_6_5, VkFormat::R5G6B5_UNORM_PACK16),
+    (GL_RGBA, GL_UNSIGNED_SHORT_4_4_4_4, VkFormat::R4G4B4A4_UNORM_PACK16),
+    (GL_RGBA, GL_UNSIGNED_SHORT_5_5_5_1, VkFormat::R5G5B5A1_UNORM_PACK16),
+];
+
+/// Header fields of a KTX 1.1 file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ktx1Header {
+    pub gl_type: u32,
+    pub gl_type_size: u32,
+    pub gl_format: u32,
+    pub gl_internal_format: u32,
+    pub gl_base_internal_format: u32,
+    pub pixel_width: u32,
+    pub pixel_height: u32,
+    pub pixel_depth: u32,
+    pub number_of_array_elements: u32,
+    pub number_of_faces: u32,
+    pub number_of_mipmap_levels: u32,
+}
+
+/// A KTX 1.1 texture. Its level images are laid out as in KTX2, every
+/// layer, face and z slice in turn, without row, cube or mip padding and in
+/// little endian byte order.
+#[derive(Clone, Debug)]
+pub struct TextureKtx1 {
+    pub header: Ktx1Header,
+
+    /// Key/value pairs in file order. Values are kept as stored, strings
+    /// usually being NUL terminated.
+    pub key_value_data: Vec<(String, Vec<u8>)>,
+
+    pub level_images: Vec<Vec<u8>>,
+}
+
+impl TextureKtx1 {
+    /// Reads a KTX 1.1 file of either endianness.
+    pub fn read_from_bytes(bytes: &[u8]) -> Result<TextureKtx1, anyhow::Error> {
+        let mut cursor = Cursor::new(bytes);
+        let mut identifier = [0u8; 12];
+        cursor.read_exact(&mut identifier)?;
+        if identifier != KTX1_IDENTIFIER {
+            anyhow::bail!("Not a KTX1 file");
+        }
+        let mut fields = [0u32; 13];
+        cursor.read_u32_into::<LittleEndian>(&mut fields)?;
+        let swap = match fields[0] {
+            ENDIANNESS => false,
+            endianness if endianness.swap_bytes() == ENDIANNESS => true,
+            endianness => anyhow::bail!("Invalid KTX1 endianness {:#x}", endianness),
+        };
+        let native = |value: u32| if swap { value.swap_bytes() } else { value };
+        let [
+            _,
+            gl_type,
+            gl_type_size,
+            gl_format,
+            gl_internal_format,
+            gl_base_internal_format,
+            pixel_width,
+            pixel_height,
+            pixel_depth,
+            number_of_array_elements,
+            number_of_faces,
+            number_of_mipmap_levels,
+            bytes_of_key_value_data,
+        ] = fields.map(native);
+        let header = Ktx1Header {
+            gl_type,
+            gl_type_size,
+            gl_format,
+            gl_internal_format,
+            gl_base_internal_format,
+            pixel_width,
+            pixel_height,
+            pixel_depth,
+            number_of_array_elements,
+            number_of_faces,
+            number_of_mipmap_levels,
+        };
+        if number_of_faces != 1 && number_of_faces != 6 {
+            anyhow::bail!("Invalid KTX1 numberOfFaces {}", number_of_faces);
+        }
+        if number_of_mipmap_levels > 32 {
+            anyhow::bail!("Invalid KTX1 numberOfMipmapLevels {}", number_of_mipmap_levels);
+        }
+
+        let mut data = &bytes[cursor.position() as usize..];
+        let mut kvd = take(&mut data, bytes_of_key_value_data as usize)?;
+        let mut key_value_data = Vec::new();
+        while !kvd.is_empty() {
+            let size = native(u32::from_le_bytes(take(&mut kvd, 4)?.try_into().unwrap()));
+            let pair = take(&mut kvd, size as usize)?;
+            skip_padding(&mut kvd, size as usize);
+            let (key, value) = match pair.iter().position(|&b| b == 0) {
+                Some(end) => (&pair[..end], &pair[end + 1..]),
+                None => (pair, &[][..]),
+            };
+            key_value_data.push((String::from_utf8_lossy(key).into_owned(), value.to_vec()));
+        }
+
+        let texel_size = match gl_type {
+            0 => None,
+            _ => Some(header.texel_size().ok_or_else(|| {
+                anyhow::anyhow!("Unsupported KTX1 glFormat {:#x} glType {:#x}", gl_format, gl_type)
+            })?),
+        };
+        // imageSize covers a single face of cubemaps that are not arrays,
+        // which are followed by cube padding, and whole levels otherwise
+        let separate_faces = number_of_faces == 6 && number_of_array_elements == 0;
+        let (chunks, images) = if separate_faces {
+            (6, 1)
+        } else {
+            (1, number_of_array_elements.max(1) as usize * number_of_faces as usize)
+        };
+        let mut level_images = Vec::new();
+        for level in 0..number_of_mipmap_levels.max(1) {
+            let image_size = native(u32::from_le_bytes(take(&mut data, 4)?.try_into().unwrap()));
+            let width = (pixel_width >> level).max(1) as usize;
+            let rows = [pixel_height, pixel_depth]
+                .into_iter()
+                .try_fold(images, |rows, size| rows.checked_mul((size >> level).max(1) as usize));
+            let level_size = (image_size as usize).checked_mul(chunks);
+            if level_size.is_none_or(|size| size > data.len()) {
+                anyhow::bail!("Invalid KTX1 imageSize {} of level {}", image_size, level);
+            }
+            let mut image = Vec::with_capacity(image_size as usize * chunks);
+            for _ in 0..chunks {
+                let chunk = take(&mut data, image_size as usize)?;
+                skip_padding(&mut data, image_size as usize);
+                let Some(texel_size) = texel_size else {
+                    image.extend_from_slice(chunk);
+                    continue;
+                };
+                // rows of uncompressed formats are aligned to 4 bytes
+                let row_size = width * texel_size;
+                let pitch = row_size.next_multiple_of(4);
+                if rows.and_then(|rows| rows.checked_mul(pitch)) != Some(chunk.len()) {
+                    anyhow::bail!("Invalid KTX1 imageSize {} of level {}", image_size, level);
+                }
+                for row in chunk.chunks_exact(pitch) {
+                    image.extend_from_slice(&row[..row_size]);
+                }
+            }
+            if swap && gl_type_size > 1 {
+                for value in image.chunks_exact_mut(gl_type_size as usize) {
+                    value.reverse();
+                }
+            }
+            level_images.push(image);
+        }
+
+        Ok(TextureKtx1 { header, key_value_data, level_images })
+    }
+
+    /// Upgrades the texture to KTX2 as `ktx2ktx2` does. The GL format is
+    /// mapped to its `VkFormat`, with a `KTXswizzle` for luminance and alpha
+    /// formats. Key/value data is kept, `KTXorientation` being rewritten in
+    /// the KTX2 form, e.g. `S=r,T=d` as `rd`.
+    pub fn to_ktx2(&self) -> Result<TextureKtx2, anyhow::Error> {
+        let header = &self.header;
+        let format = self.vk_format()?;
+        let mut texture =
+            TextureKtx2::new(header.pixel_width, header.pixel_height.max(1), format);
+        texture.header.pixel_height = header.pixel_height;
+        texture.header.pixel_depth = header.pixel_depth;
+        texture.header.layer_count = header.number_of_array_elements;
+        texture.header.face_count = header.number_of_faces;
+        // zero levels asks for mipmap generation in both versions
+        texture.header.level_count = header.number_of_mipmap_levels;
+        for (level, image) in self.level_images.iter().enumerate() {
+            if image.len() as u64 != texture.level_byte_length(level as u32) {
+                anyhow::bail!("Invalid KTX1 image size of level {} for {:?}", level, format);
+            }
+        }
+        texture.level_images = self.level_images.clone();
+
+        let dimensions = match (header.pixel_height, header.pixel_depth) {
+            (0, _) => 1,
+            (_, 0) => 2,
+            _ => 3,
+        };
+        for (key, value) in &self.key_value_data {
+            match key.as_str() {
+                // the KTX2 file has a writer of its own
+                "KTXwriter" => {}
+                "KTXorientation" => {
+                    let value = String::from_utf8_lossy(value);
+                    let mut orientation: Vec<u8> = value
+                        .trim_end_matches('\0')
+                        .split(',')
+                        .filter_map(|axis| axis.split_once('=')?.1.trim().bytes().next())
+                        .take(dimensions)
+                        .collect();
+                    orientation.push(0);
+                    texture.key_value_data.insert(key.clone(), orientation);
+                }
+                _ => {
+                    texture.key_value_data.insert(key.clone(), value.clone());
+                }
+            }
+        }
+
+        use ChannelSource::*;
+        let swizzle = match header.gl_base_internal_format {
+            GL_LUMINANCE => Some([R, R, R, One]),
+            GL_LUMINANCE_ALPHA => Some([R, R, R, G]),
+            GL_ALPHA => Some([Zero, Zero, Zero, R]),
+            _ => None,
+        };
+        if let Some(swizzle) = swizzle {
+            texture.set_stored_swizzle(swizzle)?;
+        }
+        Ok(texture)
+    }
+
+    /// The `VkFormat` of the GL format, checking that `glFormat` and `glType`
+    /// describe texels of its size.
+    fn vk_format(&self) -> Result<VkFormat, anyhow::Error> {
+        let header = &self.header;
+        let internal_format = header.gl_internal_format;
+        let sized = GL_INTERNAL_FORMATS.iter().find(|&&(gl, _)| gl == internal_format);
+        let format = if let Some(&(_, format)) = sized {
+            format
+        } else if GL_UNSIZED_INTERNAL_FORMATS.contains(&internal_format) {
+            let packed = GL_UNSIZED_PACKED_FORMATS
+                .iter()
+                .find(|&&(gl_format, gl_type, _)| {
+                    gl_format == header.gl_format && gl_type == header.gl_type
+                })
+                .map(|&(_, _, format)| format);
+            let components = header.components();
+            let by_type = GL_UNSIZED_FORMATS
+                .iter()
+                .find(|&&(gl_type, _)| gl_type == header.gl_type)
+                .zip(components)
+                .map(|(&(_, formats), components)| formats[components - 1]);
+            let Some(format) = packed.or(by_type) else {
+                anyhow::bail!(
+                    "Unsupported KTX1 glFormat {:#x} glType {:#x}",
+                    header.gl_format,
+                    header.gl_type
+                );
+            };
+            format
+        } else {
+            anyhow::bail!("Unsupported KTX1 glInternalFormat {:#x}", internal_format);
+        };
+
+        // BGR ordered data of an RGB internal format
+        let format = match (header.gl_format, format) {
+            (GL_BGRA, VkFormat::R8G8B8A8_UNORM) => VkFormat::B8G8R8A8_UNORM,
+            (GL_BGRA, VkFormat::R8G8B8A8_SRGB) => VkFormat::B8G8R8A8_SRGB,
+            (GL_BGR, VkFormat::R8G8B8_UNORM) => VkFormat::B8G8R8_UNORM,
+            (GL_BGR, VkFormat::R8G8B8_SRGB) => VkFormat::B8G8R8_SRGB,
+            (GL_BGRA | GL_BGR, _) => {
+                anyhow::bail!("Unsupported KTX1 BGR order for {:?}", format)
+            }
+            _ => format,
+        };
+        let texel_size = match header.gl_type {
+            0 => None,
+            _ => header.texel_size(),
+        };
+        let format_texel_size =
+            (!is_format_compressed(format)).then(|| get_format_block_size_bytes(format) as usize);
+        if texel_size != format_texel_size {
+            anyhow::bail!(
+                "KTX1 glFormat {:#x} glType {:#x} do not match {:?}",
+                header.gl_format,
+                header.gl_type,
+                format
+            );
+        }
+        Ok(format)
+    }
+}
+
+impl Ktx1Header {
+    fn components(&self) -> Option<usize> {
+        let components = GL_FORMAT_COMPONENTS.iter().find(|&&(gl, _)| gl == self.gl_format);
+        components.map(|&(_, components)| components)
+    }
+
+    /// Size in bytes of the uncompressed texels given by `glFormat` and
+    /// `glType`.
+    fn texel_size(&self) -> Option<usize> {
+        let components = self.components()?;
+        if GL_PACKED_TYPES.contains(&self.gl_type) {
+            Some(self.gl_type_size as usize)
+        } else {
+            Some(components * self.gl_type_size as usize)
+        }
+    }
+}
+
+impl TextureKtx2 {
+    /// Reads a KTX 1.1 file and upgrades it, see [`TextureKtx1::to_ktx2`].
+    pub fn from_ktx1(bytes: &[u8]) -> Result<TextureKtx2, anyhow::Error> {
+        TextureKtx1::read_from_bytes(bytes)?.to_ktx2()
+    }
+}
+
+/// Splits off the first `len` bytes of `data`.
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], anyhow::Error> {
+    if data.len() < len {
+        anyhow::bail!("Truncated KTX1 file");
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+/// Skips the padding after `len` bytes aligning the next value to 4 bytes.
+fn skip_padding(data: &mut &[u8], len: usize) {
+    let padding = len.next_multiple_of(4) - len;
+    *data = &data[padding.min(data.len())..];
+}
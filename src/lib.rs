@@ -8,11 +8,29 @@ pub mod pixel;
 pub mod texture;
 
 pub mod filter;
+pub mod resample;
+pub mod bc;
+pub mod metrics;
+#[cfg(feature = "png")]
+pub mod png;
+#[cfg(feature = "exr")]
+mod exr;
+mod hdr;
+mod dds;
+pub mod ktx1;
+pub mod raw;
 #[cfg(test)]
 mod tests {
     use half::f16;
 
-    use crate::{texture::TextureKtx2, vk_format::VkFormat, pixel::Pixel};
+    use crate::{
+        bc::{bc1, CompressOptions, Quality},
+        filter::*,
+        pixel::{ChannelSource, ConvertOptions, Pixel, WriteMode},
+        resample::{AddressMode, ColorSpace, Reduction, ResizeOptions},
+        texture::TextureKtx2,
+        vk_format::*,
+    };
 
     #[test]
     fn test_r16_sfloat() {
@@ -21,7 +39,7 @@ mod tests {
         let mut tex: TextureKtx2 = TextureKtx2::new(size, size, VkFormat::R16_SFLOAT);
         for i in 0..size {
             for j in 0..size {
-                tex.write_pixel(j, i, Pixel::R16_SFLOAT(f16::from_f32(1500f32)));
+                tex.write_pixel(j, i, Pixel::R16_SFLOAT(f16::from_f32(1500f32))).unwrap();
             }
         }
         tex.write_to_ktx2("output_r16_sfloat.ktx2").unwrap();
@@ -34,10 +52,10 @@ mod tests {
         let size = 2u32;
 
         let mut tex: TextureKtx2 = TextureKtx2::new(size, size, VkFormat::R8G8B8A8_UNORM);
-        tex.write_pixel(0, 0, Pixel::R8G8B8A8_UNORM([255,0,0,255]));
-        tex.write_pixel(1, 0, Pixel::R8G8B8A8_UNORM([0,255,0,255]));
-        tex.write_pixel(0, 1, Pixel::R8G8B8A8_UNORM([0,0,255,255]));
-        tex.write_pixel(1, 1, Pixel::R8G8B8A8_UNORM([255,255,0,255]));
+        tex.write_pixel(0, 0, Pixel::R8G8B8A8_UNORM([255,0,0,255])).unwrap();
+        tex.write_pixel(1, 0, Pixel::R8G8B8A8_UNORM([0,255,0,255])).unwrap();
+        tex.write_pixel(0, 1, Pixel::R8G8B8A8_UNORM([0,0,255,255])).unwrap();
+        tex.write_pixel(1, 1, Pixel::R8G8B8A8_UNORM([255,255,0,255])).unwrap();
 
         tex.write_to_ktx2("output_rgba8_unorm.ktx2").unwrap();
 
@@ -48,7 +66,7 @@ mod tests {
     fn test_bc1_new() {
         let size = 4u32;
 
-        let mut tex: TextureKtx2 = TextureKtx2::new(size, size, VkFormat::BC1_RGB_UNORM_BLOCK);
+        let tex: TextureKtx2 = TextureKtx2::new(size, size, VkFormat::BC1_RGB_UNORM_BLOCK);
         
         tex.write_to_ktx2("output_bc1_rgba_unorm.ktx2").unwrap();
 
@@ -57,10 +75,1640 @@ mod tests {
 
     #[test]
     fn test_bc1_load() {
-        let mut tex: TextureKtx2 = TextureKtx2::read_from_ktx2("output_bc1_rgba_unorm.ktx2").unwrap();
+        let tex: TextureKtx2 = TextureKtx2::read_from_ktx2("output_bc1_rgba_unorm.ktx2").unwrap();
         
         tex.write_to_ktx2("output_bc1_rgba_unorm2.ktx2").unwrap();
 
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn test_layout_empty_sections() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(3, 3, VkFormat::R16_SFLOAT);
+        tex.key_value_data.clear();
+
+        let index = tex.layout();
+        assert_eq!(index.dfd_byte_offset, 80 + 24);
+        assert_eq!(index.kvd_byte_offset, 0);
+        assert_eq!(index.kvd_byte_length, 0);
+        assert_eq!(index.sgd_byte_offset, 0);
+        assert_eq!(index.sgd_byte_length, 0);
+        assert_eq!(index.levels[0].byte_offset % 4, 0);
+        assert_eq!(index.levels[0].byte_length, 18);
+
+        let mut bytes = vec![];
+        tex.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len() as u64, index.levels[0].byte_offset + 18);
+
+        let read = TextureKtx2::read_from_bytes(&bytes).unwrap();
+        assert!(read.key_value_data.is_empty());
+        assert_eq!(read.level_images, tex.level_images);
+    }
+
+    #[test]
+    fn test_layout_alignment() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(3, 3, VkFormat::R16G16B16A16_SFLOAT);
+        tex.key_value_data
+            .insert(String::from("KTXwriter"), b"odd\0".to_vec());
+        tex.supercompression_global_data = vec![1, 2, 3];
+        tex.level_images[0].fill(0xAA);
+        tex.level_images.push(vec![0xBB; tex.level_byte_length(1) as usize]);
+        tex.header.level_count = 2;
+
+        let index = tex.layout();
+        assert_eq!(index.kvd_byte_offset, index.dfd_byte_offset + index.dfd_byte_length);
+        assert_eq!(index.kvd_byte_length % 4, 0);
+        assert_eq!(index.sgd_byte_offset % 8, 0);
+        assert!(index.sgd_byte_offset >= (index.kvd_byte_offset + index.kvd_byte_length) as u64);
+        assert_eq!(index.levels[0].byte_length, 3 * 3 * 8);
+        assert_eq!(index.levels[1].byte_length, 8);
+        // smallest level first, each aligned to lcm(8, 4)
+        assert!(index.levels[1].byte_offset < index.levels[0].byte_offset);
+        assert_eq!(index.levels[1].byte_offset % 8, 0);
+        assert_eq!(index.levels[0].byte_offset % 8, 0);
+
+        let mut bytes = vec![];
+        tex.write_to(&mut bytes).unwrap();
+        let sgd_end = (index.sgd_byte_offset + index.sgd_byte_length) as usize;
+        assert!(bytes[sgd_end..index.levels[1].byte_offset as usize]
+            .iter()
+            .all(|&b| b == 0));
+
+        let read = TextureKtx2::read_from_bytes(&bytes).unwrap();
+        assert_eq!(read.key_value_data, tex.key_value_data);
+        assert_eq!(read.supercompression_global_data, vec![1, 2, 3]);
+        assert_eq!(read.level_images, tex.level_images);
+
+        // unknown formats are rejected
+        bytes[12..16].copy_from_slice(&185u32.to_le_bytes());
+        assert!(TextureKtx2::read_from_bytes(&bytes).is_err());
+        assert_eq!(VkFormat::try_from(1000066013).unwrap(), VkFormat::ASTC_12x12_SFLOAT_BLOCK_EXT);
+        assert!(VkFormat::try_from(1000066014).is_err());
+        assert_eq!(read.dfd_descriptor_block.len(), 1);
+        assert_eq!(read.dfd_descriptor_block[0].samples.len(), 4);
+
+        // so are files the rest of the API cannot handle
+        let write = |tex: &TextureKtx2| {
+            let mut bytes = vec![];
+            tex.write_to(&mut bytes).unwrap();
+            bytes
+        };
+        let mut short = tex.clone();
+        short.level_images[1].pop();
+        assert!(TextureKtx2::read_from_bytes(&write(&short)).is_err());
+        let mut no_dfd = tex.clone();
+        no_dfd.dfd_descriptor_block.clear();
+        assert!(TextureKtx2::read_from_bytes(&write(&no_dfd)).is_err());
+        let mut levels = write(&tex);
+        levels[40..44].copy_from_slice(&3u32.to_le_bytes());
+        assert!(TextureKtx2::read_from_bytes(&levels).is_err());
+    }
+
+    #[test]
+    fn test_pixel_rgba_round_trip_all_formats() {
+        for value in 1..=130u32 {
+            let format = VkFormat::try_from(value).unwrap();
+            let Some(layout) = get_format_layout(format) else {
+                continue;
+            };
+            let mut rgba = [0.25, 0.5, 0.75, 1.0];
+            for channel in layout.channels() {
+                if matches!(
+                    channel.numeric,
+                    NumericFormat::Uint | NumericFormat::Sint | NumericFormat::Uscaled | NumericFormat::Sscaled
+                ) {
+                    rgba[layout.rgba_index(channel.channel)] = [1.0, 0.0, 1.0, 1.0][layout.rgba_index(channel.channel)];
+                }
+            }
+
+            let mut tex: TextureKtx2 = TextureKtx2::new(2, 1, format);
+            let pixel = Pixel::from_rgba_f32(format, rgba).unwrap();
+            assert_eq!(pixel.format(), format);
+            tex.write_pixel(1, 0, pixel).unwrap();
+            assert_eq!(tex.read_pixel(1, 0).unwrap(), pixel);
+            assert_eq!(tex.dfd_descriptor_block[0].row_4, layout.texel_size);
+
+            let decoded = pixel.to_rgba_f32().unwrap();
+            for channel in layout.channels() {
+                let i = layout.rgba_index(channel.channel);
+                let tolerance = 1.0 / ((1u64 << (channel.bit_length.min(24) - 1)).max(2) - 1) as f32;
+                assert!(
+                    (decoded[i] - rgba[i]).abs() <= tolerance,
+                    "{:?} channel {:?}: {} != {}",
+                    format,
+                    channel.channel,
+                    decoded[i],
+                    rgba[i]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_pixel_packed_formats() {
+        let pixel = Pixel::R5G6B5_UNORM_PACK16(0xF800);
+        assert_eq!(pixel.to_rgba_f32(), Some([1.0, 0.0, 0.0, 1.0]));
+
+        let pixel = Pixel::from_rgba_f32(VkFormat::A2B10G10R10_UNORM_PACK32, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(pixel, Some(Pixel::A2B10G10R10_UNORM_PACK32(0xC000_03FF)));
+
+        let pixel = Pixel::from_rgba_f32(VkFormat::R8_SNORM, [-1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(pixel, Some(Pixel::R8_SNORM(-127)));
+
+        let pixel = Pixel::from_rgba_f32(VkFormat::B10G11R11_UFLOAT_PACK32, [1.0, 2.0, 0.5, 1.0]);
+        assert_eq!(pixel.unwrap().to_rgba_f32(), Some([1.0, 2.0, 0.5, 1.0]));
+
+        let pixel = Pixel::from_rgba_f32(VkFormat::E5B9G9R9_UFLOAT_PACK32, [1.0, 3.0, 0.25, 1.0]);
+        assert_eq!(pixel.unwrap().to_rgba_f32(), Some([1.0, 3.0, 0.25, 1.0]));
+
+        let pixel = Pixel::from_rgba_f32(VkFormat::D32_SFLOAT_S8_UINT, [0.5, 7.0, 0.0, 1.0]);
+        assert_eq!(pixel, Some(Pixel::D32_SFLOAT_S8_UINT((0.5, 7))));
+        assert_eq!(Pixel::BC1_RGB_UNORM_BLOCK.to_rgba_f32(), None);
+    }
+
+    #[test]
+    fn test_write_pixel_checks() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::R16_SFLOAT);
+        assert!(tex.write_pixel(0, 0, Pixel::R8G8B8A8_UNORM([255, 0, 0, 255])).is_err());
+        assert!(tex.write_pixel(2, 0, Pixel::R16_SFLOAT(f16::ONE)).is_err());
+        assert!(tex.write_pixel(0, 2, Pixel::R16_SFLOAT(f16::ONE)).is_err());
+        assert!(tex.level_images[0].iter().all(|&b| b == 0));
+
+        tex.write_pixel_with_mode(1, 1, Pixel::R8_UNORM(255), WriteMode::Convert).unwrap();
+        assert_eq!(tex.read_pixel(1, 1).unwrap(), Pixel::R16_SFLOAT(f16::ONE));
+        // depth does not convert to color
+        assert!(tex.write_pixel_with_mode(0, 0, Pixel::D16_UNORM(1), WriteMode::Convert).is_err());
+
+        let mut tex: TextureKtx2 = TextureKtx2::new(1, 1, VkFormat::R16G16B16A16_SFLOAT);
+        let pixel = Pixel::R16G16B16A16_SFLOAT([f16::ONE, f16::ZERO, f16::NEG_ONE, f16::ONE]);
+        tex.write_pixel(0, 0, pixel).unwrap();
+        assert_eq!(tex.read_pixel(0, 0).unwrap(), pixel);
+
+        let mut tex: TextureKtx2 = TextureKtx2::new(1, 1, VkFormat::R8_UNORM);
+        assert!(tex.write_pixel_with_mode(0, 0, Pixel::R8_UINT(1), WriteMode::Convert).is_err());
+        assert!(tex.read_pixel(1, 0).is_err());
+        assert!(tex.read_pixel(0, u32::MAX).is_err());
+        let tex: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::BC1_RGB_UNORM_BLOCK);
+        assert!(tex.read_pixel(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_level_views() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(3, 2, VkFormat::R16_SFLOAT);
+        for (y, row) in tex.level_rows_mut::<f16>(0).unwrap().enumerate() {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = f16::from_f32((y * 3 + x) as f32);
+            }
+        }
+        assert_eq!(tex.read_pixel(2, 1).unwrap(), Pixel::R16_SFLOAT(f16::from_f32(5.0)));
+        assert_eq!(tex.level_as::<f16>(0).unwrap().len(), 6);
+        assert_eq!(tex.level_rows::<f16>(0).unwrap().count(), 2);
+        assert_eq!(tex.level_planes::<f16>(0).unwrap().count(), 1);
+        assert!(tex.level_as::<f16>(1).is_err());
+        assert!(tex.level_as::<f32>(0).is_err());
+
+        let mut tex: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::R8G8B8A8_UNORM);
+        tex.write_pixel(1, 1, Pixel::R8G8B8A8_UNORM([1, 2, 3, 4])).unwrap();
+        assert_eq!(tex.level_as::<[u8; 4]>(0).unwrap()[3], [1, 2, 3, 4]);
+        assert_eq!(tex.level_as::<u8>(0).unwrap().len(), 16);
+        assert!(tex.level_as::<[u8; 3]>(0).is_err());
+
+        let tex: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::BC1_RGB_UNORM_BLOCK);
+        let rows = tex.level_rows::<[u8; 8]>(0).unwrap();
+        assert_eq!(rows.map(|row| row.len()).collect::<Vec<_>>(), vec![2, 2]);
+    }
+
+    #[test]
+    fn test_resize_preserves_signed_values() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::R16_SFLOAT);
+        for value in tex.level_as_mut::<f16>(0).unwrap() {
+            *value = f16::from_f32(-120.0);
+        }
+        let out = TextureKtx2::resize(tex, 3, 5, FilterType::Lanczos3).unwrap();
+        assert_eq!(out.header.pixel_width, 3);
+        assert_eq!(out.header.pixel_height, 5);
+        assert!(out.level_as::<f16>(0).unwrap().iter().all(|v| v.to_f32() == -120.0));
+
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R8G8_SNORM);
+        for value in tex.level_as_mut::<[i8; 2]>(0).unwrap() {
+            *value = [-127, 64];
+        }
+        let out = TextureKtx2::resize(tex, 2, 2, FilterType::Lanczos3).unwrap();
+        assert!(out.level_as::<[i8; 2]>(0).unwrap().iter().all(|v| *v == [-127, 64]));
+    }
+
+    #[test]
+    fn test_resize_rgba() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R8G8B8A8_UNORM);
+        for (i, value) in tex.level_as_mut::<[u8; 4]>(0).unwrap().iter_mut().enumerate() {
+            // alternating columns of opaque red and half transparent blue
+            *value = if i % 2 == 0 { [255, 0, 0, 255] } else { [0, 0, 255, 128] };
+        }
+        let out = TextureKtx2::resize(tex, 2, 2, FilterType::Lanczos3).unwrap();
+        for value in out.level_as::<[u8; 4]>(0).unwrap() {
+            assert!(value[0] > 64 && value[2] > 64, "{:?}", value);
+            assert!(value[3] > 128 && value[3] < 255, "{:?}", value);
+        }
+
+        let mut tex: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::R16G16B16A16_UINT);
+        for value in tex.level_as_mut::<[u16; 4]>(0).unwrap() {
+            *value = [1000, 0, 65535, 7];
+        }
+        let out = TextureKtx2::resize(tex, 5, 5, FilterType::Lanczos3).unwrap();
+        assert!(out.level_as::<[u16; 4]>(0).unwrap().iter().all(|v| *v == [1000, 0, 65535, 7]));
+
+        let bc1: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::BC1_RGB_UNORM_BLOCK);
+        assert!(TextureKtx2::resize(bc1, 2, 2, FilterType::Lanczos3).is_err());
+    }
+
+    #[test]
+    fn test_filter_kernels() {
+        assert_eq!(triangle_kernel(0.5), 0.5);
+        assert_eq!(cubic_kernel(1.0, 0.0, 0.5), 0.0);
+        assert!((cubic_kernel(0.0, 1.0, 0.0) - 2.0 / 3.0).abs() < 1e-6);
+        assert!((cubic_kernel(0.0, 1.0 / 3.0, 1.0 / 3.0) - 8.0 / 9.0).abs() < 1e-6);
+        assert_eq!(kaiser_kernel(0.0, 4.0, 3.0), 1.0);
+        assert_eq!(kaiser_kernel(3.0, 4.0, 3.0), 0.0);
+        assert!(lanczos_kernel(2.0, 4.0).abs() < 1e-6);
+
+        let filters = [
+            FilterType::Nearest,
+            FilterType::Box,
+            FilterType::Triangle,
+            FilterType::CubicBSpline,
+            FilterType::CatmullRom,
+            FilterType::Mitchell,
+            FilterType::Gaussian,
+            FilterType::Kaiser,
+            FilterType::Lanczos3,
+            FilterType::Lanczos(2.0),
+            FilterType::Lanczos(0.0),
+            FilterType::Lanczos(f32::NAN),
+        ];
+        for filter in filters {
+            for (width, height) in [(3, 2), (16, 12)] {
+                let mut tex: TextureKtx2 = TextureKtx2::new(7, 5, VkFormat::R32_SFLOAT);
+                tex.level_as_mut::<f32>(0).unwrap().fill(42.0);
+                let out = TextureKtx2::resize(tex, width, height, filter).unwrap();
+                for value in out.level_as::<f32>(0).unwrap() {
+                    assert!((value - 42.0).abs() < 1e-3, "{:?}: {}", filter, value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_resize_custom_filter() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 1, VkFormat::R32_SFLOAT);
+        tex.level_as_mut::<f32>(0).unwrap().copy_from_slice(&[0.0, 2.0, 4.0, 6.0]);
+        // only the input pixel just left of every output pixel centre contributes
+        let mut filter = Filter {
+            kernel: Box::new(|x| if (-0.5..=0.0).contains(&x) { 1.0 } else { 0.0 }),
+            support: 1.0,
+        };
+        let out = TextureKtx2::resize_with_filter(tex, 2, 1, &mut filter).unwrap();
+        assert_eq!(out.level_as::<f32>(0).unwrap(), &[0.0, 4.0]);
+    }
+
+    #[test]
+    fn test_resize_srgb() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(2, 1, VkFormat::R8G8B8A8_SRGB);
+        tex.level_as_mut::<u8>(0)
+            .unwrap()
+            .copy_from_slice(&[0, 0, 0, 255, 255, 255, 255, 255]);
+        let mut filter = FilterType::Box.to_filter();
+
+        let out = TextureKtx2::resize_with_filter(tex.clone(), 1, 1, &mut filter).unwrap();
+        assert_eq!(out.level_as::<u8>(0).unwrap(), &[188, 188, 188, 255]);
+
+        let options = ResizeOptions { color_space: ColorSpace::Linear, ..Default::default() };
+        let out = TextureKtx2::resize_with_options(tex, 1, 1, &mut filter, &options).unwrap();
+        assert_eq!(out.level_as::<u8>(0).unwrap(), &[128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn test_resize_premultiplied_alpha() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(2, 1, VkFormat::R8G8B8A8_UNORM);
+        tex.level_as_mut::<u8>(0)
+            .unwrap()
+            .copy_from_slice(&[255, 0, 0, 255, 0, 255, 0, 0]);
+        let mut filter = FilterType::Box.to_filter();
+
+        let out = TextureKtx2::resize_with_filter(tex.clone(), 1, 1, &mut filter).unwrap();
+        assert_eq!(out.level_as::<u8>(0).unwrap(), &[128, 128, 0, 128]);
+
+        let options = ResizeOptions { premultiply_alpha: true, ..Default::default() };
+        let out = TextureKtx2::resize_with_options(tex, 1, 1, &mut filter, &options).unwrap();
+        assert_eq!(out.level_as::<u8>(0).unwrap(), &[255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn test_resize_address_modes() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 1, VkFormat::R32_SFLOAT);
+        tex.level_as_mut::<f32>(0).unwrap().copy_from_slice(&[0.0, 0.0, 0.0, 8.0]);
+        let mut filter = FilterType::Triangle.to_filter();
+
+        for (address_mode, expected) in [
+            (AddressMode::ClampToEdge, [0.0, 6.0 / 1.75]),
+            (AddressMode::Repeat, [1.0, 3.0]),
+            (AddressMode::MirroredRepeat, [0.0, 4.0]),
+            (AddressMode::ClampToBorder([4.0; 4]), [0.5, 3.5]),
+        ] {
+            let options = ResizeOptions { address_mode, ..Default::default() };
+            let out =
+                TextureKtx2::resize_with_options(tex.clone(), 2, 1, &mut filter, &options).unwrap();
+            let values = out.level_as::<f32>(0).unwrap();
+            for (value, expected) in values.iter().zip(expected) {
+                assert!((value - expected).abs() < 1e-5, "{:?}: {:?}", address_mode, values);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cubemap_mipmaps() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R32_SFLOAT);
+        tex.header.face_count = 6;
+        tex.level_images[0] = vec![0; tex.level_byte_length(0) as usize];
+        // only the +X face is lit
+        tex.level_planes_mut::<f32>(0).unwrap().next().unwrap().fill(1.0);
+
+        tex.generate_mipmaps(&mut FilterType::Triangle.to_filter(), &ResizeOptions::default())
+            .unwrap();
+        assert_eq!(tex.header.level_count, 3);
+        assert_eq!(tex.level_images.len(), 3);
+        assert_eq!(tex.level_images[1].len(), 6 * 2 * 2 * 4);
+
+        let faces: Vec<f32> = tex.level_planes::<f32>(2).unwrap().map(|face| face[0]).collect();
+        // the edges of +X pick up its dark neighbours, which in turn pick up
+        // light from +X evenly, while -X shares no edge with it
+        assert!(faces[0] > faces[2] && faces[0] < 1.0, "{:?}", faces);
+        assert_eq!(faces[1], 0.0);
+        assert!(faces[2] > 0.0);
+        for face in &faces[3..] {
+            assert!((face - faces[2]).abs() < 1e-6, "{:?}", faces);
+        }
+
+        // resized faces stay square and every face must be present
+        let out = TextureKtx2::resize(tex.clone(), 2, 2, FilterType::Box).unwrap();
+        assert_eq!(out.level_images[0].len(), 6 * 2 * 2 * 4);
+        assert!(TextureKtx2::resize(tex.clone(), 4, 2, FilterType::Box).is_err());
+        let mut short = tex.clone();
+        short.level_images[0].truncate(4 * 4 * 4);
+        assert!(TextureKtx2::resize(short, 2, 2, FilterType::Box).is_err());
+
+        // 3D textures and block compressed formats are refused
+        let mut filter = FilterType::Box.to_filter();
+        let mut volume: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R8_UNORM);
+        volume.header.pixel_depth = 4;
+        assert!(volume.generate_mipmaps(&mut filter, &ResizeOptions::default()).is_err());
+        let mut bc7: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::BC7_UNORM_BLOCK);
+        assert!(bc7.generate_mipmaps(&mut filter, &ResizeOptions::default()).is_err());
+        assert_eq!(bc7.level_images.len(), 1);
+    }
+
+    #[test]
+    fn test_resize_layers() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(5, 3, VkFormat::R16G16B16A16_SFLOAT);
+        tex.header.layer_count = 2;
+        tex.level_images[0] = vec![0; tex.level_byte_length(0) as usize];
+        for (layer, plane) in tex.level_planes_mut::<f16>(0).unwrap().enumerate() {
+            plane.fill(f16::from_f32(layer as f32 + 0.5));
+        }
+
+        let out = TextureKtx2::resize(tex, 2, 7, FilterType::Lanczos3).unwrap();
+        assert_eq!(out.header.layer_count, 2);
+        for (layer, plane) in out.level_planes::<f16>(0).unwrap().enumerate() {
+            assert_eq!(plane.len(), 2 * 7 * 4);
+            for value in plane {
+                assert!((value.to_f32() - (layer as f32 + 0.5)).abs() < 1e-3, "{:?}", plane);
+            }
+        }
+    }
+
+    #[test]
+    fn test_resize_anti_ringing() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(8, 1, VkFormat::R32_SFLOAT);
+        tex.level_as_mut::<f32>(0)
+            .unwrap()
+            .copy_from_slice(&[0.0, 0.0, 0.0, 0.0, 10.0, 10.0, 10.0, 10.0]);
+        let mut filter = FilterType::Lanczos3.to_filter();
+
+        let out = TextureKtx2::resize_with_filter(tex.clone(), 16, 1, &mut filter).unwrap();
+        let values = out.level_as::<f32>(0).unwrap();
+        assert!(values.iter().any(|v| !(0.0..=10.0).contains(v)), "{:?}", values);
+
+        let options = ResizeOptions { anti_ringing: true, ..Default::default() };
+        let out = TextureKtx2::resize_with_options(tex, 16, 1, &mut filter, &options).unwrap();
+        let values = out.level_as::<f32>(0).unwrap();
+        assert!(values.iter().all(|v| (0.0..=10.0).contains(v)), "{:?}", values);
+    }
+
+    #[test]
+    fn test_resize_no_data() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(6, 1, VkFormat::R32_SFLOAT);
+        tex.level_as_mut::<f32>(0)
+            .unwrap()
+            .copy_from_slice(&[1.0, f32::NAN, 3.0, -9999.0, -9999.0, -9999.0]);
+        let options = ResizeOptions { no_data: Some(-9999.0), ..Default::default() };
+        let mut filter = FilterType::Box.to_filter();
+
+        let out = TextureKtx2::resize_with_options(tex, 3, 1, &mut filter, &options).unwrap();
+        assert_eq!(out.level_as::<f32>(0).unwrap(), &[1.0, 3.0, -9999.0]);
+    }
+
+    #[test]
+    fn test_mipmap_reductions() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R32_SFLOAT);
+        for (i, value) in tex.level_as_mut::<f32>(0).unwrap().iter_mut().enumerate() {
+            *value = i as f32;
+        }
+        let mut filter = FilterType::Lanczos3.to_filter();
+
+        for (reduction, level_1, level_2) in [
+            (Reduction::Min, [0.0, 2.0, 8.0, 10.0], 0.0),
+            (Reduction::Max, [5.0, 7.0, 13.0, 15.0], 15.0),
+            (Reduction::Average, [2.5, 4.5, 10.5, 12.5], 7.5),
+        ] {
+            let mut mips = tex.clone();
+            let options = ResizeOptions { reduction, ..Default::default() };
+            mips.generate_mipmaps(&mut filter, &options).unwrap();
+            assert_eq!(mips.level_as::<f32>(1).unwrap(), &level_1);
+            assert_eq!(mips.level_as::<f32>(2).unwrap(), &[level_2]);
+        }
+    }
+
+    #[test]
+    fn test_bc1_encode() {
+        // a noisy gradient with a transparent corner and a partial block at the edge
+        let mut tex: TextureKtx2 = TextureKtx2::new(6, 4, VkFormat::R8G8B8A8_UNORM);
+        for (i, texel) in tex.level_as_mut::<[u8; 4]>(0).unwrap().iter_mut().enumerate() {
+            let (x, y) = (i % 6, i / 6);
+            let t = (x % 4 + 2 * y) as u8;
+            let noise = ((x * 7 + y * 3) % 5) as u8 * 3;
+            let alpha = if x + y == 0 { 0 } else { 255 };
+            *texel = [t * 20 + noise, t * 12, 200 - t * 8, alpha];
+        }
+        tex.generate_mipmaps(&mut FilterType::Box.to_filter(), &ResizeOptions::default()).unwrap();
+        let source = tex.level_as::<[u8; 4]>(0).unwrap();
+
+        let mut errors = Vec::new();
+        for (format, quality) in [
+            (VkFormat::BC1_RGB_UNORM_BLOCK, Quality::Fast),
+            (VkFormat::BC1_RGB_UNORM_BLOCK, Quality::Normal),
+            (VkFormat::BC1_RGBA_UNORM_BLOCK, Quality::Normal),
+        ] {
+            let options = CompressOptions { quality, ..Default::default() };
+            let bc1 = tex.compress(format, &options).unwrap();
+            assert_eq!(bc1.header.level_count, 3);
+            assert_eq!(bc1.level_images.iter().map(Vec::len).collect::<Vec<_>>(), [16, 8, 8]);
+
+            let alpha = format == VkFormat::BC1_RGBA_UNORM_BLOCK;
+            let blocks = [
+                bc1::decode_block(&bc1.level_images[0][..8], alpha),
+                bc1::decode_block(&bc1.level_images[0][8..], alpha),
+            ];
+            let mut squared_error = 0;
+            for (i, texel) in source.iter().enumerate() {
+                let (x, y) = (i % 6, i / 6);
+                let transparent = alpha && x + y == 0;
+                let decoded = blocks[x / 4][y * 4 + x % 4].map(|c| (c * 255.0).round() as i32);
+                if !transparent {
+                    squared_error +=
+                        (0..3).map(|c| (decoded[c] - texel[c] as i32).pow(2)).sum::<i32>();
+                }
+                assert_eq!(decoded[3], if transparent { 0 } else { 255 });
+            }
+            errors.push((squared_error as f32 / (source.len() * 3) as f32).sqrt());
+        }
+        // cluster fit beats range fit, the transparent texel is left out of the fit
+        assert!(errors[1] < errors[0], "{:?}", errors);
+        assert!(errors.iter().all(|&e| e < 12.0), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_bc_decompress() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(6, 5, VkFormat::R8G8B8A8_UNORM);
+        for (i, texel) in tex.level_as_mut::<[u8; 4]>(0).unwrap().iter_mut().enumerate() {
+            let t = (i % 6 + i / 6) as u8;
+            *texel = [t * 20, 100 + t * 10, 200 - t * 15, if i == 0 { 0 } else { 255 }];
+        }
+        let bc1 = tex
+            .compress(VkFormat::BC1_RGBA_UNORM_BLOCK, &CompressOptions::default())
+            .unwrap();
+        let rgba = bc1.decompress(VkFormat::R8G8B8A8_UNORM).unwrap();
+        assert_eq!(rgba.header.pixel_width, 6);
+        assert_eq!(rgba.header.pixel_height, 5);
+
+        // partial blocks at the right and bottom edges are cropped
+        let decoded = rgba.level_as::<[u8; 4]>(0).unwrap();
+        for (i, texel) in decoded.iter().enumerate() {
+            let (x, y) = (i % 6, i / 6);
+            let block = (y / 4 * 2 + x / 4) * 8;
+            let data = &bc1.level_images[0][block..block + 8];
+            let expected = bc1::decode_block(data, true)[y % 4 * 4 + x % 4];
+            let expected = expected.map(|c| c * 255.0);
+            assert!((0..4).all(|c| (texel[c] as f32 - expected[c]).abs() <= 1.0));
+        }
+        assert_eq!(decoded[0][3], 0);
+
+        assert!(tex.decompress(VkFormat::R8G8B8A8_UNORM).is_err());
+        assert!(bc1.decompress(VkFormat::BC1_RGB_UNORM_BLOCK).is_err());
+    }
+
+    #[test]
+    fn test_bc_decode_blocks() {
+        let decode = |format: VkFormat, block: &[u8]| {
+            let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, format);
+            tex.level_images[0] = block.to_vec();
+            let rgba = tex.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+            rgba.level_as::<[f32; 4]>(0).unwrap().to_vec()
+        };
+
+        // BC4 with 8 interpolated values, texels 0..3 select indices 0, 1, 2 and 7
+        let bc4 = [255, 0, 0b1000_1000, 0b1110, 0, 0, 0, 0];
+        let texels = decode(VkFormat::BC4_UNORM_BLOCK, &bc4);
+        assert_eq!(texels[0], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[1][0], 0.0);
+        assert!((texels[2][0] - 6.0 / 7.0).abs() < 1e-6);
+        assert!((texels[3][0] - 1.0 / 7.0).abs() < 1e-6);
+
+        // BC5 SNORM with the 6 value palette: -1, 1 and the range limits
+        let bc5 = [0x81, 127, 0b0111_1000, 0, 0, 0, 0, 0, 127, 0x80, 0b0000_0001, 0, 0, 0, 0, 0];
+        let texels = decode(VkFormat::BC5_SNORM_BLOCK, &bc5);
+        assert_eq!(texels[0], [-1.0, -1.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [1.0, 1.0, 0.0, 1.0]);
+        assert_eq!(texels[2], [1.0, 1.0, 0.0, 1.0]);
+
+        // BC7 mode 6: 7 bit endpoints with a shared p-bit
+        let bits: u128 = 1 << 6 | 0x7F << 7 | 0x40 << 35 | 0x7F << 49 | 1 << 63;
+        let texels = decode(VkFormat::BC7_UNORM_BLOCK, &bits.to_le_bytes());
+        let expected = [255.0, 1.0, 129.0, 255.0].map(|c| c / 255.0);
+        assert!(texels.iter().all(|t| (0..4).all(|c| (t[c] - expected[c]).abs() < 1e-6)));
+
+        // BC6H mode 3: 10 bit endpoints, red of endpoint 0 at bit 5
+        for (red, expected) in [(1023, 65504.0), (512, f16::from_bits(0x3E0F).to_f32())] {
+            let bits: u128 = 0x03 | red << 5;
+            let texels = decode(VkFormat::BC6H_UFLOAT_BLOCK, &bits.to_le_bytes());
+            assert_eq!(texels[15], [expected, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn test_bc4_bc5_encode() {
+        // elevation in meters, smooth with a ridge
+        let mut tex: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::R16_SFLOAT);
+        for (i, h) in tex.level_as_mut::<f16>(0).unwrap().iter_mut().enumerate() {
+            let (x, y) = ((i % 8) as f32, (i / 8) as f32);
+            let ridge = if x == 5.0 { 150.0 } else { 0.0 };
+            *h = f16::from_f32(-100.0 + x * 60.0 + y * y * 8.0 + ridge);
+        }
+        let source = tex.level_as::<f16>(0).unwrap().to_vec();
+
+        let mut errors = Vec::new();
+        for quality in [Quality::Fast, Quality::Normal] {
+            let options = CompressOptions {
+                quality,
+                value_range: Some((-100.0, 900.0)),
+                ..Default::default()
+            };
+            let bc4 = tex.compress(VkFormat::BC4_UNORM_BLOCK, &options).unwrap();
+            assert_eq!(bc4.dfd_descriptor_block[0].row_2 & 0xFF, 131);
+            assert_eq!(bc4.level_images[0].len(), 32);
+
+            let decoded = bc4.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+            let decoded = decoded.level_as::<[f32; 4]>(0).unwrap();
+            let squared_error: f32 = source
+                .iter()
+                .zip(decoded)
+                .map(|(h, d)| (h.to_f32() - (-100.0 + d[0] * 1000.0)).powi(2))
+                .sum();
+            errors.push((squared_error / 64.0).sqrt());
+        }
+        // RMSE in meters
+        assert!(errors[1] < errors[0], "{:?}", errors);
+        assert!(errors[1] < 15.0, "{:?}", errors);
+
+        // a normal map stored as UNORM becomes SNORM
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R8G8_UNORM);
+        for (i, texel) in tex.level_as_mut::<[u8; 2]>(0).unwrap().iter_mut().enumerate() {
+            *texel = [(i * 4) as u8, 200 - (i * 3) as u8];
+        }
+        let options = CompressOptions {
+            value_range: Some((0.0, 1.0)),
+            ..Default::default()
+        };
+        let bc5 = tex.compress(VkFormat::BC5_SNORM_BLOCK, &options).unwrap();
+        assert_eq!(bc5.dfd_descriptor_block[0].row_2 & 0xFF, 132);
+        let decoded = bc5.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+        let source = tex.level_as::<[u8; 2]>(0).unwrap();
+        for (s, d) in source.iter().zip(decoded.level_as::<[f32; 4]>(0).unwrap()) {
+            for c in 0..2 {
+                let expected = s[c] as f32 / 255.0 * 2.0 - 1.0;
+                assert!((d[c] - expected).abs() < 0.04, "{} {}", d[c], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bc6h_encode() {
+        // an HDR sky gradient with a bright sun at the right edge
+        let mut tex: TextureKtx2 = TextureKtx2::new(8, 4, VkFormat::R16G16B16A16_SFLOAT);
+        for (i, texel) in tex.level_as_mut::<[f16; 4]>(0).unwrap().iter_mut().enumerate() {
+            let (x, y) = ((i % 8) as f32, (i / 8) as f32);
+            let sky = [0.2 + x * 0.05, 0.4 + x * 0.06 + y * 0.02, 1.0 + y * 0.3];
+            let sun = if x >= 6.0 { 250.0 } else { 0.0 };
+            *texel = [sky[0] + sun, sky[1] + sun * 0.9, sky[2] + sun * 0.7, 1.0].map(f16::from_f32);
+        }
+        let source = tex.level_as::<[f16; 4]>(0).unwrap().to_vec();
+        let mean_relative_error = |bc6h: &TextureKtx2| {
+            let decoded = bc6h.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+            let decoded = decoded.level_as::<[f32; 4]>(0).unwrap();
+            let mut error = 0.0f32;
+            for (s, d) in source.iter().zip(decoded) {
+                for c in 0..3 {
+                    error += (d[c] - s[c].to_f32()).abs() / s[c].to_f32().abs();
+                }
+            }
+            error / (source.len() * 3) as f32
+        };
+
+        let mut errors = Vec::new();
+        for quality in [Quality::Fast, Quality::Normal] {
+            let options = CompressOptions { quality, ..Default::default() };
+            let bc6h = tex.compress(VkFormat::BC6H_UFLOAT_BLOCK, &options).unwrap();
+            assert_eq!(bc6h.dfd_descriptor_block[0].row_2 & 0xFF, 133);
+            assert_eq!(bc6h.level_images[0].len(), 32);
+            errors.push(mean_relative_error(&bc6h));
+        }
+        // two regions separate the sun from the sky
+        assert!(errors[1] < errors[0], "{:?}", errors);
+        assert!(errors[1] < 0.08, "{:?}", errors);
+
+        // negative values survive in the signed variant only
+        for texel in tex.level_as_mut::<[f16; 4]>(0).unwrap() {
+            texel[0] = -texel[0];
+        }
+        let source = tex.level_as::<[f16; 4]>(0).unwrap().to_vec();
+        let options = CompressOptions::default();
+        let signed = tex.compress(VkFormat::BC6H_SFLOAT_BLOCK, &options).unwrap();
+        let decoded = signed.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+        for (s, d) in source.iter().zip(decoded.level_as::<[f32; 4]>(0).unwrap()) {
+            assert!((0.75..1.25).contains(&(d[0] / s[0].to_f32())), "{} {}", d[0], s[0]);
+        }
+        let unsigned = tex.compress(VkFormat::BC6H_UFLOAT_BLOCK, &options).unwrap();
+        let decoded = unsigned.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+        assert!(decoded.level_as::<[f32; 4]>(0).unwrap().iter().all(|d| d[0] == 0.0));
+    }
+
+    #[test]
+    fn test_bc7_encode() {
+        // an opaque gradient, a translucent gradient, two colors and noise
+        let mut tex: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::R8G8B8A8_UNORM);
+        for (i, texel) in tex.level_as_mut::<[u8; 4]>(0).unwrap().iter_mut().enumerate() {
+            let (x, y) = (i % 8, i / 8);
+            let t = ((x % 4) * 4 + y % 4) as u8;
+            let noise = ((x * 37 + y * 91) % 23) as u8;
+            *texel = match (x / 4, y / 4) {
+                (0, 0) => [t * 15, 40 + t * 8, 200 - t * 10, 255],
+                (1, 0) => [200 - t * 5, 60, t * 12, 255 - t * 14],
+                (0, 1) => if x % 4 < 2 { [220, 30, 40, 255] } else { [20, 90, 230, 255] },
+                _ => [100 + noise * 5, 150 - noise * 3, 80 + t * 9, 180 + noise],
+            };
+        }
+        let source = tex.level_as::<[u8; 4]>(0).unwrap().to_vec();
+        let error = |bc7: &TextureKtx2, weights: [f32; 4]| {
+            let decoded = bc7.decompress(VkFormat::R8G8B8A8_UNORM).unwrap();
+            let decoded = decoded.level_as::<[u8; 4]>(0).unwrap();
+            let squared_error: f32 = source
+                .iter()
+                .zip(decoded)
+                .map(|(s, d)| (0..4).map(|c| weights[c] * (s[c] as f32 - d[c] as f32).powi(2)))
+                .map(|e| e.sum::<f32>())
+                .sum();
+            (squared_error / (source.len() * 4) as f32).sqrt()
+        };
+
+        let mut errors = Vec::new();
+        for quality in [Quality::Fast, Quality::Normal, Quality::Slow] {
+            let options = CompressOptions { quality, ..Default::default() };
+            let bc7 = tex.compress(VkFormat::BC7_UNORM_BLOCK, &options).unwrap();
+            assert_eq!(bc7.dfd_descriptor_block[0].row_2 & 0xFF, 134);
+            assert_eq!(bc7.level_images[0].len(), 64);
+            errors.push(error(&bc7, [1.0; 4]));
+        }
+        assert!(errors[1] < errors[0] && errors[2] <= errors[1], "{:?}", errors);
+        assert!(errors[2] < 4.0, "{:?}", errors);
+
+        // luma weighted errors favor green over blue
+        let luma = [0.299, 0.587, 0.114, 1.0];
+        let options = CompressOptions { perceptual: true, ..Default::default() };
+        let perceptual = tex.compress(VkFormat::BC7_UNORM_BLOCK, &options).unwrap();
+        let plain = tex.compress(VkFormat::BC7_UNORM_BLOCK, &Default::default()).unwrap();
+        assert!(error(&perceptual, luma) <= error(&plain, luma));
+
+        let srgb = tex.compress(VkFormat::BC7_SRGB_BLOCK, &Default::default()).unwrap();
+        assert!(srgb.dfd_descriptor_block[0].is_srgb());
+    }
+
+    #[test]
+    fn test_etc2_eac_encode() {
+        // a gradient, two colors, a smooth ramp and a transparent corner
+        let mut tex: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::R8G8B8A8_UNORM);
+        for (i, texel) in tex.level_as_mut::<[u8; 4]>(0).unwrap().iter_mut().enumerate() {
+            let (x, y) = ((i % 8) as u8, (i / 8) as u8);
+            let alpha = if x < 2 && y < 2 { 0 } else { 255 - x * 20 };
+            *texel = match (x / 4, y / 4) {
+                (0, 0) => [x * 50, 100 + y * 20, 200 - x * 30, alpha],
+                (1, 0) => if x % 4 < 2 { [230, 40, 30, alpha] } else { [20, 60, 220, alpha] },
+                _ => [x * 30, y * 30, 128, alpha],
+            };
+        }
+        let source = tex.level_as::<[u8; 4]>(0).unwrap().to_vec();
+        let decode = |etc: &TextureKtx2| {
+            let decoded = etc.decompress(VkFormat::R8G8B8A8_UNORM).unwrap();
+            decoded.level_as::<[u8; 4]>(0).unwrap().to_vec()
+        };
+        let rmse = |decoded: &[[u8; 4]], channels: std::ops::Range<usize>| {
+            let count = (decoded.len() * channels.len()) as f32;
+            let squared_error: f32 = source
+                .iter()
+                .zip(decoded)
+                .flat_map(|(s, d)| channels.clone().map(|c| (s[c] as f32 - d[c] as f32).powi(2)))
+                .sum();
+            (squared_error / count).sqrt()
+        };
+
+        let mut errors = Vec::new();
+        for quality in [Quality::Fast, Quality::Normal] {
+            let options = CompressOptions { quality, ..Default::default() };
+            let etc2 = tex.compress(VkFormat::ETC2_R8G8B8_UNORM_BLOCK, &options).unwrap();
+            assert_eq!(etc2.dfd_descriptor_block[0].row_2 & 0xFF, 161);
+            assert_eq!(etc2.level_images[0].len(), 32);
+            errors.push(rmse(&decode(&etc2), 0..3));
+        }
+        assert!(errors[1] <= errors[0] && errors[1] < 12.0, "{:?}", errors);
+
+        // punch-through alpha keeps the transparent corner only
+        let a1 = tex.compress(VkFormat::ETC2_R8G8B8A1_UNORM_BLOCK, &Default::default()).unwrap();
+        for (s, d) in source.iter().zip(decode(&a1)) {
+            assert_eq!(d[3], if s[3] < 128 { 0 } else { 255 });
+        }
+
+        let rgba = tex.compress(VkFormat::ETC2_R8G8B8A8_SRGB_BLOCK, &Default::default()).unwrap();
+        assert!(rgba.dfd_descriptor_block[0].is_srgb());
+        assert_eq!(rgba.level_images[0].len(), 64);
+        assert!(rmse(&decode(&rgba), 3..4) < 4.0);
+
+        // 16 steps of a signed ramp share 8 EAC levels
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R32_SFLOAT);
+        for (i, r) in tex.level_as_mut::<f32>(0).unwrap().iter_mut().enumerate() {
+            *r = -0.3 + i as f32 * 0.01;
+        }
+        let r11 = tex.compress(VkFormat::EAC_R11_SNORM_BLOCK, &Default::default()).unwrap();
+        assert_eq!(r11.dfd_descriptor_block[0].row_2 & 0xFF, 161);
+        let decoded = r11.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+        let source = tex.level_as::<f32>(0).unwrap();
+        for (s, d) in source.iter().zip(decoded.level_as::<[f32; 4]>(0).unwrap()) {
+            assert!((s - d[0]).abs() < 0.012, "{} {}", s, d[0]);
+        }
+        let unsigned = tex.compress(VkFormat::EAC_R11_UNORM_BLOCK, &Default::default()).unwrap();
+        let decoded = unsigned.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+        assert!(decoded.level_as::<[f32; 4]>(0).unwrap().iter().all(|d| d[0] == 0.0));
+    }
+
+    #[test]
+    fn test_etc2_eac_decode_blocks() {
+        // reference blocks of every ETC2 mode, all with the same index bits
+        let decode = |format: VkFormat, block: &[u8]| {
+            let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, format);
+            tex.level_images[0] = block.to_vec();
+            let rgba = tex.decompress(VkFormat::R8G8B8A8_UNORM).unwrap();
+            rgba.level_as::<[u8; 4]>(0).unwrap().to_vec()
+        };
+        let (rgb, a1) = (VkFormat::ETC2_R8G8B8_UNORM_BLOCK, VkFormat::ETC2_R8G8B8A1_UNORM_BLOCK);
+        let blocks = [
+            (
+                "individual",
+                rgb,
+                [130, 74, 198, 120, 150, 60, 90, 240],
+                [
+                    [149, 81, 217], [94, 26, 162], [67, 203, 135], [0, 64, 0],
+                    [149, 81, 217], [94, 26, 162], [0, 64, 0], [67, 203, 135],
+                    [123, 55, 191], [178, 110, 246], [1, 137, 69], [140, 255, 208],
+                    [123, 55, 191], [178, 110, 246], [140, 255, 208], [1, 137, 69],
+                ],
+            ),
+            (
+                "differential, flipped",
+                rgb,
+                [165, 42, 241, 63, 150, 60, 90, 240],
+                [
+                    [170, 46, 252], [148, 24, 230], [170, 46, 252], [148, 24, 230],
+                    [170, 46, 252], [148, 24, 230], [148, 24, 230], [170, 46, 252],
+                    [93, 10, 208], [255, 240, 255], [93, 10, 208], [255, 240, 255],
+                    [93, 10, 208], [255, 240, 255], [255, 240, 255], [93, 10, 208],
+                ],
+            ),
+            (
+                "T",
+                rgb,
+                [249, 41, 126, 75, 150, 60, 90, 240],
+                [
+                    [221, 34, 153], [87, 206, 36], [221, 34, 153], [87, 206, 36],
+                    [221, 34, 153], [87, 206, 36], [87, 206, 36], [221, 34, 153],
+                    [119, 238, 68], [151, 255, 100], [119, 238, 68], [151, 255, 100],
+                    [119, 238, 68], [151, 255, 100], [151, 255, 100], [119, 238, 68],
+                ],
+            ),
+            (
+                "H",
+                rgb,
+                [93, 243, 30, 78, 150, 60, 90, 240],
+                [
+                    [219, 219, 134], [19, 172, 121], [219, 219, 134], [19, 172, 121],
+                    [219, 219, 134], [19, 172, 121], [19, 172, 121], [219, 219, 134],
+                    [83, 236, 185], [155, 155, 70], [83, 236, 185], [155, 155, 70],
+                    [83, 236, 185], [155, 155, 70], [155, 155, 70], [83, 236, 185],
+                ],
+            ),
+            (
+                "planar",
+                rgb,
+                [81, 34, 242, 215, 200, 31, 130, 178],
+                [
+                    [162, 163, 85], [165, 173, 67], [168, 182, 49], [171, 192, 30],
+                    [182, 127, 115], [185, 137, 96], [188, 146, 78], [191, 156, 60],
+                    [203, 92, 144], [206, 101, 126], [209, 111, 108], [212, 120, 89],
+                    [223, 56, 174], [226, 65, 155], [229, 75, 137], [232, 84, 119],
+                ],
+            ),
+            // without the opaque bit index 0b10 is transparent black and
+            // differential blocks lose the small modifiers
+            (
+                "punch-through differential",
+                a1,
+                [99, 166, 64, 136, 150, 60, 90, 240],
+                [
+                    [99, 165, 66], [39, 105, 6], [123, 148, 66], [94, 119, 37],
+                    [99, 165, 66], [39, 105, 6], [94, 119, 37], [123, 148, 66],
+                    [0, 0, 0], [159, 225, 126], [0, 0, 0], [152, 177, 95],
+                    [0, 0, 0], [159, 225, 126], [152, 177, 95], [0, 0, 0],
+                ],
+            ),
+            (
+                "punch-through T",
+                a1,
+                [235, 196, 40, 244, 150, 60, 90, 240],
+                [
+                    [119, 204, 68], [23, 125, 244], [119, 204, 68], [23, 125, 244],
+                    [119, 204, 68], [23, 125, 244], [23, 125, 244], [119, 204, 68],
+                    [0, 0, 0], [45, 147, 255], [0, 0, 0], [45, 147, 255],
+                    [0, 0, 0], [45, 147, 255], [45, 147, 255], [0, 0, 0],
+                ],
+            ),
+        ];
+        for (name, format, block, expected) in blocks {
+            let texels = decode(format, &block);
+            for (i, (texel, [r, g, b])) in texels.iter().zip(expected).enumerate() {
+                let transparent = format == a1 && [8, 10, 12, 15].contains(&i);
+                let alpha = if transparent { 0 } else { 255 };
+                assert_eq!(*texel, [r, g, b, alpha], "{} texel {}", name, i);
+            }
+        }
+
+        // EAC alpha of an RGBA block, base 200, multiplier 6 and table 2,
+        // with the indices 0..7 and back in column-major order
+        let indices = [5, 57, 119, 250, 198, 136];
+        let mut rgba8 = vec![200, 98];
+        rgba8.extend(indices);
+        rgba8.extend(blocks[0].2);
+        let alpha: Vec<u8> = decode(VkFormat::ETC2_R8G8B8A8_UNORM_BLOCK, &rgba8)
+            .iter()
+            .map(|t| t[3])
+            .collect();
+        let expected = [
+            188, 206, 255, 122, 170, 224, 242, 152, 152, 242, 224, 170, 122, 255, 206, 188,
+        ];
+        assert_eq!(alpha, expected);
+
+        // R11 as 11 bit values, a zero multiplier steps by the modifiers
+        let r11: [(VkFormat, [u8; 2], [i32; 16], f32); 4] = [
+            (
+                VkFormat::EAC_R11_UNORM_BLOCK,
+                [150, 176],
+                [
+                    940, 1380, 2047, 0, 676, 1644, 1908, 412, 412, 1908, 1644, 676, 0, 2047, 1380,
+                    940,
+                ],
+                2047.0,
+            ),
+            (
+                VkFormat::EAC_R11_UNORM_BLOCK,
+                [255, 13],
+                [
+                    2043, 2044, 2047, 2034, 2042, 2045, 2046, 2041, 2041, 2046, 2045, 2042, 2034,
+                    2047, 2044, 2043,
+                ],
+                2047.0,
+            ),
+            (
+                VkFormat::EAC_R11_SNORM_BLOCK,
+                [144, 94],
+                [
+                    -1023, -776, -576, -1023, -1023, -696, -616, -1023, -1023, -616, -696, -1023,
+                    -1023, -576, -776, -1023,
+                ],
+                1023.0,
+            ),
+            // a base of -128 reads as -127
+            (
+                VkFormat::EAC_R11_SNORM_BLOCK,
+                [128, 6],
+                [
+                    -1020, -1013, -1006, -1023, -1023, -1010, -1009, -1023, -1023, -1009, -1010,
+                    -1023, -1023, -1006, -1013, -1020,
+                ],
+                1023.0,
+            ),
+        ];
+        for (format, header, expected, scale) in r11 {
+            let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, format);
+            tex.level_images[0] = [&header[..], &indices].concat();
+            let decoded = tex.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+            for (texel, value) in decoded.level_as::<[f32; 4]>(0).unwrap().iter().zip(expected) {
+                assert!((texel[0] - value as f32 / scale).abs() < 1e-6, "{:?}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn test_astc_encode() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(12, 12, VkFormat::R8G8B8A8_UNORM);
+        for (i, texel) in tex.level_as_mut::<[u8; 4]>(0).unwrap().iter_mut().enumerate() {
+            let (x, y) = ((i % 12) as u8, (i / 12) as u8);
+            *texel = match (x / 6, y / 6) {
+                (0, 0) => [x * 40, 80 + x * 20, 200 - x * 30, 255],
+                (1, 0) => if x % 6 < 3 { [230, 40, 30, 255] } else { [20, 60, 220, 255] },
+                _ => [x * 20, 100, 128, 255 - y * 10],
+            };
+        }
+        let source = tex.level_as::<[u8; 4]>(0).unwrap().to_vec();
+        let rmse = |astc: &TextureKtx2| {
+            let decoded = astc.decompress(VkFormat::R8G8B8A8_UNORM).unwrap();
+            let squared_error: f32 = source
+                .iter()
+                .zip(decoded.level_as::<[u8; 4]>(0).unwrap())
+                .flat_map(|(s, d)| (0..4).map(|c| (s[c] as f32 - d[c] as f32).powi(2)))
+                .sum();
+            (squared_error / (source.len() * 4) as f32).sqrt()
+        };
+
+        for (format, length, limit) in [
+            (VkFormat::ASTC_4x4_UNORM_BLOCK, 144, 5.0),
+            (VkFormat::ASTC_6x6_SRGB_BLOCK, 64, 4.0),
+        ] {
+            let mut errors = Vec::new();
+            for quality in [Quality::Fast, Quality::Normal] {
+                let options = CompressOptions { quality, ..Default::default() };
+                let astc = tex.compress(format, &options).unwrap();
+                assert_eq!(astc.dfd_descriptor_block[0].row_2 & 0xFF, 162);
+                assert_eq!(astc.level_images[0].len(), length);
+                errors.push(rmse(&astc));
+            }
+            assert!(errors[1] <= errors[0] && errors[1] < limit, "{:?} {:?}", format, errors);
+        }
+
+        // a constant block is stored as a void extent block
+        let solid: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R8G8B8A8_UNORM);
+        let astc = solid.compress(VkFormat::ASTC_4x4_UNORM_BLOCK, &Default::default()).unwrap();
+        assert_eq!(astc.level_images[0][..2], [0xFC, 0xFD]);
+
+        // unorm8 decoding keeps 8 bits, rgb9e5 drops alpha, and HDR formats reject unorm8
+        let mut astc = tex.compress(VkFormat::ASTC_4x4_UNORM_BLOCK, &Default::default()).unwrap();
+        astc.key_value_data.insert(String::from("KTXastcDecodeMode"), b"unorm8\0".to_vec());
+        let decoded = astc.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+        for value in decoded.level_as::<f32>(0).unwrap() {
+            assert!((value * 255.0 - (value * 255.0).round()).abs() < 1e-3, "{}", value);
+        }
+        astc.key_value_data.insert(String::from("KTXastcDecodeMode"), b"rgb9e5\0".to_vec());
+        let decoded = astc.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+        assert!(decoded.level_as::<[f32; 4]>(0).unwrap().iter().all(|d| d[3] == 1.0));
+
+        // HDR values above 1 survive in the SFLOAT formats
+        let mut tex: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::R32G32B32A32_SFLOAT);
+        for (i, texel) in tex.level_as_mut::<[f32; 4]>(0).unwrap().iter_mut().enumerate() {
+            let x = (i % 8) as f32;
+            *texel = [0.5 + x, 0.25 + x * 0.5, 2.0, 1.0];
+        }
+        let hdr = tex.compress(VkFormat::ASTC_4x4_SFLOAT_BLOCK_EXT, &Default::default()).unwrap();
+        let flags = hdr.dfd_descriptor_block[0].samples[0].row_0 >> 24;
+        assert_eq!(flags & 0xC0, 0xC0);
+        let decoded = hdr.decompress(VkFormat::R32G32B32A32_SFLOAT).unwrap();
+        let source = tex.level_as::<[f32; 4]>(0).unwrap();
+        for (s, d) in source.iter().zip(decoded.level_as::<[f32; 4]>(0).unwrap()) {
+            for c in 0..3 {
+                assert!((s[c] - d[c]).abs() / s[c] < 0.1, "{:?} {:?}", s, d);
+            }
+        }
+        let mut unorm8 = hdr.clone();
+        unorm8.key_value_data.insert(String::from("KTXastcDecodeMode"), b"unorm8\0".to_vec());
+        assert!(unorm8.decompress(VkFormat::R32G32B32A32_SFLOAT).is_err());
+    }
+
+    #[test]
+    fn test_astc_decode_blocks() {
+        // reference blocks decoded with unorm8 precision, which makes every texel exact
+        let decode = |format: VkFormat, width: u32, height: u32, block: &[u8]| {
+            let mut tex: TextureKtx2 = TextureKtx2::new(width, height, format);
+            tex.level_images[0] = block.to_vec();
+            tex.key_value_data.insert(String::from("KTXastcDecodeMode"), b"unorm8\0".to_vec());
+            let output = match is_format_srgb(format) {
+                true => VkFormat::R8G8B8A8_SRGB,
+                false => VkFormat::R8G8B8A8_UNORM,
+            };
+            let rgba = tex.decompress(output).unwrap();
+            rgba.level_as::<[u8; 4]>(0).unwrap().to_vec()
+        };
+        let blocks: [(&str, _, _, _, _, &[[u8; 4]]); 5] = [
+            (
+                "trit weights",
+                VkFormat::ASTC_4x4_UNORM_BLOCK,
+                4,
+                4,
+                [67, 128, 7, 80, 34, 58, 70, 16, 76, 24, 128, 129, 65, 35, 160, 83],
+                &[
+                    [9, 19, 30, 33], [40, 29, 8, 12], [33, 26, 13, 16],
+                    [25, 24, 18, 22], [3, 17, 35, 38], [3, 17, 35, 38],
+                    [17, 21, 24, 27], [3, 17, 35, 38], [9, 19, 30, 33],
+                    [17, 21, 24, 27], [3, 17, 35, 38], [17, 21, 24, 27],
+                    [40, 29, 8, 12], [3, 17, 35, 38], [3, 17, 35, 38],
+                    [33, 26, 13, 16],
+                ],
+            ),
+            (
+                "quint weights on a 4x3 grid",
+                VkFormat::ASTC_6x6_UNORM_BLOCK,
+                6,
+                6,
+                [33, 2, 145, 63, 130, 104, 29, 188, 1, 0, 0, 112, 147, 53, 180, 105],
+                &[
+                    [145, 103, 82, 255], [73, 151, 170, 255], [33, 178, 219, 255],
+                    [47, 169, 203, 255], [41, 173, 209, 255], [31, 180, 222, 255],
+                    [137, 108, 92, 255], [100, 133, 138, 255], [78, 148, 164, 255],
+                    [100, 133, 138, 255], [89, 141, 151, 255], [57, 162, 190, 255],
+                    [129, 113, 102, 255], [123, 117, 108, 255], [131, 112, 98, 255],
+                    [163, 90, 59, 255], [142, 104, 85, 255], [92, 139, 147, 255],
+                    [108, 128, 128, 255], [121, 119, 111, 255], [137, 108, 92, 255],
+                    [171, 85, 49, 255], [158, 94, 66, 255], [123, 117, 108, 255],
+                    [65, 157, 180, 255], [78, 148, 164, 255], [94, 137, 144, 255],
+                    [131, 112, 98, 255], [153, 97, 72, 255], [163, 90, 59, 255],
+                    [31, 180, 222, 255], [41, 173, 209, 255], [60, 160, 186, 255],
+                    [94, 137, 144, 255], [139, 106, 89, 255], [200, 65, 14, 255],
+                ],
+            ),
+            (
+                "3 partitions, endpoint modes 4, 8 and 9",
+                VkFormat::ASTC_5x5_UNORM_BLOCK,
+                5,
+                5,
+                [225, 240, 84, 77, 122, 41, 124, 248, 242, 169, 20, 16, 160, 63, 68, 134],
+                &[
+                    [238, 204, 153, 255], [114, 161, 132, 255], [34, 34, 34, 187],
+                    [34, 34, 34, 187], [34, 34, 34, 187], [238, 204, 153, 255],
+                    [127, 149, 128, 255], [34, 34, 34, 187], [34, 34, 34, 187],
+                    [238, 204, 153, 255], [17, 51, 119, 255], [34, 34, 34, 187],
+                    [34, 34, 34, 187], [221, 221, 221, 68], [17, 51, 119, 255],
+                    [17, 51, 119, 255], [34, 34, 34, 187], [34, 34, 34, 187],
+                    [221, 221, 221, 68], [238, 204, 153, 255], [127, 149, 128, 255],
+                    [221, 221, 221, 68], [221, 221, 221, 68], [238, 204, 153, 255],
+                    [238, 204, 153, 255],
+                ],
+            ),
+            (
+                "dual plane, trit endpoints",
+                VkFormat::ASTC_4x4_UNORM_BLOCK,
+                4,
+                4,
+                [191, 133, 101, 138, 130, 23, 82, 126, 0, 97, 87, 53, 50, 239, 67, 203],
+                &[
+                    [104, 139, 153, 55], [104, 125, 153, 55], [109, 158, 161, 62],
+                    [120, 246, 180, 76], [125, 214, 188, 82], [118, 144, 178, 74],
+                    [109, 155, 161, 62], [89, 232, 129, 37], [120, 232, 180, 76],
+                    [114, 176, 169, 68], [109, 160, 161, 62], [97, 186, 142, 47],
+                    [89, 204, 129, 37], [89, 232, 129, 37], [109, 200, 161, 62],
+                    [149, 97, 229, 113],
+                ],
+            ),
+            (
+                "sRGB, alpha expanded as in linear formats",
+                VkFormat::ASTC_4x4_SRGB_BLOCK,
+                4,
+                4,
+                [66, 128, 199, 134, 205, 53, 213, 20, 253, 188, 0, 0, 24, 169, 140, 185],
+                &[
+                    [131, 205, 117, 115], [195, 154, 138, 94], [131, 205, 117, 115],
+                    [164, 179, 128, 104], [131, 205, 117, 115], [99, 230, 106, 126],
+                    [195, 154, 138, 94], [99, 230, 106, 126], [131, 205, 117, 115],
+                    [131, 205, 117, 115], [131, 205, 117, 115], [164, 179, 128, 104],
+                    [99, 230, 106, 126], [164, 179, 128, 104], [131, 205, 117, 115],
+                    [99, 230, 106, 126],
+                ],
+            ),
+        ];
+        for (name, format, width, height, block, expected) in blocks {
+            assert_eq!(decode(format, width, height, &block), expected, "{}", name);
+        }
+
+        // HDR endpoint mode 11 with green as the major component, as half float bits
+        let block = [66, 96, 57, 85, 107, 162, 37, 143, 0, 0, 0, 0, 119, 112, 240, 21];
+        let expected = [
+            [15984, 17128, 16736], [17374, 18296, 17951], [17374, 18296, 17951],
+            [17374, 18296, 17951], [17968, 18816, 18564], [17968, 18816, 18564],
+            [15984, 17128, 16736], [15984, 17128, 16736], [17374, 18296, 17951],
+            [17968, 18816, 18564], [15984, 17128, 16736], [15984, 17128, 16736],
+            [17374, 18296, 17951], [17968, 18816, 18564], [17374, 18296, 17951],
+            [17968, 18816, 18564],
+        ];
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::ASTC_4x4_SFLOAT_BLOCK_EXT);
+        tex.level_images[0] = block.to_vec();
+        let decoded = tex.decompress(VkFormat::R16G16B16A16_SFLOAT).unwrap();
+        let texels = decoded.level_as::<[u16; 4]>(0).unwrap();
+        let rgb: Vec<[u16; 3]> = texels.iter().map(|t| [t[0], t[1], t[2]]).collect();
+        assert_eq!(rgb, expected);
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(16, 16, VkFormat::R8G8B8A8_SRGB);
+        for (i, texel) in tex.level_as_mut::<[u8; 4]>(0).unwrap().iter_mut().enumerate() {
+            let (x, y) = ((i % 16) as u8, (i / 16) as u8);
+            *texel = [x * 16, y * 16, 255 - x * 8, 255];
+        }
+        let same = tex.compare(&tex).unwrap();
+        assert_eq!(same.levels.len(), 1);
+        assert_eq!(same.levels[0].mse, 0.0);
+        assert!(same.min_psnr().is_infinite());
+        assert!((same.min_ssim() - 1.0).abs() < 1e-9);
+
+        // a block compressed copy decodes for the comparison
+        let bc7 = tex.compress(VkFormat::BC7_SRGB_BLOCK, &Default::default()).unwrap();
+        let comparison = tex.compare(&bc7).unwrap();
+        let level = &comparison.levels[0];
+        assert!(level.psnr > 30.0 && level.psnr.is_finite(), "{:?}", level);
+        assert!(level.ssim > 0.9 && level.ssim < 1.0, "{:?}", level);
+        assert_eq!(level.channels[3].max_error, 0.0);
+
+        let mut changed = tex.clone();
+        changed.level_as_mut::<[u8; 4]>(0).unwrap()[17][1] += 51;
+        let difference = tex.difference(&changed).unwrap();
+        assert_eq!(difference.header.vk_format, VkFormat::R32G32B32A32_SFLOAT);
+        let pixels = difference.level_as::<[f32; 4]>(0).unwrap();
+        assert!((pixels[17][1] - 0.2).abs() < 1e-6);
+        assert_eq!(pixels.iter().filter(|p| p.iter().any(|&c| c != 0.0)).count(), 1);
+        let level = &tex.compare(&changed).unwrap().levels[0];
+        assert!((level.channels[1].max_error - 0.2).abs() < 1e-6);
+        assert_eq!(level.channels[0].mse, 0.0);
+
+        let small: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::R8G8B8A8_SRGB);
+        assert!(tex.compare(&small).is_err());
+    }
+
+    #[test]
+    fn test_convert_format() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R16_SFLOAT);
+        for (i, texel) in tex.level_as_mut::<f16>(0).unwrap().iter_mut().enumerate() {
+            *texel = f16::from_f32(i as f32 * 100.0);
+        }
+        let float = tex.convert_format(VkFormat::R32_SFLOAT, &Default::default()).unwrap();
+        assert_eq!(float.level_as::<f32>(0).unwrap()[15], 1500.0);
+        let dfd = crate::dfd::BasicDataFormatDescriptor::new(VkFormat::R32_SFLOAT);
+        assert_eq!(float.dfd_descriptor_block[0].samples.len(), dfd.samples.len());
+
+        // elevation in [0, 1500] spread over all UNORM values
+        let options = ConvertOptions { value_range: Some((0.0, 1500.0)), ..Default::default() };
+        let unorm = tex.convert_format(VkFormat::R16_UNORM, &options).unwrap();
+        assert_eq!(unorm.level_as::<u16>(0).unwrap()[15], 65535);
+        assert_eq!(unorm.level_as::<u16>(0).unwrap()[5], 21845);
+        let r8 = tex.convert_format(VkFormat::R8_UNORM, &options).unwrap();
+        assert_eq!(r8.level_as::<u8>(0).unwrap()[3], 51);
+        assert!(r8.convert_format(VkFormat::R8_UINT, &Default::default()).is_err());
+        assert!(r8.convert_format(VkFormat::BC4_UNORM_BLOCK, &Default::default()).is_err());
+
+        // gray expanded to opaque RGBA, sRGB encoded on the way
+        let options = ConvertOptions {
+            channels: [ChannelSource::R, ChannelSource::R, ChannelSource::R, ChannelSource::One],
+            ..Default::default()
+        };
+        let rgba = r8.convert_format(VkFormat::R8G8B8A8_SRGB, &options).unwrap();
+        assert_eq!(rgba.level_as::<[u8; 4]>(0).unwrap()[3], [124, 124, 124, 255]);
+        let linear = rgba.convert_format(VkFormat::R8G8B8A8_UNORM, &Default::default()).unwrap();
+        assert_eq!(linear.level_as::<[u8; 4]>(0).unwrap()[3], [51, 51, 51, 255]);
+
+        // dithering keeps the mean of a value between two steps
+        let mut flat: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::R16_UNORM);
+        flat.level_as_mut::<u16>(0).unwrap().fill(32896 + 128);
+        let options = ConvertOptions { dither: true, ..Default::default() };
+        let dithered = flat.convert_format(VkFormat::R8_UNORM, &options).unwrap();
+        let values = dithered.level_as::<u8>(0).unwrap();
+        assert!(values.contains(&128) && values.contains(&129));
+        let mean = values.iter().map(|&v| v as f32).sum::<f32>() / 64.0;
+        assert!((mean - 128.5).abs() < 0.05, "{}", mean);
+    }
+
+    #[test]
+    fn test_swizzle() {
+        use ChannelSource::*;
+
+        let mut tex: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::R8G8B8A8_UNORM);
+        tex.level_as_mut::<[u8; 4]>(0).unwrap().fill([10, 20, 30, 40]);
+        tex.swizzle([B, G, R, One]).unwrap();
+        assert_eq!(tex.level_as::<[u8; 4]>(0).unwrap()[3], [30, 20, 10, 255]);
+
+        // 32 bit integers are moved exactly, missing channels read as 0
+        let mut ints: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::R32G32_UINT);
+        ints.level_as_mut::<[u32; 2]>(0).unwrap().fill([u32::MAX - 1, 7]);
+        ints.swizzle([G, R, Zero, One]).unwrap();
+        assert_eq!(ints.level_as::<[u32; 2]>(0).unwrap()[0], [7, u32::MAX - 1]);
+        ints.swizzle([B, R, Zero, One]).unwrap();
+        assert_eq!(ints.level_as::<[u32; 2]>(0).unwrap()[0], [0, 7]);
+
+        let mut packed: TextureKtx2 = TextureKtx2::new(1, 1, VkFormat::R5G6B5_UNORM_PACK16);
+        packed.level_as_mut::<u16>(0).unwrap()[0] = 31 << 11;
+        packed.swizzle([Zero, G, R, A]).unwrap();
+        assert_eq!(packed.level_as::<u16>(0).unwrap()[0], 31);
+        let mut bc1 = packed.compress(VkFormat::BC1_RGB_UNORM_BLOCK, &Default::default()).unwrap();
+        assert!(bc1.swizzle(ChannelSource::RGBA).is_err());
+
+        // a recorded mapping survives a round trip and composes
+        let mut gray: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::R8G8_UNORM);
+        gray.level_as_mut::<[u8; 2]>(0).unwrap().fill([90, 200]);
+        gray.set_stored_swizzle([R, R, R, G]).unwrap();
+        let mut buffer = Vec::new();
+        gray.write_to(&mut buffer).unwrap();
+        let mut gray = TextureKtx2::read_from_bytes(&buffer).unwrap();
+        assert_eq!(gray.key_value_data["KTXswizzle"], b"rrrg\0");
+        gray.set_stored_swizzle([A, G, B, One]).unwrap();
+        assert_eq!(gray.stored_swizzle().unwrap(), Some([G, R, R, One]));
+        let rgba = gray.apply_stored_swizzle(VkFormat::R8G8B8A8_UNORM).unwrap();
+        assert_eq!(rgba.level_as::<[u8; 4]>(0).unwrap()[0], [200, 90, 90, 255]);
+        assert!(rgba.stored_swizzle().unwrap().is_none());
+        gray.set_stored_swizzle([G, R, B, One]).unwrap();
+        assert_eq!(gray.stored_swizzle().unwrap(), Some([R, G, R, One]));
+
+        gray.key_value_data.insert("KTXswizzle".into(), b"rgx\0".to_vec());
+        assert!(gray.stored_swizzle().is_err());
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_png() {
+        use crate::png::PngOptions;
+
+        let mut tex: TextureKtx2 = TextureKtx2::new(5, 3, VkFormat::R8G8B8A8_SRGB);
+        for (i, texel) in tex.level_as_mut::<[u8; 4]>(0).unwrap().iter_mut().enumerate() {
+            *texel = [i as u8 * 10, 255 - i as u8, 7, 128 + i as u8];
+        }
+        let png = tex.to_png(0, 0, 0, &Default::default()).unwrap();
+        let read = TextureKtx2::from_png(&png).unwrap();
+        assert_eq!(read.header.vk_format, VkFormat::R8G8B8A8_SRGB);
+        assert_eq!(read.level_images[0], tex.level_images[0]);
+        assert!(tex.to_png(1, 0, 0, &Default::default()).is_err());
+
+        // float elevation through a 16 bit linear gray PNG
+        let mut height: TextureKtx2 = TextureKtx2::new(4, 2, VkFormat::R16_SFLOAT);
+        for (i, texel) in height.level_as_mut::<f16>(0).unwrap().iter_mut().enumerate() {
+            *texel = f16::from_f32(i as f32 * 100.0);
+        }
+        let options = PngOptions { value_range: Some((0.0, 700.0)), ..Default::default() };
+        let read = TextureKtx2::from_png(&height.to_png(0, 0, 0, &options).unwrap()).unwrap();
+        assert_eq!(read.header.vk_format, VkFormat::R16_UNORM);
+        assert!(!read.dfd_descriptor_block[0].is_srgb());
+        assert_eq!(read.level_as::<u16>(0).unwrap()[7], 65535);
+        assert_eq!(read.level_as::<u16>(0).unwrap()[1], 9362);
+        assert_eq!(read.key_value_data["KTXswizzle"], b"rrr1\0");
+
+        let options = PngOptions { sixteen_bit: Some(false), ..Default::default() };
+        let eight_bit = TextureKtx2::from_png(&read.to_png(0, 0, 0, &options).unwrap()).unwrap();
+        assert_eq!(eight_bit.header.vk_format, VkFormat::R8_UNORM);
+
+        // other gammas read as sRGB up to 2/3 and as linear above
+        let with_gamma = |gamma: f32| {
+            let mut png = Vec::new();
+            let mut encoder = ::png::Encoder::new(&mut png, 1, 1);
+            encoder.set_color(::png::ColorType::Grayscale);
+            encoder.set_source_gamma(::png::ScaledFloat::new(gamma));
+            encoder.write_header().unwrap().write_image_data(&[100]).unwrap();
+            TextureKtx2::from_png(&png).unwrap().header.vk_format
+        };
+        assert_eq!(with_gamma(1.0 / 1.8), VkFormat::R8_SRGB);
+        assert_eq!(with_gamma(0.8), VkFormat::R8_UNORM);
+
+        // gray with alpha keeps its swizzle and 8 bit sRGB samples
+        let mut gray = TextureKtx2::new(2, 1, VkFormat::R8G8_SRGB);
+        gray.level_images[0] = vec![40, 255, 90, 0];
+        let rrrg = [ChannelSource::R, ChannelSource::R, ChannelSource::R, ChannelSource::G];
+        gray.set_stored_swizzle(rrrg).unwrap();
+        let read = TextureKtx2::from_png(&gray.to_png(0, 0, 0, &Default::default()).unwrap());
+        let read = read.unwrap();
+        assert_eq!(read.header.vk_format, VkFormat::R8G8_SRGB);
+        assert_eq!(read.level_images[0], gray.level_images[0]);
+        assert_eq!(read.stored_swizzle().unwrap(), Some(rrrg));
+    }
+
+    #[test]
+    fn test_hdr_and_exr() {
+        let mut tex: TextureKtx2 = TextureKtx2::new(12, 3, VkFormat::R16G16B16A16_SFLOAT);
+        for (i, texel) in tex.level_as_mut::<[f16; 4]>(0).unwrap().iter_mut().enumerate() {
+            let value = if i < 20 { 1.5 } else { i as f32 * 37.25 - 600.0 };
+            *texel = [value, value * 0.5, 0.001 * i as f32, 0.25].map(f16::from_f32);
+        }
+        tex.level_as_mut::<[f16; 4]>(0).unwrap()[3][2] = f16::from_bits(1);
+
+        let hdr = TextureKtx2::from_hdr(&tex.to_hdr(0, 0, 0, 0).unwrap()).unwrap();
+        assert_eq!(hdr.header.vk_format, VkFormat::R32G32B32_SFLOAT);
+        let source = tex.level_as::<[f16; 4]>(0).unwrap();
+        for (texel, source) in hdr.level_as::<[f32; 3]>(0).unwrap().iter().zip(source) {
+            let max = source[0].to_f32().max(source[1].to_f32()).max(source[2].to_f32());
+            for c in 0..3 {
+                let expected = source[c].to_f32().max(0.0);
+                assert!((texel[c] - expected).abs() <= max / 128.0, "{:?} {:?}", texel, source);
+            }
+        }
+        let again = TextureKtx2::from_hdr(&hdr.to_hdr(0, 0, 0, 0).unwrap()).unwrap();
+        assert_eq!(again.level_images[0], hdr.level_images[0]);
+
+        // flat scanline with an old style run
+        let mut flat = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 3\n".to_vec();
+        flat.extend([128, 64, 32, 129, 1, 1, 1, 2]);
+        let flat = TextureKtx2::from_hdr(&flat).unwrap();
+        assert_eq!(flat.level_as::<[f32; 3]>(0).unwrap(), [[1.0, 0.5, 0.25]; 3]);
+        assert!(TextureKtx2::from_hdr(b"#?RADIANCE\n\n+Y 1 +X 3\n").is_err());
+
+        // empty runs and headers without the data they claim are errors
+        let mut runs = b"#?RADIANCE\n\n-Y 1 +X 4\n".to_vec();
+        runs.extend([128, 64, 32, 129].iter().chain([1, 1, 1, 0].iter().cycle().take(36)));
+        assert!(TextureKtx2::from_hdr(&runs).is_err());
+        assert!(TextureKtx2::from_hdr(b"#?RADIANCE\n\n-Y 200000 +X 200000\n").is_err());
+        let mut short = tex.clone();
+        short.level_images[0].truncate(8);
+        assert!(short.to_hdr(0, 0, 0, 0).is_err());
+
+        #[cfg(feature = "exr")]
+        {
+            // half values including subnormals come back exactly
+            let exr = TextureKtx2::from_exr(&tex.to_exr(0, 0, 0, 0).unwrap()).unwrap();
+            assert_eq!(exr.header.vk_format, VkFormat::R16G16B16A16_SFLOAT);
+            assert_eq!(exr.level_images[0], tex.level_images[0]);
+
+            let mut ints: TextureKtx2 = TextureKtx2::new(4, 4, VkFormat::R32G32_UINT);
+            for (i, texel) in ints.level_as_mut::<[u32; 2]>(0).unwrap().iter_mut().enumerate() {
+                *texel = [u32::MAX - i as u32, i as u32];
+            }
+            let exr = TextureKtx2::from_exr(&ints.to_exr(0, 0, 0, 0).unwrap()).unwrap();
+            assert_eq!(exr.header.vk_format, VkFormat::R32G32_UINT);
+            assert_eq!(exr.level_images[0], ints.level_images[0]);
+
+            let mut srgb: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::R8G8B8A8_SRGB);
+            srgb.level_as_mut::<[u8; 4]>(0).unwrap().fill([188, 255, 0, 51]);
+            let exr = TextureKtx2::from_exr(&srgb.to_exr(0, 0, 0, 0).unwrap()).unwrap();
+            assert_eq!(exr.header.vk_format, VkFormat::R32G32B32A32_SFLOAT);
+            let texel = exr.level_as::<[f32; 4]>(0).unwrap()[0];
+            assert!((texel[0] - 0.5).abs() < 0.01 && texel[1] == 1.0 && texel[3] == 0.2);
+            assert!(srgb.to_exr(0, 1, 0, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn test_dds() {
+        let fill = |tex: &mut TextureKtx2| {
+            for level in &mut tex.level_images {
+                for (i, byte) in level.iter_mut().enumerate() {
+                    *byte = (i * 7 % 251) as u8;
+                }
+            }
+        };
+        let same = |a: &TextureKtx2, b: &TextureKtx2| {
+            assert_eq!(a.header.vk_format, b.header.vk_format);
+            let extent = |t: &TextureKtx2| {
+                let h = &t.header;
+                (h.pixel_width, h.pixel_height, h.pixel_depth, h.layer_count, h.face_count)
+            };
+            assert_eq!(extent(a), extent(b));
+            assert_eq!(a.level_images, b.level_images);
+        };
+
+        // cubemap with mips in the legacy header, one face mip chain at a time
+        let mut cube: TextureKtx2 = TextureKtx2::new(8, 8, VkFormat::BC3_UNORM_BLOCK);
+        cube.header.face_count = 6;
+        cube.header.level_count = 2;
+        cube.level_images = vec![vec![0; 6 * 64], vec![0; 6 * 16]];
+        fill(&mut cube);
+        let dds = cube.to_dds().unwrap();
+        assert_eq!(&dds[84..88], b"DXT5");
+        assert_eq!(dds[128..192], cube.level_images[0][..64]);
+        assert_eq!(dds[192..208], cube.level_images[1][..16]);
+        same(&TextureKtx2::from_dds(&dds).unwrap(), &cube);
+
+        cube.dfd_descriptor_block[0].set_alpha_premultiplied(true);
+        let dds = cube.to_dds().unwrap();
+        assert_eq!(&dds[84..88], b"DXT4");
+        let read = TextureKtx2::from_dds(&dds).unwrap();
+        assert!(read.dfd_descriptor_block[0].is_alpha_premultiplied());
+
+        // sRGB arrays need the DX10 header
+        let mut array: TextureKtx2 = TextureKtx2::new(12, 4, VkFormat::BC7_SRGB_BLOCK);
+        array.header.layer_count = 3;
+        array.header.level_count = 4;
+        array.level_images = [3, 2, 1, 1].map(|blocks| vec![0; 3 * 16 * blocks]).to_vec();
+        fill(&mut array);
+        let dds = array.to_dds().unwrap();
+        assert_eq!(&dds[84..88], b"DX10");
+        assert_eq!(dds[128..132], 99u32.to_le_bytes());
+        same(&TextureKtx2::from_dds(&dds).unwrap(), &array);
+
+        // absurd sizes fail before allocating
+        let mut cubes = dds.clone();
+        cubes[136..140].copy_from_slice(&4u32.to_le_bytes());
+        cubes[140..144].copy_from_slice(&0x40000000u32.to_le_bytes());
+        assert!(TextureKtx2::from_dds(&cubes).is_err());
+        let mut huge = dds.clone();
+        huge[12..20].copy_from_slice(&[0xFF, 0xFF, 0, 0, 0xFF, 0xFF, 0, 0]);
+        assert!(TextureKtx2::from_dds(&huge).is_err());
+
+        let mut volume: TextureKtx2 = TextureKtx2::new(4, 2, VkFormat::R16G16B16A16_SFLOAT);
+        volume.header.pixel_depth = 3;
+        volume.level_images[0] = vec![0; 4 * 2 * 3 * 8];
+        fill(&mut volume);
+        let dds = volume.to_dds().unwrap();
+        assert_eq!(dds[84..88], 113u32.to_le_bytes());
+        same(&TextureKtx2::from_dds(&dds).unwrap(), &volume);
+
+        // the DX10 resource dimension marks volumes, not the caps
+        let mut volume: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::R8_UNORM);
+        volume.header.pixel_depth = 2;
+        volume.level_images[0] = vec![0; 8];
+        fill(&mut volume);
+        let mut dds = volume.to_dds().unwrap();
+        assert_eq!(&dds[84..88], b"DX10");
+        dds[112..116].fill(0);
+        same(&TextureKtx2::from_dds(&dds).unwrap(), &volume);
+
+        // X8R8G8B8 reads as opaque B8G8R8A8
+        let mut bgra: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::B8G8R8A8_UNORM);
+        bgra.level_as_mut::<[u8; 4]>(0).unwrap().fill([1, 2, 3, 4]);
+        let mut dds = bgra.to_dds().unwrap();
+        same(&TextureKtx2::from_dds(&dds).unwrap(), &bgra);
+        dds[80] &= !1;
+        dds[104..108].fill(0);
+        let opaque = TextureKtx2::from_dds(&dds).unwrap();
+        assert_eq!(opaque.level_as::<[u8; 4]>(0).unwrap()[3], [1, 2, 3, 255]);
+
+        // luminance stands for a gray swizzle
+        let mut gray: TextureKtx2 = TextureKtx2::new(2, 2, VkFormat::R8_UNORM);
+        assert_eq!(&gray.to_dds().unwrap()[84..88], b"DX10");
+        let rrr1 = [ChannelSource::R, ChannelSource::R, ChannelSource::R, ChannelSource::One];
+        gray.set_stored_swizzle(rrr1).unwrap();
+        let dds = gray.to_dds().unwrap();
+        assert_eq!(dds[80..84], 0x20000u32.to_le_bytes());
+        let read = TextureKtx2::from_dds(&dds).unwrap();
+        assert_eq!(read.stored_swizzle().unwrap(), Some(rrr1));
+
+        assert!(TextureKtx2::from_dds(&dds[..130]).is_err());
+        let mut dds = dds;
+        dds[28..32].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(TextureKtx2::from_dds(&dds).is_err());
+        assert!(TextureKtx2::from_dds(b"KTX 11").is_err());
+    }
+
+    #[test]
+    fn test_ktx1() {
+        use crate::ktx1::TextureKtx1;
+
+        let ktx1 = |big_endian: bool,
+                    header: [u32; 11],
+                    kvd: &[&[u8]],
+                    levels: &[(u32, Vec<u8>)]| {
+            let u32_bytes =
+                |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+            let mut out = b"\xABKTX 11\xBB\r\n\x1A\n".to_vec();
+            out.extend(u32_bytes(0x04030201));
+            header.iter().for_each(|&value| out.extend(u32_bytes(value)));
+            let mut data = Vec::new();
+            for pair in kvd {
+                data.extend(u32_bytes(pair.len() as u32));
+                data.extend(*pair);
+                data.resize(data.len().next_multiple_of(4), 0);
+            }
+            out.extend(u32_bytes(data.len() as u32));
+            out.extend(data);
+            for (image_size, data) in levels {
+                out.extend(u32_bytes(*image_size));
+                out.extend(data);
+            }
+            out
+        };
+
+        // big endian R16F with row padding, 3x1 and 1x1
+        let half = |values: &[f32]| -> Vec<u8> {
+            let mut row: Vec<u8> =
+                values.iter().flat_map(|&v| f16::from_f32(v).to_be_bytes()).collect();
+            row.resize(row.len().next_multiple_of(4), 0);
+            row
+        };
+        let header = [0x140B, 2, 0x1903, 0x822D, 0x1903, 3, 1, 0, 0, 1, 2];
+        let kvd: [&[u8]; 3] = [b"KTXorientation\0S=r,T=u\0", b"note\0hi\0", b"KTXwriter\0old\0"];
+        let bytes = ktx1(true, header, &kvd, &[(8, half(&[1.0, 2.0, -1.0])), (4, half(&[0.5]))]);
+        let tex = TextureKtx2::from_ktx1(&bytes).unwrap();
+        assert_eq!(tex.header.vk_format, VkFormat::R16_SFLOAT);
+        assert_eq!(tex.header.level_count, 2);
+        let values = |level| tex.level_as::<f16>(level).unwrap().iter().map(|v| v.to_f32());
+        assert_eq!(values(0).collect::<Vec<_>>(), [1.0, 2.0, -1.0]);
+        assert_eq!(values(1).collect::<Vec<_>>(), [0.5]);
+        assert_eq!(tex.key_value_data["KTXorientation"], b"ru\0");
+        assert_eq!(tex.key_value_data["note"], b"hi\0");
+        assert_ne!(tex.key_value_data["KTXwriter"], b"old\0");
+
+        // little endian unsized luminance cubemap, imageSize is per face
+        let header = [0x1401, 1, 0x1909, 0x1909, 0x1909, 1, 1, 0, 0, 6, 1];
+        let faces: Vec<u8> = (0..6).flat_map(|face| [face, 0, 0, 0]).collect();
+        let bytes = ktx1(false, header, &[], &[(4, faces)]);
+        let parsed = TextureKtx1::read_from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.header.number_of_faces, 6);
+        assert_eq!(parsed.level_images, [vec![0, 1, 2, 3, 4, 5]]);
+        let tex = parsed.to_ktx2().unwrap();
+        assert_eq!(tex.header.vk_format, VkFormat::R8_UNORM);
+        assert_eq!(tex.header.face_count, 6);
+        use ChannelSource::*;
+        assert_eq!(tex.stored_swizzle().unwrap(), Some([R, R, R, One]));
+        let mut out = Vec::new();
+        tex.write_to(&mut out).unwrap();
+        let read = TextureKtx2::read_from_bytes(&out).unwrap();
+        assert_eq!(read.level_images, tex.level_images);
+
+        // RGBA8 stored as BGRA
+        let header = [0x1401, 1, 0x80E1, 0x8058, 0x1908, 1, 1, 0, 0, 1, 1];
+        let bytes = ktx1(false, header, &[], &[(4, vec![1, 2, 3, 4])]);
+        let tex = TextureKtx2::from_ktx1(&bytes).unwrap();
+        assert_eq!(tex.header.vk_format, VkFormat::B8G8R8A8_UNORM);
+
+        // float data for an RGBA8 internal format
+        let header = [0x1406, 4, 0x1908, 0x8058, 0x1908, 1, 1, 0, 0, 1, 1];
+        assert!(TextureKtx2::from_ktx1(&ktx1(false, header, &[], &[(16, vec![0; 16])])).is_err());
+        assert!(TextureKtx2::from_ktx1(&bytes[..bytes.len() - 1]).is_err());
+        assert!(TextureKtx2::from_ktx1(b"\xABKTX 20\xBB\r\n\x1A\n").is_err());
+
+        // sizes beyond the file or past usize fail without allocating
+        let header = [0x1401, 1, 0x1908, 0x8058, 0x1908, 1, 1, 0, 0, 1, 1];
+        let bytes = ktx1(false, header, &[], &[(u32::MAX, vec![0; 4])]);
+        assert!(TextureKtx1::read_from_bytes(&bytes).is_err());
+        let max = u32::MAX;
+        let header = [0x1401, 1, 0x1908, 0x8058, 0x1908, 1, max, max, max, 6, 1];
+        let bytes = ktx1(false, header, &[], &[(4, vec![0; 4])]);
+        assert!(TextureKtx1::read_from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_raw() {
+        use crate::raw::RawLayout;
+
+        // big endian floats with padded rows, the last one unpadded
+        let values = [1.0f32, 2.0, 3.0, -4.0, 0.5, 1e6];
+        let mut bytes = Vec::new();
+        for row in values.chunks(3) {
+            bytes.extend(row.iter().flat_map(|v| v.to_be_bytes()));
+            bytes.extend([0xFF; 4]);
+        }
+        bytes.truncate(bytes.len() - 4);
+        let layout = RawLayout { row_pitch: Some(16), big_endian: true };
+        let tex = TextureKtx2::from_raw(&bytes, 3, 2, VkFormat::R32_SFLOAT, &layout).unwrap();
+        assert_eq!(tex.level_as::<f32>(0).unwrap(), values);
+        let mut out = Vec::new();
+        tex.write_to(&mut out).unwrap();
+        assert_eq!(TextureKtx2::read_from_bytes(&out).unwrap().level_images, tex.level_images);
+
+        let raw = tex.to_raw(0).unwrap();
+        let layout = RawLayout::default();
+        let read = TextureKtx2::from_raw(&raw, 3, 2, VkFormat::R32_SFLOAT, &layout).unwrap();
+        assert_eq!(read.level_images, tex.level_images);
+        assert!(tex.to_raw(1).is_err());
+
+        // rows of blocks for block compressed formats
+        let blocks: Vec<u8> = (0..32).collect();
+        let bc1 = TextureKtx2::from_raw(&blocks, 5, 5, VkFormat::BC1_RGBA_UNORM_BLOCK, &layout);
+        assert_eq!(bc1.unwrap().to_raw(0).unwrap(), blocks);
+
+        let r32 = VkFormat::R32_SFLOAT;
+        assert!(TextureKtx2::from_raw(&raw[1..], 3, 2, r32, &layout).is_err());
+        assert!(TextureKtx2::from_raw(&raw, 2, 2, r32, &layout).is_err());
+        assert!(TextureKtx2::from_raw(&raw, 0, 2, r32, &layout).is_err());
+        let layout = RawLayout { row_pitch: Some(8), big_endian: false };
+        assert!(TextureKtx2::from_raw(&raw, 3, 2, r32, &layout).is_err());
+        for format in [
+            VkFormat::UNDEFINED,
+            VkFormat::PVRTC1_4BPP_UNORM_BLOCK_IMG,
+            VkFormat::G8B8G8R8_422_UNORM,
+        ] {
+            assert!(TextureKtx2::from_raw(&[0; 8], 2, 1, format, &Default::default()).is_err());
+        }
+    }
 }
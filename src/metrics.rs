@@ -0,0 +1,163 @@
+use crate::resample::Image;
+
+// SSIM stabilizing constants for a dynamic range of 1.0
+const SSIM_C1: f64 = 0.01 * 0.01;
+const SSIM_C2: f64 = 0.03 * 0.03;
+
+/// Error of one channel between two textures.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChannelError {
+    /// Mean squared error.
+    pub mse: f64,
+
+    /// Peak signal to noise ratio in dB for a peak value of 1.0, infinite
+    /// when the channels are equal.
+    pub psnr: f64,
+
+    /// Mean structural similarity over 11x11 Gaussian windows, 1.0 when the
+    /// channels are equal.
+    pub ssim: f64,
+
+    /// Largest absolute difference.
+    pub max_error: f32,
+}
+
+/// Error of one level over all of its slices, faces and layers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelError {
+    /// Error of each channel in `[r, g, b, a]` order.
+    pub channels: [ChannelError; 4],
+
+    /// Mean squared error of the color channels.
+    pub mse: f64,
+
+    /// Peak signal to noise ratio in dB of the color channels.
+    pub psnr: f64,
+
+    /// Mean structural similarity of the color channels.
+    pub ssim: f64,
+}
+
+/// Result of [`TextureKtx2::compare`](crate::texture::TextureKtx2::compare),
+/// one entry per mip level.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Comparison {
+    pub levels: Vec<LevelError>,
+}
+
+impl Comparison {
+    /// Lowest color PSNR of all levels.
+    pub fn min_psnr(&self) -> f64 {
+        self.levels.iter().map(|level| level.psnr).fold(f64::INFINITY, f64::min)
+    }
+
+    /// Lowest color SSIM of all levels.
+    pub fn min_ssim(&self) -> f64 {
+        self.levels.iter().map(|level| level.ssim).fold(1.0, f64::min)
+    }
+}
+
+fn psnr(mse: f64) -> f64 {
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        -10.0 * mse.log10()
+    }
+}
+
+/// Compares the planes of one level, which have matching extents.
+pub(crate) fn compare_planes(a: &[Image], b: &[Image]) -> LevelError {
+    let mut squared = [0.0f64; 4];
+    let mut ssim = [0.0f64; 4];
+    let mut max_error = [0.0f32; 4];
+    let mut count = 0;
+    for (a, b) in a.iter().zip(b) {
+        for (pa, pb) in a.pixels.iter().zip(&b.pixels) {
+            for c in 0..4 {
+                let d = (pa[c] - pb[c]).abs();
+                squared[c] += d as f64 * d as f64;
+                max_error[c] = max_error[c].max(d);
+            }
+        }
+        for (c, ssim) in ssim.iter_mut().enumerate() {
+            *ssim += ssim_sum(a, b, c);
+        }
+        count += a.pixels.len();
+    }
+
+    let count = count.max(1) as f64;
+    let channels: [ChannelError; 4] = std::array::from_fn(|c| ChannelError {
+        mse: squared[c] / count,
+        psnr: psnr(squared[c] / count),
+        ssim: ssim[c] / count,
+        max_error: max_error[c],
+    });
+    let mse = channels[..3].iter().map(|c| c.mse).sum::<f64>() / 3.0;
+    LevelError {
+        channels,
+        mse,
+        psnr: psnr(mse),
+        ssim: channels[..3].iter().map(|c| c.ssim).sum::<f64>() / 3.0,
+    }
+}
+
+/// Absolute difference of two planes of matching extents.
+pub(crate) fn difference(a: &Image, b: &Image) -> Image {
+    Image {
+        width: a.width,
+        height: a.height,
+        pixels: a
+            .pixels
+            .iter()
+            .zip(&b.pixels)
+            .map(|(pa, pb)| std::array::from_fn(|c| (pa[c] - pb[c]).abs()))
+            .collect(),
+    }
+}
+
+/// Sum of the SSIM of every pixel of channel `c`, with windows clamped at
+/// the edges.
+fn ssim_sum(a: &Image, b: &Image, c: usize) -> f64 {
+    let x: Vec<f64> = a.pixels.iter().map(|p| p[c] as f64).collect();
+    let y: Vec<f64> = b.pixels.iter().map(|p| p[c] as f64).collect();
+    let (width, height) = (a.width as usize, a.height as usize);
+    let blur = |values: Vec<f64>| gaussian_blur(&values, width, height);
+
+    let xx = blur(x.iter().map(|v| v * v).collect());
+    let yy = blur(y.iter().map(|v| v * v).collect());
+    let xy = blur(x.iter().zip(&y).map(|(x, y)| x * y).collect());
+    let (mx, my) = (blur(x), blur(y));
+    (0..width * height)
+        .map(|i| {
+            let (mx, my) = (mx[i], my[i]);
+            let vx = xx[i] - mx * mx;
+            let vy = yy[i] - my * my;
+            let cov = xy[i] - mx * my;
+            (2.0 * mx * my + SSIM_C1) * (2.0 * cov + SSIM_C2)
+                / ((mx * mx + my * my + SSIM_C1) * (vx + vy + SSIM_C2))
+        })
+        .sum()
+}
+
+/// Separable 11 tap Gaussian blur with a standard deviation of 1.5.
+fn gaussian_blur(values: &[f64], width: usize, height: usize) -> Vec<f64> {
+    let kernel: Vec<f64> = (-5..=5).map(|i: i32| (-(i * i) as f64 / 4.5).exp()).collect();
+    let pass = |values: &[f64], step: usize, length: usize, index: &dyn Fn(usize) -> usize| {
+        (0..values.len())
+            .map(|i| {
+                let position = index(i);
+                let (mut sum, mut total) = (0.0, 0.0);
+                for (k, weight) in kernel.iter().enumerate() {
+                    let Some(p) = (position + k).checked_sub(5).filter(|&p| p < length) else {
+                        continue;
+                    };
+                    sum += weight * values[i + p * step - position * step];
+                    total += weight;
+                }
+                sum / total
+            })
+            .collect::<Vec<f64>>()
+    };
+    let horizontal = pass(values, 1, width, &|i| i % width);
+    pass(&horizontal, width, height, &|i| i / width)
+}
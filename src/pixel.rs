@@ -1,10 +1,689 @@
 use half::f16;
 
-#[allow(non_camel_case_types)]
-#[derive(Clone, Copy, Debug)]
-pub enum Pixel {
+use crate::resample::ColorSpace;
+use crate::vk_format::{get_format_layout, Channel, FormatLayout, NumericFormat, VkFormat};
+
+/// Largest texel of any uncompressed format (R64G64B64A64).
+pub(crate) const MAX_TEXEL_SIZE: usize = 32;
+
+/// Little-endian storage of the payload of a [`Pixel`].
+pub(crate) trait Texel: Sized {
+    const SIZE: usize;
+
+    fn read_le(bytes: &[u8]) -> Self;
+
+    fn write_le(&self, bytes: &mut [u8]);
+}
+
+macro_rules! impl_texel {
+    ($($ty:ty),*) => {
+        $(
+            impl Texel for $ty {
+                const SIZE: usize = std::mem::size_of::<$ty>();
+
+                fn read_le(bytes: &[u8]) -> Self {
+                    let mut a = [0u8; std::mem::size_of::<$ty>()];
+                    a.copy_from_slice(&bytes[..Self::SIZE]);
+                    <$ty>::from_le_bytes(a)
+                }
+
+                fn write_le(&self, bytes: &mut [u8]) {
+                    bytes[..Self::SIZE].copy_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_texel!(u8, i8, u16, i16, u32, i32, u64, i64, f16, f32, f64);
+
+impl<T: Texel, const N: usize> Texel for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+
+    fn read_le(bytes: &[u8]) -> Self {
+        std::array::from_fn(|i| T::read_le(&bytes[i * T::SIZE..]))
+    }
+
+    fn write_le(&self, bytes: &mut [u8]) {
+        for (i, value) in self.iter().enumerate() {
+            value.write_le(&mut bytes[i * T::SIZE..]);
+        }
+    }
+}
+
+// depth followed by stencil
+impl<D: Texel, S: Texel> Texel for (D, S) {
+    const SIZE: usize = D::SIZE + S::SIZE;
+
+    fn read_le(bytes: &[u8]) -> Self {
+        (D::read_le(bytes), S::read_le(&bytes[D::SIZE..]))
+    }
+
+    fn write_le(&self, bytes: &mut [u8]) {
+        self.0.write_le(bytes);
+        self.1.write_le(&mut bytes[D::SIZE..]);
+    }
+}
+
+macro_rules! pixels {
+    ($($format:ident($ty:ty),)*) => {
+        /// A single texel of an uncompressed format.
+        ///
+        /// Components are stored in the order they appear in the format name,
+        /// `_PACK` formats carry the packed word, depth/stencil formats carry
+        /// `(depth, stencil)`.
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub enum Pixel {
+            $($format($ty),)*
+            BC1_RGB_UNORM_BLOCK,
+        }
+
+        impl Pixel {
+            /// Format the pixel belongs to.
+            pub fn format(&self) -> VkFormat {
+                match self {
+                    $(Pixel::$format(_) => VkFormat::$format,)*
+                    Pixel::BC1_RGB_UNORM_BLOCK => VkFormat::BC1_RGB_UNORM_BLOCK,
+                }
+            }
+
+            /// Reads a pixel of `format` from the start of `bytes`.
+            /// Returns `None` for formats without a per-pixel representation.
+            pub fn from_bytes(format: VkFormat, bytes: &[u8]) -> Option<Pixel> {
+                match format {
+                    $(VkFormat::$format => Some(Pixel::$format(Texel::read_le(bytes))),)*
+                    _ => None,
+                }
+            }
+
+            /// Writes the pixel to the start of `bytes`.
+            pub fn write_bytes(&self, bytes: &mut [u8]) {
+                match self {
+                    $(Pixel::$format(value) => value.write_le(bytes),)*
+                    Pixel::BC1_RGB_UNORM_BLOCK => {}
+                }
+            }
+        }
+    };
+}
+
+pixels! {
+    R4G4_UNORM_PACK8(u8),
+    R4G4B4A4_UNORM_PACK16(u16),
+    B4G4R4A4_UNORM_PACK16(u16),
+    R5G6B5_UNORM_PACK16(u16),
+    B5G6R5_UNORM_PACK16(u16),
+    R5G5B5A1_UNORM_PACK16(u16),
+    B5G5R5A1_UNORM_PACK16(u16),
+    A1R5G5B5_UNORM_PACK16(u16),
+    A4R4G4B4_UNORM_PACK16_EXT(u16),
+    A4B4G4R4_UNORM_PACK16_EXT(u16),
+
+    R8_UNORM(u8),
+    R8_SNORM(i8),
+    R8_USCALED(u8),
+    R8_SSCALED(i8),
+    R8_UINT(u8),
+    R8_SINT(i8),
+    R8_SRGB(u8),
+    R8G8_UNORM([u8; 2]),
+    R8G8_SNORM([i8; 2]),
+    R8G8_USCALED([u8; 2]),
+    R8G8_SSCALED([i8; 2]),
+    R8G8_UINT([u8; 2]),
+    R8G8_SINT([i8; 2]),
+    R8G8_SRGB([u8; 2]),
+    R8G8B8_UNORM([u8; 3]),
+    R8G8B8_SNORM([i8; 3]),
+    R8G8B8_USCALED([u8; 3]),
+    R8G8B8_SSCALED([i8; 3]),
+    R8G8B8_UINT([u8; 3]),
+    R8G8B8_SINT([i8; 3]),
+    R8G8B8_SRGB([u8; 3]),
+    B8G8R8_UNORM([u8; 3]),
+    B8G8R8_SNORM([i8; 3]),
+    B8G8R8_USCALED([u8; 3]),
+    B8G8R8_SSCALED([i8; 3]),
+    B8G8R8_UINT([u8; 3]),
+    B8G8R8_SINT([i8; 3]),
+    B8G8R8_SRGB([u8; 3]),
+    R8G8B8A8_UNORM([u8; 4]),
+    R8G8B8A8_SNORM([i8; 4]),
+    R8G8B8A8_USCALED([u8; 4]),
+    R8G8B8A8_SSCALED([i8; 4]),
+    R8G8B8A8_UINT([u8; 4]),
+    R8G8B8A8_SINT([i8; 4]),
+    R8G8B8A8_SRGB([u8; 4]),
+    B8G8R8A8_UNORM([u8; 4]),
+    B8G8R8A8_SNORM([i8; 4]),
+    B8G8R8A8_USCALED([u8; 4]),
+    B8G8R8A8_SSCALED([i8; 4]),
+    B8G8R8A8_UINT([u8; 4]),
+    B8G8R8A8_SINT([i8; 4]),
+    B8G8R8A8_SRGB([u8; 4]),
+
+    A8B8G8R8_UNORM_PACK32(u32),
+    A8B8G8R8_SNORM_PACK32(u32),
+    A8B8G8R8_USCALED_PACK32(u32),
+    A8B8G8R8_SSCALED_PACK32(u32),
+    A8B8G8R8_UINT_PACK32(u32),
+    A8B8G8R8_SINT_PACK32(u32),
+    A8B8G8R8_SRGB_PACK32(u32),
+    A2R10G10B10_UNORM_PACK32(u32),
+    A2R10G10B10_SNORM_PACK32(u32),
+    A2R10G10B10_USCALED_PACK32(u32),
+    A2R10G10B10_SSCALED_PACK32(u32),
+    A2R10G10B10_UINT_PACK32(u32),
+    A2R10G10B10_SINT_PACK32(u32),
+    A2B10G10R10_UNORM_PACK32(u32),
+    A2B10G10R10_SNORM_PACK32(u32),
+    A2B10G10R10_USCALED_PACK32(u32),
+    A2B10G10R10_SSCALED_PACK32(u32),
+    A2B10G10R10_UINT_PACK32(u32),
+    A2B10G10R10_SINT_PACK32(u32),
+
+    R16_UNORM(u16),
+    R16_SNORM(i16),
+    R16_USCALED(u16),
+    R16_SSCALED(i16),
+    R16_UINT(u16),
+    R16_SINT(i16),
     R16_SFLOAT(f16),
+    R16G16_UNORM([u16; 2]),
+    R16G16_SNORM([i16; 2]),
+    R16G16_USCALED([u16; 2]),
+    R16G16_SSCALED([i16; 2]),
+    R16G16_UINT([u16; 2]),
+    R16G16_SINT([i16; 2]),
+    R16G16_SFLOAT([f16; 2]),
+    R16G16B16_UNORM([u16; 3]),
+    R16G16B16_SNORM([i16; 3]),
+    R16G16B16_USCALED([u16; 3]),
+    R16G16B16_SSCALED([i16; 3]),
+    R16G16B16_UINT([u16; 3]),
+    R16G16B16_SINT([i16; 3]),
+    R16G16B16_SFLOAT([f16; 3]),
+    R16G16B16A16_UNORM([u16; 4]),
+    R16G16B16A16_SNORM([i16; 4]),
+    R16G16B16A16_USCALED([u16; 4]),
+    R16G16B16A16_SSCALED([i16; 4]),
+    R16G16B16A16_UINT([u16; 4]),
+    R16G16B16A16_SINT([i16; 4]),
     R16G16B16A16_SFLOAT([f16; 4]),
-    R8G8B8A8_UNORM([u8; 4]),
-    BC1_RGB_UNORM_BLOCK,
+
+    R32_UINT(u32),
+    R32_SINT(i32),
+    R32_SFLOAT(f32),
+    R32G32_UINT([u32; 2]),
+    R32G32_SINT([i32; 2]),
+    R32G32_SFLOAT([f32; 2]),
+    R32G32B32_UINT([u32; 3]),
+    R32G32B32_SINT([i32; 3]),
+    R32G32B32_SFLOAT([f32; 3]),
+    R32G32B32A32_UINT([u32; 4]),
+    R32G32B32A32_SINT([i32; 4]),
+    R32G32B32A32_SFLOAT([f32; 4]),
+    R64_UINT(u64),
+    R64_SINT(i64),
+    R64_SFLOAT(f64),
+    R64G64_UINT([u64; 2]),
+    R64G64_SINT([i64; 2]),
+    R64G64_SFLOAT([f64; 2]),
+    R64G64B64_UINT([u64; 3]),
+    R64G64B64_SINT([i64; 3]),
+    R64G64B64_SFLOAT([f64; 3]),
+    R64G64B64A64_UINT([u64; 4]),
+    R64G64B64A64_SINT([i64; 4]),
+    R64G64B64A64_SFLOAT([f64; 4]),
+
+    B10G11R11_UFLOAT_PACK32(u32),
+    E5B9G9R9_UFLOAT_PACK32(u32),
+
+    D16_UNORM(u16),
+    X8_D24_UNORM_PACK32(u32),
+    D32_SFLOAT(f32),
+    S8_UINT(u8),
+    D16_UNORM_S8_UINT((u16, u8)),
+    D24_UNORM_S8_UINT(u32),
+    D32_SFLOAT_S8_UINT((f32, u8)),
+
+    R10X6_UNORM_PACK16(u16),
+    R10X6G10X6_UNORM_2PACK16([u16; 2]),
+    R10X6G10X6B10X6A10X6_UNORM_4PACK16([u16; 4]),
+    R12X4_UNORM_PACK16(u16),
+    R12X4G12X4_UNORM_2PACK16([u16; 2]),
+    R12X4G12X4B12X4A12X4_UNORM_4PACK16([u16; 4]),
+}
+
+impl Pixel {
+    /// Converts the pixel to `[r, g, b, a]`.
+    ///
+    /// Normalized formats map to `[0, 1]` (`[-1, 1]` when signed), integer
+    /// and scaled formats keep their integer value and float formats their
+    /// float value. sRGB values are returned as stored, without decoding.
+    /// Missing color channels read as 0 and missing alpha as 1. Returns
+    /// `None` for formats without a per-pixel representation.
+    pub fn to_rgba_f32(&self) -> Option<[f32; 4]> {
+        let mut bytes = [0u8; MAX_TEXEL_SIZE];
+        self.write_bytes(&mut bytes);
+        decode_rgba_f32(self.format(), &bytes)
+    }
+
+    /// Builds a pixel of `format` from `[r, g, b, a]`, the inverse of
+    /// [`Pixel::to_rgba_f32`]. Values are rounded and clamped to the range
+    /// of the format. Returns `None` for formats without a per-pixel
+    /// representation.
+    pub fn from_rgba_f32(format: VkFormat, rgba: [f32; 4]) -> Option<Pixel> {
+        let mut bytes = [0u8; MAX_TEXEL_SIZE];
+        encode_rgba_f32(format, rgba, &mut bytes)?;
+        Pixel::from_bytes(format, &bytes)
+    }
+}
+
+/// How [`crate::texture::TextureKtx2::write_pixel_with_mode`] treats a pixel
+/// whose format differs from the texture format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// The pixel format must match the texture format exactly.
+    #[default]
+    Strict,
+
+    /// Pixels of a compatible format are converted through
+    /// [`Pixel::to_rgba_f32`], see [`is_format_convertible`].
+    Convert,
+}
+
+/// Where a channel of [`crate::texture::TextureKtx2::convert_format`] and
+/// [`crate::texture::TextureKtx2::swizzle`] takes its value from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelSource {
+    R,
+    G,
+    B,
+    A,
+    Zero,
+    One,
+}
+
+impl ChannelSource {
+    /// The identity mapping `[R, G, B, A]`.
+    pub const RGBA: [ChannelSource; 4] =
+        [ChannelSource::R, ChannelSource::G, ChannelSource::B, ChannelSource::A];
+
+    /// Index of the source channel in `[r, g, b, a]`, `None` for constants.
+    pub fn rgba_index(self) -> Option<usize> {
+        match self {
+            ChannelSource::R => Some(0),
+            ChannelSource::G => Some(1),
+            ChannelSource::B => Some(2),
+            ChannelSource::A => Some(3),
+            ChannelSource::Zero | ChannelSource::One => None,
+        }
+    }
+
+    /// Value of the channel for the source pixel `rgba`.
+    pub fn select(self, rgba: [f32; 4]) -> f32 {
+        match self {
+            ChannelSource::Zero => 0.0,
+            ChannelSource::One => 1.0,
+            _ => rgba[self.rgba_index().unwrap()],
+        }
+    }
+
+    /// Parses one character of a `KTXswizzle` value: `r`, `g`, `b`, `a`, `0`
+    /// or `1`.
+    pub fn from_swizzle_char(c: u8) -> Option<ChannelSource> {
+        match c {
+            b'r' => Some(ChannelSource::R),
+            b'g' => Some(ChannelSource::G),
+            b'b' => Some(ChannelSource::B),
+            b'a' => Some(ChannelSource::A),
+            b'0' => Some(ChannelSource::Zero),
+            b'1' => Some(ChannelSource::One),
+            _ => None,
+        }
+    }
+
+    /// Character of the channel in a `KTXswizzle` value.
+    pub fn swizzle_char(self) -> u8 {
+        match self {
+            ChannelSource::R => b'r',
+            ChannelSource::G => b'g',
+            ChannelSource::B => b'b',
+            ChannelSource::A => b'a',
+            ChannelSource::Zero => b'0',
+            ChannelSource::One => b'1',
+        }
+    }
+}
+
+/// Options of [`crate::texture::TextureKtx2::convert_format`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConvertOptions {
+    /// Source of each `[r, g, b, a]` channel of the target, e.g.
+    /// `[R, R, R, One]` to expand gray to RGBA.
+    pub channels: [ChannelSource; 4],
+
+    /// Interpretation of the source color values. The target is sRGB
+    /// encoded when its format is.
+    pub color_space: ColorSpace,
+
+    /// Maps color values in `[min, max]`, such as elevation in meters, to
+    /// the full range of the target: `[0, 1]` for UNORM and float formats,
+    /// `[-1, 1]` for SNORM and all values of integer formats. Required to
+    /// convert between normalized and integer formats.
+    pub value_range: Option<(f32, f32)>,
+
+    /// Adds an ordered dither of up to half a step before rounding to
+    /// normalized and integer formats, which trades banding for noise when
+    /// reducing the bit depth.
+    pub dither: bool,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            channels: ChannelSource::RGBA,
+            color_space: ColorSpace::Auto,
+            value_range: None,
+            dither: false,
+        }
+    }
+}
+
+/// Nominal range of a channel and the distance between adjacent stored
+/// values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ChannelRange {
+    pub min: f32,
+    pub max: f32,
+
+    /// Zero for float channels.
+    pub step: f32,
+}
+
+/// [`ChannelRange`] of each `[r, g, b, a]` channel of `layout`, `None` for
+/// missing channels. Float channels have the nominal range `[0, 1]`.
+pub(crate) fn channel_ranges(layout: &FormatLayout) -> [Option<ChannelRange>; 4] {
+    let mut ranges = [None; 4];
+    for channel in layout.channels() {
+        let max = mask(channel.bit_length) as f32;
+        let signed = (mask(channel.bit_length) >> 1) as f32;
+        let (min, max, step) = match channel.numeric {
+            NumericFormat::Unorm | NumericFormat::Srgb => (0.0, 1.0, 1.0 / max),
+            NumericFormat::Snorm => (-1.0, 1.0, 1.0 / signed),
+            NumericFormat::Uint | NumericFormat::Uscaled => (0.0, max, 1.0),
+            NumericFormat::Sint | NumericFormat::Sscaled => (-signed - 1.0, signed, 1.0),
+            NumericFormat::Ufloat | NumericFormat::Sfloat => (0.0, 1.0, 0.0),
+        };
+        ranges[layout.rgba_index(channel.channel)] = Some(ChannelRange { min, max, step });
+    }
+    ranges
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FormatClass {
+    Normalized,
+    Integer,
+    Float,
+    DepthStencil,
+}
+
+fn get_format_class(format: VkFormat) -> Option<FormatClass> {
+    let layout = get_format_layout(format)?;
+    let channel = layout.channels()[0];
+    if matches!(channel.channel, Channel::Depth | Channel::Stencil) {
+        return Some(FormatClass::DepthStencil);
+    }
+    Some(match channel.numeric {
+        NumericFormat::Unorm | NumericFormat::Snorm | NumericFormat::Srgb => FormatClass::Normalized,
+        NumericFormat::Uint | NumericFormat::Sint | NumericFormat::Uscaled | NumericFormat::Sscaled => {
+            FormatClass::Integer
+        }
+        NumericFormat::Ufloat | NumericFormat::Sfloat => FormatClass::Float,
+    })
+}
+
+/// Whether pixels of `from` can be converted to `to` without changing the
+/// meaning of their values.
+///
+/// Normalized and integer formats convert among themselves and to and from
+/// float formats, but not into each other since `[0, 1]` and `[0, 255]`
+/// describe different quantities. Depth/stencil formats only convert to
+/// other depth/stencil formats.
+pub fn is_format_convertible(from: VkFormat, to: VkFormat) -> bool {
+    match (get_format_class(from), get_format_class(to)) {
+        (Some(from), Some(to)) => {
+            from == to
+                || (from != FormatClass::DepthStencil
+                    && to != FormatClass::DepthStencil
+                    && (from == FormatClass::Float || to == FormatClass::Float))
+        }
+        _ => false,
+    }
+}
+
+/// Converts an sRGB encoded value in `[0, 1]` to linear.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear value in `[0, 1]` to sRGB encoding.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decodes the texel of `format` at the start of `bytes` to `[r, g, b, a]`,
+/// see [`Pixel::to_rgba_f32`].
+pub(crate) fn decode_rgba_f32(format: VkFormat, bytes: &[u8]) -> Option<[f32; 4]> {
+    let layout = get_format_layout(format)?;
+    Some(decode_texel(format, &layout, bytes))
+}
+
+/// [`decode_rgba_f32`] with the layout of `format` looked up by the caller,
+/// for decoding many texels.
+pub(crate) fn decode_texel(format: VkFormat, layout: &FormatLayout, bytes: &[u8]) -> [f32; 4] {
+    let mut rgba = [0.0, 0.0, 0.0, 1.0];
+
+    if format == VkFormat::E5B9G9R9_UFLOAT_PACK32 {
+        let exponent = read_bits(bytes, 27, 5) as i32;
+        let scale = 2f32.powi(exponent - 15 - 9);
+        for channel in layout.channels() {
+            let mantissa = read_bits(bytes, channel.bit_offset, channel.bit_length);
+            rgba[layout.rgba_index(channel.channel)] = mantissa as f32 * scale;
+        }
+        return rgba;
+    }
+
+    for channel in layout.channels() {
+        let bits = channel.bit_length;
+        let raw = read_bits(bytes, channel.bit_offset, bits);
+        let value = match channel.numeric {
+            NumericFormat::Unorm | NumericFormat::Srgb => (raw as f64 / mask(bits) as f64) as f32,
+            NumericFormat::Snorm => {
+                let max = (mask(bits) >> 1) as f64;
+                (sign_extend(raw, bits) as f64 / max).max(-1.0) as f32
+            }
+            NumericFormat::Uint | NumericFormat::Uscaled => raw as f32,
+            NumericFormat::Sint | NumericFormat::Sscaled => sign_extend(raw, bits) as f32,
+            NumericFormat::Sfloat => match bits {
+                16 => f16::from_bits(raw as u16).to_f32(),
+                32 => f32::from_bits(raw as u32),
+                _ => f64::from_bits(raw) as f32,
+            },
+            NumericFormat::Ufloat => unpack_ufloat(raw as u32, bits - 5),
+        };
+        rgba[layout.rgba_index(channel.channel)] = value;
+    }
+    rgba
+}
+
+/// Encodes `[r, g, b, a]` as a texel of `format` at the start of `bytes`,
+/// see [`Pixel::from_rgba_f32`].
+pub(crate) fn encode_rgba_f32(format: VkFormat, rgba: [f32; 4], bytes: &mut [u8]) -> Option<()> {
+    let layout = get_format_layout(format)?;
+    encode_texel(format, &layout, rgba, bytes);
+    Some(())
+}
+
+/// [`encode_rgba_f32`] with the layout of `format` looked up by the caller,
+/// for encoding many texels.
+pub(crate) fn encode_texel(
+    format: VkFormat,
+    layout: &FormatLayout,
+    rgba: [f32; 4],
+    bytes: &mut [u8],
+) {
+    bytes[..layout.texel_size as usize].fill(0);
+
+    if format == VkFormat::E5B9G9R9_UFLOAT_PACK32 {
+        write_bits(bytes, 0, 32, pack_e5b9g9r9([rgba[0], rgba[1], rgba[2]]) as u64);
+        return;
+    }
+
+    for channel in layout.channels() {
+        let bits = channel.bit_length;
+        let value = rgba[layout.rgba_index(channel.channel)] as f64;
+        let raw = match channel.numeric {
+            NumericFormat::Unorm | NumericFormat::Srgb => {
+                let max = mask(bits) as f64;
+                (value.clamp(0.0, 1.0) * max).round() as u64
+            }
+            NumericFormat::Snorm => {
+                let max = (mask(bits) >> 1) as f64;
+                (value.clamp(-1.0, 1.0) * max).round() as i64 as u64
+            }
+            NumericFormat::Uint | NumericFormat::Uscaled => {
+                // `as` saturates at the u64 bounds and maps NaN to 0
+                (value.round() as u64).min(mask(bits))
+            }
+            NumericFormat::Sint | NumericFormat::Sscaled => {
+                let max = (mask(bits) >> 1) as i64;
+                (value.round() as i64).clamp(-max - 1, max) as u64
+            }
+            NumericFormat::Sfloat => match bits {
+                // finite values saturate instead of overflowing to infinity
+                16 if value.is_finite() => {
+                    let max = f16::MAX.to_f64();
+                    f16::from_f64(value.clamp(-max, max)).to_bits() as u64
+                }
+                16 => f16::from_f64(value).to_bits() as u64,
+                32 => (value as f32).to_bits() as u64,
+                _ => value.to_bits(),
+            },
+            NumericFormat::Ufloat => pack_ufloat(value as f32, bits - 5) as u64,
+        };
+        write_bits(bytes, channel.bit_offset, bits, raw & mask(bits));
+    }
+}
+
+#[inline]
+fn mask(bits: u32) -> u64 {
+    if bits >= 64 { u64::MAX } else { (1 << bits) - 1 }
+}
+
+#[inline]
+fn sign_extend(raw: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((raw << shift) as i64) >> shift
+}
+
+/// Reads `length` bits starting at bit `offset` of the little-endian `bytes`.
+pub(crate) fn read_bits(bytes: &[u8], offset: u32, length: u32) -> u64 {
+    if offset.is_multiple_of(8) {
+        // fast path for the byte aligned channels of most formats
+        let first = (offset / 8) as usize;
+        match length {
+            8 => return bytes[first] as u64,
+            16 => return u16::from_le_bytes([bytes[first], bytes[first + 1]]) as u64,
+            32 => return u32::from_le_bytes(bytes[first..first + 4].try_into().unwrap()) as u64,
+            _ => {}
+        }
+    }
+    let first = (offset / 8) as usize;
+    let shift = offset % 8;
+    let count = (shift + length).div_ceil(8) as usize;
+    let mut value = 0u128;
+    for (i, byte) in bytes[first..first + count].iter().enumerate() {
+        value |= (*byte as u128) << (8 * i);
+    }
+    ((value >> shift) as u64) & mask(length)
+}
+
+/// Writes the low `length` bits of `value` starting at bit `offset` of the
+/// little-endian `bytes`, leaving the other bits untouched.
+pub(crate) fn write_bits(bytes: &mut [u8], offset: u32, length: u32, value: u64) {
+    let first = (offset / 8) as usize;
+    let shift = offset % 8;
+    let count = (shift + length).div_ceil(8) as usize;
+    let field = (mask(length) as u128) << shift;
+    let value = ((value & mask(length)) as u128) << shift;
+    for (i, byte) in bytes[first..first + count].iter_mut().enumerate() {
+        let keep = !(field >> (8 * i)) as u8;
+        *byte = (*byte & keep) | (value >> (8 * i)) as u8;
+    }
+}
+
+/// Decodes an unsigned float with a 5 bit exponent and `mantissa_bits`
+/// mantissa (the 11 and 10 bit floats of B10G11R11).
+fn unpack_ufloat(raw: u32, mantissa_bits: u32) -> f32 {
+    let exponent = (raw >> mantissa_bits) as i32;
+    let mantissa = (raw & ((1 << mantissa_bits) - 1)) as f32 / (1 << mantissa_bits) as f32;
+    match exponent {
+        0 => mantissa * 2f32.powi(-14),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Encodes an unsigned float with a 5 bit exponent and `mantissa_bits`
+/// mantissa, rounding to nearest. Negative values become 0.
+fn pack_ufloat(value: f32, mantissa_bits: u32) -> u32 {
+    if value.is_nan() {
+        return (31 << mantissa_bits) | 1;
+    }
+    if value <= 0.0 {
+        return 0;
+    }
+    if value.is_infinite() {
+        return 31 << mantissa_bits;
+    }
+    // reuse the rounding of the half float encoder: same exponent range,
+    // only the mantissa is shorter
+    let shift = 10 - mantissa_bits;
+    let half = f16::from_f32(value).to_bits() as u32;
+    let (exponent, mantissa) = (half >> 10 & 0x1F, half & 0x3FF);
+    if exponent == 31 {
+        // overflowed the half float range, saturate to the largest finite value
+        return (31 << mantissa_bits) - 1;
+    }
+    let rounded = ((exponent << 10 | mantissa) + (1 << (shift - 1))) >> shift;
+    rounded.min((31 << mantissa_bits) - 1)
 }
+
+/// Encodes three unsigned floats with a shared exponent as described in the
+/// Vulkan specification.
+fn pack_e5b9g9r9(rgb: [f32; 3]) -> u32 {
+    const MAX: f32 = (511.0 / 512.0) * 65536.0;
+    let [r, g, b] = rgb.map(|c| if c.is_nan() { 0.0 } else { c.clamp(0.0, MAX) });
+    let max = r.max(g).max(b);
+    let mut exponent = (max.log2().floor() as i32).max(-16) + 1 + 15;
+    let mut scale = 2f32.powi(exponent - 15 - 9);
+    if (max / scale + 0.5).floor() as u32 == 512 {
+        exponent += 1;
+        scale *= 2.0;
+    }
+    let [r, g, b] = [r, g, b].map(|c| (c / scale + 0.5).floor() as u32);
+    (exponent as u32) << 27 | b << 18 | g << 9 | r
+}
+
@@ -0,0 +1,170 @@
+use ::png::{BitDepth, ColorType, Decoder, Encoder, ScaledFloat, SrgbRenderingIntent};
+use ::png::Transformations;
+
+use crate::pixel::ChannelSource;
+use crate::resample;
+use crate::texture::TextureKtx2;
+use crate::vk_format::{get_format_layout, VkFormat};
+
+/// Options of [`TextureKtx2::to_png`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PngOptions {
+    /// Maps color values in `[min, max]`, such as elevation in meters of a
+    /// float texture, to the full range of the PNG. Values are clamped to
+    /// `[0, 1]` otherwise.
+    pub value_range: Option<(f32, f32)>,
+
+    /// Whether samples are written with 16 bits, by default when a channel
+    /// of the format has more than 8 bits.
+    pub sixteen_bit: Option<bool>,
+
+    /// Z slice of 3D textures.
+    pub slice: u32,
+}
+
+impl TextureKtx2 {
+    /// Reads a PNG as a single level texture of the matching `UNORM` or
+    /// `SRGB` format, e.g. `R8G8B8A8_SRGB` or `R16G16B16_UNORM`. Color is
+    /// sRGB encoded unless a `gAMA` chunk marks it as linear: gammas above
+    /// 2/3, i.e. decoding exponents below 1.5, read as linear and smaller
+    /// ones, such as 1/2.2 or 1/1.8, as sRGB. Samples are kept as stored.
+    /// Gray images get the `KTXswizzle` `rrr1`, gray with alpha `rrrg`.
+    /// Palette and low bit depth images are expanded to 8 bits.
+    pub fn from_png(bytes: &[u8]) -> Result<TextureKtx2, anyhow::Error> {
+        let mut decoder = Decoder::new(bytes);
+        decoder.set_transformations(Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let info = reader.info();
+        let srgb = match (info.srgb, info.gama_chunk) {
+            (None, Some(gamma)) => gamma.into_value() <= 2.0 / 3.0,
+            _ => true,
+        };
+
+        let (color_type, bit_depth) = reader.output_color_type();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut data)?;
+        data.truncate(frame.buffer_size());
+
+        use ChannelSource::*;
+        let sixteen_bit = bit_depth == BitDepth::Sixteen;
+        let (unorm, srgb_format, wide, swizzle) = match color_type {
+            ColorType::Grayscale => {
+                (VkFormat::R8_UNORM, VkFormat::R8_SRGB, VkFormat::R16_UNORM, Some([R, R, R, One]))
+            }
+            ColorType::GrayscaleAlpha => (
+                VkFormat::R8G8_UNORM,
+                VkFormat::R8G8_SRGB,
+                VkFormat::R16G16_UNORM,
+                Some([R, R, R, G]),
+            ),
+            ColorType::Rgb => {
+                (VkFormat::R8G8B8_UNORM, VkFormat::R8G8B8_SRGB, VkFormat::R16G16B16_UNORM, None)
+            }
+            ColorType::Rgba => (
+                VkFormat::R8G8B8A8_UNORM,
+                VkFormat::R8G8B8A8_SRGB,
+                VkFormat::R16G16B16A16_UNORM,
+                None,
+            ),
+            ColorType::Indexed => anyhow::bail!("Unexpanded PNG palette"),
+        };
+        let format = match (sixteen_bit, srgb) {
+            (true, _) => wide,
+            (false, true) => srgb_format,
+            (false, false) => unorm,
+        };
+
+        let mut texture = TextureKtx2::new(frame.width, frame.height, format);
+        if sixteen_bit {
+            // there are no 16 bit sRGB formats, the DFD carries the encoding
+            texture.dfd_descriptor_block[0].set_srgb(srgb);
+            for sample in data.chunks_exact_mut(2) {
+                sample.swap(0, 1);
+            }
+        }
+        texture.level_images[0] = data;
+        if let Some(swizzle) = swizzle {
+            texture.set_stored_swizzle(swizzle)?;
+        }
+        Ok(texture)
+    }
+
+    /// Writes one 2D image of the texture as a PNG. The `KTXswizzle`
+    /// metadata is applied, single channel results are written as gray and
+    /// alpha only when the texture has it. Block compressed formats are
+    /// decompressed. Color keeps the sRGB or linear encoding of the texture,
+    /// marked by an `sRGB` or `gAMA` chunk.
+    pub fn to_png(
+        &self,
+        level: u32,
+        layer: u32,
+        face: u32,
+        options: &PngOptions,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        let dfd = &self.dfd_descriptor_block[0];
+        let srgb = dfd.is_srgb();
+        let mut image = self.decode_image(level, layer, face, options.slice, srgb)?;
+        if dfd.is_alpha_premultiplied() {
+            resample::unpremultiply_alpha(&mut image);
+        }
+
+        let layout = get_format_layout(self.header.vk_format);
+        let present = |source: ChannelSource| {
+            source.rgba_index().is_some_and(|index| {
+                layout.is_none_or(|layout| {
+                    layout.channels().iter().any(|c| layout.rgba_index(c.channel) == index)
+                })
+            })
+        };
+        let swizzle = self.stored_swizzle()?.unwrap_or(ChannelSource::RGBA);
+        let gray = swizzle[1..3].iter().all(|&s| s == swizzle[0] || !present(s));
+        let alpha = present(swizzle[3]) || swizzle[3] == ChannelSource::Zero;
+        let sixteen_bit = options.sixteen_bit.unwrap_or_else(|| {
+            layout.is_none_or(|layout| layout.channels().iter().any(|c| c.bit_length > 8))
+        });
+
+        let mut channels = vec![0];
+        if !gray {
+            channels.extend([1, 2]);
+        }
+        if alpha {
+            channels.push(3);
+        }
+        let scale = if sixteen_bit { 65535.0 } else { 255.0 };
+        let mut data = Vec::with_capacity(image.pixels.len() * channels.len() * 2);
+        for pixel in &image.pixels {
+            let pixel = swizzle.map(|s| s.select(*pixel));
+            for &c in &channels {
+                let mut value = pixel[c];
+                if let Some((min, max)) = options.value_range.filter(|_| c < 3) {
+                    value = (value - min) / (max - min);
+                }
+                let value = (value.clamp(0.0, 1.0) * scale).round() as u16;
+                if sixteen_bit {
+                    data.extend(value.to_be_bytes());
+                } else {
+                    data.push(value as u8);
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        let mut encoder = Encoder::new(&mut out, image.width, image.height);
+        encoder.set_color(match (gray, alpha) {
+            (true, false) => ColorType::Grayscale,
+            (true, true) => ColorType::GrayscaleAlpha,
+            (false, false) => ColorType::Rgb,
+            (false, true) => ColorType::Rgba,
+        });
+        encoder.set_depth(if sixteen_bit { BitDepth::Sixteen } else { BitDepth::Eight });
+        if srgb {
+            encoder.set_source_srgb(SrgbRenderingIntent::Perceptual);
+        } else {
+            encoder.set_source_gamma(ScaledFloat::new(1.0));
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(out)
+    }
+}
@@ -0,0 +1,78 @@
+use crate::dfd::BasicDataFormatDescriptor;
+use crate::texture::TextureKtx2;
+use crate::vk_format::{
+    get_format_block_extent, get_format_block_size_bytes, get_format_type_size_bytes, VkFormat,
+};
+
+/// Memory layout of a headerless buffer read by [`TextureKtx2::from_raw`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RawLayout {
+    /// Bytes from the start of one row, or row of blocks, to the next. Rows
+    /// are tightly packed by default.
+    pub row_pitch: Option<usize>,
+
+    /// Whether the values are big endian, swapped in units of the type size
+    /// of the format, e.g. 4 bytes for `R32G32_SFLOAT` and 2 bytes for
+    /// `R5G6B5_UNORM_PACK16`.
+    pub big_endian: bool,
+}
+
+impl TextureKtx2 {
+    /// Creates a single level 2D texture from a headerless buffer of texels
+    /// or blocks of the format, such as a dump of a float grid. The buffer
+    /// must hold exactly the rows the layout describes, the last row may
+    /// omit its padding. PVRTC and 4:2:2 formats are not supported.
+    pub fn from_raw(
+        bytes: &[u8],
+        width: u32,
+        height: u32,
+        format: VkFormat,
+        layout: &RawLayout,
+    ) -> Result<TextureKtx2, anyhow::Error> {
+        if width == 0 || height == 0 {
+            anyhow::bail!("Invalid raw image size {}x{}", width, height);
+        }
+        if BasicDataFormatDescriptor::try_new(format).is_none() {
+            anyhow::bail!("Unsupported raw format {:?}", format);
+        }
+        let (block_width, block_height, _) = get_format_block_extent(format);
+        let row_size =
+            width.div_ceil(block_width) as usize * get_format_block_size_bytes(format) as usize;
+        let rows = height.div_ceil(block_height) as usize;
+        let pitch = layout.row_pitch.unwrap_or(row_size);
+        if pitch < row_size {
+            anyhow::bail!("Row pitch {} is smaller than the {} bytes of a row", pitch, row_size);
+        }
+        let min_size = pitch * (rows - 1) + row_size;
+        if !(min_size..=pitch * rows).contains(&bytes.len()) {
+            anyhow::bail!(
+                "Raw buffer of {} bytes does not match {}x{} {:?}, {} bytes are needed",
+                bytes.len(),
+                width,
+                height,
+                format,
+                min_size
+            );
+        }
+
+        let mut texture = TextureKtx2::new(width, height, format);
+        let data = &mut texture.level_images[0];
+        for (row, src) in data.chunks_exact_mut(row_size).zip(bytes.chunks(pitch)) {
+            row.copy_from_slice(&src[..row_size]);
+        }
+        let type_size = get_format_type_size_bytes(format) as usize;
+        if layout.big_endian && type_size > 1 {
+            for value in data.chunks_exact_mut(type_size) {
+                value.reverse();
+            }
+        }
+        Ok(texture)
+    }
+
+    /// Tightly packed little endian data of a level, every layer, face and
+    /// z slice in turn, as read by [`TextureKtx2::from_raw`] for single
+    /// images.
+    pub fn to_raw(&self, level: u32) -> Result<Vec<u8>, anyhow::Error> {
+        Ok(self.level_as::<u8>(level)?.to_vec())
+    }
+}
@@ -0,0 +1,547 @@
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use wide::f32x4;
+
+use crate::filter::{clamp, Filter, FilterType};
+
+/// A 2D image decoded to `[r, g, b, a]` f32 values, the intermediate
+/// representation used by the resampler.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl Image {
+    #[inline]
+    pub fn get(&self, x: u32, y: u32) -> [f32; 4] {
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+/// How samples outside of the image are addressed, matching the sampler
+/// address modes of Vulkan.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AddressMode {
+    /// The kernel window is cut at the image edge and the remaining weights
+    /// are renormalized.
+    #[default]
+    ClampToEdge,
+
+    /// The image tiles, for textures sampled with wrapping coordinates.
+    Repeat,
+
+    /// The image tiles with every other copy mirrored.
+    MirroredRepeat,
+
+    /// Samples outside of the image have this color, given as stored values
+    /// in `[r, g, b, a]` order.
+    ClampToBorder([f32; 4]),
+}
+
+/// Normalized filter weights of a 1D pass, computed once per axis. The taps
+/// of output sample `i` are `indices[bounds[i]..bounds[i + 1]]` with the
+/// matching `weights`, plus `border[i]` times the border color for taps
+/// falling outside of a [`AddressMode::ClampToBorder`] image.
+struct WeightTable {
+    bounds: Vec<usize>,
+    indices: Vec<u32>,
+    weights: Vec<f32>,
+    border: Vec<f32>,
+}
+
+impl WeightTable {
+    #[inline]
+    fn taps(&self, i: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+        let range = self.bounds[i]..self.bounds[i + 1];
+        self.indices[range.clone()]
+            .iter()
+            .zip(&self.weights[range])
+            .map(|(&index, &weight)| (index as usize, weight))
+    }
+}
+
+/// Maps the input position `i` to a sample of an axis of `len` samples.
+fn address(i: i64, len: u32, mode: AddressMode) -> Option<u32> {
+    let len = len as i64;
+    match mode {
+        AddressMode::ClampToEdge => Some(clamp(i, 0, len - 1) as u32),
+        AddressMode::Repeat => Some(i.rem_euclid(len) as u32),
+        AddressMode::MirroredRepeat => {
+            let i = i.rem_euclid(2 * len);
+            Some(if i < len { i } else { 2 * len - 1 - i } as u32)
+        }
+        AddressMode::ClampToBorder(_) => (0..len).contains(&i).then_some(i as u32),
+    }
+}
+
+/// Computes the weight table of a 1D pass from `in_len` to `out_len`
+/// samples. The input axis has `pad` extra samples on both sides holding
+/// data from beyond the edge, e.g. a neighbouring cubemap face; only
+/// positions outside of those go through `mode`.
+fn weight_table(
+    in_len: u32,
+    pad: u32,
+    out_len: u32,
+    filter: &mut Filter,
+    mode: AddressMode,
+) -> WeightTable {
+    let ratio = in_len as f32 / out_len as f32;
+    let sratio = if ratio < 1.0 { 1.0 } else { ratio };
+    let src_support = filter.support * sratio;
+    let padded_len = in_len + 2 * pad;
+
+    let mut table = WeightTable {
+        bounds: vec![0],
+        indices: Vec::new(),
+        weights: Vec::new(),
+        border: Vec::new(),
+    };
+    for out in 0..out_len {
+        // Find the point in the input image corresponding to the centre
+        // of the current output sample.
+        let input = (out as f32 + 0.5) * ratio;
+
+        // Input sample i is relevant if and only if (i >= left) && (i < right).
+        let mut left = (input - src_support).floor() as i64;
+        let mut right = (input + src_support).ceil() as i64;
+        if mode == AddressMode::ClampToEdge {
+            // Invariant: -pad <= left < right <= in_len + pad
+            let pad = <i64 as From<_>>::from(pad);
+            left = clamp(left, -pad, <i64 as From<_>>::from(in_len) + pad - 1);
+            right = clamp(right, left + 1, <i64 as From<_>>::from(in_len) + pad);
+        } else {
+            right = right.max(left + 1);
+        }
+
+        // Go back to left boundary of the sample, to properly compare
+        // with i below, as the kernel treats the centre of a sample as 0.
+        let input = input - 0.5;
+
+        let start = table.weights.len();
+        let mut sum = 0.0;
+        let mut border = 0.0;
+        for i in left..right {
+            let w = (filter.kernel)((i as f32 - input) / sratio);
+            sum += w;
+            match address(i + pad as i64, padded_len, mode) {
+                Some(index) => {
+                    table.indices.push(index);
+                    table.weights.push(w);
+                }
+                None => border += w,
+            }
+        }
+        for w in &mut table.weights[start..] {
+            *w /= sum;
+        }
+        table.border.push(border / sum);
+        table.bounds.push(table.weights.len());
+    }
+    table
+}
+
+#[inline]
+fn border_color(mode: AddressMode) -> f32x4 {
+    match mode {
+        AddressMode::ClampToBorder(color) => f32x4::from(color),
+        _ => f32x4::ZERO,
+    }
+}
+
+/// Runs `f` on every `width` long row of `data` with its index, on the rayon
+/// thread pool when the `rayon` feature is enabled.
+pub(crate) fn for_each_row<T, F>(data: &mut [T], width: usize, f: F)
+where
+    T: Send,
+    F: Fn(usize, &mut [T]) + Send + Sync,
+{
+    #[cfg(feature = "rayon")]
+    data.par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| f(y, row));
+    #[cfg(not(feature = "rayon"))]
+    data.chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| f(y, row));
+}
+
+/// Whether a pass needs the per sample path of [`reduce_taps`] instead of
+/// the plain weighted sum.
+fn needs_reduction(options: &ResizeOptions) -> bool {
+    options.anti_ringing || options.no_data.is_some() || options.reduction != Reduction::Filter
+}
+
+/// Builds the weight table of a pass, with a box filter for the
+/// reductions other than [`Reduction::Filter`].
+fn pass_weight_table(
+    in_len: u32,
+    pad: u32,
+    out_len: u32,
+    filter: &mut Filter,
+    options: &ResizeOptions,
+) -> WeightTable {
+    let mode = options.address_mode;
+    if options.reduction == Reduction::Filter {
+        weight_table(in_len, pad, out_len, filter, mode)
+    } else {
+        weight_table(in_len, pad, out_len, &mut FilterType::Box.to_filter(), mode)
+    }
+}
+
+/// Combines the taps of one output sample, channel by channel, according to
+/// the reduction, no-data and anti-ringing settings of `options`.
+fn reduce_taps(taps: &[([f32; 4], f32)], options: &ResizeOptions) -> [f32; 4] {
+    let no_data = options.no_data.unwrap_or(f32::NAN);
+    let is_valid = |v: f32| options.no_data.is_none() || !(v.is_nan() || v == no_data);
+
+    let mut out = [0.0; 4];
+    for (c, out) in out.iter_mut().enumerate() {
+        let mut sum = 0.0;
+        let mut weight_sum = 0.0;
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        for &(p, w) in taps {
+            if w == 0.0 || !is_valid(p[c]) {
+                continue;
+            }
+            sum += p[c] * w;
+            weight_sum += w;
+            min = min.min(p[c]);
+            max = max.max(p[c]);
+        }
+        *out = if min > max || weight_sum.abs() < f32::EPSILON {
+            // every tap is excluded
+            no_data
+        } else {
+            match options.reduction {
+                Reduction::Min => min,
+                Reduction::Max => max,
+                Reduction::Filter | Reduction::Average => {
+                    let value = sum / weight_sum;
+                    if options.anti_ringing {
+                        value.clamp(min, max)
+                    } else {
+                        value
+                    }
+                }
+            }
+        };
+    }
+    out
+}
+
+/// Resamples `image` to `new_height` rows.
+pub(crate) fn vertical_sample(
+    image: &Image,
+    new_height: u32,
+    filter: &mut Filter,
+    options: &ResizeOptions,
+) -> Image {
+    vertical_sample_padded(image, 0, new_height, filter, options)
+}
+
+/// Resamples the rows of `image` without the `pad` rows on either side to
+/// `new_height` rows, reading into the padding where the kernel reaches.
+fn vertical_sample_padded(
+    image: &Image,
+    pad: u32,
+    new_height: u32,
+    filter: &mut Filter,
+    options: &ResizeOptions,
+) -> Image {
+    let width = image.width as usize;
+    let mut out = Image {
+        width: image.width,
+        height: new_height,
+        pixels: vec![[0.0; 4]; width * new_height as usize],
+    };
+    let border = border_color(options.address_mode);
+    let table = pass_weight_table(image.height - 2 * pad, pad, new_height, filter, options);
+
+    if needs_reduction(options) {
+        for_each_row(&mut out.pixels, width, |outy, out_row| {
+            let mut taps = Vec::new();
+            for (x, p) in out_row.iter_mut().enumerate() {
+                taps.clear();
+                taps.push((border.to_array(), table.border[outy]));
+                for (y, w) in table.taps(outy) {
+                    taps.push((image.pixels[y * width + x], w));
+                }
+                *p = reduce_taps(&taps, options);
+            }
+        });
+        return out;
+    }
+
+    for_each_row(&mut out.pixels, width, |outy, out_row| {
+        // accumulate whole input rows so the inner loop runs over
+        // contiguous memory
+        let mut acc = vec![border * table.border[outy]; width];
+        for (y, w) in table.taps(outy) {
+            let row = &image.pixels[y * width..(y + 1) * width];
+            let w = f32x4::splat(w);
+            for (t, p) in acc.iter_mut().zip(row) {
+                *t += f32x4::from(*p) * w;
+            }
+        }
+        for (p, t) in out_row.iter_mut().zip(acc) {
+            *p = t.to_array();
+        }
+    });
+    out
+}
+
+/// Resamples `image` to `new_width` columns.
+pub(crate) fn horizontal_sample(
+    image: &Image,
+    new_width: u32,
+    filter: &mut Filter,
+    options: &ResizeOptions,
+) -> Image {
+    horizontal_sample_padded(image, 0, new_width, filter, options)
+}
+
+/// Resamples the columns of `image` without the `pad` columns on either side
+/// to `new_width` columns, reading into the padding where the kernel reaches.
+fn horizontal_sample_padded(
+    image: &Image,
+    pad: u32,
+    new_width: u32,
+    filter: &mut Filter,
+    options: &ResizeOptions,
+) -> Image {
+    let width = image.width as usize;
+    let mut out = Image {
+        width: new_width,
+        height: image.height,
+        pixels: vec![[0.0; 4]; new_width as usize * image.height as usize],
+    };
+    let border = border_color(options.address_mode);
+    let table = pass_weight_table(image.width - 2 * pad, pad, new_width, filter, options);
+    let reduce = needs_reduction(options);
+
+    for_each_row(&mut out.pixels, new_width as usize, |y, out_row| {
+        let row = &image.pixels[y * width..(y + 1) * width];
+        let mut taps = Vec::new();
+        for (outx, p) in out_row.iter_mut().enumerate() {
+            if reduce {
+                taps.clear();
+                taps.push((border.to_array(), table.border[outx]));
+                taps.extend(table.taps(outx).map(|(x, w)| (row[x], w)));
+                *p = reduce_taps(&taps, options);
+                continue;
+            }
+            let mut t = border * table.border[outx];
+            for (x, w) in table.taps(outx) {
+                t += f32x4::from(row[x]) * f32x4::splat(w);
+            }
+            *p = t.to_array();
+        }
+    });
+    out
+}
+
+/// Resizes `image` with a vertical and a horizontal pass. Both run on f32 so
+/// the intermediate image is not quantized.
+pub(crate) fn resize(
+    image: &Image,
+    new_width: u32,
+    new_height: u32,
+    filter: &mut Filter,
+    options: &ResizeOptions,
+) -> Image {
+    let tmp = vertical_sample(image, new_height, filter, options);
+    horizontal_sample(&tmp, new_width, filter, options)
+}
+
+/// Resizes the six faces of a cubemap, in the +X, -X, +Y, -Y, +Z, -Z order of
+/// KTX. Kernels reaching over an edge read the adjacent face instead of
+/// clamping, so filtering doesn't open seams between faces.
+pub(crate) fn resize_cube_faces(
+    faces: &[Image],
+    new_size: u32,
+    filter: &mut Filter,
+    options: &ResizeOptions,
+) -> Vec<Image> {
+    let size = faces[0].width;
+    let ratio = size as f32 / new_size as f32;
+    let pad = ((filter.support * ratio.max(1.0)).ceil() as u32 + 1).min(size);
+    let options = ResizeOptions {
+        address_mode: AddressMode::ClampToEdge,
+        ..*options
+    };
+    (0..6)
+        .map(|face| {
+            let padded = pad_cube_face(faces, face, pad);
+            let tmp = vertical_sample_padded(&padded, pad, new_size, filter, &options);
+            horizontal_sample_padded(&tmp, pad, new_size, filter, &options)
+        })
+        .collect()
+}
+
+/// Direction of the face coordinates `(sc, tc)`, in `[-1, 1]` inside the
+/// face, following the cube map face selection of the Vulkan specification.
+fn cube_direction(face: usize, sc: f32, tc: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -tc, -sc],
+        1 => [-1.0, -tc, sc],
+        2 => [sc, 1.0, tc],
+        3 => [sc, -1.0, -tc],
+        4 => [sc, -tc, 1.0],
+        _ => [-sc, -tc, -1.0],
+    }
+}
+
+/// Face and face coordinates a direction points at.
+fn cube_face(direction: [f32; 3]) -> (usize, f32, f32) {
+    let [x, y, z] = direction;
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (0, -z / ax, -y / ax)
+        } else {
+            (1, z / ax, -y / ax)
+        }
+    } else if ay >= az {
+        if y > 0.0 {
+            (2, x / ay, z / ay)
+        } else {
+            (3, x / ay, -z / ay)
+        }
+    } else if z > 0.0 {
+        (4, x / az, -y / az)
+    } else {
+        (5, -x / az, -y / az)
+    }
+}
+
+/// Copies `face` into an image with a border of `pad` texels taken from the
+/// faces around it.
+fn pad_cube_face(faces: &[Image], face: usize, pad: u32) -> Image {
+    let size = faces[face].width;
+    let padded_size = size + 2 * pad;
+    let to_face = |texel: i64| 2.0 * (texel as f32 + 0.5) / size as f32 - 1.0;
+    let to_texel = |coord: f32| {
+        let texel = ((coord + 1.0) * 0.5 * size as f32).floor() as i64;
+        clamp(texel, 0, size as i64 - 1) as u32
+    };
+
+    let mut pixels = Vec::with_capacity(padded_size as usize * padded_size as usize);
+    for y in 0..padded_size as i64 {
+        for x in 0..padded_size as i64 {
+            let (x, y) = (x - pad as i64, y - pad as i64);
+            if (0..size as i64).contains(&x) && (0..size as i64).contains(&y) {
+                pixels.push(faces[face].get(x as u32, y as u32));
+            } else {
+                let direction = cube_direction(face, to_face(x), to_face(y));
+                let (other, sc, tc) = cube_face(direction);
+                pixels.push(faces[other].get(to_texel(sc), to_texel(tc)));
+            }
+        }
+    }
+    Image {
+        width: padded_size,
+        height: padded_size,
+        pixels,
+    }
+}
+
+/// How color values are interpreted while filtering.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Follow the transfer function of the texture's data format descriptor.
+    #[default]
+    Auto,
+
+    /// Filter the stored values as they are.
+    Linear,
+
+    /// Decode the color channels from sRGB before filtering and encode them
+    /// again afterwards. Alpha is always linear.
+    Srgb,
+}
+
+/// Options of [`crate::texture::TextureKtx2::resize_with_options`] and
+/// [`crate::texture::TextureKtx2::generate_mipmaps`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResizeOptions {
+    pub color_space: ColorSpace,
+
+    /// Addressing of samples beyond the image edges. Cubemaps always filter
+    /// across their faces instead.
+    pub address_mode: AddressMode,
+
+    /// Multiply color by alpha while filtering, so fully transparent pixels
+    /// don't bleed their color into their neighbours. Ignored for formats
+    /// without alpha and for data that is already premultiplied.
+    pub premultiply_alpha: bool,
+
+    /// Clamp every output value to the range of the input values it is
+    /// filtered from, which removes the over- and undershoot of sharpening
+    /// kernels like Lanczos at steep edges.
+    pub anti_ringing: bool,
+
+    /// Sentinel marking samples without data. When set, NaN and this value
+    /// are left out of the weighted sum and the remaining weights are
+    /// renormalized; outputs without any valid input get the sentinel.
+    pub no_data: Option<f32>,
+
+    pub reduction: Reduction,
+}
+
+/// How the input samples covered by an output sample are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Reduction {
+    /// Weighted sum with the filter kernel.
+    #[default]
+    Filter,
+
+    /// Smallest input value, e.g. for conservative depth or elevation
+    /// mip levels. The filter kernel is ignored, the footprint is a box.
+    Min,
+
+    /// Largest input value over a box footprint.
+    Max,
+
+    /// Unweighted mean over a box footprint.
+    Average,
+}
+
+/// Converts the color channels of `image` to linear light.
+pub(crate) fn srgb_to_linear(image: &mut Image) {
+    for pixel in &mut image.pixels {
+        for c in &mut pixel[..3] {
+            *c = crate::pixel::srgb_to_linear(*c);
+        }
+    }
+}
+
+/// Converts the color channels of `image` from linear light to sRGB.
+pub(crate) fn linear_to_srgb(image: &mut Image) {
+    for pixel in &mut image.pixels {
+        for c in &mut pixel[..3] {
+            *c = crate::pixel::linear_to_srgb(c.clamp(0.0, 1.0));
+        }
+    }
+}
+
+pub(crate) fn premultiply_alpha(image: &mut Image) {
+    for pixel in &mut image.pixels {
+        let alpha = pixel[3];
+        for c in &mut pixel[..3] {
+            *c *= alpha;
+        }
+    }
+}
+
+/// Inverse of [`premultiply_alpha`], fully transparent pixels become black.
+pub(crate) fn unpremultiply_alpha(image: &mut Image) {
+    for pixel in &mut image.pixels {
+        let alpha = pixel[3];
+        for c in &mut pixel[..3] {
+            *c = if alpha > 0.0 { *c / alpha } else { 0.0 };
+        }
+    }
+}
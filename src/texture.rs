@@ -2,44 +2,75 @@ extern crate anyhow;
 extern crate byteorder;
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use half::f16;
-use std::f32;
+use bytemuck::Pod;
+use std::any::type_name;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io;
 use std::io::Cursor;
 use std::io::Read;
 use std::io::Write;
-use std::mem::size_of_val;
+use std::slice::{ChunksExact, ChunksExactMut};
 
+use crate::bc;
+use crate::bc::astc::{self, DecodeMode};
+use crate::bc::eac::Variant;
+use crate::bc::{bc1, bc4, bc6h, bc7, eac, etc2, Block, CompressOptions};
 use crate::dfd::BasicDataFormatDescriptor;
 use crate::dfd::DFDSampleType;
 use crate::header::Header;
 use crate::index::Index;
 use crate::level::Level;
-use crate::pixel::Pixel;
+use crate::metrics;
+use crate::metrics::Comparison;
+use crate::pixel::{
+    channel_ranges, decode_texel, encode_texel, is_format_convertible, read_bits, write_bits,
+    ChannelSource, ConvertOptions, Pixel, WriteMode, MAX_TEXEL_SIZE,
+};
+use crate::resample;
+use crate::resample::{AddressMode, ColorSpace, Image, ResizeOptions};
 use crate::vk_format::*;
 
 use crate::filter::*;
 
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+// identifier + 9 header fields
+const HEADER_SIZE: u64 = 12 + 9 * 4;
+// dfd/kvd offsets and lengths (u32) + sgd offset and length (u64)
+const INDEX_SIZE: u64 = 4 * 4 + 2 * 8;
+// byteOffset, byteLength, uncompressedByteLength
+const LEVEL_INDEX_ENTRY_SIZE: u64 = 3 * 8;
+
+/// Ordered dither thresholds in `[0, 16)`.
+const BAYER_4X4: [[f32; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+/// Decoded planes of every level of a texture.
+type DecodedLevels = Vec<Vec<Image>>;
+
 #[repr(C, align(1))]
 #[derive(Clone)]
 pub struct TextureKtx2 {
     pub header: Header,
 
-    // Index
-    index: Index,
-
     // Data Format Descriptor
     pub dfd_descriptor_block: Vec<BasicDataFormatDescriptor>,
 
-    // Key/Value Data
-    pub key_value_data: [u8; 52],
+    // Key/Value Data, sorted by key as required by the spec
+    pub key_value_data: BTreeMap<String, Vec<u8>>,
 
     // Supercompression Global Data
     pub supercompression_global_data: Vec<u8>,
 
-    // Mip Level Array
-    pub level_images: Vec<u8>,
+    // Mip Level Array, one buffer per level starting with the base level
+    pub level_images: Vec<Vec<u8>>,
 }
 
 impl TextureKtx2 {
@@ -47,9 +78,7 @@ impl TextureKtx2 {
         let type_size = get_format_type_size_bytes(format);
 
         let header = Header {
-            identifier: [
-                0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
-            ],
+            identifier: KTX2_IDENTIFIER,
             vk_format: format,
             type_size,
             pixel_width: width,
@@ -63,224 +92,367 @@ impl TextureKtx2 {
 
         let dfd = BasicDataFormatDescriptor::new(format);
 
-        let mut levels = Vec::new();
-        let pixel_size = get_format_pixel_size_bytes(format);
-        let byte_length = (width as f32 * height as f32 * pixel_size) as u64;
-
-        levels.push(Level {
-            byte_offset: 200u64,
-            byte_length,
-            uncompressed_byte_length: byte_length,
-        });
-
-        let index_size = (32 + std::mem::size_of::<Level>() * levels.len()) as u32;
-        let mut index = Index {
-            // Index
-            dfd_byte_offset: 0,
-            dfd_byte_length: dfd.dfd_total_size,
-            kvd_byte_offset: 148u32,
-            kvd_byte_length: 52u32,
-            sgd_byte_offset: 0u64,
-            sgd_byte_length: 0u64,
-            // Level Index
-            levels,
-        };
-        let dfd_byte_offset = size_of_val(&header) as u32 + index_size;
-        index.dfd_byte_offset = dfd_byte_offset;
-        index.kvd_byte_offset = dfd_byte_offset + dfd.dfd_total_size;
-        index.sgd_byte_offset = 0; //index.kvd_byte_offset as u64 + 52;
-        index.levels[0].byte_offset = index.kvd_byte_offset as u64 + 52;
+        let mut key_value_data = BTreeMap::new();
+        key_value_data.insert(String::from("KTXorientation"), b"rd\0".to_vec());
+        key_value_data.insert(String::from("KTXwriter"), b"SeniorSKY_dt2\0".to_vec());
 
-        TextureKtx2 {
+        let mut texture = TextureKtx2 {
             header,
-            index,
 
             // Data Format Descriptor
             dfd_descriptor_block: vec![dfd],
 
             // Key/Value Data
-            key_value_data: [
-                0x12, 0x00, 0x00, 0x00, // 18 bytes for first entry
-                0x4B, 0x54, 0x58, 0x6F, // KTXo
-                0x72, 0x69, 0x65, 0x6E, // rien
-                0x74, 0x61, 0x74, 0x69, // tati
-                0x6F, 0x6E, 0x00, 0x72, // on NUL r
-                0x64, 0x00, 0x00, 0x00, // d  <3 bytes of valuePadding>
-                0x18, 0x00, 0x00, 0x00, // 24 bytes for second entry
-                0x4B, 0x54, 0x58, 0x77, // KTXw
-                0x72, 0x69, 0x74, 0x65, // rite
-                0x72, 0x00, 0x53, 0x65, // r NULL Se
-                0x6e, 0x69, 0x6f, 0x72, // nior
-                0x53, 0x4b, 0x59, 0x5f, // SKY_
-                0x64, 0x74, 0x32, 0x00, // dt2 NULL
-            ],
+            key_value_data,
 
             // Supercompression Global Data
             supercompression_global_data: Vec::new(),
 
             // Mip Level Array
-            level_images: vec![0x00; byte_length as usize],
-        }
-    }
-
-    pub fn read_pixel(&self, x: u32, y: u32) -> Pixel {
-        let pixel_size = get_format_pixel_size_bytes(self.header.vk_format);
-        let index: usize = ((x as f32) * pixel_size
-            + (self.header.pixel_width as f32) * (y as f32) * pixel_size)
-            as usize;
-        match self.header.vk_format {
-            VkFormat::R16_SFLOAT => {
-                let mut a: [u8; 2] = [0, 0];
-                a[0] = self.level_images[index];
-                a[1] = self.level_images[index + 1];
-                let value = half::f16::from_le_bytes(a);
-                Pixel::R16_SFLOAT(value)
-            }
-            VkFormat::R8G8B8A8_UNORM => {
-                let mut a: [u8; 4] = [0, 0, 0, 0];
-                a[0] = self.level_images[index];
-                a[1] = self.level_images[index + 1];
-                a[2] = self.level_images[index + 2];
-                a[3] = self.level_images[index + 3];
-                Pixel::R8G8B8A8_UNORM(a)
-            }
-            _ => panic!(
-                "Unsupported format for direct pixel read {:?}",
-                self.header.vk_format
-            ),
+            level_images: Vec::new(),
+        };
+        let byte_length = texture.level_byte_length(0);
+        texture.level_images.push(vec![0x00; byte_length as usize]);
+        texture
+    }
+
+    /// Size in bytes of the uncompressed image data of the given mip level,
+    /// covering all layers, faces and z slices.
+    pub fn level_byte_length(&self, level: u32) -> u64 {
+        self.checked_level_byte_length(level).expect("Level size overflows u64")
+    }
+
+    /// [`Self::level_byte_length`], or `None` when it overflows.
+    fn checked_level_byte_length(&self, level: u32) -> Option<u64> {
+        let format = self.header.vk_format;
+        let (block_width, block_height, block_depth) = get_format_block_extent(format);
+        let (width, height, depth) = self.level_extent(level);
+
+        (width.div_ceil(block_width) as u64)
+            .checked_mul(height.div_ceil(block_height) as u64)?
+            .checked_mul(depth.div_ceil(block_depth) as u64)?
+            .checked_mul(get_format_block_size_bytes(format) as u64)?
+            .checked_mul(self.header.layer_count.max(1) as u64)?
+            .checked_mul(self.header.face_count.max(1) as u64)
+    }
+
+    /// Width, height and depth in pixels of the given mip level.
+    pub fn level_extent(&self, level: u32) -> (u32, u32, u32) {
+        (
+            (self.header.pixel_width >> level).max(1),
+            (self.header.pixel_height >> level).max(1),
+            (self.header.pixel_depth >> level).max(1),
+        )
+    }
+
+    /// Number of `T` elements in one row of texel blocks of the given level.
+    fn level_row_length<T: Pod>(&self, level: u32) -> Result<usize, anyhow::Error> {
+        let format = self.header.vk_format;
+        let block_size = get_format_block_size_bytes(format) as usize;
+        if !block_size.is_multiple_of(size_of::<T>()) {
+            anyhow::bail!(
+                "Cannot view {:?} texel blocks of {} bytes as {}",
+                format,
+                block_size,
+                type_name::<T>()
+            );
+        }
+        if level as usize >= self.level_images.len() {
+            anyhow::bail!("Level {} does not exist", level);
         }
+        let (width, _, _) = self.level_extent(level);
+        let (block_width, _, _) = get_format_block_extent(format);
+        Ok(width.div_ceil(block_width) as usize * block_size / size_of::<T>())
     }
 
-    pub fn write_pixel(&mut self, x: u32, y: u32, pixel: Pixel) {
-        // TODO check format and Pixel format
-        let pixel_size = get_format_pixel_size_bytes(self.header.vk_format);
-        let index: usize = ((x as f32) * pixel_size
-            + (self.header.pixel_width as f32) * (y as f32) * pixel_size)
-            as usize;
-        let mut data = vec![];
-        match pixel {
-            Pixel::R16_SFLOAT(value) => {
-                data.write_u16::<LittleEndian>(f16::to_bits(value)).unwrap();
-                self.level_images[index] = data[0];
-                self.level_images[index + 1] = data[1];
-            }
-            Pixel::R16G16B16A16_SFLOAT(value) => {
-                data.write_u16::<LittleEndian>(f16::to_bits(value[0]))
-                    .unwrap();
-                data.write_u16::<LittleEndian>(f16::to_bits(value[1]))
-                    .unwrap();
-                data.write_u16::<LittleEndian>(f16::to_bits(value[2]))
-                    .unwrap();
-                data.write_u16::<LittleEndian>(f16::to_bits(value[3]))
-                    .unwrap();
-                self.level_images[index] = data[0];
-                self.level_images[index + 1] = data[1];
-                self.level_images[index + 2] = data[2];
-                self.level_images[index + 3] = data[3];
-            }
-            Pixel::R8G8B8A8_UNORM(data) => {
-                self.level_images[index] = data[0];
-                self.level_images[index + 1] = data[1];
-                self.level_images[index + 2] = data[2];
-                self.level_images[index + 3] = data[3];
+    /// Number of `T` elements in one 2D image (a z slice of one face of one
+    /// layer) of the given level.
+    fn level_plane_length<T: Pod>(&self, level: u32) -> Result<usize, anyhow::Error> {
+        let (_, height, _) = self.level_extent(level);
+        let (_, block_height, _) = get_format_block_extent(self.header.vk_format);
+        Ok(self.level_row_length::<T>(level)? * height.div_ceil(block_height) as usize)
+    }
+
+    /// Views the data of a mip level as a slice of `T` without copying, e.g.
+    /// `f16` for R16_SFLOAT or `[u8; 4]` for R8G8B8A8_UNORM.
+    ///
+    /// The size of `T` must divide the texel block size of the format. Fails
+    /// when the level does not exist or its data is not aligned for `T`.
+    pub fn level_as<T: Pod>(&self, level: u32) -> Result<&[T], anyhow::Error> {
+        self.level_row_length::<T>(level)?;
+        bytemuck::try_cast_slice(&self.level_images[level as usize]).map_err(|e| {
+            anyhow::anyhow!("Cannot view level {} as {}: {:?}", level, type_name::<T>(), e)
+        })
+    }
+
+    /// Mutable variant of [`TextureKtx2::level_as`].
+    pub fn level_as_mut<T: Pod>(&mut self, level: u32) -> Result<&mut [T], anyhow::Error> {
+        self.level_row_length::<T>(level)?;
+        bytemuck::try_cast_slice_mut(&mut self.level_images[level as usize]).map_err(|e| {
+            anyhow::anyhow!("Cannot view level {} as {}: {:?}", level, type_name::<T>(), e)
+        })
+    }
+
+    /// Iterates over the rows of texel blocks of a mip level, through all
+    /// z slices, faces and layers in storage order.
+    pub fn level_rows<T: Pod>(&self, level: u32) -> Result<ChunksExact<'_, T>, anyhow::Error> {
+        let row_length = self.level_row_length::<T>(level)?;
+        Ok(self.level_as::<T>(level)?.chunks_exact(row_length))
+    }
+
+    /// Mutable variant of [`TextureKtx2::level_rows`].
+    pub fn level_rows_mut<T: Pod>(
+        &mut self,
+        level: u32,
+    ) -> Result<ChunksExactMut<'_, T>, anyhow::Error> {
+        let row_length = self.level_row_length::<T>(level)?;
+        Ok(self.level_as_mut::<T>(level)?.chunks_exact_mut(row_length))
+    }
+
+    /// Iterates over the 2D images of a mip level: every z slice of every
+    /// face of every layer, in storage order.
+    pub fn level_planes<T: Pod>(&self, level: u32) -> Result<ChunksExact<'_, T>, anyhow::Error> {
+        let plane_length = self.level_plane_length::<T>(level)?;
+        Ok(self.level_as::<T>(level)?.chunks_exact(plane_length))
+    }
+
+    /// Mutable variant of [`TextureKtx2::level_planes`].
+    pub fn level_planes_mut<T: Pod>(
+        &mut self,
+        level: u32,
+    ) -> Result<ChunksExactMut<'_, T>, anyhow::Error> {
+        let plane_length = self.level_plane_length::<T>(level)?;
+        Ok(self.level_as_mut::<T>(level)?.chunks_exact_mut(plane_length))
+    }
+
+    /// Byte offset of pixel (x, y) of an uncompressed format within the base level.
+    fn pixel_offset(&self, x: u32, y: u32) -> usize {
+        let texel_size = get_format_block_size_bytes(self.header.vk_format) as usize;
+        (y as usize * self.header.pixel_width as usize + x as usize) * texel_size
+    }
+
+    /// Reads the pixel at (x, y) of the base level. Fails when the pixel is
+    /// outside the image or the format has no per-pixel representation.
+    pub fn read_pixel(&self, x: u32, y: u32) -> Result<Pixel, anyhow::Error> {
+        let format = self.header.vk_format;
+        self.check_pixel(x, y)?;
+        if get_format_layout(format).is_none() {
+            anyhow::bail!("Unsupported format for direct pixel read {:?}", format);
+        }
+        let index = self.pixel_offset(x, y);
+        match Pixel::from_bytes(format, &self.level_images[0][index..]) {
+            Some(pixel) => Ok(pixel),
+            None => anyhow::bail!("Unsupported format for direct pixel read {:?}", format),
+        }
+    }
+
+    /// Fails when (x, y) is outside the base level.
+    fn check_pixel(&self, x: u32, y: u32) -> Result<(), anyhow::Error> {
+        if x >= self.header.pixel_width || y >= self.header.pixel_height.max(1) {
+            anyhow::bail!(
+                "Pixel ({}, {}) is outside of the {}x{} texture",
+                x,
+                y,
+                self.header.pixel_width,
+                self.header.pixel_height
+            );
+        }
+        Ok(())
+    }
+
+    /// Writes `pixel` at (x, y) of the base level. Fails when the pixel is
+    /// outside the image or its format differs from the texture format.
+    pub fn write_pixel(&mut self, x: u32, y: u32, pixel: Pixel) -> Result<(), anyhow::Error> {
+        self.write_pixel_with_mode(x, y, pixel, WriteMode::Strict)
+    }
+
+    /// Writes `pixel` at (x, y) of the base level, converting it to the
+    /// texture format first when `mode` is [`WriteMode::Convert`].
+    pub fn write_pixel_with_mode(
+        &mut self,
+        x: u32,
+        y: u32,
+        pixel: Pixel,
+        mode: WriteMode,
+    ) -> Result<(), anyhow::Error> {
+        let format = self.header.vk_format;
+        self.check_pixel(x, y)?;
+        if get_format_layout(format).is_none() {
+            anyhow::bail!("Unsupported format for direct pixel write {:?}", format);
+        }
+        let pixel = if pixel.format() == format {
+            pixel
+        } else if mode == WriteMode::Convert && is_format_convertible(pixel.format(), format) {
+            match pixel.to_rgba_f32().and_then(|rgba| Pixel::from_rgba_f32(format, rgba)) {
+                Some(pixel) => pixel,
+                None => anyhow::bail!("Unsupported format for direct pixel write {:?}", format),
             }
-            _ => panic!(
-                "Unsupported format for direct pixel write {:?}",
-                self.header.vk_format
-            ),
+        } else {
+            anyhow::bail!(
+                "Pixel format {:?} does not match texture format {:?}",
+                pixel.format(),
+                format
+            );
+        };
+
+        let index = self.pixel_offset(x, y);
+        pixel.write_bytes(&mut self.level_images[0][index..]);
+        Ok(())
+    }
+
+    /// Alignment of each mip level in the file: lcm(texel block size, 4),
+    /// or 1 when the level data is supercompressed.
+    fn level_alignment(&self) -> u64 {
+        if self.header.supercompression_scheme != 0 {
+            return 1;
+        }
+        lcm(get_format_block_size_bytes(self.header.vk_format) as u64, 4)
+    }
+
+    fn dfd_total_size(&self) -> u32 {
+        // dfdTotalSize itself followed by every descriptor block
+        4 + self
+            .dfd_descriptor_block
+            .iter()
+            .map(|descriptor| descriptor.row_1 >> 16)
+            .sum::<u32>()
+    }
+
+    fn key_value_data_length(&self) -> u32 {
+        self.key_value_data
+            .iter()
+            .map(|(key, value)| 4 + align(key.len() as u64 + 1 + value.len() as u64, 4) as u32)
+            .sum()
+    }
+
+    /// Computes the byte offsets and lengths of every section of the file
+    /// from the current contents of the texture.
+    ///
+    /// Levels are stored from the smallest to the largest, each aligned to
+    /// [`TextureKtx2::level_alignment`]; empty KVD and SGD sections get a
+    /// zero offset.
+    pub(crate) fn layout(&self) -> Index {
+        let level_count = self.level_images.len() as u64;
+        let dfd_byte_offset = HEADER_SIZE + INDEX_SIZE + LEVEL_INDEX_ENTRY_SIZE * level_count;
+        let dfd_byte_length = self.dfd_total_size();
+        let mut offset = dfd_byte_offset + dfd_byte_length as u64;
+
+        let kvd_byte_length = self.key_value_data_length();
+        let kvd_byte_offset = if kvd_byte_length == 0 {
+            0
+        } else {
+            offset as u32
+        };
+        offset += kvd_byte_length as u64;
+
+        let sgd_byte_length = self.supercompression_global_data.len() as u64;
+        let sgd_byte_offset = if sgd_byte_length == 0 {
+            0
+        } else {
+            align(offset, 8)
+        };
+        if sgd_byte_length != 0 {
+            offset = sgd_byte_offset + sgd_byte_length;
+        }
+
+        let alignment = self.level_alignment();
+        let mut levels = vec![
+            Level {
+                byte_offset: 0,
+                byte_length: 0,
+                uncompressed_byte_length: 0,
+            };
+            self.level_images.len()
+        ];
+        for (level, image) in levels.iter_mut().zip(&self.level_images).rev() {
+            offset = align(offset, alignment);
+            level.byte_offset = offset;
+            level.byte_length = image.len() as u64;
+            level.uncompressed_byte_length = image.len() as u64;
+            offset += image.len() as u64;
+        }
+
+        Index {
+            dfd_byte_offset: dfd_byte_offset as u32,
+            dfd_byte_length,
+            kvd_byte_offset,
+            kvd_byte_length,
+            sgd_byte_offset,
+            sgd_byte_length,
+            levels,
         }
     }
 
-    pub fn write_to_ktx2(&mut self, file_name: &str) -> io::Result<()> {
+    pub fn write_to_ktx2(&self, file_name: &str) -> io::Result<()> {
         let mut buffer = File::create(file_name)?;
-        buffer.write_all(&self.header.identifier)?;
-
-        let mut header = vec![];
-        header
-            .write_u32::<LittleEndian>(self.header.vk_format as u32)
-            .unwrap();
-        header
-            .write_u32::<LittleEndian>(self.header.type_size)
-            .unwrap();
-        header
-            .write_u32::<LittleEndian>(self.header.pixel_width)
-            .unwrap();
-        header
-            .write_u32::<LittleEndian>(self.header.pixel_height)
-            .unwrap();
-        header
-            .write_u32::<LittleEndian>(self.header.pixel_depth)
-            .unwrap();
-        header
-            .write_u32::<LittleEndian>(self.header.layer_count)
-            .unwrap();
-        header
-            .write_u32::<LittleEndian>(self.header.face_count)
-            .unwrap();
-        header
-            .write_u32::<LittleEndian>(self.header.level_count)
-            .unwrap();
-        header
-            .write_u32::<LittleEndian>(self.header.supercompression_scheme)
-            .unwrap();
-        buffer.write_all(&header)?;
-
-        let mut index = vec![];
-        index
-            .write_u32::<LittleEndian>(self.index.dfd_byte_offset)
-            .unwrap();
-        index
-            .write_u32::<LittleEndian>(self.index.dfd_byte_length)
-            .unwrap();
-        index
-            .write_u32::<LittleEndian>(self.index.kvd_byte_offset)
-            .unwrap();
-        index
-            .write_u32::<LittleEndian>(self.index.kvd_byte_length)
-            .unwrap();
-        index
-            .write_u64::<LittleEndian>(self.index.sgd_byte_offset)
-            .unwrap();
-        index
-            .write_u64::<LittleEndian>(self.index.sgd_byte_length)
-            .unwrap();
-        buffer.write_all(&index)?;
-
-        let mut levels = vec![];
-        for level in &self.index.levels {
-            levels.write_u64::<LittleEndian>(level.byte_offset).unwrap();
-            levels.write_u64::<LittleEndian>(level.byte_length).unwrap();
-            levels
-                .write_u64::<LittleEndian>(level.uncompressed_byte_length)
-                .unwrap();
-        }
-        buffer.write_all(&levels)?;
+        self.write_to(&mut buffer)
+    }
 
+    pub fn write_to<W: Write>(&self, buffer: &mut W) -> io::Result<()> {
+        let index = self.layout();
+
+        let mut out = vec![];
+        out.write_all(&self.header.identifier)?;
+        out.write_u32::<LittleEndian>(self.header.vk_format as u32)?;
+        out.write_u32::<LittleEndian>(self.header.type_size)?;
+        out.write_u32::<LittleEndian>(self.header.pixel_width)?;
+        out.write_u32::<LittleEndian>(self.header.pixel_height)?;
+        out.write_u32::<LittleEndian>(self.header.pixel_depth)?;
+        out.write_u32::<LittleEndian>(self.header.layer_count)?;
+        out.write_u32::<LittleEndian>(self.header.face_count)?;
+        out.write_u32::<LittleEndian>(self.header.level_count)?;
+        out.write_u32::<LittleEndian>(self.header.supercompression_scheme)?;
+
+        out.write_u32::<LittleEndian>(index.dfd_byte_offset)?;
+        out.write_u32::<LittleEndian>(index.dfd_byte_length)?;
+        out.write_u32::<LittleEndian>(index.kvd_byte_offset)?;
+        out.write_u32::<LittleEndian>(index.kvd_byte_length)?;
+        out.write_u64::<LittleEndian>(index.sgd_byte_offset)?;
+        out.write_u64::<LittleEndian>(index.sgd_byte_length)?;
+
+        for level in &index.levels {
+            out.write_u64::<LittleEndian>(level.byte_offset)?;
+            out.write_u64::<LittleEndian>(level.byte_length)?;
+            out.write_u64::<LittleEndian>(level.uncompressed_byte_length)?;
+        }
+
+        out.write_u32::<LittleEndian>(index.dfd_byte_length)?;
         for descriptor in &self.dfd_descriptor_block {
-            let mut dfd = vec![];
-            dfd.write_u32::<LittleEndian>(descriptor.dfd_total_size)
-                .unwrap();
-            dfd.write_u32::<LittleEndian>(descriptor.row_0).unwrap();
-            dfd.write_u32::<LittleEndian>(descriptor.row_1).unwrap();
-            dfd.write_u32::<LittleEndian>(descriptor.row_2).unwrap();
-            dfd.write_u32::<LittleEndian>(descriptor.row_3).unwrap();
-            dfd.write_u32::<LittleEndian>(descriptor.row_4).unwrap();
-            dfd.write_u32::<LittleEndian>(descriptor.row_5).unwrap();
-            buffer.write_all(&dfd)?;
+            out.write_u32::<LittleEndian>(descriptor.row_0)?;
+            out.write_u32::<LittleEndian>(descriptor.row_1)?;
+            out.write_u32::<LittleEndian>(descriptor.row_2)?;
+            out.write_u32::<LittleEndian>(descriptor.row_3)?;
+            out.write_u32::<LittleEndian>(descriptor.row_4)?;
+            out.write_u32::<LittleEndian>(descriptor.row_5)?;
             for sample in &descriptor.samples {
-                let mut spl = vec![];
-                spl.write_u32::<LittleEndian>(sample.row_0).unwrap();
-                spl.write_u32::<LittleEndian>(sample.row_1).unwrap();
-                spl.write_u32::<LittleEndian>(sample.row_2).unwrap();
-                spl.write_u32::<LittleEndian>(sample.row_3).unwrap();
-                buffer.write_all(&spl)?;
+                out.write_u32::<LittleEndian>(sample.row_0)?;
+                out.write_u32::<LittleEndian>(sample.row_1)?;
+                out.write_u32::<LittleEndian>(sample.row_2)?;
+                out.write_u32::<LittleEndian>(sample.row_3)?;
             }
         }
-        buffer.write_all(&self.key_value_data)?;
 
-        buffer.write_all(&self.supercompression_global_data)?;
-        buffer.write_all(&self.level_images)?;
-        Ok(())
+        for (key, value) in &self.key_value_data {
+            let key_and_value_byte_length = key.len() + 1 + value.len();
+            out.write_u32::<LittleEndian>(key_and_value_byte_length as u32)?;
+            out.write_all(key.as_bytes())?;
+            out.write_u8(0)?;
+            out.write_all(value)?;
+            let padded = align(out.len() as u64, 4);
+            pad_to(&mut out, padded);
+        }
+
+        if index.sgd_byte_length != 0 {
+            pad_to(&mut out, index.sgd_byte_offset);
+            out.write_all(&self.supercompression_global_data)?;
+        }
+
+        for (level, image) in index.levels.iter().zip(&self.level_images).rev() {
+            pad_to(&mut out, level.byte_offset);
+            out.write_all(image)?;
+        }
+
+        buffer.write_all(&out)
     }
 
     pub fn read_from_ktx2(file_name: &str) -> Result<TextureKtx2, anyhow::Error> {
@@ -288,128 +460,119 @@ impl TextureKtx2 {
 
         let mut buffer: Vec<u8> = vec![];
         file.read_to_end(&mut buffer)?;
+        TextureKtx2::read_from_bytes(&buffer)
+    }
 
-        let mut vk_format_rdr = Cursor::new(&buffer[12..16]);
-        let vk_format: VkFormat =
-            unsafe { std::mem::transmute(vk_format_rdr.read_u32::<LittleEndian>().unwrap()) };
-        // println!("vkFormat: {:?}", vkFormat);
-        let mut type_size_rdr = Cursor::new(&buffer[16..20]);
-        let type_size: u32 = type_size_rdr.read_u32::<LittleEndian>()?;
-        // println!("typeSize: {:?}", typeSize);
-        let mut pixel_width_rdr = Cursor::new(&buffer[20..24]);
-        let pixel_width: u32 = pixel_width_rdr.read_u32::<LittleEndian>()?;
-        // println!("pixelWidth: {:?}", pixelWidth);
-        let mut pixel_height_rdr = Cursor::new(&buffer[24..28]);
-        let pixel_height: u32 = pixel_height_rdr.read_u32::<LittleEndian>()?;
-        // println!("pixelHeight: {:?}", pixelHeight);
-        let mut pixel_depth_rdr = Cursor::new(&buffer[28..32]);
-        let pixel_depth: u32 = pixel_depth_rdr.read_u32::<LittleEndian>()?;
-        // println!("pixelDepth: {:?}", pixelDepth);
-        let mut layer_count_rdr = Cursor::new(&buffer[32..36]);
-        let layer_count: u32 = layer_count_rdr.read_u32::<LittleEndian>()?;
-        // println!("layerCount: {:?}", layerCount);
-        let mut face_count_rdr = Cursor::new(&buffer[36..40]);
-        let face_count: u32 = face_count_rdr.read_u32::<LittleEndian>()?;
-        // println!("faceCount: {:?}", faceCount);
-        let mut level_count_rdr = Cursor::new(&buffer[40..44]);
-        let level_count: u32 = level_count_rdr.read_u32::<LittleEndian>()?;
-        // println!("levelCount: {:?}", levelCount);
-        let mut supercompression_scheme_rdr = Cursor::new(&buffer[44..48]);
-        let supercompression_scheme: u32 =
-            supercompression_scheme_rdr.read_u32::<LittleEndian>()?;
-        // println!("supercompressionScheme: {:?}", supercompressionScheme);
-        let mut dfd_byte_offset_rdr = Cursor::new(&buffer[48..52]);
-        let dfd_byte_offset: u32 = dfd_byte_offset_rdr.read_u32::<LittleEndian>()?;
-        // println!("dfdByteOffset: {:?}", dfdByteOffset);
-        let mut dfd_byte_length_rdr = Cursor::new(&buffer[52..56]);
-        let dfd_byte_length: u32 = dfd_byte_length_rdr.read_u32::<LittleEndian>()?;
-        // println!("dfdByteLength: {:?}", dfdByteLength);
-        let mut kvd_byte_offset_rdr = Cursor::new(&buffer[56..60]);
-        let kvd_byte_offset: u32 = kvd_byte_offset_rdr.read_u32::<LittleEndian>()?;
-        // println!("kvdByteOffset: {:?}", kvdByteOffset);
-        let mut kvd_byte_length_rdr = Cursor::new(&buffer[60..64]);
-        let kvd_byte_length: u32 = kvd_byte_length_rdr.read_u32::<LittleEndian>()?;
-        // println!("kvdByteLength: {:?}", kvdByteLength);
-        let mut sgd_byte_offset_rdr = Cursor::new(&buffer[64..72]);
-        let sgd_byte_offset: u64 = sgd_byte_offset_rdr.read_u64::<LittleEndian>()?;
-        // println!("sgdByteOffset: {:?}", sgdByteOffset);
-        let mut sgd_byte_length_rdr = Cursor::new(&buffer[72..80]);
-        let sgd_byte_length: u64 = sgd_byte_length_rdr.read_u64::<LittleEndian>()?;
-        // println!("sgdByteLength: {:?}", sgdByteLength);
-
-        // read level info
-        let mut levels: Vec<Level> = vec![];
-        for l in 0..level_count {
-            // println!("Level {:?}", l);
-            let mut byte_offset_rdr =
-                Cursor::new(&buffer[80 + l as usize * 8..88 + l as usize * 8]);
-            let mut byte_length_rdr =
-                Cursor::new(&buffer[88 + l as usize * 8..96 + l as usize * 8]);
-            let mut uncompressed_byte_length_rdr =
-                Cursor::new(&buffer[96 + l as usize * 8..104 + l as usize * 8]);
-
-            let byte_offset: u64 = byte_offset_rdr.read_u64::<LittleEndian>()?;
-            let byte_length: u64 = byte_length_rdr.read_u64::<LittleEndian>()?;
-            let uncompressed_byte_length: u64 =
-                uncompressed_byte_length_rdr.read_u64::<LittleEndian>()?;
-
-            // println!("\tbyte_offset: {:?}", byte_offset);
-            // println!("\tbyte_length: {:?}", _byte_length);
-            // println!("\tuncompressed_byte_length: {:?}", uncompressed_byte_length);
+    pub fn read_from_bytes(buffer: &[u8]) -> Result<TextureKtx2, anyhow::Error> {
+        let mut rdr = Cursor::new(buffer);
 
+        let mut identifier = [0u8; 12];
+        rdr.read_exact(&mut identifier)?;
+        if identifier != KTX2_IDENTIFIER {
+            anyhow::bail!("Not a KTX2 file");
+        }
+        let vk_format = VkFormat::try_from(rdr.read_u32::<LittleEndian>()?)?;
+        if !has_format_block(vk_format) {
+            anyhow::bail!("Unsupported vkFormat {:?}", vk_format);
+        }
+        let type_size = rdr.read_u32::<LittleEndian>()?;
+        let pixel_width = rdr.read_u32::<LittleEndian>()?;
+        let pixel_height = rdr.read_u32::<LittleEndian>()?;
+        let pixel_depth = rdr.read_u32::<LittleEndian>()?;
+        let layer_count = rdr.read_u32::<LittleEndian>()?;
+        let face_count = rdr.read_u32::<LittleEndian>()?;
+        let level_count = rdr.read_u32::<LittleEndian>()?;
+        let supercompression_scheme = rdr.read_u32::<LittleEndian>()?;
+        if supercompression_scheme != 0 {
+            anyhow::bail!(
+                "Unsupported supercompression scheme {}",
+                supercompression_scheme
+            );
+        }
+        let max_levels = pixel_width.max(pixel_height).max(pixel_depth).max(1).ilog2() + 1;
+        if level_count > max_levels {
+            anyhow::bail!(
+                "Invalid levelCount {} for {}x{}x{}",
+                level_count,
+                pixel_width,
+                pixel_height,
+                pixel_depth
+            );
+        }
+
+        let dfd_byte_offset = rdr.read_u32::<LittleEndian>()?;
+        let dfd_byte_length = rdr.read_u32::<LittleEndian>()?;
+        let kvd_byte_offset = rdr.read_u32::<LittleEndian>()?;
+        let kvd_byte_length = rdr.read_u32::<LittleEndian>()?;
+        let sgd_byte_offset = rdr.read_u64::<LittleEndian>()?;
+        let sgd_byte_length = rdr.read_u64::<LittleEndian>()?;
+
+        // read level info, a file with levelCount 0 still stores the base level
+        let mut levels: Vec<Level> = vec![];
+        for _ in 0..level_count.max(1) {
             levels.push(Level {
-                byte_offset,
-                byte_length,
-                uncompressed_byte_length,
+                byte_offset: rdr.read_u64::<LittleEndian>()?,
+                byte_length: rdr.read_u64::<LittleEndian>()?,
+                uncompressed_byte_length: rdr.read_u64::<LittleEndian>()?,
             });
         }
 
-        // read DFD (assuming only 1)
-        let mut dfd_total_size_rdr =
-            Cursor::new(&buffer[dfd_byte_offset as usize..dfd_byte_offset as usize + 4]);
-        let mut dfd_rdr0 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 4..dfd_byte_offset as usize + 8]);
-        let mut dfd_rdr1 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 8..dfd_byte_offset as usize + 12]);
-        let mut dfd_rdr2 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 12..dfd_byte_offset as usize + 16]);
-        let mut dfd_rdr3 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 16..dfd_byte_offset as usize + 20]);
-        let mut dfd_rdr4 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 20..dfd_byte_offset as usize + 24]);
-        let mut dfd_rdr5 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 24..dfd_byte_offset as usize + 28]);
-        let mut dfd = BasicDataFormatDescriptor {
-            dfd_total_size: dfd_total_size_rdr.read_u32::<LittleEndian>().unwrap(),
-            row_0: dfd_rdr0.read_u32::<LittleEndian>().unwrap(),
-            row_1: dfd_rdr1.read_u32::<LittleEndian>().unwrap(),
-            row_2: dfd_rdr2.read_u32::<LittleEndian>().unwrap(),
-            row_3: dfd_rdr3.read_u32::<LittleEndian>().unwrap(),
-            row_4: dfd_rdr4.read_u32::<LittleEndian>().unwrap(),
-            row_5: dfd_rdr5.read_u32::<LittleEndian>().unwrap(),
-            samples: vec![],
-        };
-        // read DFDSampleType (assuming 1)
-        let mut dfd_sample_type_rdr0 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 28..dfd_byte_offset as usize + 32]);
-        let mut dfd_sample_type_rdr1 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 32..dfd_byte_offset as usize + 36]);
-        let mut dfd_sample_type_rdr2 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 36..dfd_byte_offset as usize + 40]);
-        let mut dfd_sample_type_rdr3 =
-            Cursor::new(&buffer[dfd_byte_offset as usize + 40..dfd_byte_offset as usize + 44]);
-        let sample = DFDSampleType {
-            row_0: dfd_sample_type_rdr0.read_u32::<LittleEndian>().unwrap(),
-            row_1: dfd_sample_type_rdr1.read_u32::<LittleEndian>().unwrap(),
-            row_2: dfd_sample_type_rdr2.read_u32::<LittleEndian>().unwrap(),
-            row_3: dfd_sample_type_rdr3.read_u32::<LittleEndian>().unwrap(),
-        };
-        dfd.samples.push(sample);
-        Ok(TextureKtx2 {
+        // read DFD, a sequence of descriptor blocks following dfdTotalSize
+        let dfd = section(buffer, dfd_byte_offset as u64, dfd_byte_length as u64)?;
+        let mut rdr = Cursor::new(dfd);
+        let dfd_total_size = rdr.read_u32::<LittleEndian>()?;
+        let mut dfd_descriptor_block = vec![];
+        while rdr.position() < dfd.len() as u64 {
+            let mut descriptor = BasicDataFormatDescriptor {
+                dfd_total_size,
+                row_0: rdr.read_u32::<LittleEndian>()?,
+                row_1: rdr.read_u32::<LittleEndian>()?,
+                row_2: rdr.read_u32::<LittleEndian>()?,
+                row_3: rdr.read_u32::<LittleEndian>()?,
+                row_4: rdr.read_u32::<LittleEndian>()?,
+                row_5: rdr.read_u32::<LittleEndian>()?,
+                samples: vec![],
+            };
+            let descriptor_block_size = descriptor.row_1 >> 16;
+            if descriptor_block_size < 24 {
+                anyhow::bail!("Invalid descriptor block size {}", descriptor_block_size);
+            }
+            for _ in 0..(descriptor_block_size - 24) / 16 {
+                descriptor.samples.push(DFDSampleType {
+                    row_0: rdr.read_u32::<LittleEndian>()?,
+                    row_1: rdr.read_u32::<LittleEndian>()?,
+                    row_2: rdr.read_u32::<LittleEndian>()?,
+                    row_3: rdr.read_u32::<LittleEndian>()?,
+                });
+            }
+            dfd_descriptor_block.push(descriptor);
+        }
+        if dfd_descriptor_block.is_empty() {
+            anyhow::bail!("Missing data format descriptor block");
+        }
+
+        // read KVD, each entry is padded to 4 bytes
+        let kvd = section(buffer, kvd_byte_offset as u64, kvd_byte_length as u64)?;
+        let mut rdr = Cursor::new(kvd);
+        let mut key_value_data = BTreeMap::new();
+        while rdr.position() < kvd.len() as u64 {
+            let key_and_value_byte_length = rdr.read_u32::<LittleEndian>()? as u64;
+            let start = rdr.position();
+            let entry = section(kvd, start, key_and_value_byte_length)?;
+            let Some(nul) = entry.iter().position(|&b| b == 0) else {
+                anyhow::bail!("Key/value entry without NUL terminated key");
+            };
+            let key = String::from_utf8(entry[..nul].to_vec())?;
+            key_value_data.insert(key, entry[nul + 1..].to_vec());
+            rdr.set_position(align(start + key_and_value_byte_length, 4));
+        }
+
+        let supercompression_global_data =
+            section(buffer, sgd_byte_offset, sgd_byte_length)?.to_vec();
+
+        let mut texture = TextureKtx2 {
             header: Header {
-                identifier: [
-                    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
-                ],
+                identifier,
                 vk_format,
                 type_size,
                 pixel_width,
@@ -421,166 +584,757 @@ impl TextureKtx2 {
                 supercompression_scheme,
             },
 
-            dfd_descriptor_block: vec![dfd],
-            key_value_data: [
-                0x12, 0x00, 0x00, 0x00, // 18 bytes for first entry
-                0x4B, 0x54, 0x58, 0x6F, // KTXo
-                0x72, 0x69, 0x65, 0x6E, // rien
-                0x74, 0x61, 0x74, 0x69, // tati
-                0x6F, 0x6E, 0x00, 0x72, // on NUL r
-                0x64, 0x00, 0x00, 0x00, // d  <3 bytes of valuePadding>
-                0x18, 0x00, 0x00, 0x00, // 24 bytes for second entry
-                0x4B, 0x54, 0x58, 0x77, // KTXw
-                0x72, 0x69, 0x74, 0x65, // rite
-                0x72, 0x00, 0x53, 0x65, // r NULL Se
-                0x6e, 0x69, 0x6f, 0x72, // nior
-                0x53, 0x4b, 0x59, 0x5f, // SKY_
-                0x64, 0x74, 0x32, 0x00, // dt2 NULL
-            ],
-            supercompression_global_data: vec![0u8; 0],
-            level_images: buffer[levels[0].byte_offset as usize..].to_vec(),
-            index: Index {
-                dfd_byte_offset,
-                dfd_byte_length,
-                kvd_byte_offset,
-                kvd_byte_length,
-                sgd_byte_offset,
-                sgd_byte_length,
-                levels,
-            },
-        })
+            dfd_descriptor_block,
+            key_value_data,
+            supercompression_global_data,
+            level_images: vec![],
+        };
+        for (index, level) in levels.iter().enumerate() {
+            let expected = texture.checked_level_byte_length(index as u32);
+            if expected != Some(level.byte_length) {
+                anyhow::bail!("Invalid byteLength {} of level {}", level.byte_length, index);
+            }
+            let image = section(buffer, level.byte_offset, level.byte_length)?;
+            texture.level_images.push(image.to_vec());
+        }
+        Ok(texture)
     }
 
-    pub fn vertical_sample(
-        image: TextureKtx2,
-        new_height: u32,
-        filter: &mut Filter,
-    ) -> TextureKtx2 {
-        let width = image.header.pixel_width;
-        let height = image.header.pixel_height;
-        let mut out: TextureKtx2 = TextureKtx2::new(width, new_height, image.header.vk_format);
-        let mut ws: Vec<f32> = Vec::new();
-
-        let max: f32 = half::f16::to_f32(half::f16::MAX);
-        let ratio = height as f32 / new_height as f32;
-        let sratio = if ratio < 1.0 { 1.0 } else { ratio };
-        let src_support = filter.support * sratio;
-
-        for outy in 0..new_height {
-            // For an explanation of this algorithm, see the comments
-            // in horizontal_sample.
-            let inputy = (outy as f32 + 0.5) * ratio;
-
-            let left = (inputy - src_support).floor() as i64;
-            let left = clamp(left, 0, <i64 as From<_>>::from(height) - 1) as u32;
-
-            let right = (inputy + src_support).ceil() as i64;
-            let right = clamp(
-                right,
-                <i64 as From<_>>::from(left) + 1,
-                <i64 as From<_>>::from(height),
-            ) as u32;
-
-            let inputy = inputy - 0.5;
-
-            ws.clear();
-            let mut sum = 0.0;
-            for i in left..right {
-                let w = (filter.kernel)((i as f32 - inputy) / sratio);
-                ws.push(w);
-                sum += w;
-            }
+    /// Decodes every 2D image (z slice, face and layer, in storage order) of
+    /// the given level to RGBA f32. Panics for block compressed formats.
+    pub(crate) fn decode_planes(&self, level: u32) -> Vec<Image> {
+        let format = self.header.vk_format;
+        let Some(layout) = get_format_layout(format) else {
+            panic!("Unsupported format for resampling {:?}", format);
+        };
+        let (width, height, _) = self.level_extent(level);
+        let texel_size = get_format_block_size_bytes(format) as usize;
+        let row_size = texel_size * width as usize;
+        self.level_images[level as usize]
+            .chunks_exact(row_size * height as usize)
+            .map(|data| {
+                let mut pixels = vec![[0.0; 4]; width as usize * height as usize];
+                resample::for_each_row(&mut pixels, width as usize, |y, row| {
+                    let texels = data[y * row_size..(y + 1) * row_size].chunks_exact(texel_size);
+                    for (rgba, texel) in row.iter_mut().zip(texels) {
+                        *rgba = decode_texel(format, &layout, texel);
+                    }
+                });
+                Image {
+                    width,
+                    height,
+                    pixels,
+                }
+            })
+            .collect()
+    }
+
+    /// Encodes `planes` as the data of one level, rounded and clamped to the
+    /// format.
+    fn encode_planes(&self, planes: &[Image]) -> Vec<u8> {
+        let format = self.header.vk_format;
+        let layout = get_format_layout(format).unwrap();
+        let texel_size = get_format_block_size_bytes(format) as usize;
+        let plane_size = planes[0].pixels.len() * texel_size;
+        let row_size = planes[0].width as usize * texel_size;
 
-            for x in 0..width {
-                let mut t = 0.0f32;
-
-                for (i, w) in ws.iter().enumerate() {
-                    let pixel = image.read_pixel(x, left + i as u32);
-                    let p = match pixel {
-                        Pixel::R16_SFLOAT(p) => half::f16::to_f32(p),
-                        _ => panic!("Unsupported format"),
-                    };
-                    let k1 = p;
-                    t += k1 * w;
+        let mut data = vec![0u8; planes.len() * plane_size];
+        for (plane, data) in planes.iter().zip(data.chunks_exact_mut(plane_size)) {
+            resample::for_each_row(data, row_size, |y, row| {
+                let width = plane.width as usize;
+                let pixels = &plane.pixels[y * width..(y + 1) * width];
+                for (texel, rgba) in row.chunks_exact_mut(texel_size).zip(pixels) {
+                    encode_texel(format, &layout, *rgba, texel);
                 }
+            });
+        }
+        data
+    }
 
-                let t1 = t / sum;
-                let t = half::f16::from_f32(clamp(t1, 0.0, max));
+    /// Creates a single level texture with the format, DFD and metadata of
+    /// `self` holding `image`, rounded and clamped to the format.
+    pub(crate) fn encode_image(&self, image: &Image) -> TextureKtx2 {
+        let mut out = TextureKtx2::new(image.width, image.height, self.header.vk_format);
+        out.dfd_descriptor_block = self.dfd_descriptor_block.clone();
+        out.key_value_data = self.key_value_data.clone();
+        out.level_images[0] = self.encode_planes(std::slice::from_ref(image));
+        out
+    }
 
-                out.write_pixel(x, outy, Pixel::R16_SFLOAT(t));
+    /// An all zero texture of `format` with the dimensions, layers, faces,
+    /// levels and metadata of `self`.
+    fn with_format(&self, format: VkFormat) -> TextureKtx2 {
+        let mut out = TextureKtx2::new(self.header.pixel_width, self.header.pixel_height, format);
+        out.header.pixel_depth = self.header.pixel_depth;
+        out.header.layer_count = self.header.layer_count;
+        out.header.face_count = self.header.face_count;
+        out.header.level_count = self.header.level_count;
+        out.key_value_data = self.key_value_data.clone();
+        out.level_images = (0..self.level_images.len() as u32)
+            .map(|level| vec![0; out.level_byte_length(level) as usize])
+            .collect();
+        out
+    }
+
+    /// Compresses every level, layer and face to the block compressed
+    /// `format`. Color channels are converted when the source and `format`
+    /// differ in sRGB encoding. Partial blocks at the edges repeat the last
+    /// row and column.
+    pub fn compress(
+        &self,
+        format: VkFormat,
+        options: &CompressOptions,
+    ) -> Result<TextureKtx2, anyhow::Error> {
+        if get_format_layout(self.header.vk_format).is_none() {
+            anyhow::bail!("Cannot compress from {:?}", self.header.vk_format);
+        }
+        let quality = options.quality;
+        let signed = is_snorm_block(format);
+        let channel = |block: &Block, c: usize| block.map(|texel| texel[c]);
+        match format {
+            VkFormat::BC1_RGB_UNORM_BLOCK | VkFormat::BC1_RGB_SRGB_BLOCK => {
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    out.copy_from_slice(&bc1::encode_block(block, quality, None))
+                }))
+            }
+            VkFormat::BC1_RGBA_UNORM_BLOCK | VkFormat::BC1_RGBA_SRGB_BLOCK => {
+                let threshold = Some(options.alpha_threshold);
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    out.copy_from_slice(&bc1::encode_block(block, quality, threshold))
+                }))
+            }
+            VkFormat::BC4_UNORM_BLOCK | VkFormat::BC4_SNORM_BLOCK => {
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    out.copy_from_slice(&bc4::encode_block(&channel(block, 0), signed, quality))
+                }))
+            }
+            VkFormat::BC5_UNORM_BLOCK | VkFormat::BC5_SNORM_BLOCK => {
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    for (c, out) in out.chunks_exact_mut(8).enumerate() {
+                        let values = channel(block, c);
+                        out.copy_from_slice(&bc4::encode_block(&values, signed, quality));
+                    }
+                }))
+            }
+            VkFormat::BC6H_UFLOAT_BLOCK | VkFormat::BC6H_SFLOAT_BLOCK => {
+                let signed = format == VkFormat::BC6H_SFLOAT_BLOCK;
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    out.copy_from_slice(&bc6h::encode_block(block, signed, quality))
+                }))
+            }
+            VkFormat::BC7_UNORM_BLOCK | VkFormat::BC7_SRGB_BLOCK => {
+                let perceptual = options.perceptual;
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    out.copy_from_slice(&bc7::encode_block(block, quality, perceptual))
+                }))
+            }
+            VkFormat::ETC2_R8G8B8_UNORM_BLOCK | VkFormat::ETC2_R8G8B8_SRGB_BLOCK => {
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    out.copy_from_slice(&etc2::encode_block(block, quality, None))
+                }))
+            }
+            VkFormat::ETC2_R8G8B8A1_UNORM_BLOCK | VkFormat::ETC2_R8G8B8A1_SRGB_BLOCK => {
+                let threshold = Some(options.alpha_threshold);
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    out.copy_from_slice(&etc2::encode_block(block, quality, threshold))
+                }))
+            }
+            VkFormat::ETC2_R8G8B8A8_UNORM_BLOCK | VkFormat::ETC2_R8G8B8A8_SRGB_BLOCK => {
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    let alpha = channel(block, 3);
+                    out[..8].copy_from_slice(&eac::encode_block(&alpha, Variant::Alpha, quality));
+                    out[8..].copy_from_slice(&etc2::encode_block(block, quality, None));
+                }))
+            }
+            VkFormat::EAC_R11_UNORM_BLOCK
+            | VkFormat::EAC_R11_SNORM_BLOCK
+            | VkFormat::EAC_R11G11_UNORM_BLOCK
+            | VkFormat::EAC_R11G11_SNORM_BLOCK => {
+                let variant = Variant::R11 { signed };
+                Ok(self.compress_blocks(format, options, |block, out| {
+                    for (c, out) in out.chunks_exact_mut(8).enumerate() {
+                        let values = channel(block, c);
+                        out.copy_from_slice(&eac::encode_block(&values, variant, quality));
+                    }
+                }))
+            }
+            _ if astc::is_astc_2d(format) => {
+                let encoder = astc::Encoder::new(format, self.astc_decode_mode(format)?, quality);
+                Ok(self.compress_texels(format, options, |texels, out| {
+                    out.copy_from_slice(&encoder.encode_block(texels))
+                }))
+            }
+            _ => anyhow::bail!("Unsupported compression format {:?}", format),
+        }
+    }
+
+    /// Runs `encode` on every 4x4 block of every level, layer and face.
+    fn compress_blocks<F>(
+        &self,
+        format: VkFormat,
+        options: &CompressOptions,
+        encode: F,
+    ) -> TextureKtx2
+    where
+        F: Fn(&Block, &mut [u8]) + Send + Sync,
+    {
+        self.compress_texels(format, options, |texels, out| {
+            encode(texels.try_into().unwrap(), out)
+        })
+    }
+
+    /// Runs `encode` on the texels, in row-major order, of every block of
+    /// every level, layer and face.
+    fn compress_texels<F>(
+        &self,
+        format: VkFormat,
+        options: &CompressOptions,
+        encode: F,
+    ) -> TextureKtx2
+    where
+        F: Fn(&[[f32; 4]], &mut [u8]) + Send + Sync,
+    {
+        let mut out = self.with_format(format);
+        let srgb = self.dfd_descriptor_block[0].is_srgb();
+        let block_size = get_format_block_size_bytes(format) as usize;
+        let (block_width, block_height, _) = get_format_block_extent(format);
+        let signed = is_snorm_block(format);
+
+        for (level, data) in out.level_images.iter_mut().enumerate() {
+            let mut planes = self.decode_planes(level as u32);
+            convert_transfer(&mut planes, srgb, is_format_srgb(format));
+            if let Some((min, max)) = options.value_range {
+                for pixel in planes.iter_mut().flat_map(|plane| &mut plane.pixels) {
+                    for value in &mut pixel[..3] {
+                        let t = (*value - min) / (max - min);
+                        *value = if signed { t * 2.0 - 1.0 } else { t };
+                    }
+                }
+            }
+
+            let (width, height, _) = self.level_extent(level as u32);
+            let row_size = width.div_ceil(block_width) as usize * block_size;
+            let plane_size = row_size * height.div_ceil(block_height) as usize;
+            for (plane, data) in planes.iter().zip(data.chunks_exact_mut(plane_size)) {
+                resample::for_each_row(data, row_size, |by, row| {
+                    let mut texels = vec![[0.0; 4]; (block_width * block_height) as usize];
+                    for (bx, out) in row.chunks_exact_mut(block_size).enumerate() {
+                        for (i, texel) in texels.iter_mut().enumerate() {
+                            let (tx, ty) = (i as u32 % block_width, i as u32 / block_width);
+                            let x = (bx as u32 * block_width + tx).min(width - 1);
+                            let y = (by as u32 * block_height + ty).min(height - 1);
+                            *texel = plane.get(x, y);
+                        }
+                        encode(&texels, out);
+                    }
+                });
             }
         }
         out
     }
 
-    pub fn horizontal_sample(
-        image: &mut TextureKtx2,
-        new_width: u32,
-        filter: &mut Filter,
-    ) -> TextureKtx2 {
-        let width = image.header.pixel_width;
-        let height = image.header.pixel_height;
-        let mut out: TextureKtx2 = TextureKtx2::new(new_width, height, image.header.vk_format);
-        let mut ws: Vec<f32> = Vec::new();
-
-        let max: f32 = half::f16::to_f32(half::f16::MAX);
-        let ratio = width as f32 / new_width as f32;
-        let sratio = if ratio < 1.0 { 1.0 } else { ratio };
-        let src_support = filter.support * sratio;
-
-        for outx in 0..new_width {
-            // Find the point in the input image corresponding to the centre
-            // of the current pixel in the output image.
-            let inputx = (outx as f32 + 0.5) * ratio;
-
-            // Left and right are slice bounds for the input pixels relevant
-            // to the output pixel we are calculating.  Pixel x is relevant
-            // if and only if (x >= left) && (x < right).
-
-            // Invariant: 0 <= left < right <= width
-
-            let left = (inputx - src_support).floor() as i64;
-            let left = clamp(left, 0, <i64 as From<_>>::from(width) - 1) as u32;
-
-            let right = (inputx + src_support).ceil() as i64;
-            let right = clamp(
-                right,
-                <i64 as From<_>>::from(left) + 1,
-                <i64 as From<_>>::from(width),
-            ) as u32;
-
-            // Go back to left boundary of pixel, to properly compare with i
-            // below, as the kernel treats the centre of a pixel as 0.
-            let inputx = inputx - 0.5;
-
-            ws.clear();
-            let mut sum = 0.0;
-            for i in left..right {
-                let w = (filter.kernel)((i as f32 - inputx) / sratio);
-                ws.push(w);
-                sum += w;
+    /// Decodes every level, layer and face of a block compressed texture to
+    /// the uncompressed `format`, such as `R8G8B8A8_UNORM` or
+    /// `R32G32B32A32_SFLOAT`. Color channels are converted when the source
+    /// and `format` differ in sRGB encoding.
+    pub fn decompress(&self, format: VkFormat) -> Result<TextureKtx2, anyhow::Error> {
+        if get_format_layout(format).is_none() {
+            anyhow::bail!("Cannot decompress to {:?}", format);
+        }
+        let mut out = self.with_format(format);
+        let srgb = self.dfd_descriptor_block[0].is_srgb();
+        for level in 0..self.level_images.len() {
+            let mut planes = self.decompress_planes(level as u32)?;
+            convert_transfer(&mut planes, srgb, is_format_srgb(format));
+            out.level_images[level] = out.encode_planes(&planes);
+        }
+        Ok(out)
+    }
+
+    /// Decodes every 2D image of the given level of a block compressed
+    /// texture, like [`Self::decode_planes`] does for uncompressed formats.
+    fn decompress_planes(&self, level: u32) -> Result<Vec<Image>, anyhow::Error> {
+        let source = self.header.vk_format;
+        let (block_width, block_height, _) = get_format_block_extent(source);
+        type Decoder = Box<dyn Fn(&[u8], &mut [[f32; 4]]) + Send + Sync>;
+        let decode: Decoder = if let Some(decode) = bc::block_decoder(source) {
+            Box::new(move |data, texels| texels.copy_from_slice(&decode(data)))
+        } else if astc::is_astc_2d(source) {
+            let profile = astc::Profile::new(source, self.astc_decode_mode(source)?);
+            Box::new(move |data, texels| {
+                astc::decode_block(data, block_width, block_height, profile, texels)
+            })
+        } else {
+            anyhow::bail!("Unsupported compressed format {:?}", source);
+        };
+        let block_size = get_format_block_size_bytes(source) as usize;
+        let (block_width, block_height) = (block_width as usize, block_height as usize);
+
+        let (width, height, _) = self.level_extent(level);
+        let row_size = (width as usize).div_ceil(block_width) * block_size;
+        let plane_size = row_size * (height as usize).div_ceil(block_height);
+        Ok(self.level_images[level as usize]
+            .chunks_exact(plane_size)
+            .map(|data| {
+                let mut pixels = vec![[0.0; 4]; width as usize * height as usize];
+                // one chunk holds the rows of texels of a row of blocks
+                let chunk = width as usize * block_height;
+                resample::for_each_row(&mut pixels, chunk, |by, rows| {
+                    let blocks = &data[by * row_size..(by + 1) * row_size];
+                    let mut texels = vec![[0.0; 4]; block_width * block_height];
+                    for (bx, block) in blocks.chunks_exact(block_size).enumerate() {
+                        decode(block, &mut texels);
+                        for (y, row) in rows.chunks_mut(width as usize).enumerate() {
+                            let x = bx * block_width;
+                            let count = (width as usize - x).min(block_width);
+                            let start = y * block_width;
+                            row[x..x + count].copy_from_slice(&texels[start..start + count]);
+                        }
+                    }
+                });
+                Image {
+                    width,
+                    height,
+                    pixels,
+                }
+            })
+            .collect())
+    }
+
+    /// Converts every level, layer and face to the uncompressed `format`,
+    /// such as `R16_SFLOAT` to `R8_UNORM`. Color channels are converted when
+    /// the source, as interpreted by `options.color_space`, and `format`
+    /// differ in sRGB encoding. Normalized and integer formats only convert
+    /// into each other with a `value_range`.
+    pub fn convert_format(
+        &self,
+        format: VkFormat,
+        options: &ConvertOptions,
+    ) -> Result<TextureKtx2, anyhow::Error> {
+        let source = self.header.vk_format;
+        if get_format_layout(source).is_none() {
+            anyhow::bail!("Cannot convert from {:?}", source);
+        }
+        let Some(layout) = get_format_layout(format) else {
+            anyhow::bail!("Cannot convert to {:?}", format);
+        };
+        if options.value_range.is_none() && !is_format_convertible(source, format) {
+            anyhow::bail!("Cannot convert {:?} to {:?} without a value range", source, format);
+        }
+
+        let dfd = &self.dfd_descriptor_block[0];
+        let srgb = match options.color_space {
+            ColorSpace::Auto => dfd.is_srgb(),
+            ColorSpace::Linear => false,
+            ColorSpace::Srgb => true,
+        };
+        let ranges = channel_ranges(&layout);
+        let mut out = self.with_format(format);
+        out.dfd_descriptor_block[0]
+            .set_alpha_premultiplied(dfd.is_alpha_premultiplied() && ranges[3].is_some());
+
+        for level in 0..self.level_images.len() {
+            let mut planes = self.decode_planes(level as u32);
+            convert_transfer(&mut planes, srgb, is_format_srgb(format));
+            for plane in &mut planes {
+                let width = plane.width as usize;
+                resample::for_each_row(&mut plane.pixels, width, |y, row| {
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        let source = *pixel;
+                        for (c, value) in pixel.iter_mut().enumerate() {
+                            *value = options.channels[c].select(source);
+                            let Some(range) = ranges[c] else {
+                                continue;
+                            };
+                            if let Some((min, max)) = options.value_range.filter(|_| c < 3) {
+                                let t = (*value - min) / (max - min);
+                                *value = range.min + t * (range.max - range.min);
+                            }
+                            if options.dither {
+                                *value += (BAYER_4X4[y % 4][x % 4] + 0.5 - 8.0) / 16.0 * range.step;
+                            }
+                        }
+                    }
+                });
             }
+            out.level_images[level] = out.encode_planes(&planes);
+        }
+        Ok(out)
+    }
+
+    /// Rewrites the channels of every texel of an uncompressed texture, e.g.
+    /// `[G, R, Zero, One]` to swap red and green, clear blue and make it
+    /// opaque. Missing source channels read as 0 and missing alpha as 1.
+    pub fn swizzle(&mut self, channels: [ChannelSource; 4]) -> Result<(), anyhow::Error> {
+        let format = self.header.vk_format;
+        let layout = get_format_layout(format)
+            .filter(|layout| {
+                let depth_stencil = [Channel::Depth, Channel::Stencil];
+                !layout.channels().iter().any(|c| depth_stencil.contains(&c.channel))
+            })
+            .ok_or_else(|| anyhow::anyhow!("Cannot swizzle {:?}", format))?;
+
+        // channels of different sizes go through float, which is exact for
+        // them, equal ones are moved as bits to keep 32 and 64 bit values
+        let first = layout.channels()[0];
+        let uniform = format != VkFormat::E5B9G9R9_UFLOAT_PACK32
+            && layout
+                .channels()
+                .iter()
+                .all(|c| c.numeric == first.numeric && c.bit_length == first.bit_length);
+        if !uniform {
+            let options =
+                ConvertOptions { channels, color_space: ColorSpace::Linear, ..Default::default() };
+            self.level_images = self.convert_format(format, &options)?.level_images;
+            return Ok(());
+        }
 
-            for y in 0..height {
-                let mut t = 0.0;
-
-                for (i, w) in ws.iter().enumerate() {
-                    let pixel = image.read_pixel(left + i as u32, y);
-                    let p = match pixel {
-                        Pixel::R16_SFLOAT(p) => half::f16::to_f32(p),
-                        _ => panic!("Unsupported format"),
-                    };
-                    let k1 = p;
-                    t += k1 * w;
+        let texel_size = layout.texel_size as usize;
+        let mut one = [0u8; MAX_TEXEL_SIZE];
+        encode_texel(format, &layout, [1.0; 4], &mut one);
+        let source_channel =
+            |index: usize| layout.channels().iter().find(|c| layout.rgba_index(c.channel) == index);
+        let row_size = texel_size * self.header.pixel_width as usize;
+        for data in &mut self.level_images {
+            resample::for_each_row(data, row_size, |_, row| {
+                for texel in row.chunks_exact_mut(texel_size) {
+                    let mut source = [0u8; MAX_TEXEL_SIZE];
+                    source[..texel_size].copy_from_slice(texel);
+                    for channel in layout.channels() {
+                        let bits = channel.bit_length;
+                        let target = channels[layout.rgba_index(channel.channel)];
+                        let value = match (target, target.rgba_index().and_then(source_channel)) {
+                            (_, Some(c)) => read_bits(&source, c.bit_offset, bits),
+                            (ChannelSource::A | ChannelSource::One, None) => {
+                                read_bits(&one, channel.bit_offset, bits)
+                            }
+                            _ => 0,
+                        };
+                        write_bits(texel, channel.bit_offset, bits, value);
+                    }
                 }
+            });
+        }
+        Ok(())
+    }
+
+    /// Channel mapping recorded in the `KTXswizzle` metadata, `None` when
+    /// there is none.
+    pub fn stored_swizzle(&self) -> Result<Option<[ChannelSource; 4]>, anyhow::Error> {
+        let Some(value) = self.key_value_data.get("KTXswizzle") else {
+            return Ok(None);
+        };
+        let chars = value.strip_suffix(b"\0").unwrap_or(value);
+        let channels: Vec<ChannelSource> =
+            chars.iter().filter_map(|&c| ChannelSource::from_swizzle_char(c)).collect();
+        match <[ChannelSource; 4]>::try_from(channels) {
+            Ok(channels) if chars.len() == 4 => Ok(Some(channels)),
+            _ => anyhow::bail!("Invalid KTXswizzle {:?}", String::from_utf8_lossy(value)),
+        }
+    }
 
-                let t1 = t / sum;
-                let t = half::f16::from_f32(clamp(t1, 0.0, max));
+    /// Records `channels` in the `KTXswizzle` metadata instead of rewriting
+    /// the texels, applied after any mapping that is already recorded.
+    pub fn set_stored_swizzle(
+        &mut self,
+        channels: [ChannelSource; 4],
+    ) -> Result<(), anyhow::Error> {
+        let stored = self.stored_swizzle()?.unwrap_or(ChannelSource::RGBA);
+        let combined = channels.map(|c| c.rgba_index().map_or(c, |i| stored[i]));
+        if combined == ChannelSource::RGBA {
+            self.key_value_data.remove("KTXswizzle");
+        } else {
+            let mut value: Vec<u8> = combined.iter().map(|c| c.swizzle_char()).collect();
+            value.push(0);
+            self.key_value_data.insert(String::from("KTXswizzle"), value);
+        }
+        Ok(())
+    }
+
+    /// Applies the `KTXswizzle` metadata to the texels while converting to
+    /// the uncompressed `format`, see [`Self::convert_format`], and removes
+    /// it from the result.
+    pub fn apply_stored_swizzle(&self, format: VkFormat) -> Result<TextureKtx2, anyhow::Error> {
+        let channels = self.stored_swizzle()?.unwrap_or(ChannelSource::RGBA);
+        let mut out = if format == self.header.vk_format {
+            let mut out = self.clone();
+            out.swizzle(channels)?;
+            out
+        } else {
+            self.convert_format(format, &ConvertOptions { channels, ..Default::default() })?
+        };
+        out.key_value_data.remove("KTXswizzle");
+        Ok(out)
+    }
 
-                out.write_pixel(outx, y, Pixel::R16_SFLOAT(t));
+    /// Compares every level with `other`, which must have the same
+    /// dimensions, layers, faces and levels. Either texture may be block
+    /// compressed. Values are compared as decoded, in the sRGB encoding of
+    /// `self`.
+    pub fn compare(&self, other: &TextureKtx2) -> Result<Comparison, anyhow::Error> {
+        let (a, b) = self.decode_pair(other)?;
+        Ok(Comparison {
+            levels: a.iter().zip(&b).map(|(a, b)| metrics::compare_planes(a, b)).collect(),
+        })
+    }
+
+    /// Absolute differences with `other`, as compared by [`Self::compare`],
+    /// as an `R32G32B32A32_SFLOAT` texture.
+    pub fn difference(&self, other: &TextureKtx2) -> Result<TextureKtx2, anyhow::Error> {
+        let (a, b) = self.decode_pair(other)?;
+        let mut out = self.with_format(VkFormat::R32G32B32A32_SFLOAT);
+        for (level, (a, b)) in a.iter().zip(&b).enumerate() {
+            let planes: Vec<Image> =
+                a.iter().zip(b).map(|(a, b)| metrics::difference(a, b)).collect();
+            out.level_images[level] = out.encode_planes(&planes);
+        }
+        Ok(out)
+    }
+
+    /// Decodes every level of `self` and `other` in the sRGB encoding of
+    /// `self`.
+    fn decode_pair(
+        &self,
+        other: &TextureKtx2,
+    ) -> Result<(DecodedLevels, DecodedLevels), anyhow::Error> {
+        let extent = |h: &Header| {
+            (h.pixel_width, h.pixel_height, h.pixel_depth, h.layer_count, h.face_count)
+        };
+        if extent(&self.header) != extent(&other.header)
+            || self.level_images.len() != other.level_images.len()
+        {
+            anyhow::bail!("Cannot compare textures of different dimensions");
+        }
+        let srgb = self.dfd_descriptor_block[0].is_srgb();
+        Ok((self.decode_levels(srgb)?, other.decode_levels(srgb)?))
+    }
+
+    /// Decodes every level, decompressing block compressed formats, with
+    /// color channels sRGB encoded when `srgb`.
+    pub(crate) fn decode_levels(&self, srgb: bool) -> Result<DecodedLevels, anyhow::Error> {
+        (0..self.level_images.len() as u32)
+            .map(|level| self.decode_level(level, srgb))
+            .collect()
+    }
+
+    /// Decodes the 2D images of one level, see [`Self::decode_levels`].
+    fn decode_level(&self, level: u32, srgb: bool) -> Result<Vec<Image>, anyhow::Error> {
+        let mut planes = if get_format_layout(self.header.vk_format).is_some() {
+            self.decode_planes(level)
+        } else {
+            self.decompress_planes(level)?
+        };
+        convert_transfer(&mut planes, self.dfd_descriptor_block[0].is_srgb(), srgb);
+        Ok(planes)
+    }
+
+    /// Index in [`Self::decode_planes`] of the 2D image at `slice` of `face`
+    /// of `layer` of `level`, checking that it exists.
+    pub(crate) fn plane_index(
+        &self,
+        level: u32,
+        layer: u32,
+        face: u32,
+        slice: u32,
+    ) -> Result<usize, anyhow::Error> {
+        let depth = self.level_extent(level).2.max(1);
+        let faces = self.header.face_count;
+        if level as usize >= self.level_images.len()
+            || layer >= self.header.layer_count.max(1)
+            || face >= faces
+            || slice >= depth
+        {
+            anyhow::bail!(
+                "No image at level {} layer {} face {} slice {}",
+                level,
+                layer,
+                face,
+                slice
+            );
+        }
+        Ok(((layer * faces + face) * depth + slice) as usize)
+    }
+
+    /// Decodes one 2D image, see [`Self::plane_index`] and
+    /// [`Self::decode_levels`]. Only its level is decoded.
+    pub(crate) fn decode_image(
+        &self,
+        level: u32,
+        layer: u32,
+        face: u32,
+        slice: u32,
+        srgb: bool,
+    ) -> Result<Image, anyhow::Error> {
+        let plane = self.plane_index(level, layer, face, slice)?;
+        let mut planes = self.decode_level(level, srgb)?;
+        if plane >= planes.len() {
+            anyhow::bail!("Level {} holds {} images, not {}", level, planes.len(), plane + 1);
+        }
+        Ok(planes.swap_remove(plane))
+    }
+
+    /// Precision of decoded ASTC texels from the `KTXastcDecodeMode`
+    /// metadata, which only LDR formats may set to `unorm8`.
+    fn astc_decode_mode(&self, format: VkFormat) -> Result<DecodeMode, anyhow::Error> {
+        let Some(value) = self.key_value_data.get("KTXastcDecodeMode") else {
+            return Ok(DecodeMode::default());
+        };
+        match value.strip_suffix(b"\0").unwrap_or(value) {
+            b"rgb9e5" => Ok(DecodeMode::Rgb9e5),
+            b"unorm8" if !astc::is_astc_hdr(format) => Ok(DecodeMode::Unorm8),
+            _ => anyhow::bail!(
+                "Invalid KTXastcDecodeMode {:?} for {:?}",
+                String::from_utf8_lossy(value),
+                format
+            ),
+        }
+    }
+
+    /// Whether color channels are filtered in linear light and whether they
+    /// get premultiplied by alpha, according to `options`.
+    fn filter_space(&self, options: &ResizeOptions) -> (bool, bool) {
+        let dfd = &self.dfd_descriptor_block[0];
+        let srgb = match options.color_space {
+            ColorSpace::Auto => dfd.is_srgb(),
+            ColorSpace::Linear => false,
+            ColorSpace::Srgb => true,
+        };
+        let has_alpha = get_format_layout(self.header.vk_format)
+            .is_some_and(|layout| layout.channels().iter().any(|c| c.channel == Channel::A));
+        let premultiply = options.premultiply_alpha && has_alpha && !dfd.is_alpha_premultiplied();
+        (srgb, premultiply)
+    }
+
+    /// Converts decoded images and the border color of `options` to the
+    /// space they are filtered in.
+    fn enter_filter_space(&self, planes: &mut [Image], options: &mut ResizeOptions) {
+        let (srgb, premultiply) = self.filter_space(options);
+        if let AddressMode::ClampToBorder(color) = &mut options.address_mode {
+            let mut border = Image {
+                width: 1,
+                height: 1,
+                pixels: vec![*color],
+            };
+            if srgb {
+                resample::srgb_to_linear(&mut border);
             }
+            if premultiply {
+                resample::premultiply_alpha(&mut border);
+            }
+            *color = border.pixels[0];
         }
-        out
+        for image in planes {
+            if srgb {
+                resample::srgb_to_linear(image);
+            }
+            if premultiply {
+                resample::premultiply_alpha(image);
+            }
+        }
+    }
+
+    /// Inverse of [`TextureKtx2::enter_filter_space`].
+    fn leave_filter_space(&self, planes: &mut [Image], options: &ResizeOptions) {
+        let (srgb, premultiply) = self.filter_space(options);
+        for image in planes {
+            if premultiply {
+                resample::unpremultiply_alpha(image);
+            }
+            if srgb {
+                resample::linear_to_srgb(image);
+            }
+        }
+    }
+
+    /// Whether the level images are the faces of a cubemap, filtered across
+    /// face edges.
+    fn is_cubemap(&self) -> bool {
+        self.header.face_count == 6
+    }
+
+    /// Resizes the 2D images of a level that are already in filter space.
+    fn resize_planes(
+        &self,
+        planes: &[Image],
+        width: u32,
+        height: u32,
+        filter: &mut Filter,
+        options: &ResizeOptions,
+    ) -> Vec<Image> {
+        if self.is_cubemap() && width == height {
+            planes
+                .chunks(6)
+                .flat_map(|faces| resample::resize_cube_faces(faces, width, filter, options))
+                .collect()
+        } else {
+            planes
+                .iter()
+                .map(|plane| resample::resize(plane, width, height, filter, options))
+                .collect()
+        }
+    }
+
+    /// Decodes the base level, runs `passes` on every 2D image of it
+    /// according to `options` and encodes the result in the format of `self`.
+    fn resample_image(
+        &self,
+        options: &ResizeOptions,
+        passes: impl FnOnce(&[Image], &ResizeOptions) -> Vec<Image>,
+    ) -> Result<TextureKtx2, anyhow::Error> {
+        if get_format_layout(self.header.vk_format).is_none() {
+            anyhow::bail!("Cannot resize {:?}", self.header.vk_format);
+        }
+        let mut options = *options;
+        let mut planes = self.decode_planes(0);
+        let images = self.header.layer_count.max(1) as usize
+            * self.header.face_count.max(1) as usize
+            * self.header.pixel_depth.max(1) as usize;
+        if planes.len() != images {
+            anyhow::bail!("Base level holds {} images instead of {}", planes.len(), images);
+        }
+        self.enter_filter_space(&mut planes, &mut options);
+        let mut out = passes(&planes, &options);
+        if self.is_cubemap() && out[0].width != out[0].height {
+            anyhow::bail!("Cubemap faces cannot be resized to {}x{}", out[0].width, out[0].height);
+        }
+        self.leave_filter_space(&mut out, &options);
+
+        let mut texture = self.encode_image(&out[0]);
+        texture.header.pixel_depth = self.header.pixel_depth;
+        texture.header.layer_count = self.header.layer_count;
+        texture.header.face_count = self.header.face_count;
+        texture.level_images[0] = self.encode_planes(&out);
+        Ok(texture)
+    }
+
+    pub fn vertical_sample(
+        image: TextureKtx2,
+        new_height: u32,
+        filter: &mut Filter,
+    ) -> Result<TextureKtx2, anyhow::Error> {
+        image.resample_image(&ResizeOptions::default(), |planes, options| {
+            planes
+                .iter()
+                .map(|plane| {
+                    resample::vertical_sample(plane, new_height, filter, options)
+                })
+                .collect()
+        })
+    }
+
+    pub fn horizontal_sample(
+        image: &mut TextureKtx2,
+        new_width: u32,
+        filter: &mut Filter,
+    ) -> Result<TextureKtx2, anyhow::Error> {
+        image.resample_image(&ResizeOptions::default(), |planes, options| {
+            planes
+                .iter()
+                .map(|plane| {
+                    resample::horizontal_sample(plane, new_width, filter, options)
+                })
+                .collect()
+        })
     }
 
     pub fn resize(
@@ -588,19 +1342,119 @@ impl TextureKtx2 {
         nwidth: u32,
         nheight: u32,
         filter: FilterType,
-    ) -> TextureKtx2 {
-        let mut method = match filter {
-            FilterType::Nearest => Filter {
-                kernel: Box::new(box_kernel),
-                support: 0.0,
-            },
-            FilterType::Lanczos3 => Filter {
-                kernel: Box::new(lanczos3_kernel),
-                support: 3.0,
-            },
-        };
+    ) -> Result<TextureKtx2, anyhow::Error> {
+        TextureKtx2::resize_with_filter(image, nwidth, nheight, &mut filter.to_filter())
+    }
+
+    /// Resizes the base image with a user supplied filter kernel.
+    pub fn resize_with_filter(
+        image: TextureKtx2,
+        nwidth: u32,
+        nheight: u32,
+        filter: &mut Filter,
+    ) -> Result<TextureKtx2, anyhow::Error> {
+        TextureKtx2::resize_with_options(image, nwidth, nheight, filter, &ResizeOptions::default())
+    }
+
+    /// Resizes the base image, with sRGB decoding, alpha premultiplication and
+    /// edge handling controlled by `options`. Every layer, face and z slice
+    /// is resized; cubemap faces are filtered across their edges and must
+    /// stay square. Fails for block compressed formats.
+    pub fn resize_with_options(
+        image: TextureKtx2,
+        nwidth: u32,
+        nheight: u32,
+        filter: &mut Filter,
+        options: &ResizeOptions,
+    ) -> Result<TextureKtx2, anyhow::Error> {
+        image.resample_image(options, |planes, options| {
+            image.resize_planes(planes, nwidth, nheight, filter, options)
+        })
+    }
+
+    /// Replaces all levels below the base level with a full mip chain down to
+    /// 1x1, each level filtered from the one above it in f32. Fails for 3D
+    /// textures and block compressed formats.
+    pub fn generate_mipmaps(
+        &mut self,
+        filter: &mut Filter,
+        options: &ResizeOptions,
+    ) -> Result<(), anyhow::Error> {
+        if self.header.pixel_depth > 1 {
+            anyhow::bail!("Mipmap generation of 3D textures is not supported");
+        }
+        if get_format_layout(self.header.vk_format).is_none() {
+            anyhow::bail!("Cannot generate mipmaps of {:?}", self.header.vk_format);
+        }
+        let mut options = *options;
+        let mut planes = self.decode_planes(0);
+        self.enter_filter_space(&mut planes, &mut options);
+
+        let size = self.header.pixel_width.max(self.header.pixel_height);
+        let level_count = 32 - size.leading_zeros();
+        self.header.level_count = level_count;
+        self.level_images.truncate(1);
+        for level in 1..level_count {
+            let (width, height, _) = self.level_extent(level);
+            planes = self.resize_planes(&planes, width, height, filter, &options);
+            let mut encoded = planes.clone();
+            self.leave_filter_space(&mut encoded, &options);
+            self.level_images.push(self.encode_planes(&encoded));
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+/// Whether `format` is block compressed with values in `[-1, 1]`.
+fn is_snorm_block(format: VkFormat) -> bool {
+    matches!(
+        format,
+        VkFormat::BC4_SNORM_BLOCK
+            | VkFormat::BC5_SNORM_BLOCK
+            | VkFormat::EAC_R11_SNORM_BLOCK
+            | VkFormat::EAC_R11G11_SNORM_BLOCK
+    )
+}
+
+/// Converts the color channels of `planes` between sRGB and linear encoding
+/// when `from_srgb` and `to_srgb` differ.
+fn convert_transfer(planes: &mut [Image], from_srgb: bool, to_srgb: bool) {
+    for plane in planes {
+        match (from_srgb, to_srgb) {
+            (true, false) => resample::srgb_to_linear(plane),
+            (false, true) => resample::linear_to_srgb(plane),
+            _ => {}
+        }
+    }
+}
+
+fn align(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    a / gcd(a, b) * b
+}
+
+/// Pads `out` with zero bytes up to `offset`.
+fn pad_to(out: &mut Vec<u8>, offset: u64) {
+    out.resize(offset as usize, 0);
+}
 
-        let mut tmp = TextureKtx2::vertical_sample(image, nheight, &mut method);
-        TextureKtx2::horizontal_sample(&mut tmp, nwidth, &mut method)
+/// Returns the `length` bytes of `buffer` starting at `offset`.
+fn section(buffer: &[u8], offset: u64, length: u64) -> Result<&[u8], anyhow::Error> {
+    let end = offset.checked_add(length);
+    match end {
+        Some(end) if end <= buffer.len() as u64 => Ok(&buffer[offset as usize..end as usize]),
+        _ => anyhow::bail!(
+            "Section at {} with length {} is out of bounds",
+            offset,
+            length
+        ),
     }
 }
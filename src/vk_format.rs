@@ -1,7 +1,7 @@
 use std::panic;
 
 #[allow(non_camel_case_types)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum VkFormat {
     UNDEFINED = 0,
@@ -280,24 +280,631 @@ pub enum VkFormat {
     MAX_ENUM = 0x7FFFFFFF,
 }
 
+/// Values of the [`VkFormat`] variants as inclusive ranges, which must
+/// follow the enum.
+const VK_FORMAT_RANGES: [(u32, u32); 7] = [
+    (0, 184),
+    (1000054000, 1000054007),
+    (1000066000, 1000066013),
+    (1000156000, 1000156033),
+    (1000288000, 1000288029),
+    (1000340000, 1000340001),
+    (0x7FFFFFFF, 0x7FFFFFFF),
+];
+
+impl TryFrom<u32> for VkFormat {
+    type Error = anyhow::Error;
+
+    /// Checks that the value is a known format, e.g. a `vkFormat` read from
+    /// a file.
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        if !VK_FORMAT_RANGES.iter().any(|&(first, last)| (first..=last).contains(&value)) {
+            anyhow::bail!("Unknown vkFormat {}", value);
+        }
+        // SAFETY: VkFormat is repr(u32) and the value is one of its variants
+        Ok(unsafe { std::mem::transmute::<u32, VkFormat>(value) })
+    }
+}
+
+/// Interpretation of the bits of a single channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericFormat {
+    Unorm,
+    Snorm,
+    Uscaled,
+    Sscaled,
+    Uint,
+    Sint,
+    Ufloat,
+    Sfloat,
+    Srgb,
+}
+
+/// Channel identifiers, numbered as in the KHR_DF_MODEL_RGBSDA color model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Channel {
+    R = 0,
+    G = 1,
+    B = 2,
+    Stencil = 13,
+    Depth = 14,
+    A = 15,
+}
+
+/// Position of a channel inside a texel, in bits from the least significant
+/// bit of the little-endian texel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelLayout {
+    pub channel: Channel,
+    pub numeric: NumericFormat,
+    pub bit_offset: u32,
+    pub bit_length: u32,
+}
+
+/// Memory layout of a texel of an uncompressed format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatLayout {
+    /// Size of a texel in bytes.
+    pub texel_size: u32,
+    /// Size of the data type used for endianness conversion (KTX2 typeSize).
+    pub type_size: u32,
+    channel_count: usize,
+    channels: [ChannelLayout; 4],
+}
+
+impl FormatLayout {
+    /// Channels in order of increasing bit offset.
+    pub fn channels(&self) -> &[ChannelLayout] {
+        &self.channels[..self.channel_count]
+    }
+
+    /// Index of the channel in an `[r, g, b, a]` value. Depth maps to red,
+    /// stencil to green when combined with depth and to red otherwise.
+    pub fn rgba_index(&self, channel: Channel) -> usize {
+        match channel {
+            Channel::R | Channel::Depth => 0,
+            Channel::G => 1,
+            Channel::B => 2,
+            Channel::A => 3,
+            Channel::Stencil => {
+                if self.channels().iter().any(|c| c.channel == Channel::Depth) {
+                    1
+                } else {
+                    0
+                }
+            }
+        }
+    }
+
+    const fn empty(texel_size: u32, type_size: u32) -> Self {
+        FormatLayout {
+            texel_size,
+            type_size,
+            channel_count: 0,
+            channels: [ChannelLayout {
+                channel: Channel::R,
+                numeric: NumericFormat::Unorm,
+                bit_offset: 0,
+                bit_length: 0,
+            }; 4],
+        }
+    }
+
+    const fn with(
+        mut self,
+        channel: Channel,
+        numeric: NumericFormat,
+        bit_offset: u32,
+        bit_length: u32,
+    ) -> Self {
+        // alpha of sRGB formats is always stored linearly
+        let numeric = match (channel, numeric) {
+            (Channel::A, NumericFormat::Srgb) => NumericFormat::Unorm,
+            _ => numeric,
+        };
+        // keep channels sorted by bit offset
+        let mut i = self.channel_count;
+        while i > 0 && self.channels[i - 1].bit_offset > bit_offset {
+            self.channels[i] = self.channels[i - 1];
+            i -= 1;
+        }
+        self.channels[i] = ChannelLayout {
+            channel,
+            numeric,
+            bit_offset,
+            bit_length,
+        };
+        self.channel_count += 1;
+        self
+    }
+
+    /// Components stored in consecutive words of `type_size` bytes, in
+    /// memory order. Components narrower than the word occupy its most
+    /// significant bits.
+    const fn unpacked(numeric: NumericFormat, type_size: u32, bits: u32, order: &[Channel]) -> Self {
+        let mut layout = Self::empty(type_size * order.len() as u32, type_size);
+        let mut i = 0;
+        while i < order.len() {
+            let bit_offset = (i as u32 + 1) * type_size * 8 - bits;
+            layout = layout.with(order[i], numeric, bit_offset, bits);
+            i += 1;
+        }
+        layout
+    }
+
+    /// Components packed in a single word of `size` bytes, listed from the
+    /// most to the least significant bits as in the Vulkan format name.
+    const fn packed(numeric: NumericFormat, size: u32, fields: &[(Channel, u32)]) -> Self {
+        let mut layout = Self::empty(size, size);
+        let mut bit_offset = size * 8;
+        let mut i = 0;
+        while i < fields.len() {
+            bit_offset -= fields[i].1;
+            layout = layout.with(fields[i].0, numeric, bit_offset, fields[i].1);
+            i += 1;
+        }
+        layout
+    }
+}
+
+/// Texel layout of an uncompressed, single plane format or `None` for
+/// block compressed, subsampled and multi-planar formats.
+pub const fn get_format_layout(format: VkFormat) -> Option<FormatLayout> {
+    use Channel::*;
+    use NumericFormat::*;
+
+    const RGBA: &[Channel] = &[R, G, B, A];
+    const BGRA: &[Channel] = &[B, G, R, A];
+    const RGB: &[Channel] = &[R, G, B];
+    const BGR: &[Channel] = &[B, G, R];
+    const RG: &[Channel] = &[R, G];
+
+    let layout = match format {
+        VkFormat::R4G4_UNORM_PACK8 => FormatLayout::packed(Unorm, 1, &[(R, 4), (G, 4)]),
+        VkFormat::R4G4B4A4_UNORM_PACK16 => {
+            FormatLayout::packed(Unorm, 2, &[(R, 4), (G, 4), (B, 4), (A, 4)])
+        }
+        VkFormat::B4G4R4A4_UNORM_PACK16 => {
+            FormatLayout::packed(Unorm, 2, &[(B, 4), (G, 4), (R, 4), (A, 4)])
+        }
+        VkFormat::A4R4G4B4_UNORM_PACK16_EXT => {
+            FormatLayout::packed(Unorm, 2, &[(A, 4), (R, 4), (G, 4), (B, 4)])
+        }
+        VkFormat::A4B4G4R4_UNORM_PACK16_EXT => {
+            FormatLayout::packed(Unorm, 2, &[(A, 4), (B, 4), (G, 4), (R, 4)])
+        }
+        VkFormat::R5G6B5_UNORM_PACK16 => FormatLayout::packed(Unorm, 2, &[(R, 5), (G, 6), (B, 5)]),
+        VkFormat::B5G6R5_UNORM_PACK16 => FormatLayout::packed(Unorm, 2, &[(B, 5), (G, 6), (R, 5)]),
+        VkFormat::R5G5B5A1_UNORM_PACK16 => {
+            FormatLayout::packed(Unorm, 2, &[(R, 5), (G, 5), (B, 5), (A, 1)])
+        }
+        VkFormat::B5G5R5A1_UNORM_PACK16 => {
+            FormatLayout::packed(Unorm, 2, &[(B, 5), (G, 5), (R, 5), (A, 1)])
+        }
+        VkFormat::A1R5G5B5_UNORM_PACK16 => {
+            FormatLayout::packed(Unorm, 2, &[(A, 1), (R, 5), (G, 5), (B, 5)])
+        }
+
+        VkFormat::R8_UNORM => FormatLayout::unpacked(Unorm, 1, 8, &[R]),
+        VkFormat::R8_SNORM => FormatLayout::unpacked(Snorm, 1, 8, &[R]),
+        VkFormat::R8_USCALED => FormatLayout::unpacked(Uscaled, 1, 8, &[R]),
+        VkFormat::R8_SSCALED => FormatLayout::unpacked(Sscaled, 1, 8, &[R]),
+        VkFormat::R8_UINT => FormatLayout::unpacked(Uint, 1, 8, &[R]),
+        VkFormat::R8_SINT => FormatLayout::unpacked(Sint, 1, 8, &[R]),
+        VkFormat::R8_SRGB => FormatLayout::unpacked(Srgb, 1, 8, &[R]),
+        VkFormat::R8G8_UNORM => FormatLayout::unpacked(Unorm, 1, 8, RG),
+        VkFormat::R8G8_SNORM => FormatLayout::unpacked(Snorm, 1, 8, RG),
+        VkFormat::R8G8_USCALED => FormatLayout::unpacked(Uscaled, 1, 8, RG),
+        VkFormat::R8G8_SSCALED => FormatLayout::unpacked(Sscaled, 1, 8, RG),
+        VkFormat::R8G8_UINT => FormatLayout::unpacked(Uint, 1, 8, RG),
+        VkFormat::R8G8_SINT => FormatLayout::unpacked(Sint, 1, 8, RG),
+        VkFormat::R8G8_SRGB => FormatLayout::unpacked(Srgb, 1, 8, RG),
+        VkFormat::R8G8B8_UNORM => FormatLayout::unpacked(Unorm, 1, 8, RGB),
+        VkFormat::R8G8B8_SNORM => FormatLayout::unpacked(Snorm, 1, 8, RGB),
+        VkFormat::R8G8B8_USCALED => FormatLayout::unpacked(Uscaled, 1, 8, RGB),
+        VkFormat::R8G8B8_SSCALED => FormatLayout::unpacked(Sscaled, 1, 8, RGB),
+        VkFormat::R8G8B8_UINT => FormatLayout::unpacked(Uint, 1, 8, RGB),
+        VkFormat::R8G8B8_SINT => FormatLayout::unpacked(Sint, 1, 8, RGB),
+        VkFormat::R8G8B8_SRGB => FormatLayout::unpacked(Srgb, 1, 8, RGB),
+        VkFormat::B8G8R8_UNORM => FormatLayout::unpacked(Unorm, 1, 8, BGR),
+        VkFormat::B8G8R8_SNORM => FormatLayout::unpacked(Snorm, 1, 8, BGR),
+        VkFormat::B8G8R8_USCALED => FormatLayout::unpacked(Uscaled, 1, 8, BGR),
+        VkFormat::B8G8R8_SSCALED => FormatLayout::unpacked(Sscaled, 1, 8, BGR),
+        VkFormat::B8G8R8_UINT => FormatLayout::unpacked(Uint, 1, 8, BGR),
+        VkFormat::B8G8R8_SINT => FormatLayout::unpacked(Sint, 1, 8, BGR),
+        VkFormat::B8G8R8_SRGB => FormatLayout::unpacked(Srgb, 1, 8, BGR),
+        VkFormat::R8G8B8A8_UNORM => FormatLayout::unpacked(Unorm, 1, 8, RGBA),
+        VkFormat::R8G8B8A8_SNORM => FormatLayout::unpacked(Snorm, 1, 8, RGBA),
+        VkFormat::R8G8B8A8_USCALED => FormatLayout::unpacked(Uscaled, 1, 8, RGBA),
+        VkFormat::R8G8B8A8_SSCALED => FormatLayout::unpacked(Sscaled, 1, 8, RGBA),
+        VkFormat::R8G8B8A8_UINT => FormatLayout::unpacked(Uint, 1, 8, RGBA),
+        VkFormat::R8G8B8A8_SINT => FormatLayout::unpacked(Sint, 1, 8, RGBA),
+        VkFormat::R8G8B8A8_SRGB => FormatLayout::unpacked(Srgb, 1, 8, RGBA),
+        VkFormat::B8G8R8A8_UNORM => FormatLayout::unpacked(Unorm, 1, 8, BGRA),
+        VkFormat::B8G8R8A8_SNORM => FormatLayout::unpacked(Snorm, 1, 8, BGRA),
+        VkFormat::B8G8R8A8_USCALED => FormatLayout::unpacked(Uscaled, 1, 8, BGRA),
+        VkFormat::B8G8R8A8_SSCALED => FormatLayout::unpacked(Sscaled, 1, 8, BGRA),
+        VkFormat::B8G8R8A8_UINT => FormatLayout::unpacked(Uint, 1, 8, BGRA),
+        VkFormat::B8G8R8A8_SINT => FormatLayout::unpacked(Sint, 1, 8, BGRA),
+        VkFormat::B8G8R8A8_SRGB => FormatLayout::unpacked(Srgb, 1, 8, BGRA),
+
+        VkFormat::A8B8G8R8_UNORM_PACK32 => {
+            FormatLayout::packed(Unorm, 4, &[(A, 8), (B, 8), (G, 8), (R, 8)])
+        }
+        VkFormat::A8B8G8R8_SNORM_PACK32 => {
+            FormatLayout::packed(Snorm, 4, &[(A, 8), (B, 8), (G, 8), (R, 8)])
+        }
+        VkFormat::A8B8G8R8_USCALED_PACK32 => {
+            FormatLayout::packed(Uscaled, 4, &[(A, 8), (B, 8), (G, 8), (R, 8)])
+        }
+        VkFormat::A8B8G8R8_SSCALED_PACK32 => {
+            FormatLayout::packed(Sscaled, 4, &[(A, 8), (B, 8), (G, 8), (R, 8)])
+        }
+        VkFormat::A8B8G8R8_UINT_PACK32 => {
+            FormatLayout::packed(Uint, 4, &[(A, 8), (B, 8), (G, 8), (R, 8)])
+        }
+        VkFormat::A8B8G8R8_SINT_PACK32 => {
+            FormatLayout::packed(Sint, 4, &[(A, 8), (B, 8), (G, 8), (R, 8)])
+        }
+        VkFormat::A8B8G8R8_SRGB_PACK32 => {
+            FormatLayout::packed(Srgb, 4, &[(A, 8), (B, 8), (G, 8), (R, 8)])
+        }
+        VkFormat::A2R10G10B10_UNORM_PACK32 => {
+            FormatLayout::packed(Unorm, 4, &[(A, 2), (R, 10), (G, 10), (B, 10)])
+        }
+        VkFormat::A2R10G10B10_SNORM_PACK32 => {
+            FormatLayout::packed(Snorm, 4, &[(A, 2), (R, 10), (G, 10), (B, 10)])
+        }
+        VkFormat::A2R10G10B10_USCALED_PACK32 => {
+            FormatLayout::packed(Uscaled, 4, &[(A, 2), (R, 10), (G, 10), (B, 10)])
+        }
+        VkFormat::A2R10G10B10_SSCALED_PACK32 => {
+            FormatLayout::packed(Sscaled, 4, &[(A, 2), (R, 10), (G, 10), (B, 10)])
+        }
+        VkFormat::A2R10G10B10_UINT_PACK32 => {
+            FormatLayout::packed(Uint, 4, &[(A, 2), (R, 10), (G, 10), (B, 10)])
+        }
+        VkFormat::A2R10G10B10_SINT_PACK32 => {
+            FormatLayout::packed(Sint, 4, &[(A, 2), (R, 10), (G, 10), (B, 10)])
+        }
+        VkFormat::A2B10G10R10_UNORM_PACK32 => {
+            FormatLayout::packed(Unorm, 4, &[(A, 2), (B, 10), (G, 10), (R, 10)])
+        }
+        VkFormat::A2B10G10R10_SNORM_PACK32 => {
+            FormatLayout::packed(Snorm, 4, &[(A, 2), (B, 10), (G, 10), (R, 10)])
+        }
+        VkFormat::A2B10G10R10_USCALED_PACK32 => {
+            FormatLayout::packed(Uscaled, 4, &[(A, 2), (B, 10), (G, 10), (R, 10)])
+        }
+        VkFormat::A2B10G10R10_SSCALED_PACK32 => {
+            FormatLayout::packed(Sscaled, 4, &[(A, 2), (B, 10), (G, 10), (R, 10)])
+        }
+        VkFormat::A2B10G10R10_UINT_PACK32 => {
+            FormatLayout::packed(Uint, 4, &[(A, 2), (B, 10), (G, 10), (R, 10)])
+        }
+        VkFormat::A2B10G10R10_SINT_PACK32 => {
+            FormatLayout::packed(Sint, 4, &[(A, 2), (B, 10), (G, 10), (R, 10)])
+        }
+
+        VkFormat::R16_UNORM => FormatLayout::unpacked(Unorm, 2, 16, &[R]),
+        VkFormat::R16_SNORM => FormatLayout::unpacked(Snorm, 2, 16, &[R]),
+        VkFormat::R16_USCALED => FormatLayout::unpacked(Uscaled, 2, 16, &[R]),
+        VkFormat::R16_SSCALED => FormatLayout::unpacked(Sscaled, 2, 16, &[R]),
+        VkFormat::R16_UINT => FormatLayout::unpacked(Uint, 2, 16, &[R]),
+        VkFormat::R16_SINT => FormatLayout::unpacked(Sint, 2, 16, &[R]),
+        VkFormat::R16_SFLOAT => FormatLayout::unpacked(Sfloat, 2, 16, &[R]),
+        VkFormat::R16G16_UNORM => FormatLayout::unpacked(Unorm, 2, 16, RG),
+        VkFormat::R16G16_SNORM => FormatLayout::unpacked(Snorm, 2, 16, RG),
+        VkFormat::R16G16_USCALED => FormatLayout::unpacked(Uscaled, 2, 16, RG),
+        VkFormat::R16G16_SSCALED => FormatLayout::unpacked(Sscaled, 2, 16, RG),
+        VkFormat::R16G16_UINT => FormatLayout::unpacked(Uint, 2, 16, RG),
+        VkFormat::R16G16_SINT => FormatLayout::unpacked(Sint, 2, 16, RG),
+        VkFormat::R16G16_SFLOAT => FormatLayout::unpacked(Sfloat, 2, 16, RG),
+        VkFormat::R16G16B16_UNORM => FormatLayout::unpacked(Unorm, 2, 16, RGB),
+        VkFormat::R16G16B16_SNORM => FormatLayout::unpacked(Snorm, 2, 16, RGB),
+        VkFormat::R16G16B16_USCALED => FormatLayout::unpacked(Uscaled, 2, 16, RGB),
+        VkFormat::R16G16B16_SSCALED => FormatLayout::unpacked(Sscaled, 2, 16, RGB),
+        VkFormat::R16G16B16_UINT => FormatLayout::unpacked(Uint, 2, 16, RGB),
+        VkFormat::R16G16B16_SINT => FormatLayout::unpacked(Sint, 2, 16, RGB),
+        VkFormat::R16G16B16_SFLOAT => FormatLayout::unpacked(Sfloat, 2, 16, RGB),
+        VkFormat::R16G16B16A16_UNORM => FormatLayout::unpacked(Unorm, 2, 16, RGBA),
+        VkFormat::R16G16B16A16_SNORM => FormatLayout::unpacked(Snorm, 2, 16, RGBA),
+        VkFormat::R16G16B16A16_USCALED => FormatLayout::unpacked(Uscaled, 2, 16, RGBA),
+        VkFormat::R16G16B16A16_SSCALED => FormatLayout::unpacked(Sscaled, 2, 16, RGBA),
+        VkFormat::R16G16B16A16_UINT => FormatLayout::unpacked(Uint, 2, 16, RGBA),
+        VkFormat::R16G16B16A16_SINT => FormatLayout::unpacked(Sint, 2, 16, RGBA),
+        VkFormat::R16G16B16A16_SFLOAT => FormatLayout::unpacked(Sfloat, 2, 16, RGBA),
+
+        VkFormat::R32_UINT => FormatLayout::unpacked(Uint, 4, 32, &[R]),
+        VkFormat::R32_SINT => FormatLayout::unpacked(Sint, 4, 32, &[R]),
+        VkFormat::R32_SFLOAT => FormatLayout::unpacked(Sfloat, 4, 32, &[R]),
+        VkFormat::R32G32_UINT => FormatLayout::unpacked(Uint, 4, 32, RG),
+        VkFormat::R32G32_SINT => FormatLayout::unpacked(Sint, 4, 32, RG),
+        VkFormat::R32G32_SFLOAT => FormatLayout::unpacked(Sfloat, 4, 32, RG),
+        VkFormat::R32G32B32_UINT => FormatLayout::unpacked(Uint, 4, 32, RGB),
+        VkFormat::R32G32B32_SINT => FormatLayout::unpacked(Sint, 4, 32, RGB),
+        VkFormat::R32G32B32_SFLOAT => FormatLayout::unpacked(Sfloat, 4, 32, RGB),
+        VkFormat::R32G32B32A32_UINT => FormatLayout::unpacked(Uint, 4, 32, RGBA),
+        VkFormat::R32G32B32A32_SINT => FormatLayout::unpacked(Sint, 4, 32, RGBA),
+        VkFormat::R32G32B32A32_SFLOAT => FormatLayout::unpacked(Sfloat, 4, 32, RGBA),
+        VkFormat::R64_UINT => FormatLayout::unpacked(Uint, 8, 64, &[R]),
+        VkFormat::R64_SINT => FormatLayout::unpacked(Sint, 8, 64, &[R]),
+        VkFormat::R64_SFLOAT => FormatLayout::unpacked(Sfloat, 8, 64, &[R]),
+        VkFormat::R64G64_UINT => FormatLayout::unpacked(Uint, 8, 64, RG),
+        VkFormat::R64G64_SINT => FormatLayout::unpacked(Sint, 8, 64, RG),
+        VkFormat::R64G64_SFLOAT => FormatLayout::unpacked(Sfloat, 8, 64, RG),
+        VkFormat::R64G64B64_UINT => FormatLayout::unpacked(Uint, 8, 64, RGB),
+        VkFormat::R64G64B64_SINT => FormatLayout::unpacked(Sint, 8, 64, RGB),
+        VkFormat::R64G64B64_SFLOAT => FormatLayout::unpacked(Sfloat, 8, 64, RGB),
+        VkFormat::R64G64B64A64_UINT => FormatLayout::unpacked(Uint, 8, 64, RGBA),
+        VkFormat::R64G64B64A64_SINT => FormatLayout::unpacked(Sint, 8, 64, RGBA),
+        VkFormat::R64G64B64A64_SFLOAT => FormatLayout::unpacked(Sfloat, 8, 64, RGBA),
+
+        VkFormat::B10G11R11_UFLOAT_PACK32 => {
+            FormatLayout::packed(Ufloat, 4, &[(B, 10), (G, 11), (R, 11)])
+        }
+        // 9 bit mantissas, the shared 5 bit exponent lives in bits 27..32
+        VkFormat::E5B9G9R9_UFLOAT_PACK32 => FormatLayout::empty(4, 4)
+            .with(R, Ufloat, 0, 9)
+            .with(G, Ufloat, 9, 9)
+            .with(B, Ufloat, 18, 9),
+
+        VkFormat::D16_UNORM => FormatLayout::unpacked(Unorm, 2, 16, &[Depth]),
+        VkFormat::X8_D24_UNORM_PACK32 => FormatLayout::empty(4, 4).with(Depth, Unorm, 0, 24),
+        VkFormat::D32_SFLOAT => FormatLayout::unpacked(Sfloat, 4, 32, &[Depth]),
+        VkFormat::S8_UINT => FormatLayout::unpacked(Uint, 1, 8, &[Stencil]),
+        VkFormat::D16_UNORM_S8_UINT => FormatLayout::empty(4, 2)
+            .with(Depth, Unorm, 0, 16)
+            .with(Stencil, Uint, 16, 8),
+        VkFormat::D24_UNORM_S8_UINT => FormatLayout::empty(4, 4)
+            .with(Depth, Unorm, 0, 24)
+            .with(Stencil, Uint, 24, 8),
+        VkFormat::D32_SFLOAT_S8_UINT => FormatLayout::empty(8, 4)
+            .with(Depth, Sfloat, 0, 32)
+            .with(Stencil, Uint, 32, 8),
+
+        VkFormat::R10X6_UNORM_PACK16 => FormatLayout::unpacked(Unorm, 2, 10, &[R]),
+        VkFormat::R10X6G10X6_UNORM_2PACK16 => FormatLayout::unpacked(Unorm, 2, 10, RG),
+        VkFormat::R10X6G10X6B10X6A10X6_UNORM_4PACK16 => FormatLayout::unpacked(Unorm, 2, 10, RGBA),
+        VkFormat::R12X4_UNORM_PACK16 => FormatLayout::unpacked(Unorm, 2, 12, &[R]),
+        VkFormat::R12X4G12X4_UNORM_2PACK16 => FormatLayout::unpacked(Unorm, 2, 12, RG),
+        VkFormat::R12X4G12X4B12X4A12X4_UNORM_4PACK16 => FormatLayout::unpacked(Unorm, 2, 12, RGBA),
+
+        _ => return None,
+    };
+    Some(layout)
+}
+
+/// Whether the texel block size and dimensions of the format are known,
+/// which the size functions below require.
+pub const fn has_format_block(format: VkFormat) -> bool {
+    get_format_layout(format).is_some() || get_format_block(format).is_some()
+}
+
+/// Texel block size in bytes and block dimensions of block compressed and
+/// subsampled formats.
+const fn get_format_block(format: VkFormat) -> Option<(u32, (u32, u32, u32))> {
+    let block = match format {
+        VkFormat::BC1_RGB_UNORM_BLOCK
+        | VkFormat::BC1_RGB_SRGB_BLOCK
+        | VkFormat::BC1_RGBA_UNORM_BLOCK
+        | VkFormat::BC1_RGBA_SRGB_BLOCK
+        | VkFormat::BC4_UNORM_BLOCK
+        | VkFormat::BC4_SNORM_BLOCK
+        | VkFormat::ETC2_R8G8B8_UNORM_BLOCK
+        | VkFormat::ETC2_R8G8B8_SRGB_BLOCK
+        | VkFormat::ETC2_R8G8B8A1_UNORM_BLOCK
+        | VkFormat::ETC2_R8G8B8A1_SRGB_BLOCK
+        | VkFormat::EAC_R11_UNORM_BLOCK
+        | VkFormat::EAC_R11_SNORM_BLOCK => (8, (4, 4, 1)),
+        VkFormat::BC2_UNORM_BLOCK
+        | VkFormat::BC2_SRGB_BLOCK
+        | VkFormat::BC3_UNORM_BLOCK
+        | VkFormat::BC3_SRGB_BLOCK
+        | VkFormat::BC5_UNORM_BLOCK
+        | VkFormat::BC5_SNORM_BLOCK
+        | VkFormat::BC6H_UFLOAT_BLOCK
+        | VkFormat::BC6H_SFLOAT_BLOCK
+        | VkFormat::BC7_UNORM_BLOCK
+        | VkFormat::BC7_SRGB_BLOCK
+        | VkFormat::ETC2_R8G8B8A8_UNORM_BLOCK
+        | VkFormat::ETC2_R8G8B8A8_SRGB_BLOCK
+        | VkFormat::EAC_R11G11_UNORM_BLOCK
+        | VkFormat::EAC_R11G11_SNORM_BLOCK => (16, (4, 4, 1)),
+
+        VkFormat::ASTC_4x4_UNORM_BLOCK
+        | VkFormat::ASTC_4x4_SRGB_BLOCK
+        | VkFormat::ASTC_4x4_SFLOAT_BLOCK_EXT => (16, (4, 4, 1)),
+        VkFormat::ASTC_5x4_UNORM_BLOCK
+        | VkFormat::ASTC_5x4_SRGB_BLOCK
+        | VkFormat::ASTC_5x4_SFLOAT_BLOCK_EXT => (16, (5, 4, 1)),
+        VkFormat::ASTC_5x5_UNORM_BLOCK
+        | VkFormat::ASTC_5x5_SRGB_BLOCK
+        | VkFormat::ASTC_5x5_SFLOAT_BLOCK_EXT => (16, (5, 5, 1)),
+        VkFormat::ASTC_6x5_UNORM_BLOCK
+        | VkFormat::ASTC_6x5_SRGB_BLOCK
+        | VkFormat::ASTC_6x5_SFLOAT_BLOCK_EXT => (16, (6, 5, 1)),
+        VkFormat::ASTC_6x6_UNORM_BLOCK
+        | VkFormat::ASTC_6x6_SRGB_BLOCK
+        | VkFormat::ASTC_6x6_SFLOAT_BLOCK_EXT => (16, (6, 6, 1)),
+        VkFormat::ASTC_8x5_UNORM_BLOCK
+        | VkFormat::ASTC_8x5_SRGB_BLOCK
+        | VkFormat::ASTC_8x5_SFLOAT_BLOCK_EXT => (16, (8, 5, 1)),
+        VkFormat::ASTC_8x6_UNORM_BLOCK
+        | VkFormat::ASTC_8x6_SRGB_BLOCK
+        | VkFormat::ASTC_8x6_SFLOAT_BLOCK_EXT => (16, (8, 6, 1)),
+        VkFormat::ASTC_8x8_UNORM_BLOCK
+        | VkFormat::ASTC_8x8_SRGB_BLOCK
+        | VkFormat::ASTC_8x8_SFLOAT_BLOCK_EXT => (16, (8, 8, 1)),
+        VkFormat::ASTC_10x5_UNORM_BLOCK
+        | VkFormat::ASTC_10x5_SRGB_BLOCK
+        | VkFormat::ASTC_10x5_SFLOAT_BLOCK_EXT => (16, (10, 5, 1)),
+        VkFormat::ASTC_10x6_UNORM_BLOCK
+        | VkFormat::ASTC_10x6_SRGB_BLOCK
+        | VkFormat::ASTC_10x6_SFLOAT_BLOCK_EXT => (16, (10, 6, 1)),
+        VkFormat::ASTC_10x8_UNORM_BLOCK
+        | VkFormat::ASTC_10x8_SRGB_BLOCK
+        | VkFormat::ASTC_10x8_SFLOAT_BLOCK_EXT => (16, (10, 8, 1)),
+        VkFormat::ASTC_10x10_UNORM_BLOCK
+        | VkFormat::ASTC_10x10_SRGB_BLOCK
+        | VkFormat::ASTC_10x10_SFLOAT_BLOCK_EXT => (16, (10, 10, 1)),
+        VkFormat::ASTC_12x10_UNORM_BLOCK
+        | VkFormat::ASTC_12x10_SRGB_BLOCK
+        | VkFormat::ASTC_12x10_SFLOAT_BLOCK_EXT => (16, (12, 10, 1)),
+        VkFormat::ASTC_12x12_UNORM_BLOCK
+        | VkFormat::ASTC_12x12_SRGB_BLOCK
+        | VkFormat::ASTC_12x12_SFLOAT_BLOCK_EXT => (16, (12, 12, 1)),
+        VkFormat::ASTC_3x3x3_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_3x3x3_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_3x3x3_SFLOAT_BLOCK_EXT => (16, (3, 3, 3)),
+        VkFormat::ASTC_4x3x3_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_4x3x3_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_4x3x3_SFLOAT_BLOCK_EXT => (16, (4, 3, 3)),
+        VkFormat::ASTC_4x4x3_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_4x4x3_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_4x4x3_SFLOAT_BLOCK_EXT => (16, (4, 4, 3)),
+        VkFormat::ASTC_4x4x4_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_4x4x4_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_4x4x4_SFLOAT_BLOCK_EXT => (16, (4, 4, 4)),
+        VkFormat::ASTC_5x4x4_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_5x4x4_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_5x4x4_SFLOAT_BLOCK_EXT => (16, (5, 4, 4)),
+        VkFormat::ASTC_5x5x4_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_5x5x4_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_5x5x4_SFLOAT_BLOCK_EXT => (16, (5, 5, 4)),
+        VkFormat::ASTC_5x5x5_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_5x5x5_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_5x5x5_SFLOAT_BLOCK_EXT => (16, (5, 5, 5)),
+        VkFormat::ASTC_6x5x5_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_6x5x5_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_6x5x5_SFLOAT_BLOCK_EXT => (16, (6, 5, 5)),
+        VkFormat::ASTC_6x6x5_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_6x6x5_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_6x6x5_SFLOAT_BLOCK_EXT => (16, (6, 6, 5)),
+        VkFormat::ASTC_6x6x6_UNORM_BLOCK_EXT
+        | VkFormat::ASTC_6x6x6_SRGB_BLOCK_EXT
+        | VkFormat::ASTC_6x6x6_SFLOAT_BLOCK_EXT => (16, (6, 6, 6)),
+
+        VkFormat::PVRTC1_2BPP_UNORM_BLOCK_IMG
+        | VkFormat::PVRTC1_2BPP_SRGB_BLOCK_IMG
+        | VkFormat::PVRTC2_2BPP_UNORM_BLOCK_IMG
+        | VkFormat::PVRTC2_2BPP_SRGB_BLOCK_IMG => (8, (8, 4, 1)),
+        VkFormat::PVRTC1_4BPP_UNORM_BLOCK_IMG
+        | VkFormat::PVRTC1_4BPP_SRGB_BLOCK_IMG
+        | VkFormat::PVRTC2_4BPP_UNORM_BLOCK_IMG
+        | VkFormat::PVRTC2_4BPP_SRGB_BLOCK_IMG => (8, (4, 4, 1)),
+
+        VkFormat::G8B8G8R8_422_UNORM | VkFormat::B8G8R8G8_422_UNORM => (4, (2, 1, 1)),
+        VkFormat::G10X6B10X6G10X6R10X6_422_UNORM_4PACK16
+        | VkFormat::B10X6G10X6R10X6G10X6_422_UNORM_4PACK16
+        | VkFormat::G12X4B12X4G12X4R12X4_422_UNORM_4PACK16
+        | VkFormat::B12X4G12X4R12X4G12X4_422_UNORM_4PACK16
+        | VkFormat::G16B16G16R16_422_UNORM
+        | VkFormat::B16G16R16G16_422_UNORM => (8, (2, 1, 1)),
+        _ => return None,
+    };
+    Some(block)
+}
+
+/// Whether the color channels of the format are sRGB encoded.
+pub const fn is_format_srgb(format: VkFormat) -> bool {
+    matches!(
+        format,
+        VkFormat::R8_SRGB
+            | VkFormat::R8G8_SRGB
+            | VkFormat::R8G8B8_SRGB
+            | VkFormat::B8G8R8_SRGB
+            | VkFormat::R8G8B8A8_SRGB
+            | VkFormat::B8G8R8A8_SRGB
+            | VkFormat::A8B8G8R8_SRGB_PACK32
+            | VkFormat::BC1_RGB_SRGB_BLOCK
+            | VkFormat::BC1_RGBA_SRGB_BLOCK
+            | VkFormat::BC2_SRGB_BLOCK
+            | VkFormat::BC3_SRGB_BLOCK
+            | VkFormat::BC7_SRGB_BLOCK
+            | VkFormat::ETC2_R8G8B8_SRGB_BLOCK
+            | VkFormat::ETC2_R8G8B8A1_SRGB_BLOCK
+            | VkFormat::ETC2_R8G8B8A8_SRGB_BLOCK
+            | VkFormat::ASTC_4x4_SRGB_BLOCK
+            | VkFormat::ASTC_5x4_SRGB_BLOCK
+            | VkFormat::ASTC_5x5_SRGB_BLOCK
+            | VkFormat::ASTC_6x5_SRGB_BLOCK
+            | VkFormat::ASTC_6x6_SRGB_BLOCK
+            | VkFormat::ASTC_8x5_SRGB_BLOCK
+            | VkFormat::ASTC_8x6_SRGB_BLOCK
+            | VkFormat::ASTC_8x8_SRGB_BLOCK
+            | VkFormat::ASTC_10x5_SRGB_BLOCK
+            | VkFormat::ASTC_10x6_SRGB_BLOCK
+            | VkFormat::ASTC_10x8_SRGB_BLOCK
+            | VkFormat::ASTC_10x10_SRGB_BLOCK
+            | VkFormat::ASTC_12x10_SRGB_BLOCK
+            | VkFormat::ASTC_12x12_SRGB_BLOCK
+            | VkFormat::PVRTC1_2BPP_SRGB_BLOCK_IMG
+            | VkFormat::PVRTC1_4BPP_SRGB_BLOCK_IMG
+            | VkFormat::PVRTC2_2BPP_SRGB_BLOCK_IMG
+            | VkFormat::PVRTC2_4BPP_SRGB_BLOCK_IMG
+            | VkFormat::ASTC_3x3x3_SRGB_BLOCK_EXT
+            | VkFormat::ASTC_4x3x3_SRGB_BLOCK_EXT
+            | VkFormat::ASTC_4x4x3_SRGB_BLOCK_EXT
+            | VkFormat::ASTC_4x4x4_SRGB_BLOCK_EXT
+            | VkFormat::ASTC_5x4x4_SRGB_BLOCK_EXT
+            | VkFormat::ASTC_5x5x4_SRGB_BLOCK_EXT
+            | VkFormat::ASTC_5x5x5_SRGB_BLOCK_EXT
+            | VkFormat::ASTC_6x5x5_SRGB_BLOCK_EXT
+            | VkFormat::ASTC_6x6x5_SRGB_BLOCK_EXT
+            | VkFormat::ASTC_6x6x6_SRGB_BLOCK_EXT
+    )
+}
+
+/// Whether the format stores texels in compressed blocks.
+pub const fn is_format_compressed(format: VkFormat) -> bool {
+    get_format_layout(format).is_none() && get_format_block(format).is_some()
+}
+
 #[inline(always)]
 pub const fn get_format_type_size_bytes(format: VkFormat) -> u32 {
+    if let Some(layout) = get_format_layout(format) {
+        return layout.type_size;
+    }
     match format {
-        VkFormat::R16_SFLOAT => 2,
-        VkFormat::R16G16B16A16_SFLOAT => 2,
-        VkFormat::R8G8B8A8_UNORM => 1,
-        VkFormat::BC1_RGB_UNORM_BLOCK => 1,
+        VkFormat::G10X6B10X6G10X6R10X6_422_UNORM_4PACK16
+        | VkFormat::B10X6G10X6R10X6G10X6_422_UNORM_4PACK16
+        | VkFormat::G12X4B12X4G12X4R12X4_422_UNORM_4PACK16
+        | VkFormat::B12X4G12X4R12X4G12X4_422_UNORM_4PACK16
+        | VkFormat::G16B16G16R16_422_UNORM
+        | VkFormat::B16G16R16G16_422_UNORM => 2,
+        _ if get_format_block(format).is_some() => 1,
         _ => panic!("Unsupported format"),
     }
 }
 
 #[inline(always)]
 pub const fn get_format_pixel_size_bytes(format: VkFormat) -> f32 {
-    match format {
-        VkFormat::R16_SFLOAT => 2.0,
-        VkFormat::R16G16B16A16_SFLOAT => 8.0,
-        VkFormat::R8G8B8A8_UNORM => 4.0,
-        VkFormat::BC1_RGB_UNORM_BLOCK => 0.5,
-        _ => panic!("Unsupported format"),
+    let (width, height, depth) = get_format_block_extent(format);
+    get_format_block_size_bytes(format) as f32 / (width * height * depth) as f32
+}
+
+/// Size in bytes of a single texel block of the format.
+#[inline(always)]
+pub const fn get_format_block_size_bytes(format: VkFormat) -> u32 {
+    if let Some(layout) = get_format_layout(format) {
+        return layout.texel_size;
+    }
+    match get_format_block(format) {
+        Some((size, _)) => size,
+        None => panic!("Unsupported format"),
+    }
+}
+
+/// Dimensions (width, height, depth) in pixels of a single texel block of the format.
+#[inline(always)]
+pub const fn get_format_block_extent(format: VkFormat) -> (u32, u32, u32) {
+    if get_format_layout(format).is_some() {
+        return (1, 1, 1);
+    }
+    match get_format_block(format) {
+        Some((_, extent)) => extent,
+        None => panic!("Unsupported format"),
     }
 }